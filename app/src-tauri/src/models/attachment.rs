@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// Archivo relacionado que se guarda junto a un documento
+///
+/// Por ejemplo una imagen de portada o una bibliografía.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Attachment {
+    /// Nombre del adjunto; es único dentro de cada documento
+    pub name: String,
+
+    /// Tipo MIME (p. ej. "image/png")
+    pub mime: String,
+
+    /// Contenido del archivo
+    pub bytes: Vec<u8>,
+}
+
+impl Attachment {
+    /// Crea un nuevo adjunto
+    pub fn new(name: String, mime: String, bytes: Vec<u8>) -> Self {
+        Self { name, mime, bytes }
+    }
+
+    /// Tamaño del contenido en bytes
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_creation() {
+        let attachment = Attachment::new(
+            "portada.png".to_string(),
+            "image/png".to_string(),
+            vec![1, 2, 3],
+        );
+
+        assert_eq!(attachment.name, "portada.png");
+        assert_eq!(attachment.mime, "image/png");
+        assert_eq!(attachment.size(), 3);
+    }
+}
@@ -1,4 +1,8 @@
+use crate::models::chunk_metadata::ChunkMetadata;
+use crate::models::tokens::token_count;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Representa un fragmento (chunk) de texto extraído de un documento
 ///
@@ -26,8 +30,185 @@ pub struct Chunk {
     /// Número de caracteres en el chunk (útil para validación)
     pub char_count: usize,
 
-    /// Metadata adicional en formato JSON (puede contener info extra)
-    pub metadata: Option<String>,
+    /// Metadata adicional (sección, idioma, etc.); los chunks viejos la
+    /// traen como texto JSON y se convierte al leerlos
+    pub metadata: Option<ChunkMetadata>,
+
+    /// Offset (en caracteres) donde empieza el chunk dentro del texto completo
+    /// del documento, si el chunker lo registró
+    #[serde(default)]
+    pub start_offset: Option<usize>,
+
+    /// Tokens estimados del texto (ver `token_count`); los chunks guardados
+    /// antes de existir este campo lo traen en 0
+    #[serde(default)]
+    pub token_count: usize,
+
+    /// Última página que cubre el chunk, si cruza páginas (`page_number` es
+    /// la primera)
+    #[serde(default)]
+    pub end_page: Option<usize>,
+
+    /// Offset (en caracteres, exclusivo) donde termina el chunk dentro del
+    /// texto completo del documento: `text` es ese tramo tal cual
+    #[serde(default)]
+    pub end_offset: Option<usize>,
+
+    /// Idioma detectado del texto (ISO 639-1, p. ej. "es"), si no fue
+    /// ambiguo (ver `services::language`)
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// `true` si al leerlo de la BD `char_count` no coincidía con el texto y
+    /// se recalculó (ver `repair_char_count`); no se guarda
+    #[serde(skip)]
+    pub char_count_repaired: bool,
+}
+
+/// Motivo por el que `Chunk::validate` rechaza un chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkValidationError {
+    /// El id está vacío (o solo con espacios)
+    EmptyId { index: usize },
+    /// El chunk no dice a qué documento pertenece
+    EmptyDocumentId { chunk_id: String },
+    /// `char_count` no es la cantidad de caracteres del texto
+    CharCountMismatch {
+        chunk_id: String,
+        char_count: usize,
+        actual: usize,
+    },
+    /// `index` no entra en la clave del chunk (ver `Chunk::MAX_INDEX`)
+    IndexOutOfRange { chunk_id: String, index: usize },
+    /// Página 0 (se numeran desde 1) o `end_page` antes de `page_number`
+    InvalidPage {
+        chunk_id: String,
+        page_number: usize,
+        end_page: Option<usize>,
+    },
+    /// `end_offset` antes de `start_offset`
+    InvalidOffsets {
+        chunk_id: String,
+        start: usize,
+        end: usize,
+    },
+}
+
+impl fmt::Display for ChunkValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkValidationError::EmptyId { index } => {
+                write!(f, "chunk at index {} has an empty id", index)
+            }
+            ChunkValidationError::EmptyDocumentId { chunk_id } => {
+                write!(f, "chunk {} has an empty document id", chunk_id)
+            }
+            ChunkValidationError::CharCountMismatch {
+                chunk_id,
+                char_count,
+                actual,
+            } => write!(
+                f,
+                "chunk {} has char_count {} but its text has {} chars",
+                chunk_id, char_count, actual
+            ),
+            ChunkValidationError::IndexOutOfRange { chunk_id, index } => write!(
+                f,
+                "chunk {} has index {} (max {})",
+                chunk_id,
+                index,
+                Chunk::MAX_INDEX
+            ),
+            ChunkValidationError::InvalidPage {
+                chunk_id,
+                page_number,
+                end_page,
+            } => write!(
+                f,
+                "chunk {} has invalid pages {}..{:?}",
+                chunk_id, page_number, end_page
+            ),
+            ChunkValidationError::InvalidOffsets {
+                chunk_id,
+                start,
+                end,
+            } => write!(
+                f,
+                "chunk {} ends at offset {} before it starts at {}",
+                chunk_id, end, start
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChunkValidationError {}
+
+/// Criterio para ordenar los chunks de un documento al reconstruir su texto
+/// o navegar entre chunks vecinos
+///
+/// - `Index`: usa el campo `index`. Es el orden de creación y sirve siempre,
+///   pero depende de que los índices se hayan asignado bien.
+/// - `PageOffset`: ordena por `(page_number, start_offset)`. Es más robusto
+///   ante errores de reindexado, pero solo se aplica si todos los chunks
+///   tienen `start_offset`; si falta en alguno se usa `Index`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChunkOrder {
+    #[default]
+    Index,
+    PageOffset,
+}
+
+/// Ordena los chunks según el criterio indicado
+pub fn sort_chunks(chunks: &mut [Chunk], order: ChunkOrder) {
+    let use_offsets =
+        order == ChunkOrder::PageOffset && chunks.iter().all(|c| c.start_offset.is_some());
+
+    if use_offsets {
+        chunks.sort_by_key(|c| (c.page_number, c.start_offset, c.index));
+    } else {
+        chunks.sort_by_key(|c| c.index);
+    }
+}
+
+/// Reconstruye el texto de un documento a partir de sus chunks
+///
+/// Con `ChunkOrder::Index` los textos se unen con `separator`. Con
+/// `ChunkOrder::PageOffset` (y offsets presentes) los chunks se colocan según
+/// su offset: los contiguos se pegan sin separador y el texto solapado se
+/// incluye una sola vez; `separator` solo se inserta donde hay huecos.
+pub fn join_chunks(chunks: &[Chunk], order: ChunkOrder, separator: &str) -> String {
+    let mut sorted = chunks.to_vec();
+    sort_chunks(&mut sorted, order);
+
+    let use_offsets =
+        order == ChunkOrder::PageOffset && sorted.iter().all(|c| c.start_offset.is_some());
+    if !use_offsets {
+        return sorted
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect::<Vec<_>>()
+            .join(separator);
+    }
+
+    let mut out = String::new();
+    let mut covered = 0;
+    for (i, chunk) in sorted.iter().enumerate() {
+        let start = chunk.start_offset.unwrap_or(0);
+        let len = chunk.text.chars().count();
+        if i == 0 || start >= covered {
+            if i > 0 && start > covered {
+                out.push_str(separator);
+            }
+            out.push_str(&chunk.text);
+        } else if start + len > covered {
+            // Solo agregamos la parte que no estaba cubierta por el chunk anterior
+            out.extend(chunk.text.chars().skip(covered - start));
+        } else {
+            continue;
+        }
+        covered = start + len;
+    }
+    out
 }
 
 impl Chunk {
@@ -52,6 +233,7 @@ impl Chunk {
         page_number: usize,
     ) -> Self {
         let char_count = text.chars().count();
+        let token_count = token_count(&text);
 
         Self {
             id,
@@ -61,24 +243,227 @@ impl Chunk {
             page_number,
             char_count,
             metadata: None,
+            start_offset: None,
+            token_count,
+            end_page: None,
+            end_offset: None,
+            language: None,
+            char_count_repaired: false,
         }
     }
 
+    /// Id estable para el chunk `index` de un documento: `{document_id}-chunk-{index}`
+    ///
+    /// Reimportar el mismo documento produce los mismos ids, así las
+    /// referencias a chunks (citas, embeddings) siguen siendo válidas.
+    pub fn deterministic_id(document_id: &str, index: usize) -> String {
+        format!("{}-chunk-{}", document_id, index)
+    }
+
     /// Agrega metadata adicional al chunk
-    pub fn with_metadata(mut self, metadata: String) -> Self {
-        self.metadata = Some(metadata);
+    ///
+    /// Acepta un `ChunkMetadata` o, como antes, un `String` con JSON.
+    pub fn with_metadata(mut self, metadata: impl Into<ChunkMetadata>) -> Self {
+        self.metadata = Some(metadata.into());
+        self
+    }
+
+    /// Registra el offset (en caracteres) donde empieza el chunk en el documento
+    pub fn with_start_offset(mut self, start_offset: usize) -> Self {
+        self.start_offset = Some(start_offset);
+        self
+    }
+
+    /// Registra el tramo `start..end` (en caracteres) del texto del
+    /// documento de donde salió el chunk
+    pub fn with_offsets(mut self, start: usize, end: usize) -> Self {
+        self.start_offset = Some(start);
+        self.end_offset = Some(end);
+        self
+    }
+
+    /// Registra la última página que cubre el chunk
+    pub fn with_end_page(mut self, end_page: usize) -> Self {
+        self.end_page = Some(end_page);
         self
     }
 
+    /// Guarda un valor tipado como metadata, serializándolo a JSON
+    ///
+    /// El valor debe serializarse como un objeto; sus campos que no son de
+    /// `ChunkMetadata` van a `extra`. Reemplaza cualquier metadata previa. Si
+    /// el valor no se puede serializar, la metadata existente no se modifica.
+    pub fn set_metadata_json<T: Serialize>(&mut self, value: &T) -> Result<(), String> {
+        let json =
+            serde_json::to_value(value).map_err(|e| format!("metadata serialize error: {}", e))?;
+        self.metadata = Some(ChunkMetadata::from_json(json)?);
+        Ok(())
+    }
+
+    /// Lee la metadata como un valor tipado
+    ///
+    /// Retorna `Ok(None)` si el chunk no tiene metadata y un error si el JSON
+    /// guardado no es válido o no corresponde al tipo pedido.
+    pub fn metadata_as<T: DeserializeOwned>(&self) -> Result<Option<T>, String> {
+        match &self.metadata {
+            Some(metadata) => serde_json::from_value(metadata.to_json())
+                .map(Some)
+                .map_err(|e| format!("metadata deserialize error: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Mayor `index` posible: el que entra en los 10 dígitos de la clave del
+    /// chunk en la BD
+    pub const MAX_INDEX: usize = 9_999_999_999;
+
+    /// Comprueba que el chunk se pueda guardar
+    ///
+    /// Exige id y `document_id` no vacíos, `char_count` igual a los
+    /// caracteres del texto, `index` hasta `MAX_INDEX`, páginas desde 1 (con
+    /// `end_page` no menor a `page_number`) y, si tiene ambos offsets,
+    /// `start_offset <= end_offset`.
+    pub fn validate(&self) -> Result<(), ChunkValidationError> {
+        let chunk_id = || self.id.clone();
+        if self.id.trim().is_empty() {
+            return Err(ChunkValidationError::EmptyId { index: self.index });
+        }
+        if self.document_id.trim().is_empty() {
+            return Err(ChunkValidationError::EmptyDocumentId {
+                chunk_id: chunk_id(),
+            });
+        }
+        let actual = self.text.chars().count();
+        if self.char_count != actual {
+            return Err(ChunkValidationError::CharCountMismatch {
+                chunk_id: chunk_id(),
+                char_count: self.char_count,
+                actual,
+            });
+        }
+        if self.index > Self::MAX_INDEX {
+            return Err(ChunkValidationError::IndexOutOfRange {
+                chunk_id: chunk_id(),
+                index: self.index,
+            });
+        }
+        if self.page_number == 0 || self.end_page.is_some_and(|end| end < self.page_number) {
+            return Err(ChunkValidationError::InvalidPage {
+                chunk_id: chunk_id(),
+                page_number: self.page_number,
+                end_page: self.end_page,
+            });
+        }
+        if let (Some(start), Some(end)) = (self.start_offset, self.end_offset) {
+            if end < start {
+                return Err(ChunkValidationError::InvalidOffsets {
+                    chunk_id: chunk_id(),
+                    start,
+                    end,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Recalcula `char_count` si no coincide con el texto
+    ///
+    /// Se usa al leer chunks guardados, para no confiar en un valor viejo.
+    /// Marca `char_count_repaired` y retorna `true` si tuvo que corregirlo.
+    pub fn repair_char_count(&mut self) -> bool {
+        let actual = self.text.chars().count();
+        if self.char_count == actual {
+            return false;
+        }
+        self.char_count = actual;
+        self.char_count_repaired = true;
+        true
+    }
+
     /// Verifica si el chunk está vacío
     pub fn is_empty(&self) -> bool {
         self.text.trim().is_empty()
     }
 }
 
+/// Campos que tenía `Chunk` en la primera versión guardada de la biblioteca;
+/// todos los registros tienen al menos estos
+pub(crate) const BASE_FIELD_COUNT: usize = 7;
+
+/// Lee los campos guardados de `Chunk` en el orden de la struct, los que haya
+///
+/// Igual que `document_fields!` en `document.rs`: define `FIELD_COUNT` y
+/// `read_prefix`, y los campos nuevos se agregan siempre al final.
+macro_rules! chunk_fields {
+    ($($field:ident),* $(,)?) => {
+        /// Cantidad de campos guardados que tiene `Chunk` hoy
+        pub(crate) const FIELD_COUNT: usize = [$(stringify!($field)),*].len();
+
+        /// Lee los primeros `count` campos; el resto queda con su valor por
+        /// defecto
+        fn read_prefix<'de, A: SeqAccess<'de>>(
+            seq: &mut A,
+            count: usize,
+        ) -> Result<Chunk, A::Error> {
+            let mut chunk = Chunk::new(String::new(), String::new(), String::new(), 0, 0);
+            let mut read = 0;
+            $(
+                if read == count {
+                    return Ok(chunk);
+                }
+                chunk.$field = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(read, &"a chunk"))?;
+                read += 1;
+            )*
+            Ok(chunk)
+        }
+    };
+}
+
+chunk_fields!(
+    id,
+    document_id,
+    text,
+    index,
+    page_number,
+    char_count,
+    metadata,
+    start_offset,
+    token_count,
+    end_page,
+    end_offset,
+    language,
+);
+
+/// Deserializa un `Chunk` guardado cuando la struct tenía solo sus primeros
+/// `.0` campos (ver `DocumentPrefix`)
+pub(crate) struct ChunkPrefix(pub usize);
+
+impl<'de> DeserializeSeed<'de> for ChunkPrefix {
+    type Value = Chunk;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Chunk, D::Error> {
+        deserializer.deserialize_tuple(self.0, self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChunkPrefix {
+    type Value = Chunk;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a chunk with {} fields", self.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Chunk, A::Error> {
+        read_prefix(&mut seq, self.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::RAW_METADATA_KEY;
     use serde_json;
 
     #[test]
@@ -100,6 +485,23 @@ mod tests {
         assert!(chunk.metadata.is_none());
     }
 
+    #[test]
+    fn test_chunk_deterministic_id() {
+        assert_eq!(Chunk::deterministic_id("doc-1", 3), "doc-1-chunk-3");
+        assert_eq!(
+            Chunk::deterministic_id("doc-1", 3),
+            Chunk::deterministic_id("doc-1", 3)
+        );
+        assert_ne!(
+            Chunk::deterministic_id("doc-1", 3),
+            Chunk::deterministic_id("doc-1", 4)
+        );
+        assert_ne!(
+            Chunk::deterministic_id("doc-1", 3),
+            Chunk::deterministic_id("doc-2", 3)
+        );
+    }
+
     #[test]
     fn test_chunk_with_metadata() {
         let chunk = Chunk::new(
@@ -111,8 +513,60 @@ mod tests {
         )
         .with_metadata(r#"{"key": "value"}"#.to_string());
 
-        assert!(chunk.metadata.is_some());
-        assert_eq!(chunk.metadata.unwrap(), r#"{"key": "value"}"#);
+        let metadata = chunk.metadata.unwrap();
+        assert_eq!(metadata.extra["key"], "value");
+        assert_eq!(metadata.section_title, None);
+    }
+
+    #[test]
+    fn test_chunk_typed_metadata() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Meta {
+            section: String,
+            confidence: f32,
+        }
+
+        let mut chunk = Chunk::new(
+            "chunk-1".to_string(),
+            "doc-123".to_string(),
+            "Texto".to_string(),
+            0,
+            1,
+        );
+
+        // Sin metadata debe retornar None
+        assert_eq!(chunk.metadata_as::<Meta>().unwrap(), None);
+
+        let meta = Meta {
+            section: "Introducción".to_string(),
+            confidence: 0.5,
+        };
+        chunk.set_metadata_json(&meta).unwrap();
+
+        let restored: Option<Meta> = chunk.metadata_as().unwrap();
+        assert_eq!(restored, Some(meta));
+    }
+
+    #[test]
+    fn test_chunk_invalid_metadata() {
+        let chunk = Chunk::new(
+            "chunk-1".to_string(),
+            "doc-123".to_string(),
+            "Texto".to_string(),
+            0,
+            1,
+        )
+        .with_metadata("{no es json".to_string());
+
+        // JSON malformado no se pierde, y leerlo como otro tipo es un error
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Meta {
+            section: String,
+        }
+        assert!(chunk.metadata_as::<Meta>().is_err());
+        let raw = chunk.metadata_as::<serde_json::Value>().unwrap().unwrap();
+        assert_eq!(raw[RAW_METADATA_KEY], "{no es json");
     }
 
     #[test]
@@ -219,4 +673,210 @@ mod tests {
         );
         assert_eq!(chunk3.char_count, 9); // Incluye espacios y ñ
     }
+
+    #[test]
+    fn test_chunk_token_count() {
+        let chunk = Chunk::new(
+            "chunk-1".to_string(),
+            "doc-1".to_string(),
+            "Hola mundo".to_string(),
+            0,
+            1,
+        );
+        assert_eq!(chunk.token_count, 3);
+
+        // Chunks guardados antes del campo deserializan con 0
+        let legacy = r#"{"id":"c","document_id":"d","text":"Hola","index":0,"page_number":1,"char_count":4,"metadata":null}"#;
+        let restored: Chunk = serde_json::from_str(legacy).unwrap();
+        assert_eq!(restored.token_count, 0);
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let chunk = Chunk::new("c".into(), "d".into(), "texto".into(), 0, 1).with_offsets(10, 15);
+        assert_eq!(chunk.start_offset, Some(10));
+        assert_eq!(chunk.end_offset, Some(15));
+
+        let json = serde_json::to_string(&chunk).unwrap();
+        let restored: Chunk = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, chunk);
+
+        // Chunks guardados antes de los offsets deserializan sin ellos
+        let legacy = r#"{"id":"c","document_id":"d","text":"Hola","index":0,"page_number":1,"char_count":4,"metadata":null}"#;
+        let restored: Chunk = serde_json::from_str(legacy).unwrap();
+        assert_eq!(restored.start_offset, None);
+        assert_eq!(restored.end_offset, None);
+        assert_eq!(restored.language, None);
+    }
+
+    #[test]
+    fn test_join_chunks_by_offset_with_scrambled_indices() {
+        let original = "Primera parte del texto. Segunda parte. Fin.";
+        let pieces = [
+            (0, "Primera parte "),
+            (14, "del texto. "),
+            (25, "Segunda parte. Fin."),
+        ];
+
+        // Índices desordenados a propósito (simula un error de reindexado)
+        let scrambled = [2, 0, 1];
+        let mut chunks: Vec<Chunk> = pieces
+            .iter()
+            .zip(scrambled)
+            .map(|((offset, text), index)| {
+                Chunk::new(
+                    format!("chunk-{}", index),
+                    "doc-1".to_string(),
+                    text.to_string(),
+                    index,
+                    1,
+                )
+                .with_start_offset(*offset)
+            })
+            .collect();
+        chunks.reverse();
+
+        assert_eq!(join_chunks(&chunks, ChunkOrder::PageOffset, ""), original);
+        // Por índice el resultado sale desordenado
+        assert_ne!(join_chunks(&chunks, ChunkOrder::Index, ""), original);
+    }
+
+    #[test]
+    fn test_join_chunks_offset_overlap_and_fallback() {
+        // Chunks solapados: "abcdef" y "defghi" cubren "abcdefghi"
+        let a = Chunk::new("a".into(), "d".into(), "abcdef".into(), 0, 1).with_start_offset(0);
+        let b = Chunk::new("b".into(), "d".into(), "defghi".into(), 1, 1).with_start_offset(3);
+        assert_eq!(
+            join_chunks(&[b.clone(), a.clone()], ChunkOrder::PageOffset, " "),
+            "abcdefghi"
+        );
+
+        // Si a algún chunk le falta el offset se ordena por índice
+        let c = Chunk::new("c".into(), "d".into(), "xyz".into(), 2, 1);
+        assert_eq!(
+            join_chunks(&[c, b, a], ChunkOrder::PageOffset, "|"),
+            "abcdef|defghi|xyz"
+        );
+    }
+
+    #[test]
+    fn test_chunk_metadata_roundtrips() {
+        let metadata = ChunkMetadata {
+            section_title: Some("Capítulo 1".to_string()),
+            source_strategy: Some("recursive".to_string()),
+            language: Some("es".to_string()),
+            extra: [("score".to_string(), serde_json::json!(0.5))].into(),
+        };
+        let chunk =
+            Chunk::new("c".into(), "d".into(), "Hola".into(), 0, 1).with_metadata(metadata.clone());
+
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert!(json.contains(r#""section_title":"Capítulo 1""#));
+        assert_eq!(serde_json::from_str::<Chunk>(&json).unwrap(), chunk);
+
+        let bytes = bincode::serialize(&chunk).unwrap();
+        assert_eq!(bincode::deserialize::<Chunk>(&bytes).unwrap(), chunk);
+
+        // Metadata vieja: un string con JSON, tanto en JSON como en bincode
+        let legacy = r#"{"id":"c","document_id":"d","text":"Hola","index":0,"page_number":1,"char_count":4,"metadata":"{\"language\":\"es\",\"page\":3}"}"#;
+        let restored: Chunk = serde_json::from_str(legacy).unwrap();
+        let restored = restored.metadata.unwrap();
+        assert_eq!(restored.language.as_deref(), Some("es"));
+        assert_eq!(restored.extra["page"], 3);
+
+        let legacy = bincode::serialize(&Some(r#"{"section_title":"Anexo"}"#.to_string())).unwrap();
+        let restored: Option<ChunkMetadata> = bincode::deserialize(&legacy).unwrap();
+        assert_eq!(restored.unwrap().section_title.as_deref(), Some("Anexo"));
+
+        let legacy = bincode::serialize(&Some("texto libre".to_string())).unwrap();
+        let restored: Option<ChunkMetadata> = bincode::deserialize(&legacy).unwrap();
+        assert_eq!(restored.unwrap().extra[RAW_METADATA_KEY], "texto libre");
+    }
+
+    #[test]
+    fn test_chunk_validation() {
+        let valid = || Chunk::new("c-1".into(), "doc-1".into(), "Texto".into(), 0, 1);
+        assert_eq!(valid().validate(), Ok(()));
+        assert_eq!(
+            valid().with_offsets(4, 9).with_end_page(2).validate(),
+            Ok(())
+        );
+
+        let mut chunk = valid();
+        chunk.id = " ".into();
+        assert_eq!(
+            chunk.validate(),
+            Err(ChunkValidationError::EmptyId { index: 0 })
+        );
+
+        let mut chunk = valid();
+        chunk.document_id = String::new();
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkValidationError::EmptyDocumentId { chunk_id }) if chunk_id == "c-1"
+        ));
+
+        let mut chunk = valid();
+        chunk.char_count = 3;
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkValidationError::CharCountMismatch {
+                char_count: 3,
+                actual: 5,
+                ..
+            })
+        ));
+
+        let mut chunk = valid();
+        chunk.index = Chunk::MAX_INDEX + 1;
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkValidationError::IndexOutOfRange { .. })
+        ));
+
+        let chunk = Chunk::new("c-1".into(), "doc-1".into(), "Texto".into(), 0, 0);
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkValidationError::InvalidPage { page_number: 0, .. })
+        ));
+        let chunk = Chunk::new("c-1".into(), "doc-1".into(), "Texto".into(), 0, 3).with_end_page(2);
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkValidationError::InvalidPage {
+                end_page: Some(2),
+                ..
+            })
+        ));
+
+        let err = valid().with_offsets(9, 4).validate().unwrap_err();
+        assert_eq!(
+            err,
+            ChunkValidationError::InvalidOffsets {
+                chunk_id: "c-1".into(),
+                start: 9,
+                end: 4
+            }
+        );
+        assert!(err.to_string().contains("c-1"));
+    }
+
+    #[test]
+    fn test_repair_char_count() {
+        let mut chunk = Chunk::new("c-1".into(), "doc-1".into(), "Canción".into(), 0, 1);
+        assert!(!chunk.repair_char_count());
+        assert!(!chunk.char_count_repaired);
+
+        chunk.char_count = 8;
+        assert!(chunk.repair_char_count());
+        assert_eq!(chunk.char_count, 7);
+        assert!(chunk.char_count_repaired);
+    }
+
+    #[test]
+    fn test_field_count_matches_struct() {
+        // Si falla, falta agregar el campo nuevo a `chunk_fields!`
+        let chunk = Chunk::new("c".into(), "d".into(), "t".into(), 0, 1);
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), FIELD_COUNT);
+    }
 }
@@ -0,0 +1,130 @@
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Clave de `extra` donde queda la metadata vieja que no era un objeto JSON
+pub const RAW_METADATA_KEY: &str = "raw";
+
+/// Metadata de un chunk
+///
+/// Antes era un `String` con JSON libre; los registros viejos se siguen
+/// leyendo (ver `From<String>`). En JSON se serializa como objeto, con lo
+/// de `extra` al mismo nivel que los campos conocidos. En formatos binarios
+/// (bincode, el de la BD) se guarda como el texto de ese JSON, igual que la
+/// metadata vieja, así los chunks guardados antes y después del cambio se
+/// leen con el mismo código.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", default)]
+pub struct ChunkMetadata {
+    /// Título de la sección donde está el chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section_title: Option<String>,
+
+    /// Estrategia de chunking que generó el chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_strategy: Option<String>,
+
+    /// Idioma declarado por el origen del chunk; el detectado automáticamente
+    /// va en `Chunk::language`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// Cualquier otro dato
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl ChunkMetadata {
+    /// Metadata como objeto JSON
+    pub fn to_json(&self) -> Value {
+        // Serializar a `Value` no falla: las claves son strings
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Arma la metadata desde un objeto JSON; las claves desconocidas van a
+    /// `extra`
+    pub fn from_json(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Object(_) => {
+                Self::deserialize(value).map_err(|e| format!("metadata deserialize error: {}", e))
+            }
+            other => Err(format!("metadata must be a JSON object, got {}", other)),
+        }
+    }
+}
+
+impl From<String> for ChunkMetadata {
+    /// Convierte la metadata vieja (texto JSON libre)
+    ///
+    /// Si el texto no es un objeto JSON válido se conserva tal cual en
+    /// `extra[RAW_METADATA_KEY]`.
+    fn from(raw: String) -> Self {
+        serde_json::from_str(&raw)
+            .map_err(|e| e.to_string())
+            .and_then(Self::from_json)
+            .unwrap_or_else(|_| Self {
+                extra: HashMap::from([(RAW_METADATA_KEY.to_string(), Value::String(raw))]),
+                ..Self::default()
+            })
+    }
+}
+
+impl Serialize for ChunkMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            ChunkMetadata::serialize(self, serializer)
+        } else {
+            serializer.serialize_str(&self.to_json().to_string())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Ok(String::deserialize(deserializer)?.into());
+        }
+        match Value::deserialize(deserializer)? {
+            Value::String(raw) => Ok(raw.into()),
+            Value::Object(map) => {
+                ChunkMetadata::deserialize(Value::Object(map)).map_err(D::Error::custom)
+            }
+            other => Err(D::Error::custom(format!(
+                "metadata must be a JSON object or string, got {}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_string_is_parsed() {
+        let meta = ChunkMetadata::from(
+            r#"{"section_title": "Introducción", "confidence": 0.5}"#.to_string(),
+        );
+        assert_eq!(meta.section_title.as_deref(), Some("Introducción"));
+        assert_eq!(meta.extra["confidence"], 0.5);
+
+        // Texto que no es un objeto JSON: se conserva sin perderlo
+        let meta = ChunkMetadata::from("{no es json".to_string());
+        assert_eq!(meta.extra[RAW_METADATA_KEY], "{no es json");
+        let meta = ChunkMetadata::from("[1, 2]".to_string());
+        assert_eq!(meta.extra[RAW_METADATA_KEY], "[1, 2]");
+    }
+
+    #[test]
+    fn test_json_is_an_object() {
+        let meta = ChunkMetadata {
+            language: Some("es".to_string()),
+            extra: HashMap::from([("score".to_string(), Value::from(3))]),
+            ..ChunkMetadata::default()
+        };
+        let json = serde_json::to_string(&meta).unwrap();
+        assert_eq!(json, r#"{"language":"es","score":3}"#);
+        assert_eq!(serde_json::from_str::<ChunkMetadata>(&json).unwrap(), meta);
+    }
+}
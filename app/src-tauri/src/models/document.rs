@@ -1,5 +1,10 @@
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
 /// Representa un documento PDF cargado en el sistema
 ///
@@ -28,6 +33,84 @@ pub struct Document {
 
     /// Indica si el documento ya fue indexado (tiene embeddings generados)
     pub is_indexed: bool,
+
+    /// Hash SHA-256 del contenido del archivo (hex), usado para detectar duplicados
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Número de chunks guardados para este documento (lo mantiene la BD)
+    #[serde(default)]
+    pub chunk_count: usize,
+
+    /// Momento (timestamp Unix) en que se terminaron de generar los embeddings
+    #[serde(default)]
+    pub embedded_at: Option<u64>,
+
+    /// Última vez (timestamp Unix) que el usuario abrió el documento
+    #[serde(default)]
+    pub last_accessed: Option<u64>,
+
+    /// Resumen breve generado por el LLM, para mostrar en la biblioteca
+    #[serde(default)]
+    pub summary: Option<String>,
+
+    /// Idioma de la mayoría de sus chunks (ISO 639-1), si se detectó
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// Etiquetas para organizar la biblioteca, sin repetidas
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Título del diccionario de información del PDF, si lo trae
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Autor según el PDF
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// Tema según el PDF
+    #[serde(default)]
+    pub subject: Option<String>,
+
+    /// Tamaño del archivo en bytes, si se registró al importarlo
+    #[serde(default)]
+    pub file_size: Option<u64>,
+
+    /// Formato del archivo, si se registró al importarlo
+    #[serde(default)]
+    pub doc_type: Option<DocType>,
+
+    /// Páginas (desde 1) que quedaron sin texto al importarlo, p. ej. las
+    /// escaneadas
+    #[serde(default)]
+    pub empty_pages: Vec<usize>,
+
+    /// Momento (timestamp Unix) en que se borró con `soft_delete_document`;
+    /// `None` si está en la biblioteca
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+}
+
+/// Formato del archivo de un documento (ver `services::extract`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocType {
+    Pdf,
+    /// Texto plano (.txt)
+    Text,
+    Markdown,
+    Epub,
+    Html,
+}
+
+/// Timestamp Unix actual en segundos
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
 }
 
 impl Document {
@@ -44,10 +127,7 @@ impl Document {
     /// );
     /// ```
     pub fn new(id: String, name: String, file_path: String, page_count: usize) -> Self {
-        let created_at = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let created_at = unix_now();
 
         Self {
             id,
@@ -56,12 +136,220 @@ impl Document {
             page_count,
             created_at,
             is_indexed: false,
+            sha256: None,
+            chunk_count: 0,
+            embedded_at: None,
+            last_accessed: None,
+            summary: None,
+            language: None,
+            tags: Vec::new(),
+            title: None,
+            author: None,
+            subject: None,
+            file_size: None,
+            doc_type: None,
+            empty_pages: Vec::new(),
+            deleted_at: None,
+        }
+    }
+
+    /// Calcula el ID de un documento a partir del contenido de su archivo
+    ///
+    /// El ID es el hash SHA-256 (hex en minúsculas) del archivo, por lo que
+    /// importar dos veces el mismo PDF produce el mismo ID. El archivo se lee
+    /// por bloques, sin cargarlo entero en memoria.
+    pub fn id_from_file(path: impl AsRef<Path>) -> Result<String, String> {
+        let path = path.as_ref();
+        let mut file =
+            File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+
+        let mut hasher = Sha256::new();
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Asigna el hash SHA-256 del contenido del archivo
+    pub fn with_sha256(mut self, sha256: String) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    /// Asigna el resumen del documento
+    pub fn set_summary(mut self, summary: String) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    /// Nombre para mostrar en la biblioteca: el título del PDF o, si no
+    /// tiene, el nombre del archivo
+    pub fn display_name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Agrega una etiqueta; retorna `false` si ya la tenía
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        if self.has_tag(tag) {
+            return false;
         }
+        self.tags.push(tag.to_string());
+        true
     }
 
-    /// Marca el documento como indexado
+    /// Quita una etiqueta; retorna `false` si no la tenía
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let before = self.tags.len();
+        self.tags.retain(|t| t != tag);
+        self.tags.len() != before
+    }
+
+    /// Indica si el documento tiene la etiqueta
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Marca el documento como indexado y registra cuándo terminaron los embeddings
     pub fn mark_as_indexed(&mut self) {
         self.is_indexed = true;
+        self.embedded_at = Some(unix_now());
+    }
+
+    /// Marca el documento como pendiente de indexar (p. ej. tras cambiar el chunking)
+    ///
+    /// Se conserva `embedded_at` como registro de la última indexación.
+    pub fn mark_as_unindexed(&mut self) {
+        self.is_indexed = false;
+    }
+
+    /// Indica si el documento está borrado (ver `soft_delete_document`)
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Indica si el archivo original sigue existiendo en `file_path`
+    pub fn file_exists(&self) -> bool {
+        Path::new(&self.file_path).is_file()
+    }
+
+    /// Registra que el documento se acaba de abrir
+    pub fn touch(&mut self) {
+        self.last_accessed = Some(unix_now());
+    }
+
+    /// Segundos desde que se creó el documento; 0 si el reloj retrocedió
+    pub fn age_seconds(&self) -> u64 {
+        unix_now().saturating_sub(self.created_at)
+    }
+
+    /// Fecha de creación como `SystemTime`
+    pub fn created_datetime(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.created_at)
+    }
+
+    /// Indica si conviene reindexar tras un cambio de modelo de embeddings
+    ///
+    /// `model_changed_at` es el timestamp Unix en que se configuró el modelo
+    /// actual. Los documentos sin embeddings, o con embeddings generados antes
+    /// de ese momento, necesitan reindexarse.
+    pub fn needs_reindex(&self, model_changed_at: u64) -> bool {
+        match self.embedded_at {
+            Some(embedded_at) => !self.is_indexed || embedded_at < model_changed_at,
+            None => true,
+        }
+    }
+}
+
+/// Campos que tenía `Document` en la primera versión guardada de la
+/// biblioteca; todos los registros tienen al menos estos
+pub(crate) const BASE_FIELD_COUNT: usize = 6;
+
+/// Lee los campos de `Document` en el orden de la struct, los que haya
+///
+/// Define `FIELD_COUNT` y `read_prefix`, así la lista de campos está en un
+/// solo lugar. Los campos nuevos se agregan siempre al final.
+macro_rules! document_fields {
+    ($($field:ident),* $(,)?) => {
+        /// Cantidad de campos que tiene `Document` hoy
+        pub(crate) const FIELD_COUNT: usize = [$(stringify!($field)),*].len();
+
+        /// Lee los primeros `count` campos; el resto queda con su valor por
+        /// defecto
+        fn read_prefix<'de, A: SeqAccess<'de>>(
+            seq: &mut A,
+            count: usize,
+        ) -> Result<Document, A::Error> {
+            let mut doc = Document::new(String::new(), String::new(), String::new(), 0);
+            let mut read = 0;
+            $(
+                if read == count {
+                    return Ok(doc);
+                }
+                doc.$field = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(read, &"a document"))?;
+                read += 1;
+            )*
+            Ok(doc)
+        }
+    };
+}
+
+document_fields!(
+    id,
+    name,
+    file_path,
+    page_count,
+    created_at,
+    is_indexed,
+    sha256,
+    chunk_count,
+    embedded_at,
+    last_accessed,
+    summary,
+    language,
+    tags,
+    title,
+    author,
+    subject,
+    file_size,
+    doc_type,
+    empty_pages,
+    deleted_at,
+);
+
+/// Deserializa un `Document` guardado cuando la struct tenía solo sus
+/// primeros `.0` campos
+///
+/// Sirve para formatos que no guardan los nombres de los campos (bincode):
+/// un registro viejo es un prefijo del actual.
+pub(crate) struct DocumentPrefix(pub usize);
+
+impl<'de> DeserializeSeed<'de> for DocumentPrefix {
+    type Value = Document;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Document, D::Error> {
+        deserializer.deserialize_tuple(self.0, self)
+    }
+}
+
+impl<'de> Visitor<'de> for DocumentPrefix {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a document with {} fields", self.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Document, A::Error> {
+        read_prefix(&mut seq, self.0)
     }
 }
 
@@ -98,6 +386,9 @@ mod tests {
         assert!(!doc.is_indexed);
         doc.mark_as_indexed();
         assert!(doc.is_indexed);
+        doc.mark_as_unindexed();
+        assert!(!doc.is_indexed);
+        assert!(doc.needs_reindex(0));
     }
 
     #[test]
@@ -144,4 +435,126 @@ mod tests {
         assert_eq!(original.created_at, restored.created_at);
         assert_eq!(original.is_indexed, restored.is_indexed);
     }
+
+    #[test]
+    fn test_document_with_sha256() {
+        let doc = Document::new(
+            "doc-123".to_string(),
+            "documento.pdf".to_string(),
+            "/ruta/documento.pdf".to_string(),
+            10,
+        );
+        assert!(doc.sha256.is_none());
+
+        let doc = doc.with_sha256("abc123".to_string());
+        assert_eq!(doc.sha256.as_deref(), Some("abc123"));
+
+        // JSON sin el campo (registros antiguos) debe seguir deserializando
+        let legacy = r#"{"id":"d","name":"n","file_path":"/p","page_count":1,"created_at":1,"is_indexed":false}"#;
+        let restored: Document = serde_json::from_str(legacy).unwrap();
+        assert!(restored.sha256.is_none());
+    }
+
+    #[test]
+    fn test_document_summary_bincode_roundtrip() {
+        let doc = Document::new(
+            "doc-123".to_string(),
+            "documento.pdf".to_string(),
+            "/ruta/documento.pdf".to_string(),
+            10,
+        );
+        assert!(doc.summary.is_none());
+        let restored: Document = bincode::deserialize(&bincode::serialize(&doc).unwrap()).unwrap();
+        assert_eq!(restored, doc);
+
+        let doc = doc.set_summary("Apuntes de álgebra lineal.".to_string());
+        let restored: Document = bincode::deserialize(&bincode::serialize(&doc).unwrap()).unwrap();
+        assert_eq!(
+            restored.summary.as_deref(),
+            Some("Apuntes de álgebra lineal.")
+        );
+        assert_eq!(restored, doc);
+
+        let legacy = r#"{"id":"d","name":"n","file_path":"/p","page_count":1,"created_at":1,"is_indexed":false}"#;
+        let restored: Document = serde_json::from_str(legacy).unwrap();
+        assert!(restored.summary.is_none());
+    }
+
+    #[test]
+    fn test_document_id_from_file() {
+        let dir = std::env::temp_dir().join(format!("test_id_from_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("fixture.pdf");
+        std::fs::write(&path, b"%PDF-1.4 contenido de prueba").unwrap();
+
+        // El mismo archivo debe producir siempre el mismo ID
+        let id1 = Document::id_from_file(&path).unwrap();
+        let id2 = Document::id_from_file(&path).unwrap();
+        assert_eq!(id1, id2);
+        assert_eq!(id1.len(), 64);
+
+        // Cambiar un solo byte debe producir un ID distinto
+        std::fs::write(&path, b"%PDF-1.4 contenido de prueba!").unwrap();
+        let id3 = Document::id_from_file(&path).unwrap();
+        assert_ne!(id1, id3);
+
+        // Un archivo inexistente debe retornar error
+        assert!(Document::id_from_file(dir.join("no_existe.pdf")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_document_embedded_at_and_needs_reindex() {
+        let mut doc = Document::new(
+            "doc-123".to_string(),
+            "documento.pdf".to_string(),
+            "/ruta/documento.pdf".to_string(),
+            10,
+        );
+        assert!(doc.embedded_at.is_none());
+        assert!(doc.needs_reindex(0));
+
+        doc.mark_as_indexed();
+        let embedded_at = doc.embedded_at.expect("Debe registrar el timestamp");
+        assert!(embedded_at >= doc.created_at);
+
+        // Un modelo configurado antes de indexar no requiere reindexar
+        assert!(!doc.needs_reindex(embedded_at));
+        // Un cambio de modelo posterior marca el documento para reindexar
+        assert!(doc.needs_reindex(embedded_at + 60));
+    }
+
+    #[test]
+    fn test_document_age() {
+        let mut doc = Document::new(
+            "doc-123".to_string(),
+            "documento.pdf".to_string(),
+            "/ruta/documento.pdf".to_string(),
+            1,
+        );
+        assert!(doc.age_seconds() < 5);
+        assert_eq!(
+            doc.created_datetime()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            doc.created_at
+        );
+
+        doc.created_at -= 3 * 24 * 60 * 60;
+        assert!(doc.age_seconds() >= 3 * 24 * 60 * 60);
+
+        // Creado "en el futuro" (reloj atrasado): no se desborda
+        doc.created_at = unix_now() + 3600;
+        assert_eq!(doc.age_seconds(), 0);
+    }
+
+    #[test]
+    fn test_field_count_matches_struct() {
+        // Si falla, falta agregar el campo nuevo a `document_fields!`
+        let doc = Document::new("d".into(), "n".into(), "/p".into(), 1);
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), FIELD_COUNT);
+    }
 }
@@ -1,10 +1,14 @@
 // Módulo que contiene todos los modelos de datos de la aplicación
 
-pub mod document;
+pub mod attachment;
 pub mod chunk;
+pub mod chunk_metadata;
+pub mod document;
+pub mod tokens;
 
 // Re-exportamos los tipos principales para facilitar su uso
-pub use document::Document;
-pub use chunk::Chunk;
-
-
+pub use attachment::Attachment;
+pub use chunk::{Chunk, ChunkOrder, ChunkValidationError};
+pub use chunk_metadata::{ChunkMetadata, RAW_METADATA_KEY};
+pub use document::{DocType, Document};
+pub use tokens::{token_count, HeuristicTokenCounter, TokenCounter, CHARS_PER_TOKEN};
@@ -0,0 +1,69 @@
+/// Caracteres por token que asume la estimación heurística
+///
+/// Es el promedio habitual de los tokenizadores BPE en texto en inglés o
+/// español; sirve para presupuestos, no para contar exacto.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Cuenta (o estima) los tokens de un texto
+///
+/// Permite cambiar la heurística por el tokenizador real del modelo sin
+/// tocar a quienes la usan.
+pub trait TokenCounter {
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Estimación de `CHARS_PER_TOKEN` caracteres por token, palabra por palabra
+///
+/// Cada palabra (separada por espacios) cuenta al menos un token, porque
+/// los tokenizadores casi nunca juntan dos palabras en uno; así un texto de
+/// palabras cortas no queda subestimado. Los espacios no cuentan.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenCounter;
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        text.split_whitespace()
+            .map(|word| word.chars().count().div_ceil(CHARS_PER_TOKEN))
+            .sum()
+    }
+}
+
+/// Tokens estimados de `text` con `HeuristicTokenCounter`
+pub fn token_count(text: &str) -> usize {
+    HeuristicTokenCounter.count(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_count_known_strings() {
+        assert_eq!(token_count(""), 0);
+        assert_eq!(token_count("   \n\t"), 0);
+        assert_eq!(token_count("hola"), 1);
+        // "mundo" tiene 5 caracteres: 2 tokens
+        assert_eq!(token_count("Hola mundo"), 3);
+        // Palabras cortas: una por token aunque sumen pocos caracteres
+        assert_eq!(token_count("a b c d e f g h"), 8);
+        // Se cuentan caracteres, no bytes
+        assert_eq!(token_count("añño"), 1);
+        assert_eq!(token_count("electroencefalografía"), 6);
+    }
+
+    #[test]
+    fn test_token_counter_is_pluggable() {
+        struct Words;
+        impl TokenCounter for Words {
+            fn count(&self, text: &str) -> usize {
+                text.split_whitespace().count()
+            }
+        }
+        let counters: [&dyn TokenCounter; 2] = [&HeuristicTokenCounter, &Words];
+        let counts: Vec<usize> = counters
+            .iter()
+            .map(|c| c.count("electroencefalografía de rutina"))
+            .collect();
+        assert_eq!(counts, [6 + 1 + 2, 3]);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::models::Attachment;
+use crate::services::database::{
+    decode, encode, ensure_writable, flush_after_write, open_documents_tree, open_tree, DbError,
+};
+use crate::services::keys::ATTACHMENTS_TREE;
+use sled;
+use std::sync::Arc;
+
+/// Tamaño máximo permitido por adjunto (10 MiB)
+pub const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Árbol document_id -> lista de adjuntos del documento
+pub(crate) fn open_attachments_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, ATTACHMENTS_TREE)
+}
+
+fn read_list(tree: &sled::Tree, document_id: &str) -> Result<Vec<Attachment>, DbError> {
+    match tree.get(document_id.as_bytes())? {
+        Some(bytes) => decode(&bytes),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn write_list(
+    db: &Arc<sled::Db>,
+    tree: &sled::Tree,
+    document_id: &str,
+    list: &[Attachment],
+) -> Result<(), DbError> {
+    if list.is_empty() {
+        tree.remove(document_id.as_bytes())?;
+    } else {
+        tree.insert(document_id.as_bytes(), encode(list)?)?;
+    }
+    flush_after_write(db)
+}
+
+/// Agrega un adjunto a un documento existente
+///
+/// Si ya había un adjunto con el mismo nombre se reemplaza. Los adjuntos que
+/// superan `MAX_ATTACHMENT_BYTES` se rechazan con `DbError::InvalidInput`.
+pub fn add_attachment(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    attachment: Attachment,
+) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    if attachment.size() > MAX_ATTACHMENT_BYTES {
+        return Err(DbError::InvalidInput(format!(
+            "attachment {} is {} bytes, limit is {}",
+            attachment.name,
+            attachment.size(),
+            MAX_ATTACHMENT_BYTES
+        )));
+    }
+    if !open_documents_tree(db)?.contains_key(document_id.as_bytes())? {
+        return Err(DbError::NotFound(document_id.to_string()));
+    }
+
+    let tree = open_attachments_tree(db)?;
+    let mut list = read_list(&tree, document_id)?;
+    list.retain(|a| a.name != attachment.name);
+    list.push(attachment);
+    write_list(db, &tree, document_id, &list)
+}
+
+/// Lista los adjuntos de un documento (vacío si no tiene)
+pub fn list_attachments(db: &Arc<sled::Db>, document_id: &str) -> Result<Vec<Attachment>, DbError> {
+    read_list(&open_attachments_tree(db)?, document_id)
+}
+
+/// Quita un adjunto por nombre; retorna `false` si no existía
+pub fn remove_attachment(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    name: &str,
+) -> Result<bool, DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_attachments_tree(db)?;
+    let mut list = read_list(&tree, document_id)?;
+    let before = list.len();
+    list.retain(|a| a.name != name);
+    if list.len() == before {
+        return Ok(false);
+    }
+    write_list(db, &tree, document_id, &list)?;
+    Ok(true)
+}
+
+/// Elimina todos los adjuntos de un documento (usado al borrarlo)
+pub(crate) fn delete_attachments(db: &Arc<sled::Db>, document_id: &str) -> Result<(), DbError> {
+    open_attachments_tree(db)?.remove(document_id.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Document;
+    use crate::services::database::{delete_document, insert_document};
+    use crate::services::test_support::{sample_document, temp_db};
+
+    #[test]
+    fn test_attachments_lifecycle() {
+        let (db, path) = temp_db("test_attachments");
+
+        let doc = Document::new(
+            "doc-1".to_string(),
+            "libro.pdf".to_string(),
+            "/tmp/libro.pdf".to_string(),
+            1,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        let cover = Attachment::new("portada.png".into(), "image/png".into(), vec![0x89, 0x50]);
+        let bib = Attachment::new(
+            "refs.bib".into(),
+            "text/x-bibtex".into(),
+            b"@book{}".to_vec(),
+        );
+        add_attachment(&db, "doc-1", cover.clone()).unwrap();
+        add_attachment(&db, "doc-1", bib.clone()).unwrap();
+
+        let list = list_attachments(&db, "doc-1").unwrap();
+        assert_eq!(list, vec![cover, bib]);
+
+        assert!(remove_attachment(&db, "doc-1", "refs.bib").unwrap());
+        assert!(!remove_attachment(&db, "doc-1", "refs.bib").unwrap());
+        assert_eq!(list_attachments(&db, "doc-1").unwrap().len(), 1);
+
+        // Borrar el documento borra sus adjuntos
+        delete_document(&db, "doc-1").unwrap();
+        assert!(list_attachments(&db, "doc-1").unwrap().is_empty());
+        assert!(open_attachments_tree(&db).unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_attachment_limits() {
+        let (db, path) = temp_db("test_attachment_limits");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        let huge = Attachment::new(
+            "enorme.bin".into(),
+            "application/octet-stream".into(),
+            vec![0; MAX_ATTACHMENT_BYTES + 1],
+        );
+        assert!(matches!(
+            add_attachment(&db, "doc-1", huge),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        let small = Attachment::new("a.txt".into(), "text/plain".into(), vec![1]);
+        assert!(matches!(
+            add_attachment(&db, "no-existe", small),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::services::database::{
+    blob_storage_enabled, ensure_writable, flush_after_write, open_tree, DbError,
+};
+use crate::services::keys::{document_prefix, parse_segment_key, segment_key, BLOBS_TREE};
+use sled;
+use std::sync::Arc;
+
+/// Tamaño máximo de cada segmento de un blob (1 MiB)
+///
+/// Los archivos se guardan partidos para no pedirle a sled que maneje un
+/// único valor de cientos de MB.
+pub const BLOB_SEGMENT_SIZE: usize = 1024 * 1024;
+
+pub(crate) fn open_blobs_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, BLOBS_TREE)
+}
+
+/// Retorna las claves de todos los segmentos del blob de un documento
+///
+/// Se verifica que lo que sigue al prefijo sea solo el número de segmento
+/// para no mezclar blobs de documentos cuyo id empieza igual (p. ej. "a" y "a:b").
+fn segment_keys(tree: &sled::Tree, doc_id: &str) -> Result<Vec<sled::IVec>, DbError> {
+    let prefix = document_prefix(doc_id);
+    let mut keys = Vec::new();
+    for k in tree.scan_prefix(prefix.as_bytes()).keys() {
+        let k = k?;
+        if parse_segment_key(&k).is_some_and(|(id, _)| id == doc_id) {
+            keys.push(k);
+        }
+    }
+    Ok(keys)
+}
+
+/// Guarda una copia del archivo original de un documento
+///
+/// Los bytes se dividen en segmentos de `BLOB_SEGMENT_SIZE` con claves
+/// `{doc_id}:{segmento}`. Si el documento ya tenía un blob, se reemplaza
+/// completo en un único batch.
+pub fn store_document_blob(db: &Arc<sled::Db>, doc_id: &str, bytes: &[u8]) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_blobs_tree(db)?;
+
+    let mut batch = sled::Batch::default();
+    for key in segment_keys(&tree, doc_id)? {
+        batch.remove(key);
+    }
+    if bytes.is_empty() {
+        // Un segmento vacío distingue "blob vacío" de "sin blob"
+        batch.insert(segment_key(doc_id, 0).as_bytes(), &[][..]);
+    }
+    for (i, segment) in bytes.chunks(BLOB_SEGMENT_SIZE).enumerate() {
+        batch.insert(segment_key(doc_id, i).as_bytes(), segment);
+    }
+
+    tree.apply_batch(batch)?;
+    flush_after_write(db)
+}
+
+/// Guarda la copia del archivo solo si el usuario activó el guardado de blobs
+///
+/// Es lo que debe usar el flujo de importación; retorna `true` si se guardó.
+/// `store_document_blob` sigue disponible para guardar de forma explícita.
+pub fn store_document_blob_if_enabled(
+    db: &Arc<sled::Db>,
+    doc_id: &str,
+    bytes: &[u8],
+) -> Result<bool, DbError> {
+    if !blob_storage_enabled(db) {
+        return Ok(false);
+    }
+    store_document_blob(db, doc_id, bytes)?;
+    Ok(true)
+}
+
+/// Lee el archivo original guardado para un documento, si existe
+pub fn get_document_blob(db: &Arc<sled::Db>, doc_id: &str) -> Result<Option<Vec<u8>>, DbError> {
+    let tree = open_blobs_tree(db)?;
+    let keys = segment_keys(&tree, doc_id)?;
+    if keys.is_empty() {
+        return Ok(None);
+    }
+
+    let mut out = Vec::new();
+    for key in keys {
+        let segment = tree
+            .get(&key)?
+            .ok_or_else(|| DbError::NotFound(String::from_utf8_lossy(&key).into_owned()))?;
+        out.extend_from_slice(&segment);
+    }
+    Ok(Some(out))
+}
+
+/// Elimina el blob de un documento; retorna cuántos segmentos se borraron
+pub fn delete_document_blob(db: &Arc<sled::Db>, doc_id: &str) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_blobs_tree(db)?;
+    let keys = segment_keys(&tree, doc_id)?;
+
+    let mut batch = sled::Batch::default();
+    for key in &keys {
+        batch.remove(key);
+    }
+    tree.apply_batch(batch)?;
+    Ok(keys.len())
+}
+
+/// Bytes ocupados por todos los blobs guardados (sin contar claves)
+///
+/// Permite mostrar el almacenamiento de archivos por separado del resto de
+/// la BD en las estadísticas.
+pub fn blob_storage_bytes(db: &Arc<sled::Db>) -> Result<u64, DbError> {
+    let tree = open_blobs_tree(db)?;
+    let mut total = 0u64;
+    for item in tree.iter() {
+        let (_k, v) = item?;
+        total += v.len() as u64;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{delete_document, insert_document, set_blob_storage};
+    use crate::services::test_support::{sample_document, temp_db};
+
+    #[test]
+    fn test_blob_roundtrip_multi_segment() {
+        let (db, path) = temp_db("test_blob_roundtrip");
+
+        // 2.5 segmentos, con contenido no uniforme para detectar desorden
+        let payload: Vec<u8> = (0..BLOB_SEGMENT_SIZE * 5 / 2)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        store_document_blob(&db, "doc-1", &payload).unwrap();
+
+        let tree = open_blobs_tree(&db).unwrap();
+        assert_eq!(segment_keys(&tree, "doc-1").unwrap().len(), 3);
+
+        let restored = get_document_blob(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(restored, payload);
+        assert_eq!(blob_storage_bytes(&db).unwrap(), payload.len() as u64);
+
+        // Reemplazar por un blob más chico no debe dejar segmentos viejos
+        store_document_blob(&db, "doc-1", b"pdf chico").unwrap();
+        assert_eq!(
+            get_document_blob(&db, "doc-1").unwrap().unwrap(),
+            b"pdf chico"
+        );
+        assert_eq!(blob_storage_bytes(&db).unwrap(), 9);
+
+        assert!(get_document_blob(&db, "otro").unwrap().is_none());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_blob_storage_is_opt_in() {
+        let (db, path) = temp_db("test_blob_opt_in");
+
+        // Desactivado por defecto: no se guarda nada
+        assert!(!store_document_blob_if_enabled(&db, "doc-1", b"%PDF-1.4").unwrap());
+        assert!(get_document_blob(&db, "doc-1").unwrap().is_none());
+
+        set_blob_storage(&db, true);
+        assert!(store_document_blob_if_enabled(&db, "doc-1", b"%PDF-1.4").unwrap());
+        assert_eq!(
+            get_document_blob(&db, "doc-1").unwrap().unwrap(),
+            b"%PDF-1.4"
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_blob_prefix_does_not_mix_documents() {
+        let (db, path) = temp_db("test_blob_prefix");
+
+        store_document_blob(&db, "a", b"uno").unwrap();
+        store_document_blob(&db, "a:b", b"dos").unwrap();
+
+        assert_eq!(get_document_blob(&db, "a").unwrap().unwrap(), b"uno");
+        assert_eq!(get_document_blob(&db, "a:b").unwrap().unwrap(), b"dos");
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_delete_document_removes_blob() {
+        let (db, path) = temp_db("test_blob_cascade");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        store_document_blob(&db, "doc-1", &vec![7u8; BLOB_SEGMENT_SIZE + 10]).unwrap();
+
+        delete_document(&db, "doc-1").unwrap();
+        assert!(get_document_blob(&db, "doc-1").unwrap().is_none());
+        assert_eq!(blob_storage_bytes(&db).unwrap(), 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
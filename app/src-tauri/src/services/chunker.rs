@@ -0,0 +1,1504 @@
+use crate::models::{Chunk, HeuristicTokenCounter, TokenCounter, CHARS_PER_TOKEN};
+use crate::services::embeddings::EMBED_BATCH_SIZE;
+use crate::services::language::tag_chunk_languages;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use unicode_normalization::UnicodeNormalization;
+
+/// Cómo dividir el texto de un documento en chunks
+///
+/// Cada tipo de documento puede usar una estrategia distinta (un paper por
+/// párrafos, un libro escaneado por tamaño fijo, etc.). Los tamaños se miden
+/// en caracteres, no en bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// Ventanas de `size` caracteres; cada una repite los últimos `overlap`
+    /// caracteres de la anterior para no cortar ideas por la mitad
+    FixedChars { size: usize, overlap: usize },
+    /// Oraciones completas agrupadas hasta `max_chars`; una oración más
+    /// larga que el límite se corta en ventanas de `max_chars`
+    BySentence { max_chars: usize },
+    /// Un chunk por párrafo (bloques separados por líneas en blanco)
+    ByParagraph,
+    /// Párrafos completos agrupados hasta `max_chars`; un párrafo más largo
+    /// que el límite se divide por oraciones como `BySentence`
+    Paragraph { max_chars: usize },
+    /// Divisor recursivo al estilo de LangChain: corta por el primer
+    /// separador de `separators` que aparezca en el texto, vuelve a dividir
+    /// con los siguientes los tramos que no entran en `max_chars` y junta
+    /// tramos vecinos repitiendo hasta `overlap_chars` del final del chunk
+    /// anterior. Un separador vacío corta por caracteres.
+    Recursive {
+        separators: Vec<String>,
+        max_chars: usize,
+        overlap_chars: usize,
+    },
+    /// Palabras completas agrupadas hasta `max_tokens` tokens estimados
+    /// (ver `token_count`); cada chunk repite las últimas palabras del
+    /// anterior que sumen a lo sumo `overlap_tokens`
+    TokenBudget {
+        max_tokens: usize,
+        overlap_tokens: usize,
+    },
+}
+
+/// Separadores por defecto de `ChunkStrategy::Recursive` (los mismos que
+/// `RecursiveCharacterTextSplitter`): párrafos, líneas, palabras y caracteres
+pub const RECURSIVE_SEPARATORS: [&str; 4] = ["\n\n", "\n", " ", ""];
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        ChunkStrategy::FixedChars {
+            size: 1000,
+            overlap: 200,
+        }
+    }
+}
+
+/// Cómo se divide un documento al indexarlo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    pub strategy: ChunkStrategy,
+    /// Chunks que se guardan y se envían al embedder en cada lote
+    pub batch_size: usize,
+    /// Pasar el texto por `normalize_text` antes de cortarlo; viene
+    /// desactivado para no cambiar los chunks de quien no lo pide
+    pub normalize: bool,
+    /// Si se indica, los chunks más cortos se unen a un vecino con
+    /// `merge_small_chunks`
+    pub min_chars: Option<usize>,
+    /// Guardar una sola vez los chunks de texto idéntico dentro de un
+    /// documento (ver `dedupe_chunks`); viene desactivado, así el texto se
+    /// conserva tal cual, con encabezados y pies repetidos
+    pub dedupe: bool,
+    /// Detectar el idioma de cada chunk (ver `tag_chunk_languages`); viene
+    /// desactivado para no cambiar los chunks de quien no lo pide
+    pub detect_language: bool,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkStrategy::default(),
+            batch_size: EMBED_BATCH_SIZE,
+            normalize: false,
+            min_chars: None,
+            dedupe: false,
+            detect_language: false,
+        }
+    }
+}
+
+impl ChunkingConfig {
+    /// Divisor recursivo con `RECURSIVE_SEPARATORS` y los tamaños de la
+    /// estrategia por defecto
+    pub fn recursive_default() -> Self {
+        Self {
+            strategy: ChunkStrategy::Recursive {
+                separators: RECURSIVE_SEPARATORS.map(String::from).to_vec(),
+                max_chars: 1000,
+                overlap_chars: 200,
+            },
+            ..Self::default()
+        }
+    }
+}
+
+/// Chunk recién cortado, antes de tener id y documento
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkDraft {
+    pub text: String,
+    /// Posición del chunk dentro del texto cortado
+    pub index: usize,
+    /// Primer carácter (no byte) del texto original que cubre el chunk
+    pub start_char: usize,
+    /// Carácter siguiente al último que cubre el chunk
+    pub end_char: usize,
+    /// Primera página que cubre el chunk (ver `chunk_pages`)
+    pub start_page: usize,
+    /// Última página que cubre el chunk
+    pub end_page: usize,
+}
+
+impl ChunkDraft {
+    /// Convierte el borrador en el chunk `index` del documento
+    ///
+    /// `page_number` es `start_page`; `end_page` y los offsets guardan el
+    /// resto de la ubicación.
+    pub fn into_chunk(self, document_id: &str) -> Chunk {
+        Chunk::new(
+            Chunk::deterministic_id(document_id, self.index),
+            document_id.to_string(),
+            self.text,
+            self.index,
+            self.start_page,
+        )
+        .with_end_page(self.end_page)
+        .with_offsets(self.start_char, self.end_char)
+    }
+}
+
+/// Texto extraído de una página
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageText {
+    pub page_number: usize,
+    pub text: String,
+}
+
+/// Separador entre páginas al cortar con `chunk_pages`
+const PAGE_SEPARATOR: &str = "\n\n";
+
+/// Divide un documento entero según `config.strategy`, permitiendo que un
+/// chunk cruce de una página a otra
+///
+/// Las páginas se unen con una línea en blanco (así las estrategias por
+/// párrafo u oración cortan entre páginas) y cada borrador registra la
+/// primera y la última página que cubre. Los offsets en caracteres son
+/// sobre ese texto unido (con `config.normalize`, el de las páginas ya
+/// normalizadas).
+pub fn chunk_pages(pages: &[PageText], config: &ChunkingConfig) -> Vec<ChunkDraft> {
+    let mut text = String::new();
+    // Primer carácter de cada página dentro del texto unido
+    let mut page_starts = Vec::with_capacity(pages.len());
+    let mut chars = 0;
+    for (i, page) in pages.iter().enumerate() {
+        if i > 0 {
+            text.push_str(PAGE_SEPARATOR);
+            chars += PAGE_SEPARATOR.chars().count();
+        }
+        page_starts.push(chars);
+        let page_text = prepare(&page.text, config);
+        text.push_str(&page_text);
+        chars += page_text.chars().count();
+    }
+
+    let page_at = |char_pos: usize| {
+        let i = page_starts.partition_point(|&start| start <= char_pos);
+        pages[i.saturating_sub(1)].page_number
+    };
+    let drafts = split(&text, &config.strategy)
+        .into_iter()
+        .map(|mut draft| {
+            draft.start_page = page_at(draft.start_char);
+            draft.end_page = page_at(draft.end_char.saturating_sub(1).max(draft.start_char));
+            draft
+        })
+        .collect();
+    merge_if_configured(drafts, config, &text)
+}
+
+/// Divide `text` según `config.strategy`, con la ubicación de cada chunk
+///
+/// Los cortes caen siempre entre caracteres, nunca dentro de una secuencia
+/// UTF-8. Con cualquier estrategia el texto de cada chunk es exactamente
+/// `text[start_char..end_char]` (en caracteres). Nunca retorna chunks
+/// vacíos o solo con espacios (los que `Chunk::is_empty` consideraría vacíos).
+/// El texto se toma como una sola página: `start_page` y `end_page` son 1.
+/// Con `config.normalize` el texto pasa antes por `normalize_text` y los
+/// offsets son sobre el texto normalizado.
+pub fn chunk_text(text: &str, config: &ChunkingConfig) -> Vec<ChunkDraft> {
+    let text = prepare(text, config);
+    let drafts = split(&text, &config.strategy);
+    merge_if_configured(drafts, config, &text)
+}
+
+/// Aplica `merge_small_chunks` si `config.min_chars` lo pide; cada chunk
+/// unido toma como texto su tramo de `text`, con lo que había entre ellos
+fn merge_if_configured(
+    drafts: Vec<ChunkDraft>,
+    config: &ChunkingConfig,
+    text: &str,
+) -> Vec<ChunkDraft> {
+    let Some(min_chars) = config.min_chars else {
+        return drafts;
+    };
+    let offsets = CharOffsets::new(text);
+    let mut drafts = merge_small_chunks(drafts, min_chars);
+    for draft in &mut drafts {
+        draft.text =
+            text[offsets.byte_at(draft.start_char)..offsets.byte_at(draft.end_char)].to_string();
+    }
+    drafts
+}
+
+/// Une cada chunk de menos de `min_chars` caracteres con el anterior (o con
+/// el siguiente, si es el primero)
+///
+/// Pensado para títulos o números de página sueltos, que gastan una llamada
+/// al embedder y ensucian la búsqueda. Los textos se unen con un salto de
+/// línea; el resultado cubre desde el primer hasta el último carácter y
+/// página de los chunks unidos, y los índices se renumeran desde 0. Si todo
+/// el texto es más corto que `min_chars` queda un único chunk.
+pub fn merge_small_chunks(drafts: Vec<ChunkDraft>, min_chars: usize) -> Vec<ChunkDraft> {
+    let is_small = |draft: &ChunkDraft| draft.text.chars().count() < min_chars;
+    let mut out: Vec<ChunkDraft> = Vec::with_capacity(drafts.len());
+    for draft in drafts {
+        // Un chunk chico se une al anterior; si el anterior es el primero y
+        // quedó chico, es él quien absorbe al siguiente
+        let only_one = out.len() == 1;
+        match out.last_mut() {
+            Some(last) if is_small(&draft) || (only_one && is_small(last)) => {
+                last.text.push('\n');
+                last.text.push_str(&draft.text);
+                last.start_char = last.start_char.min(draft.start_char);
+                last.end_char = last.end_char.max(draft.end_char);
+                last.start_page = last.start_page.min(draft.start_page);
+                last.end_page = last.end_page.max(draft.end_page);
+            }
+            _ => out.push(draft),
+        }
+    }
+    for (index, draft) in out.iter_mut().enumerate() {
+        draft.index = index;
+    }
+    out
+}
+
+/// El texto a cortar: normalizado o tal cual según `config.normalize`
+fn prepare<'a>(text: &'a str, config: &ChunkingConfig) -> Cow<'a, str> {
+    if config.normalize {
+        Cow::Owned(normalize_text(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Limpia el texto extraído de un PDF antes de cortarlo
+///
+/// - Normaliza a Unicode NFC ("a" + acento combinado pasa a ser "á").
+/// - Une las palabras cortadas con guion al final de línea
+///   ("informa-\nción" → "información") si la línea siguiente sigue en
+///   minúscula.
+/// - Convierte "\r\n" y "\r" en "\n" y quita los demás caracteres de
+///   control.
+/// - Reduce cada tramo de espacios o tabs a un espacio, quita los espacios
+///   al borde de las líneas y del texto y deja a lo sumo una línea en
+///   blanco seguida (así se conservan los párrafos).
+pub fn normalize_text(text: &str) -> String {
+    let chars: Vec<char> = text.nfc().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut pending_space = false;
+    let mut newlines = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '-' if !pending_space
+                && newlines == 0
+                && out.chars().next_back().is_some_and(char::is_alphabetic) =>
+            {
+                if let Some(next) = wrapped_word_end(&chars, i) {
+                    i = next;
+                    continue;
+                }
+            }
+            '\r' | '\n' => {
+                if c == '\r' && chars.get(i) == Some(&'\n') {
+                    i += 1;
+                }
+                pending_space = false;
+                newlines += 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                pending_space = true;
+                continue;
+            }
+            c if c.is_control() => continue,
+            _ => {}
+        }
+        if !out.is_empty() {
+            match newlines {
+                0 if pending_space => out.push(' '),
+                0 => {}
+                1 => out.push('\n'),
+                _ => out.push_str("\n\n"),
+            }
+        }
+        pending_space = false;
+        newlines = 0;
+        out.push(c);
+    }
+    out
+}
+
+/// Si tras un guion en `chars[from - 1]` viene un salto de línea y la
+/// palabra sigue en minúscula, retorna dónde empieza esa continuación
+fn wrapped_word_end(chars: &[char], from: usize) -> Option<usize> {
+    let is_blank = |c: &char| *c == ' ' || *c == '\t';
+    let mut j = from;
+    while chars.get(j).is_some_and(is_blank) {
+        j += 1;
+    }
+    if chars.get(j) == Some(&'\r') {
+        j += 1;
+    }
+    if chars.get(j) != Some(&'\n') {
+        return None;
+    }
+    j += 1;
+    while chars.get(j).is_some_and(is_blank) {
+        j += 1;
+    }
+    chars.get(j).is_some_and(|c| c.is_lowercase()).then_some(j)
+}
+
+/// Corta `text` (ya preparado) según `strategy`
+fn split(text: &str, strategy: &ChunkStrategy) -> Vec<ChunkDraft> {
+    let pieces = match strategy {
+        ChunkStrategy::FixedChars { size, overlap } => fixed_chars(text, 0, *size, *overlap),
+        ChunkStrategy::BySentence { max_chars } => by_sentence(text, *max_chars),
+        ChunkStrategy::ByParagraph => by_paragraph(text),
+        ChunkStrategy::Paragraph { max_chars } => merged_paragraphs(text, *max_chars),
+        ChunkStrategy::Recursive {
+            separators,
+            max_chars,
+            overlap_chars,
+        } => {
+            let mut splitter = RecursiveSplitter {
+                text,
+                max_chars: (*max_chars).max(1),
+                overlap_chars: *overlap_chars,
+                out: Vec::new(),
+            };
+            splitter.split(0, text.len(), separators);
+            splitter.out
+        }
+        ChunkStrategy::TokenBudget {
+            max_tokens,
+            overlap_tokens,
+        } => token_budget(text, *max_tokens, *overlap_tokens, &HeuristicTokenCounter),
+    };
+    let offsets = CharOffsets::new(text);
+    pieces
+        .into_iter()
+        .filter(|p| !p.text.trim().is_empty())
+        .enumerate()
+        .map(|(index, p)| ChunkDraft {
+            text: p.text,
+            index,
+            start_char: offsets.char_at(p.start),
+            end_char: offsets.char_at(p.end),
+            start_page: 1,
+            end_page: 1,
+        })
+        .collect()
+}
+
+/// Divide `text` según `strategy`
+///
+/// Igual que `chunk_text`, pero solo con el texto de cada chunk.
+pub fn chunk(text: &str, strategy: &ChunkStrategy) -> Vec<String> {
+    let config = ChunkingConfig {
+        strategy: strategy.clone(),
+        ..ChunkingConfig::default()
+    };
+    chunk_text(text, &config)
+        .into_iter()
+        .map(|d| d.text)
+        .collect()
+}
+
+/// Divide el texto extraído de un documento (una entrada por página) en chunks
+///
+/// Los índices son correlativos en todo el documento y los ids salen de
+/// `Chunk::deterministic_id`, así reimportar el mismo archivo da los mismos
+/// chunks. Un chunk no cruza páginas. Los offsets de cada chunk son sobre
+/// `document_text`.
+pub fn build_chunks(document_id: &str, pages: &[String], strategy: &ChunkStrategy) -> Vec<Chunk> {
+    let config = ChunkingConfig {
+        strategy: strategy.clone(),
+        ..ChunkingConfig::default()
+    };
+    build_chunks_with(document_id, pages, &config)
+}
+
+/// Igual que `build_chunks`, respetando el resto de `config` (`normalize`,
+/// `min_chars`, `dedupe` y `detect_language`)
+pub fn build_chunks_with(
+    document_id: &str,
+    pages: &[String],
+    config: &ChunkingConfig,
+) -> Vec<Chunk> {
+    let mut out = Vec::new();
+    // Primer carácter de la página dentro de `document_text`
+    let mut base = 0;
+    for (page, text) in pages.iter().enumerate() {
+        let text = prepare(text, config);
+        let drafts = split(&text, &config.strategy);
+        for draft in merge_if_configured(drafts, config, &text) {
+            let index = out.len();
+            out.push(
+                Chunk::new(
+                    Chunk::deterministic_id(document_id, index),
+                    document_id.to_string(),
+                    draft.text,
+                    index,
+                    page + 1,
+                )
+                .with_offsets(base + draft.start_char, base + draft.end_char),
+            );
+        }
+        base += text.chars().count() + PAGE_SEPARATOR.chars().count();
+    }
+    if config.dedupe {
+        out = dedupe_chunks(out).0;
+    }
+    if config.detect_language {
+        tag_chunk_languages(&mut out);
+    }
+    out
+}
+
+/// Texto completo del documento sobre el que se miden los offsets de
+/// `build_chunks`: las páginas (preparadas según `config.normalize`) unidas
+/// con una línea en blanco, igual que en `chunk_pages`
+pub fn document_text(pages: &[String], config: &ChunkingConfig) -> String {
+    pages
+        .iter()
+        .map(|page| prepare(page, config))
+        .collect::<Vec<_>>()
+        .join(PAGE_SEPARATOR)
+}
+
+/// Quita los chunks cuyo texto ya apareció antes en `chunks`
+///
+/// Compara el SHA-256 del texto con los espacios normalizados (bordes
+/// recortados y cada tramo de espacios como uno solo), así un pie de página
+/// repetido en cada hoja queda una sola vez. Los chunks que quedan se
+/// renumeran desde 0 con `Chunk::deterministic_id`. Retorna los chunks y
+/// cuántos se descartaron.
+pub fn dedupe_chunks(chunks: Vec<Chunk>) -> (Vec<Chunk>, usize) {
+    let total = chunks.len();
+    let mut seen = HashSet::new();
+    let mut out: Vec<Chunk> = Vec::with_capacity(total);
+    for mut chunk in chunks {
+        let normalized = chunk.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let hash: [u8; 32] = Sha256::digest(normalized.as_bytes()).into();
+        if !seen.insert(hash) {
+            continue;
+        }
+        chunk.index = out.len();
+        chunk.id = Chunk::deterministic_id(&chunk.document_id, chunk.index);
+        out.push(chunk);
+    }
+    let skipped = total - out.len();
+    (out, skipped)
+}
+
+/// Texto de un chunk y el tramo del original que cubre, en bytes
+struct Piece {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+/// Convierte offsets en bytes a offsets en caracteres y viceversa
+struct CharOffsets(Vec<usize>);
+
+impl CharOffsets {
+    fn new(text: &str) -> Self {
+        Self(
+            text.char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(text.len()))
+                .collect(),
+        )
+    }
+
+    /// Byte donde empieza el carácter `char_pos` (o el largo del texto)
+    fn byte_at(&self, char_pos: usize) -> usize {
+        self.0[char_pos]
+    }
+
+    /// `byte` debe ser un límite de carácter (o el largo del texto)
+    fn char_at(&self, byte: usize) -> usize {
+        self.0.partition_point(|&b| b < byte)
+    }
+}
+
+/// Ventanas de `size` caracteres sobre `text`, que empieza en el byte `base`
+/// del texto original
+fn fixed_chars(text: &str, base: usize, size: usize, overlap: usize) -> Vec<Piece> {
+    let size = size.max(1);
+    let step = size - overlap.min(size - 1);
+    // Límites de carácter en bytes, incluido el final del texto
+    let bounds: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let len = bounds.len() - 1;
+
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < len {
+        let end = (start + size).min(len);
+        out.push(Piece {
+            text: text[bounds[start]..bounds[end]].to_string(),
+            start: base + bounds[start],
+            end: base + bounds[end],
+        });
+        if end == len {
+            break;
+        }
+        start += step;
+    }
+    out
+}
+
+fn by_sentence(text: &str, max_chars: usize) -> Vec<Piece> {
+    let max_chars = max_chars.max(1);
+    pack_spans(text, split_sentences(text), max_chars, |start, end| {
+        fixed_chars(&text[start..end], start, max_chars, 0)
+    })
+}
+
+/// Agrupa tramos consecutivos de `text` mientras el chunk (del inicio del
+/// primero al final del último, con lo que haya entre ellos) no pase de
+/// `max_chars`; los tramos que solos pasan el límite van a `oversized`
+fn pack_spans(
+    text: &str,
+    spans: Vec<(usize, usize)>,
+    max_chars: usize,
+    oversized: impl Fn(usize, usize) -> Vec<Piece>,
+) -> Vec<Piece> {
+    let chars = |start: usize, end: usize| text[start..end].chars().count();
+    let mut out = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (start, end) in spans {
+        if chars(start, end) > max_chars {
+            out.extend(current.take().map(|(s, e)| slice_piece(text, s, e)));
+            out.extend(oversized(start, end));
+            continue;
+        }
+        current = match current {
+            Some((s, _)) if chars(s, end) <= max_chars => Some((s, end)),
+            Some((s, e)) => {
+                out.push(slice_piece(text, s, e));
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    out.extend(current.map(|(s, e)| slice_piece(text, s, e)));
+    out
+}
+
+/// `Piece` con el tramo `start..end` (en bytes) de `text` tal cual
+fn slice_piece(text: &str, start: usize, end: usize) -> Piece {
+    Piece {
+        text: text[start..end].to_string(),
+        start,
+        end,
+    }
+}
+
+/// Abreviaturas (en minúsculas, sin el punto final) cuyo punto no termina
+/// la oración
+const ABBREVIATIONS: &[&str] = &[
+    "sr", "sra", "srta", "dr", "dra", "prof", "ing", "lic", "ud", "uds", "vs", "ej", "p.ej", "e.g",
+    "i.e", "aprox", "pág", "págs", "núm", "fig", "cap", "vol", "mr", "mrs", "ms",
+];
+
+/// Separa oraciones en '.', '!' o '?' seguidos de espacio o fin de texto, y
+/// en líneas en blanco (títulos o párrafos sin punto final)
+///
+/// Un punto tras una abreviatura de `ABBREVIATIONS` o una inicial ("J. R.")
+/// no corta. Retorna el tramo en bytes de cada oración, sin los espacios de
+/// los bordes.
+fn split_sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '.' | '!' | '?' => {
+                let at_boundary = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+                if !at_boundary || (c == '.' && ends_with_abbreviation(&text[start..i])) {
+                    continue;
+                }
+                i + c.len_utf8()
+            }
+            '\n' if is_blank_line_after(&text[i + 1..]) => i,
+            _ => continue,
+        };
+        out.extend(trimmed_span(text, start, end));
+        start = end;
+    }
+    out.extend(trimmed_span(text, start, text.len()));
+    out
+}
+
+/// Indica si `before` (el texto hasta un punto) termina en una abreviatura
+fn ends_with_abbreviation(before: &str) -> bool {
+    let word = before
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or("")
+        .trim_start_matches(['(', '"', '¿', '¡'])
+        .to_lowercase();
+    let mut letters = word.chars();
+    let is_initial = letters.next().is_some_and(char::is_alphabetic) && letters.next().is_none();
+    is_initial || ABBREVIATIONS.contains(&word.as_str())
+}
+
+/// Indica si lo que sigue a un salto de línea empieza con una línea en blanco
+fn is_blank_line_after(rest: &str) -> bool {
+    let line = rest.split('\n').next().unwrap_or("");
+    rest.contains('\n') && line.trim().is_empty()
+}
+
+/// Tramo `start..end` sin espacios en los bordes; `None` si queda vacío
+fn trimmed_span(text: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let slice = &text[start..end];
+    let trimmed = slice.trim_start();
+    let start = start + (slice.len() - trimmed.len());
+    let end = start + trimmed.trim_end().len();
+    (start < end).then_some((start, end))
+}
+
+fn by_paragraph(text: &str) -> Vec<Piece> {
+    paragraph_spans(text)
+        .into_iter()
+        .map(|(start, end)| slice_piece(text, start, end))
+        .collect()
+}
+
+/// Tramos (en bytes) de los párrafos de `text`: bloques de líneas no
+/// vacías, sin los espacios al final
+fn paragraph_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let content = line.trim_end();
+        if content.trim_start().is_empty() {
+            out.extend(current.take());
+            continue;
+        }
+        let end = line_start + content.len();
+        current = Some((current.map_or(line_start, |(start, _)| start), end));
+    }
+    out.extend(current);
+    out
+}
+
+/// Agrupa párrafos consecutivos (separados por "\n\n") hasta `max_chars`
+fn merged_paragraphs(text: &str, max_chars: usize) -> Vec<Piece> {
+    let max_chars = max_chars.max(1);
+    pack_spans(text, paragraph_spans(text), max_chars, |start, end| {
+        by_sentence(&text[start..end], max_chars)
+            .into_iter()
+            .map(|p| Piece {
+                text: p.text,
+                start: start + p.start,
+                end: start + p.end,
+            })
+            .collect()
+    })
+}
+
+/// Agrupa palabras hasta `max_tokens` según `counter`
+///
+/// Cada chunk es el tramo del original entre su primera y su última
+/// palabra. Una palabra que sola supera el presupuesto se corta en ventanas
+/// de `max_tokens * CHARS_PER_TOKEN` caracteres.
+fn token_budget(
+    text: &str,
+    max_tokens: usize,
+    overlap_tokens: usize,
+    counter: &dyn TokenCounter,
+) -> Vec<Piece> {
+    let max_tokens = max_tokens.max(1);
+    let mut words: Vec<(usize, usize, usize)> = Vec::new();
+    for (start, word) in word_spans(text) {
+        let tokens = counter.count(word);
+        if tokens <= max_tokens {
+            words.push((start, start + word.len(), tokens));
+            continue;
+        }
+        for piece in fixed_chars(word, start, max_tokens * CHARS_PER_TOKEN, 0) {
+            let tokens = counter.count(&piece.text);
+            words.push((piece.start, piece.end, tokens));
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut current: VecDeque<(usize, usize, usize)> = VecDeque::new();
+    let mut total = 0;
+    for (start, end, tokens) in words {
+        if total + tokens > max_tokens && !current.is_empty() {
+            out.extend(joined_span(text, &current));
+            while total > overlap_tokens || (total > 0 && total + tokens > max_tokens) {
+                let Some((_, _, dropped)) = current.pop_front() else {
+                    break;
+                };
+                total -= dropped;
+            }
+        }
+        current.push_back((start, end, tokens));
+        total += tokens;
+    }
+    out.extend(joined_span(text, &current));
+    out
+}
+
+/// Palabras de `text` (separadas por espacios) con su byte de inicio
+fn word_spans(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_whitespace()
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+/// Tramo del original desde el primer hasta el último elemento de `spans`
+fn joined_span(text: &str, spans: &VecDeque<(usize, usize, usize)>) -> Option<Piece> {
+    let (first, last) = (spans.front()?, spans.back()?);
+    Some(Piece {
+        text: text[first.0..last.1].to_string(),
+        start: first.0,
+        end: last.1,
+    })
+}
+
+/// Estado de `ChunkStrategy::Recursive` mientras recorre el texto
+///
+/// Sigue el algoritmo de `RecursiveCharacterTextSplitter` (con el
+/// separador al comienzo de cada tramo) para que los chunks coincidan con
+/// los de otras herramientas RAG. Como los tramos se unen sin agregar nada,
+/// cada chunk es un pedazo contiguo del original sin espacios en los bordes.
+struct RecursiveSplitter<'a> {
+    text: &'a str,
+    max_chars: usize,
+    overlap_chars: usize,
+    out: Vec<Piece>,
+}
+
+impl RecursiveSplitter<'_> {
+    /// Divide `text[start..end]` con el primer separador que aparezca
+    fn split(&mut self, start: usize, end: usize, separators: &[String]) {
+        let slice = &self.text[start..end];
+        let mut separator = separators.last().map_or("", String::as_str);
+        let mut rest: &[String] = &[];
+        for (i, candidate) in separators.iter().enumerate() {
+            if candidate.is_empty() {
+                separator = "";
+                break;
+            }
+            if slice.contains(candidate.as_str()) {
+                separator = candidate;
+                rest = &separators[i + 1..];
+                break;
+            }
+        }
+
+        let mut fitting = Vec::new();
+        for (s, e) in split_keeping_separator(slice, separator) {
+            let (s, e) = (start + s, start + e);
+            let len = self.text[s..e].chars().count();
+            if len < self.max_chars {
+                fitting.push((s, e, len));
+                continue;
+            }
+            self.merge(&fitting);
+            fitting.clear();
+            if rest.is_empty() {
+                self.out.push(Piece {
+                    text: self.text[s..e].to_string(),
+                    start: s,
+                    end: e,
+                });
+            } else {
+                self.split(s, e, rest);
+            }
+        }
+        self.merge(&fitting);
+    }
+
+    /// Junta tramos consecutivos (inicio, fin, caracteres) hasta
+    /// `max_chars`; cada chunk nuevo arranca con los últimos tramos del
+    /// anterior que sumen a lo sumo `overlap_chars`
+    fn merge(&mut self, splits: &[(usize, usize, usize)]) {
+        let mut current: VecDeque<(usize, usize, usize)> = VecDeque::new();
+        let mut total = 0;
+        for &(start, end, len) in splits {
+            if total + len > self.max_chars && !current.is_empty() {
+                self.push_joined(&current);
+                while total > self.overlap_chars || (total > 0 && total + len > self.max_chars) {
+                    let Some((_, _, dropped)) = current.pop_front() else {
+                        break;
+                    };
+                    total -= dropped;
+                }
+            }
+            current.push_back((start, end, len));
+            total += len;
+        }
+        self.push_joined(&current);
+    }
+
+    fn push_joined(&mut self, splits: &VecDeque<(usize, usize, usize)>) {
+        let (Some(first), Some(last)) = (splits.front(), splits.back()) else {
+            return;
+        };
+        if let Some((start, end)) = trimmed_span(self.text, first.0, last.1) {
+            self.out.push(Piece {
+                text: self.text[start..end].to_string(),
+                start,
+                end,
+            });
+        }
+    }
+}
+
+/// Corta `text` antes de cada aparición de `separator`, que queda al
+/// comienzo del tramo siguiente; con un separador vacío, un tramo por
+/// carácter. Retorna tramos no vacíos en bytes.
+fn split_keeping_separator(text: &str, separator: &str) -> Vec<(usize, usize)> {
+    if separator.is_empty() {
+        return text
+            .char_indices()
+            .map(|(i, c)| (i, i + c.len_utf8()))
+            .collect();
+    }
+    let mut bounds: Vec<usize> = text.match_indices(separator).map(|(i, _)| i).collect();
+    bounds.insert(0, 0);
+    bounds.push(text.len());
+    bounds
+        .windows(2)
+        .map(|w| (w[0], w[1]))
+        .filter(|(s, e)| s < e)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::token_count;
+
+    const TEXT: &str = "Primera oración. Segunda oración!\n\n   \n\nTercer párrafo, ¿sí? Fin.";
+
+    #[test]
+    fn test_fixed_chars_with_overlap() {
+        let chunks = chunk(
+            TEXT,
+            &ChunkStrategy::FixedChars {
+                size: 20,
+                overlap: 5,
+            },
+        );
+        assert!(chunks.iter().all(|c| c.chars().count() <= 20));
+        // Cada chunk empieza con los últimos 5 caracteres del anterior
+        for pair in chunks.windows(2) {
+            let tail: String = pair[0].chars().skip(15).collect();
+            assert!(pair[1].starts_with(&tail));
+        }
+        assert!(chunks[0].starts_with("Primera"));
+        assert!(chunks.last().unwrap().ends_with("Fin."));
+    }
+
+    #[test]
+    fn test_by_sentence() {
+        let chunks = chunk(TEXT, &ChunkStrategy::BySentence { max_chars: 35 });
+        assert_eq!(
+            chunks,
+            [
+                "Primera oración. Segunda oración!",
+                "Tercer párrafo, ¿sí? Fin.",
+            ]
+        );
+
+        // Una oración más larga que el límite se corta
+        let chunks = chunk(TEXT, &ChunkStrategy::BySentence { max_chars: 10 });
+        assert!(chunks.iter().all(|c| c.chars().count() <= 10));
+    }
+
+    #[test]
+    fn test_by_paragraph() {
+        let chunks = chunk(TEXT, &ChunkStrategy::ByParagraph);
+        assert_eq!(
+            chunks,
+            [
+                "Primera oración. Segunda oración!",
+                "Tercer párrafo, ¿sí? Fin."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_empty_chunks() {
+        let blank = "   \n\n\t  \n";
+        for strategy in [
+            ChunkStrategy::FixedChars {
+                size: 2,
+                overlap: 0,
+            },
+            ChunkStrategy::BySentence { max_chars: 5 },
+            ChunkStrategy::ByParagraph,
+        ] {
+            assert!(chunk(blank, &strategy).is_empty());
+            assert!(chunk("", &strategy).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_build_chunks_is_deterministic() {
+        let pages = vec![
+            TEXT.to_string(),
+            "".to_string(),
+            "Última página.".to_string(),
+        ];
+        let chunks = build_chunks("doc-1", &pages, &ChunkStrategy::ByParagraph);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].id, "doc-1-chunk-0");
+        assert_eq!(chunks[2].id, "doc-1-chunk-2");
+        assert_eq!(chunks[2].index, 2);
+        assert_eq!(chunks[2].page_number, 3);
+
+        assert_eq!(
+            build_chunks("doc-1", &pages, &ChunkStrategy::ByParagraph),
+            chunks
+        );
+    }
+
+    fn fixed(size: usize, overlap: usize) -> ChunkingConfig {
+        ChunkingConfig {
+            strategy: ChunkStrategy::FixedChars { size, overlap },
+            normalize: false,
+            ..ChunkingConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_chunk_text_offsets_with_multibyte_chars() {
+        let text = "El niño comió piñas y ñoquis en la montaña añeja.";
+        let chars: Vec<char> = text.chars().collect();
+        let drafts = chunk_text(text, &fixed(12, 4));
+
+        assert!(drafts.len() > 1);
+        for (i, draft) in drafts.iter().enumerate() {
+            assert_eq!(draft.index, i);
+            assert!(draft.end_char - draft.start_char <= 12);
+            let expected: String = chars[draft.start_char..draft.end_char].iter().collect();
+            assert_eq!(draft.text, expected);
+        }
+        // Cada ventana arranca 8 caracteres después de la anterior
+        for pair in drafts.windows(2) {
+            assert_eq!(pair[1].start_char, pair[0].start_char + 8);
+        }
+        assert_eq!(drafts.last().unwrap().end_char, chars.len());
+    }
+
+    #[test]
+    fn test_chunk_text_shorter_than_max() {
+        let drafts = chunk_text("Acción rápida.", &fixed(1000, 200));
+        assert_eq!(
+            drafts,
+            [ChunkDraft {
+                text: "Acción rápida.".into(),
+                index: 0,
+                start_char: 0,
+                end_char: 14,
+                start_page: 1,
+                end_page: 1,
+            }]
+        );
+        assert!(chunk_text("  \n ", &fixed(1000, 200)).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_text_offsets_for_sentences_and_paragraphs() {
+        let chars: Vec<char> = TEXT.chars().collect();
+        let span = |d: &ChunkDraft| chars[d.start_char..d.end_char].iter().collect::<String>();
+
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::ByParagraph,
+            normalize: false,
+            ..ChunkingConfig::default()
+        };
+        let drafts = chunk_text(TEXT, &config);
+        assert_eq!(span(&drafts[1]), "Tercer párrafo, ¿sí? Fin.");
+
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::BySentence { max_chars: 35 },
+            normalize: false,
+            ..ChunkingConfig::default()
+        };
+        let drafts = chunk_text(TEXT, &config);
+        assert_eq!(span(&drafts[0]), "Primera oración. Segunda oración!");
+        assert_eq!(drafts[1].start_char, drafts[1].end_char - 25);
+    }
+
+    fn sentences(max_chars: usize) -> ChunkingConfig {
+        ChunkingConfig {
+            strategy: ChunkStrategy::BySentence { max_chars },
+            normalize: false,
+            ..ChunkingConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_sentences_ignore_abbreviations() {
+        let text = "El Sr. Pérez visitó al Dr. García, p. ej. los lunes. \
+            Usó varios métodos, e.g. regresión y J. R. Smith lo confirmó. ¿Funcionó?";
+        let split: Vec<&str> = split_sentences(text)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(
+            split,
+            [
+                "El Sr. Pérez visitó al Dr. García, p. ej. los lunes.",
+                "Usó varios métodos, e.g. regresión y J. R. Smith lo confirmó.",
+                "¿Funcionó?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentences_split_on_blank_lines() {
+        let text = "Introducción\n\nEl texto sigue aquí. Y termina.";
+        let chunks = chunk(text, &ChunkStrategy::BySentence { max_chars: 20 });
+        assert_eq!(
+            chunks,
+            ["Introducción", "El texto sigue aquí.", "Y termina."]
+        );
+    }
+
+    #[test]
+    fn test_oversized_sentence_falls_back_to_fixed_size() {
+        let long = format!("{}.", "palabra ".repeat(1_250).trim_end());
+        let text = format!("Antes. {} Después.", long);
+        let drafts = chunk_text(&text, &sentences(500));
+
+        assert_eq!(drafts[0].text, "Antes.");
+        assert_eq!(drafts.last().unwrap().text, "Después.");
+        let middle = &drafts[1..drafts.len() - 1];
+        assert_eq!(middle.len(), long.chars().count().div_ceil(500));
+        assert!(middle.iter().all(|d| d.text.chars().count() <= 500));
+        let rebuilt: String = middle.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(rebuilt, long);
+    }
+
+    #[test]
+    fn test_sentence_chunks_start_at_sentence_boundaries() {
+        let text = "Primera idea completa. Segunda idea, algo más larga que la primera! \
+            ¿Tercera idea? Cuarta idea con el Sr. López. Quinta.";
+        let starts: Vec<usize> = split_sentences(text).iter().map(|(s, _)| *s).collect();
+        let offsets = CharOffsets::new(text);
+        for max_chars in [50, 80, 120] {
+            for draft in chunk_text(text, &sentences(max_chars)) {
+                let start_byte = offsets.0[draft.start_char];
+                assert!(starts.contains(&start_byte), "{:?}", draft.text);
+                assert!(draft.text.chars().count() <= max_chars);
+            }
+        }
+    }
+
+    #[test]
+    fn test_paragraphs_merge_up_to_max_chars() {
+        let text = "Uno.\n\nDos.\n\n\n\nTres, un poco más largo.\n\
+            \n  \t \n\n\
+            Un párrafo largo. Tiene varias oraciones. No entra en un solo chunk.\n\n\
+            Cierre.\n\n\n";
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::Paragraph { max_chars: 30 },
+            normalize: false,
+            ..ChunkingConfig::default()
+        };
+        let drafts = chunk_text(text, &config);
+        let texts: Vec<&str> = drafts.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Uno.\n\nDos.",
+                "Tres, un poco más largo.",
+                "Un párrafo largo.",
+                "Tiene varias oraciones.",
+                "No entra en un solo chunk.",
+                "Cierre.",
+            ]
+        );
+        let span = |d: &ChunkDraft| -> String {
+            text.chars()
+                .skip(d.start_char)
+                .take(d.end_char - d.start_char)
+                .collect()
+        };
+        assert_eq!(span(&drafts[0]), "Uno.\n\nDos.");
+        assert_eq!(span(&drafts[5]), "Cierre.");
+
+        let pages = vec![text.to_string()];
+        for chunk in build_chunks("doc-1", &pages, &config.strategy) {
+            assert_eq!(chunk.char_count, chunk.text.chars().count());
+            assert!(chunk.char_count <= 30);
+        }
+    }
+
+    #[test]
+    fn test_paragraphs_discard_whitespace_only_text() {
+        let strategy = ChunkStrategy::Paragraph { max_chars: 100 };
+        assert!(chunk("\n\n  \n\t\n\n", &strategy).is_empty());
+        assert_eq!(chunk("Solo.\n\n\n", &strategy), ["Solo."]);
+    }
+
+    fn recursive(separators: &[&str], max_chars: usize, overlap_chars: usize) -> ChunkStrategy {
+        ChunkStrategy::Recursive {
+            separators: separators.iter().map(|s| s.to_string()).collect(),
+            max_chars,
+            overlap_chars,
+        }
+    }
+
+    // Mismas entradas y salidas que los tests de RecursiveCharacterTextSplitter
+    #[test]
+    fn test_recursive_matches_langchain() {
+        let text = "Hi.\n\nI'm Harrison.\n\nHow? Are? You?\nOkay then f f f f.\n\
+            This is a weird text to write, but gotta test the splittingggg some how.\n\n\
+            Bye!\n\n-H.";
+        let chunks = chunk(text, &recursive(&RECURSIVE_SEPARATORS, 10, 1));
+        assert_eq!(
+            chunks,
+            [
+                "Hi.",
+                "I'm",
+                "Harrison.",
+                "How? Are?",
+                "You?",
+                "Okay then",
+                "f f f f.",
+                "This is a",
+                "weird",
+                "text to",
+                "write,",
+                "but gotta",
+                "test the",
+                "splitting",
+                "gggg",
+                "some how.",
+                "Bye!",
+                "-H.",
+            ]
+        );
+
+        let chunks = chunk("....5X..3Y...4X....5Y...", &recursive(&["X", "Y"], 6, 0));
+        assert_eq!(chunks, ["....5", "X..3", "Y...4", "X....5", "Y..."]);
+    }
+
+    #[test]
+    fn test_recursive_overlap_comes_from_previous_chunk_end() {
+        let text = "uno dos tres cuatro cinco";
+        let drafts = chunk_text(
+            text,
+            &ChunkingConfig {
+                strategy: recursive(&RECURSIVE_SEPARATORS, 12, 5),
+                ..ChunkingConfig::default()
+            },
+        );
+        let texts: Vec<&str> = drafts.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(texts, ["uno dos tres", "tres cuatro", "cinco"]);
+        // Cada chunk es un tramo contiguo del original
+        for d in &drafts {
+            let span: String = text
+                .chars()
+                .skip(d.start_char)
+                .take(d.end_char - d.start_char)
+                .collect();
+            assert_eq!(span, d.text);
+        }
+    }
+
+    #[test]
+    fn test_recursive_default_config() {
+        let config = ChunkingConfig::recursive_default();
+        let ChunkStrategy::Recursive {
+            separators,
+            max_chars,
+            overlap_chars,
+        } = &config.strategy
+        else {
+            panic!("expected recursive strategy");
+        };
+        assert_eq!(separators, &["\n\n", "\n", " ", ""]);
+        assert!(overlap_chars < max_chars);
+
+        let text = "Párrafo uno. ".repeat(200);
+        let drafts = chunk_text(&text, &config);
+        assert!(drafts.len() > 1);
+        assert!(drafts.iter().all(|d| d.text.chars().count() <= *max_chars));
+    }
+
+    #[test]
+    fn test_token_budget_limits_chunks_by_tokens() {
+        let text = "El método de   Newton converge rápido.\nLa electroencefalografía \
+            registra actividad eléctrica cerebral durante horas.";
+        let budget = |max_tokens, overlap_tokens| ChunkingConfig {
+            strategy: ChunkStrategy::TokenBudget {
+                max_tokens,
+                overlap_tokens,
+            },
+            normalize: false,
+            ..ChunkingConfig::default()
+        };
+
+        let drafts = chunk_text(text, &budget(6, 0));
+        let texts: Vec<&str> = drafts.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "El método de   Newton",
+                "converge rápido.\nLa",
+                "electroencefalografía",
+                "registra actividad",
+                "eléctrica cerebral",
+                "durante horas.",
+            ]
+        );
+        for max_tokens in [3, 6, 10, 40] {
+            for overlap in [0, 2] {
+                for draft in chunk_text(text, &budget(max_tokens, overlap)) {
+                    assert!(token_count(&draft.text) <= max_tokens, "{:?}", draft.text);
+                }
+            }
+        }
+
+        // Con solapamiento el chunk siguiente repite el final del anterior
+        let drafts = chunk_text(text, &budget(6, 2));
+        assert_eq!(drafts[0].text, "El método de   Newton");
+        assert!(drafts[1].text.starts_with("Newton"));
+    }
+
+    #[test]
+    fn test_token_budget_splits_oversized_word() {
+        let word = "a".repeat(50);
+        let chunks = chunk(
+            &word,
+            &ChunkStrategy::TokenBudget {
+                max_tokens: 4,
+                overlap_tokens: 0,
+            },
+        );
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| token_count(c) <= 4));
+        assert_eq!(chunks.concat(), word);
+    }
+
+    fn pages(texts: &[&str]) -> Vec<PageText> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| PageText {
+                page_number: i + 1,
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_pages_merges_short_pages() {
+        let pages = pages(&["Portada.", "Índice breve.", "Prólogo."]);
+        let drafts = chunk_pages(&pages, &ChunkingConfig::default());
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].text, "Portada.\n\nÍndice breve.\n\nPrólogo.");
+        assert_eq!((drafts[0].start_page, drafts[0].end_page), (1, 3));
+
+        let chunk = drafts[0].clone().into_chunk("doc-1");
+        assert_eq!(chunk.page_number, 1);
+        assert_eq!(chunk.end_page, Some(3));
+        assert_eq!(chunk.id, Chunk::deterministic_id("doc-1", 0));
+    }
+
+    #[test]
+    fn test_chunk_pages_long_page_keeps_its_number() {
+        let long = "Oración de relleno para la página larga. ".repeat(40);
+        let pages = pages(&["Corta.", &long, "Final."]);
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::Paragraph { max_chars: 200 },
+            ..ChunkingConfig::default()
+        };
+        let drafts = chunk_pages(&pages, &config);
+        assert!(drafts.len() > 5);
+        assert_eq!((drafts[0].start_page, drafts[0].end_page), (1, 1));
+        let last = drafts.last().unwrap();
+        assert_eq!((last.start_page, last.end_page), (3, 3));
+        for draft in &drafts[1..drafts.len() - 1] {
+            assert_eq!(
+                (draft.start_page, draft.end_page),
+                (2, 2),
+                "{:?}",
+                draft.text
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalize_text_spanish_pdf_artifacts() {
+        let before = "La informa-\r\nción  del\tcapítulo\u{0}\u{7} es \r\n\
+            impor- \r\n  tante.\r\n\r\n\r\n\r\nSegundo   párrafo:\r\nPost-Guerra y \
+            me-\njor.  \r\n";
+        assert_eq!(
+            normalize_text(before),
+            "La información del capítulo es\nimportante.\n\n\
+            Segundo párrafo:\nPost-Guerra y mejor."
+        );
+        // Un guion al final de línea antes de mayúscula o número se conserva
+        assert_eq!(
+            normalize_text("Norte-\nSur y 1990-\n2000"),
+            "Norte-\nSur y 1990-\n2000"
+        );
+        // NFD (e + acento combinado) pasa a NFC
+        assert_eq!(normalize_text("cafe\u{301} ti\u{301}mido"), "café tímido");
+        assert_eq!(normalize_text("  \r\n\t "), "");
+    }
+
+    #[test]
+    fn test_chunk_text_normalizes_when_enabled() {
+        let text = "Electro-\r\nforesis   en\tgeles.\r\n";
+        let strategy = ChunkStrategy::FixedChars {
+            size: 1000,
+            overlap: 0,
+        };
+        // Por defecto el texto queda tal cual
+        let chunks = build_chunks("doc-1", &[text.to_string()], &strategy);
+        assert_eq!(chunks[0].text, text);
+        let config: ChunkingConfig = serde_json::from_str(r#"{"batch_size":8}"#).unwrap();
+        assert!(!config.normalize);
+
+        let config = ChunkingConfig {
+            strategy,
+            normalize: true,
+            ..ChunkingConfig::default()
+        };
+        let chunks = build_chunks_with("doc-1", &[text.to_string()], &config);
+        assert_eq!(chunks[0].text, "Electroforesis en geles.");
+        assert_eq!(chunks[0].char_count, 24);
+        assert_eq!(
+            chunk_text(text, &config)[0].text,
+            "Electroforesis en geles."
+        );
+    }
+
+    fn draft(index: usize, len: usize, start_char: usize, page: usize) -> ChunkDraft {
+        ChunkDraft {
+            text: char::from(b'a' + index as u8).to_string().repeat(len),
+            index,
+            start_char,
+            end_char: start_char + len,
+            start_page: page,
+            end_page: page,
+        }
+    }
+
+    #[test]
+    fn test_merge_small_chunks() {
+        let drafts = vec![
+            draft(0, 500, 0, 1),
+            draft(1, 20, 500, 2),
+            draft(2, 480, 520, 2),
+            draft(3, 15, 1000, 3),
+        ];
+        let merged = merge_small_chunks(drafts, 50);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(
+            merged[0].text,
+            format!("{}\n{}", "a".repeat(500), "b".repeat(20))
+        );
+        assert_eq!(
+            merged[1].text,
+            format!("{}\n{}", "c".repeat(480), "d".repeat(15))
+        );
+        let location: Vec<_> = merged
+            .iter()
+            .map(|d| (d.index, d.start_char, d.end_char, d.start_page, d.end_page))
+            .collect();
+        assert_eq!(location, [(0, 0, 520, 1, 2), (1, 520, 1015, 2, 3)]);
+
+        // El primero, si es chico, absorbe al siguiente
+        let merged = merge_small_chunks(vec![draft(0, 10, 0, 1), draft(1, 100, 10, 1)], 50);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].end_char, 110);
+    }
+
+    #[test]
+    fn test_min_chars_in_pipeline() {
+        let text = "Capítulo 1\n\nEl primer párrafo tiene bastante texto para quedar solo.\n\n7";
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::ByParagraph,
+            min_chars: Some(20),
+            ..ChunkingConfig::default()
+        };
+        let drafts = chunk_text(text, &config);
+        // El chunk unido es el tramo del texto que cubre, separadores incluidos
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].text, text);
+        assert_eq!(drafts[0].end_char, text.chars().count());
+    }
+
+    #[test]
+    fn test_chunk_offsets_slice_document_text() {
+        let pages = vec![
+            "Título del libro\n\nEl Sr. Pérez llegó   tarde.  ¿Por qué? Nadie lo sabe.\nSeguía lloviendo sobre la ciudad.".to_string(),
+            "   \n7\n".to_string(),
+            "Segunda página con acentos: canción, árbol, pingüino.\n\nÚltimo párrafo, muy corto.".to_string(),
+        ];
+        let strategies = [
+            ChunkStrategy::FixedChars {
+                size: 30,
+                overlap: 10,
+            },
+            ChunkStrategy::BySentence { max_chars: 40 },
+            ChunkStrategy::ByParagraph,
+            ChunkStrategy::Paragraph { max_chars: 60 },
+            recursive(&["\n\n", "\n", " ", ""], 40, 10),
+            ChunkStrategy::TokenBudget {
+                max_tokens: 8,
+                overlap_tokens: 2,
+            },
+        ];
+        for normalize in [true, false] {
+            for min_chars in [None, Some(15)] {
+                for strategy in &strategies {
+                    let config = ChunkingConfig {
+                        strategy: strategy.clone(),
+                        normalize,
+                        min_chars,
+                        dedupe: false,
+                        ..ChunkingConfig::default()
+                    };
+                    let document: Vec<char> = document_text(&pages, &config).chars().collect();
+                    let chunks = build_chunks_with("doc", &pages, &config);
+                    assert!(!chunks.is_empty());
+                    for chunk in chunks {
+                        let (start, end) = (chunk.start_offset.unwrap(), chunk.end_offset.unwrap());
+                        let slice: String = document[start..end].iter().collect();
+                        assert_eq!(slice, chunk.text, "{:?}", config);
+                    }
+
+                    // Lo mismo al cortar el documento entero
+                    let pages: Vec<PageText> = pages
+                        .iter()
+                        .enumerate()
+                        .map(|(i, text)| PageText {
+                            page_number: i + 1,
+                            text: text.clone(),
+                        })
+                        .collect();
+                    for chunk in chunk_pages(&pages, &config)
+                        .into_iter()
+                        .map(|d| d.into_chunk("doc"))
+                    {
+                        let (start, end) = (chunk.start_offset.unwrap(), chunk.end_offset.unwrap());
+                        let slice: String = document[start..end].iter().collect();
+                        assert_eq!(slice, chunk.text, "{:?}", config);
+                    }
+                }
+            }
+        }
+    }
+}
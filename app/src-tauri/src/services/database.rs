@@ -1,353 +1,3044 @@
-use crate::models::Document;
-use bincode;
+use crate::models::chunk::{self, join_chunks, ChunkPrefix};
+use crate::models::document::{self, unix_now, DocumentPrefix};
+use crate::models::{Chunk, ChunkOrder, Document};
+pub use crate::services::error::DbError;
+use crate::services::keys::{
+    chunk_key, document_prefix, parse_chunk_key, CHUNKS_TREE, CHUNK_IDS_TREE,
+    DOCUMENTS_BY_HASH_TREE, DOCUMENTS_TREE, META_TREE,
+};
+use crate::services::{attachments, blobs, embeddings, trash, vector_index};
+use bincode::{self, Options};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sled;
+use sled::transaction::{ConflictableTransactionError, Transactional};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock, Weak};
 use std::{fs, path::PathBuf, sync::Arc};
 
 fn default_app_name() -> &'static str {
     env!("CARGO_PKG_NAME")
 }
 
+/// Variable de entorno que, si está definida y no vacía, reemplaza al
+/// directorio de datos del sistema como base de `get_db_dir`
+pub const DATA_DIR_ENV: &str = "LIBIA_DATA_DIR";
+
+/// Directorio de datos de la app: `<base>/<app_name>`
+///
+/// La base es `LIBIA_DATA_DIR` si está definida y, si no, el directorio de
+/// datos local del usuario.
 pub fn get_db_dir(app_name: Option<&str>) -> PathBuf {
     let app_name = app_name.unwrap_or(default_app_name());
 
-    // Usamos dirs::data_local_dir() que es multiplataforma
-    // Retorna el directorio de datos local del usuario
-    let mut base = dirs::data_local_dir()
-        .or_else(|| dirs::data_dir())
+    // LIBIA_DATA_DIR permite redirigir los datos (CI, instalaciones portables)
+    // sin tocar el código; si no está, usamos dirs::data_local_dir() que es
+    // multiplataforma y retorna el directorio de datos local del usuario
+    let mut base = std::env::var_os(DATA_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::data_local_dir)
+        .or_else(dirs::data_dir)
         .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
 
     base.push(app_name);
     base
 }
 
-pub fn get_db_path(app_name: Option<&str>, db_subdir: Option<&str>) -> Result<PathBuf, String> {
+pub fn get_db_path(app_name: Option<&str>, db_subdir: Option<&str>) -> Result<PathBuf, DbError> {
     let mut dir = get_db_dir(app_name);
     let sub = db_subdir.unwrap_or("sled_db");
     dir.push(sub);
-    fs::create_dir_all(&dir).map_err(|e| format!("failed to create db dir: {}", e))?;
+    fs::create_dir_all(&dir)?;
     Ok(dir)
 }
 
-pub fn init_db(app_name: Option<&str>, db_subdir: Option<&str>) -> Result<Arc<sled::Db>, String> {
+pub fn init_db(app_name: Option<&str>, db_subdir: Option<&str>) -> Result<Arc<sled::Db>, DbError> {
     let db_dir = get_db_path(app_name, db_subdir)?;
-    let db = sled::open(&db_dir).map_err(|e| format!("failed to open sled db: {}", e))?;
-    Ok(Arc::new(db))
-}
-
-fn open_documents_tree(db: &sled::Db) -> Result<sled::Tree, String> {
-    db.open_tree("documents")
-        .map_err(|e| format!("failed to open documents tree: {}", e))
+    init_db_at(db_dir)
 }
 
-pub fn insert_document(db: &Arc<sled::Db>, doc: &Document) -> Result<(), String> {
-    let tree = open_documents_tree(&*db)?;
-    let v = bincode::serialize(doc).map_err(|e| format!("serialize error: {}", e))?;
-    tree.insert(doc.id.as_bytes(), v)
-        .map_err(|e| format!("sled insert error: {}", e))?;
-    tree.flush().map_err(|e| format!("flush error: {}", e))?;
-    Ok(())
+/// Modo de sled: priorizar velocidad de escritura o espacio en disco
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SledMode {
+    /// Escribe más rápido a costa de más espacio (el modo por defecto de sled)
+    #[default]
+    HighThroughput,
+    /// Compacta más seguido; para equipos con poco disco
+    LowSpace,
 }
 
-pub fn get_document(db: &Arc<sled::Db>, id: &str) -> Result<Option<Document>, String> {
-    let tree = open_documents_tree(&*db)?;
-    match tree
-        .get(id.as_bytes())
-        .map_err(|e| format!("sled get error: {}", e))?
-    {
-        Some(bytes) => {
-            let doc: Document =
-                bincode::deserialize(&bytes).map_err(|e| format!("deseralization error: {}", e))?;
-            Ok(Some(doc))
+impl From<SledMode> for sled::Mode {
+    fn from(mode: SledMode) -> Self {
+        match mode {
+            SledMode::HighThroughput => sled::Mode::HighThroughput,
+            SledMode::LowSpace => sled::Mode::LowSpace,
         }
-        None => Ok(None),
     }
 }
 
-pub fn get_all_documents(db: &Arc<sled::Db>) -> Result<Vec<Document>, String> {
-    let tree = open_documents_tree(&*db)?;
-    let mut out = Vec::new();
-    for item in tree.iter() {
-        let (_k, v) = item.map_err(|e| format!("sled iter error: {}", e))?;
-        let doc: Document =
-            bincode::deserialize(&v).map_err(|e| format!("desearialize error: {}", e))?;
-        out.push(doc);
-    }
-    Ok(out)
+/// Igual que `init_db`, pero con el tamaño de la caché de sled y su modo
+///
+/// Una caché grande acelera las búsquedas en bibliotecas grandes; una chica
+/// sirve para equipos con poca memoria. `init_db` usa los valores por
+/// defecto de sled.
+pub fn init_db_with_config(
+    app_name: Option<&str>,
+    db_subdir: Option<&str>,
+    cache_capacity_bytes: u64,
+    mode: SledMode,
+) -> Result<Arc<sled::Db>, DbError> {
+    let db_dir = get_db_path(app_name, db_subdir)?;
+    init_db_at_with_config(db_dir, cache_capacity_bytes, mode)
 }
 
-pub fn delete_document(db: &Arc<sled::Db>, id: &str) -> Result<(), String> {
-    let tree = open_documents_tree(&*db)?;
-    tree.remove(id.as_bytes())
-        .map_err(|e| format!("sled remove error: {}", e))?;
-    tree.flush().map_err(|e| format!("flush error: {}", e))?;
-    Ok(())
+/// Igual que `init_db_with_config`, pero en `path` (como `init_db_at`)
+pub fn init_db_at_with_config(
+    path: PathBuf,
+    cache_capacity_bytes: u64,
+    mode: SledMode,
+) -> Result<Arc<sled::Db>, DbError> {
+    let config = sled::Config::new()
+        .cache_capacity(cache_capacity_bytes)
+        .mode(mode.into());
+    open_with(path, config)
 }
 
-// TEST -------------------------------------------- TEST
+/// Abre la BD directamente en `path`, creando los directorios que falten
+///
+/// Útil para instalaciones portables (USB), varios perfiles o tests que no
+/// deben tocar el directorio de datos del usuario.
+pub fn init_db_at(path: PathBuf) -> Result<Arc<sled::Db>, DbError> {
+    open_with(path, sled::Config::new())
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+fn open_with(path: PathBuf, config: sled::Config) -> Result<Arc<sled::Db>, DbError> {
+    fs::create_dir_all(&path)?;
+    let db = config
+        .path(&path)
+        .open()
+        .map_err(|e| DbError::Open(format!("{}: {}", path.display(), e)))?;
+    Ok(Arc::new(db))
+}
 
-    #[test]
-    fn test_get_db_dir() {
-        let dir = get_db_dir(None);
+/// Igual que `init_db_at`, pero con una política de flush distinta de
+/// `FlushPolicy::Always` (p. ej. para importaciones masivas)
+pub fn init_db_at_with_flush_policy(
+    path: PathBuf,
+    policy: FlushPolicy,
+) -> Result<Arc<sled::Db>, DbError> {
+    let db = init_db_at(path)?;
+    set_flush_policy(&db, policy);
+    Ok(db)
+}
 
-        // Verificar que el path existe o puede ser creado
-        assert!(!dir.as_os_str().is_empty());
+/// Cuándo se fuerza la escritura a disco tras insertar o borrar
+///
+/// Cada flush hace un fsync, que en operaciones masivas domina el tiempo
+/// total. Con `Never` (o entre flushes de `EveryN`) lo escrito vive solo en
+/// memoria y en el log de sled: si el proceso se cae antes de un `flush_db`
+/// explícito, esas escrituras pueden perderse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FlushPolicy {
+    /// Flush después de cada escritura
+    #[default]
+    Always,
+    /// Nunca; el llamador debe usar `flush_db` al terminar
+    Never,
+    /// Flush cada `n` escrituras
+    EveryN(usize),
+}
 
-        // Verificar que contiene el nombre de la app
-        let dir_str = dir.to_string_lossy();
-        assert!(dir_str.contains("libAi") || dir_str.contains("LibAI"));
-    }
+/// Estado en memoria asociado a una instancia abierta de la BD
+///
+/// sled no permite guardar datos propios en el `Db`, así que el estado se
+/// registra por proceso usando la dirección del `Arc`. Se guarda un `Weak`
+/// para descartar entradas de BDs ya cerradas (y, mientras exista, impide que
+/// otra BD reutilice la misma dirección).
+#[derive(Default)]
+struct DbState {
+    /// Número de `MaintenanceGuard` activos; mientras sea > 0 la BD es de solo lectura
+    maintenance: AtomicUsize,
+    /// Escrituras en curso (`WriteGuard` vivos)
+    writers: Mutex<usize>,
+    /// Avisa a `MaintenanceGuard::enter` cuando `writers` llega a 0
+    writers_done: Condvar,
+    /// Si se guarda una copia del archivo original al importar (ver `blobs`)
+    store_blobs: AtomicBool,
+    /// Si los chunks nuevos se guardan comprimidos con zstd
+    compress_chunks: AtomicBool,
+    /// Si los embeddings nuevos se normalizan (norma 1) antes de guardarse
+    normalize_embeddings: AtomicBool,
+    /// Si los embeddings nuevos se guardan cuantizados a 8 bits
+    quantize_embeddings: AtomicBool,
+    /// Cuándo se hace flush tras insertar o borrar
+    flush_policy: Mutex<FlushPolicy>,
+    /// Escrituras desde el último flush (lo usa `FlushPolicy::EveryN`)
+    unflushed_writes: AtomicUsize,
+}
 
-    #[test]
-    fn test_get_db_dir_custom_app_name() {
-        let custom_name = "test_app";
-        let dir = get_db_dir(Some(custom_name));
-        let dir_str = dir.to_string_lossy();
+type DbStateRegistry = Mutex<HashMap<usize, (Weak<sled::Db>, Arc<DbState>)>>;
+
+fn db_state(db: &Arc<sled::Db>) -> Arc<DbState> {
+    static REGISTRY: OnceLock<DbStateRegistry> = OnceLock::new();
+    let mut states = REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    states.retain(|_, (weak, _)| weak.strong_count() > 0);
+    let key = Arc::as_ptr(db) as usize;
+    let (_, state) = states
+        .entry(key)
+        .or_insert_with(|| (Arc::downgrade(db), Arc::default()));
+    Arc::clone(state)
+}
 
-        // Verificar que contiene el nombre personalizado
-        assert!(dir_str.contains(custom_name));
-    }
+/// Indica si la BD está en modo mantenimiento (escrituras bloqueadas)
+pub fn is_read_only(db: &Arc<sled::Db>) -> bool {
+    db_state(db).maintenance.load(Ordering::SeqCst) > 0
+}
 
-    #[test]
-    fn test_get_db_path() {
-        // Usar un nombre de app único para tests
-        let test_app = format!("test_libai_{}", std::process::id());
-        let result = get_db_path(Some(&test_app), Some("test_db"));
+/// Activa o desactiva el guardado de copias de los archivos originales
+///
+/// Está desactivado por defecto porque los PDFs pueden ocupar mucho espacio;
+/// la app lo activa al abrir la BD según la configuración del usuario.
+pub fn set_blob_storage(db: &Arc<sled::Db>, enabled: bool) {
+    db_state(db).store_blobs.store(enabled, Ordering::SeqCst);
+}
 
-        assert!(result.is_ok());
-        let path = result.unwrap();
+/// Indica si el guardado de archivos originales está activado
+pub fn blob_storage_enabled(db: &Arc<sled::Db>) -> bool {
+    db_state(db).store_blobs.load(Ordering::SeqCst)
+}
 
-        // Verificar que el directorio fue creado
-        assert!(path.exists(), "El directorio de BD debe existir");
-        assert!(path.is_dir(), "El path debe ser un directorio");
+/// Activa o desactiva la compresión zstd del texto de los chunks nuevos
+///
+/// Solo afecta las escrituras: los chunks ya guardados se leen igual estén o
+/// no comprimidos, así que se puede cambiar en cualquier momento.
+pub fn set_chunk_compression(db: &Arc<sled::Db>, enabled: bool) {
+    db_state(db)
+        .compress_chunks
+        .store(enabled, Ordering::SeqCst);
+}
 
-        // Limpiar después del test
-        let _ = fs::remove_dir_all(&path);
-    }
+/// Indica si los chunks nuevos se guardan comprimidos
+pub fn chunk_compression_enabled(db: &Arc<sled::Db>) -> bool {
+    db_state(db).compress_chunks.load(Ordering::SeqCst)
+}
 
-    #[test]
-    fn test_get_db_path_default_subdir() {
-        let test_app = format!("test_libai_default_{}", std::process::id());
-        let result = get_db_path(Some(&test_app), None);
+/// Activa o desactiva la normalización de embeddings al guardarlos
+///
+/// Los vectores normalizados permiten que la búsqueda use el producto punto
+/// en lugar del coseno completo. Para convertir los que ya estaban guardados
+/// usar `embeddings::normalize_embeddings`.
+pub fn set_embedding_normalization(db: &Arc<sled::Db>, enabled: bool) {
+    db_state(db)
+        .normalize_embeddings
+        .store(enabled, Ordering::SeqCst);
+}
 
-        assert!(result.is_ok());
-        let path = result.unwrap();
+/// Indica si los embeddings nuevos se guardan normalizados
+pub fn embedding_normalization_enabled(db: &Arc<sled::Db>) -> bool {
+    db_state(db).normalize_embeddings.load(Ordering::SeqCst)
+}
 
-        // Verificar que el subdirectorio por defecto es "sled_db"
-        assert!(path.ends_with("sled_db") || path.to_string_lossy().contains("sled_db"));
-        assert!(path.exists());
+/// Activa o desactiva la cuantización int8 de los embeddings nuevos
+///
+/// Ocupa cerca de un cuarto que f32 a cambio de perder precisión. Como el
+/// formato queda registrado en cada embedding, la biblioteca puede tener
+/// vectores de ambos tipos y se puede cambiar en cualquier momento.
+pub fn set_embedding_quantization(db: &Arc<sled::Db>, enabled: bool) {
+    db_state(db)
+        .quantize_embeddings
+        .store(enabled, Ordering::SeqCst);
+}
 
-        // Limpiar después del test
-        let _ = fs::remove_dir_all(path.parent().unwrap());
-    }
+/// Indica si los embeddings nuevos se guardan cuantizados
+pub fn embedding_quantization_enabled(db: &Arc<sled::Db>) -> bool {
+    db_state(db).quantize_embeddings.load(Ordering::SeqCst)
+}
 
-    #[test]
-    fn test_init_db() {
-        // Usar un nombre único para cada test
-        let test_app = format!("test_libai_init_{}", std::process::id());
-        let test_subdir = format!("test_init_db_{}", std::process::id());
+/// Cambia la política de flush de las inserciones y borrados
+pub fn set_flush_policy(db: &Arc<sled::Db>, policy: FlushPolicy) {
+    *db_state(db)
+        .flush_policy
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = policy;
+}
 
-        // Inicializar la BD
-        let db_result = init_db(Some(&test_app), Some(&test_subdir));
-        assert!(db_result.is_ok(), "init_db debe retornar Ok");
+/// Política de flush vigente
+pub fn flush_policy(db: &Arc<sled::Db>) -> FlushPolicy {
+    *db_state(db)
+        .flush_policy
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
 
-        let db = db_result.unwrap();
+/// Escribe a disco todo lo pendiente, sea cual sea la política de flush
+pub fn flush_db(db: &Arc<sled::Db>) -> Result<(), DbError> {
+    db.flush()?;
+    db_state(db).unflushed_writes.store(0, Ordering::SeqCst);
+    Ok(())
+}
 
-        // Verificar que la BD está abierta (podemos hacer operaciones básicas)
-        // Intentar insertar y leer un valor de prueba
-        let test_key = b"test_key";
-        let test_value = b"test_value";
+/// Hace flush después de una escritura si la política lo pide
+pub(crate) fn flush_after_write(db: &Arc<sled::Db>) -> Result<(), DbError> {
+    let writes = db_state(db).unflushed_writes.fetch_add(1, Ordering::SeqCst) + 1;
+    match flush_policy(db) {
+        FlushPolicy::Always => flush_db(db),
+        FlushPolicy::Never => Ok(()),
+        FlushPolicy::EveryN(n) => {
+            if writes >= n.max(1) {
+                flush_db(db)?;
+            }
+            Ok(())
+        }
+    }
+}
 
-        let insert_result = db.insert(test_key, test_value);
-        assert!(insert_result.is_ok(), "Debe poder insertar en la BD");
+/// Registra una escritura en curso, o falla si la BD está en mantenimiento
+///
+/// El guard debe vivir hasta terminar de escribir: `MaintenanceGuard::enter`
+/// espera a que se suelten todos antes de retornar.
+pub(crate) fn ensure_writable(db: &Arc<sled::Db>) -> Result<WriteGuard, DbError> {
+    let state = db_state(db);
+    {
+        let mut writers = state.writers.lock().unwrap_or_else(|e| e.into_inner());
+        if state.maintenance.load(Ordering::SeqCst) > 0 {
+            return Err(DbError::ReadOnly);
+        }
+        *writers += 1;
+    }
+    Ok(WriteGuard { state })
+}
 
-        // Leer el valor insertado
-        let read_result = db.get(test_key);
-        assert!(read_result.is_ok(), "Debe poder leer de la BD");
+/// Escritura en curso, ver `ensure_writable`
+#[must_use]
+pub(crate) struct WriteGuard {
+    state: Arc<DbState>,
+}
 
-        let retrieved = read_result.unwrap();
-        assert!(retrieved.is_some(), "Debe encontrar el valor insertado");
-        assert_eq!(retrieved.unwrap().as_ref(), test_value);
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        let mut writers = self.state.writers.lock().unwrap_or_else(|e| e.into_inner());
+        *writers -= 1;
+        if *writers == 0 {
+            self.state.writers_done.notify_all();
+        }
+    }
+}
 
-        // Limpiar: eliminar el test key
-        let _ = db.remove(test_key);
+/// Pone la BD en modo mantenimiento mientras exista
+///
+/// Mientras el guard esté vivo, todas las funciones de escritura retornan
+/// `DbError::ReadOnly` y las lecturas siguen funcionando, lo que permite hacer
+/// backups o exportaciones consistentes con la app abierta. Al hacer drop del
+/// guard las escrituras se vuelven a permitir. Se pueden anidar varios guards.
+///
+/// `enter` bloquea las escrituras nuevas y espera a que terminen las que ya
+/// estaban en curso, así que no se debe llamar mientras se escribe.
+pub struct MaintenanceGuard {
+    state: Arc<DbState>,
+}
 
-        // Verificar que el directorio de BD existe en disco
-        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
-        assert!(
-            db_path.exists(),
-            "El directorio de BD debe existir en disco"
+impl MaintenanceGuard {
+    pub fn enter(db: &Arc<sled::Db>) -> Self {
+        let state = db_state(db);
+        state.maintenance.fetch_add(1, Ordering::SeqCst);
+        let writers = state.writers.lock().unwrap_or_else(|e| e.into_inner());
+        drop(
+            state
+                .writers_done
+                .wait_while(writers, |writers| *writers > 0)
+                .unwrap_or_else(|e| e.into_inner()),
         );
+        Self { state }
+    }
+}
 
-        // Limpiar después del test
-        let _ = fs::remove_dir_all(&db_path);
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        self.state.maintenance.fetch_sub(1, Ordering::SeqCst);
     }
+}
 
-    #[test]
-    fn test_init_db_open_and_close() {
-        let test_app = format!("test_libai_openclose_{}", std::process::id());
-        let test_subdir = format!("test_openclose_{}", std::process::id());
+/// Serializa un registro con bincode
+pub(crate) fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, DbError> {
+    bincode::serialize(value).map_err(|e| DbError::Serialize(e.to_string()))
+}
 
-        // Abrir la BD
-        let db1_result = init_db(Some(&test_app), Some(&test_subdir));
-        assert!(db1_result.is_ok());
+/// Deserializa un registro guardado con `encode`
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DbError> {
+    Ok(bincode::deserialize(bytes)?)
+}
 
-        let db1 = db1_result.unwrap();
+/// Deserializa un documento guardado con `encode`, incluidos los guardados
+/// por versiones anteriores de la app
+///
+/// bincode no guarda los nombres de los campos, así que un registro escrito
+/// antes de que se agregara un campo a `Document` se corta antes de tiempo y
+/// `decode` falla. Como los campos nuevos siempre van al final de la struct,
+/// ese registro es un prefijo del actual: se prueba leyendo cada vez menos
+/// campos hasta que los bytes alcancen justo, y los que faltan quedan con
+/// su valor por defecto.
+pub(crate) fn decode_document(bytes: &[u8]) -> Result<Document, DbError> {
+    let err = match decode(bytes) {
+        Ok(doc) => return Ok(doc),
+        Err(e) => e,
+    };
+    // Las mismas opciones que `bincode::serialize`, pero sin aceptar bytes
+    // de sobra: así solo encaja la cantidad de campos que tiene el registro
+    let options = bincode::DefaultOptions::new().with_fixint_encoding();
+    (document::BASE_FIELD_COUNT..document::FIELD_COUNT)
+        .rev()
+        .find_map(|fields| options.deserialize_seed(DocumentPrefix(fields), bytes).ok())
+        .ok_or(err)
+}
 
-        // Insertar datos
-        let _ = db1.insert(b"key1", b"value1");
-        let _ = db1.insert(b"key2", b"value2");
+/// Encabezado de los chunks comprimidos con zstd: firma y versión del formato
+///
+/// Los chunks sin comprimir se guardan en bincode puro, como siempre, y
+/// empiezan con el largo del id en u64, así que cualquier primer byte es
+/// posible (0xC1 es un id de 193 bytes). Con cuatro bytes un chunk sin
+/// comprimir solo coincidiría con un id de más de 20 MB, y aun así
+/// `decode_chunk` lo lee sin comprimir si lo que sigue no es un chunk zstd.
+const CHUNK_ZSTD_HEADER: [u8; 4] = [0xC1, b'Z', b'S', 1];
+
+/// Nivel de compresión zstd (3 es el valor por defecto de zstd)
+const CHUNK_ZSTD_LEVEL: i32 = 3;
+
+/// Serializa un chunk, comprimido o no según `set_chunk_compression`
+pub(crate) fn encode_chunk(db: &Arc<sled::Db>, chunk: &Chunk) -> Result<Vec<u8>, DbError> {
+    encode_chunk_with(chunk, chunk_compression_enabled(db))
+}
 
-        // Cerrar la BD (drop)
-        drop(db1);
+/// Serializa un chunk, comprimiéndolo con zstd si `compress` es `true`
+pub(crate) fn encode_chunk_with(chunk: &Chunk, compress: bool) -> Result<Vec<u8>, DbError> {
+    let plain = encode(chunk)?;
+    if !compress {
+        return Ok(plain);
+    }
+    let mut out = CHUNK_ZSTD_HEADER.to_vec();
+    out.extend(
+        zstd::encode_all(plain.as_slice(), CHUNK_ZSTD_LEVEL)
+            .map_err(|e| DbError::Serialize(e.to_string()))?,
+    );
+    Ok(out)
+}
 
-        // Reabrir la BD (debe persistir los datos)
-        let db2_result = init_db(Some(&test_app), Some(&test_subdir));
-        assert!(db2_result.is_ok());
+/// Lee un chunk guardado, esté comprimido o en el formato sin comprimir
+///
+/// Si empieza con `CHUNK_ZSTD_HEADER` pero el resto no se puede descomprimir
+/// y leer, se lee como chunk sin comprimir. Si su `char_count` no coincide
+/// con el texto se recalcula (ver `Chunk::repair_char_count`).
+pub(crate) fn decode_chunk(bytes: &[u8]) -> Result<Chunk, DbError> {
+    let compressed = bytes
+        .strip_prefix(&CHUNK_ZSTD_HEADER)
+        .and_then(|rest| zstd::decode_all(rest).ok())
+        .and_then(|plain| decode_chunk_fields(&plain).ok());
+    let mut chunk = match compressed {
+        Some(chunk) => chunk,
+        None => decode_chunk_fields(bytes)?,
+    };
+    chunk.repair_char_count();
+    Ok(chunk)
+}
 
-        let db2 = db2_result.unwrap();
+/// Deserializa un chunk en bincode, incluidos los guardados por versiones
+/// anteriores de la app
+///
+/// Igual que `decode_document`: un chunk escrito antes de que se agregara un
+/// campo es un prefijo del actual, así que se prueba con cada vez menos
+/// campos y los que faltan quedan con su valor por defecto.
+fn decode_chunk_fields(bytes: &[u8]) -> Result<Chunk, DbError> {
+    let err = match decode(bytes) {
+        Ok(chunk) => return Ok(chunk),
+        Err(e) => e,
+    };
+    let options = bincode::DefaultOptions::new().with_fixint_encoding();
+    (chunk::BASE_FIELD_COUNT..chunk::FIELD_COUNT)
+        .rev()
+        .find_map(|fields| options.deserialize_seed(ChunkPrefix(fields), bytes).ok())
+        .ok_or(err)
+}
 
-        // Verificar que los datos persisten
-        let value1 = db2.get(b"key1").unwrap();
-        assert!(value1.is_some());
-        assert_eq!(value1.unwrap().as_ref(), b"value1");
+pub(crate) fn open_tree(db: &sled::Db, name: &str) -> Result<sled::Tree, DbError> {
+    db.open_tree(name)
+        .map_err(|e| DbError::Open(format!("{} tree: {}", name, e)))
+}
 
-        let value2 = db2.get(b"key2").unwrap();
-        assert!(value2.is_some());
-        assert_eq!(value2.unwrap().as_ref(), b"value2");
+pub(crate) fn open_documents_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, DOCUMENTS_TREE)
+}
 
-        // Limpiar
-        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
-        let _ = fs::remove_dir_all(&db_path);
-    }
+pub(crate) fn open_hash_index_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, DOCUMENTS_BY_HASH_TREE)
+}
 
-    #[test]
-    fn test_db_path_correct_for_os() {
-        let test_app = "test_os_path";
-        let path = get_db_path(Some(test_app), Some("test")).unwrap();
-        let path_str = path.to_string_lossy().to_lowercase();
+pub(crate) fn open_chunks_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, CHUNKS_TREE)
+}
 
-        // Verificar que el path es correcto según el OS
-        #[cfg(windows)]
-        {
-            // En Windows debería estar en LocalAppData
-            assert!(
-                path_str.contains("appdata") || path_str.contains("local"),
-                "En Windows debe estar en AppData\\Local"
-            );
-        }
+/// Índice chunk_id -> clave del chunk en el árbol "chunks"
+pub(crate) fn open_chunk_ids_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, CHUNK_IDS_TREE)
+}
 
-        #[cfg(target_os = "macos")]
-        {
-            // En macOS debería estar en ~/Library/Application Support
-            assert!(
-                path_str.contains("library") || path_str.contains("application support"),
-                "En macOS debe estar en ~/Library/Application Support"
-            );
-        }
+/// Metadatos de la biblioteca (dimensión de embeddings, etc.)
+pub(crate) fn open_meta_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, META_TREE)
+}
 
-        #[cfg(target_os = "linux")]
-        {
-            // En Linux debería estar en ~/.local/share
-            assert!(
-                path_str.contains(".local") || path_str.contains("share"),
-                "En Linux debe estar en ~/.local/share"
-            );
+/// Claves de todos los chunks de un documento, en orden de índice
+pub(crate) fn document_chunk_keys(
+    chunks: &sled::Tree,
+    document_id: &str,
+) -> Result<Vec<sled::IVec>, DbError> {
+    let prefix = document_prefix(document_id);
+    let mut keys = Vec::new();
+    for k in chunks.scan_prefix(prefix.as_bytes()).keys() {
+        let k = k?;
+        // Descarta documentos cuyo id empieza igual (p. ej. "a" y "a:b")
+        if parse_chunk_key(&k).is_some_and(|parsed| parsed.document_id == document_id) {
+            keys.push(k);
         }
+    }
+    Ok(keys)
+}
 
-        // Limpiar
-        let _ = fs::remove_dir_all(path.parent().unwrap());
+fn abort(e: DbError) -> ConflictableTransactionError<DbError> {
+    ConflictableTransactionError::Abort(e)
+}
+
+pub fn insert_document(db: &Arc<sled::Db>, doc: &Document) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_documents_tree(db)?;
+    let by_hash = open_hash_index_tree(db)?;
+    let v = encode(doc)?;
+
+    // Si el documento ya existía con otro hash, hay que quitar la entrada vieja del índice
+    let previous_hash = match tree.get(doc.id.as_bytes())? {
+        Some(bytes) => decode_document(&bytes)?.sha256,
+        None => None,
+    };
+    if let Some(old) = previous_hash.filter(|h| Some(h) != doc.sha256.as_ref()) {
+        by_hash.remove(old.as_bytes())?;
     }
 
-    #[test]
-    fn test_multiple_db_instances() {
-        let test_app = format!("test_multi_{}", std::process::id());
-        let test_subdir = format!("test_multi_db_{}", std::process::id());
+    tree.insert(doc.id.as_bytes(), v)?;
+    if let Some(hash) = &doc.sha256 {
+        by_hash.insert(hash.as_bytes(), doc.id.as_bytes())?;
+    }
+    flush_after_write(db)
+}
 
-        // Nota: Sled no permite abrir múltiples instancias de la misma BD simultáneamente
-        // debido a locks de archivo. Este test verifica que podemos usar Arc para compartir
-        // una única instancia entre múltiples referencias.
+/// Busca un documento por el hash SHA-256 de su contenido
+pub fn find_document_by_hash(
+    db: &Arc<sled::Db>,
+    sha256: &str,
+) -> Result<Option<Document>, DbError> {
+    let by_hash = open_hash_index_tree(db)?;
+    match by_hash.get(sha256.as_bytes())? {
+        Some(id) => {
+            let id = String::from_utf8_lossy(&id).into_owned();
+            get_document(db, &id)
+        }
+        None => Ok(None),
+    }
+}
 
-        // Crear una instancia de BD
-        let db1 = init_db(Some(&test_app), Some(&test_subdir)).unwrap();
+/// Retorna el documento existente con el mismo hash o inserta `doc`
+///
+/// El booleano indica si el documento fue insertado (`true`) o si ya existía
+/// uno con el mismo contenido (`false`). Documentos sin hash siempre se insertan.
+pub fn get_or_insert_document(
+    db: &Arc<sled::Db>,
+    doc: Document,
+) -> Result<(Document, bool), DbError> {
+    if let Some(hash) = &doc.sha256 {
+        if let Some(existing) = find_document_by_hash(db, hash)? {
+            return Ok((existing, false));
+        }
+    }
+    insert_document(db, &doc)?;
+    Ok((doc, true))
+}
 
-        // Clonar la referencia Arc (no crea una nueva BD, solo otra referencia)
-        let db2 = Arc::clone(&db1);
+pub fn get_document(db: &Arc<sled::Db>, id: &str) -> Result<Option<Document>, DbError> {
+    let tree = open_documents_tree(db)?;
+    match tree.get(id.as_bytes())? {
+        Some(bytes) => Ok(Some(decode_document(&bytes)?)),
+        None => Ok(None),
+    }
+}
 
-        // Insertar en una referencia
-        let _ = db1.insert(b"shared_key", b"shared_value");
+/// Indica si existe un documento con ese id, sin deserializarlo
+pub fn document_exists(db: &Arc<sled::Db>, id: &str) -> Result<bool, DbError> {
+    Ok(open_documents_tree(db)?.contains_key(id.as_bytes())?)
+}
 
-        // Leer desde la otra referencia (debe ver los mismos datos)
-        let value = db2.get(b"shared_key").unwrap();
-        assert!(value.is_some());
-        assert_eq!(value.unwrap().as_ref(), b"shared_value");
+/// Cantidad total de documentos (solo recorre claves)
+pub fn count_documents(db: &Arc<sled::Db>) -> Result<usize, DbError> {
+    Ok(open_documents_tree(db)?.len())
+}
 
-        // Limpiar
-        drop(db1);
-        drop(db2);
-        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
-        let _ = fs::remove_dir_all(&db_path);
+/// Cantidad de chunks de la biblioteca o, si se indica, de un solo documento
+pub fn count_chunks(db: &Arc<sled::Db>, document_id: Option<&str>) -> Result<usize, DbError> {
+    match document_id {
+        Some(id) => count_chunks_for_document(db, id),
+        None => Ok(open_chunks_tree(db)?.len()),
     }
+}
 
-    #[test]
-    fn test_insert_and_get_document_minimal() {
-        let test_app = format!("test_insert_{}", std::process::id());
-        let test_sub = format!("test_db_{}", std::process::id());
-        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+/// Cantidad de chunks de un documento
+///
+/// Solo recorre las claves bajo el prefijo del documento, sin tocar los
+/// chunks de otros documentos ni deserializar valores.
+pub fn count_chunks_for_document(db: &Arc<sled::Db>, document_id: &str) -> Result<usize, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let prefix = document_prefix(document_id);
+    let mut count = 0;
+    for k in chunks.scan_prefix(prefix.as_bytes()).keys() {
+        let k = k?;
+        // Descarta documentos cuyo id empieza igual (p. ej. "a" y "a:b")
+        if parse_chunk_key(&k).is_some_and(|parsed| parsed.document_id == document_id) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
 
-        // Crear documento
-        let doc = Document::new(
-            "doc-1".to_string(),
-            "prueba.pdf".to_string(),
-            "/tmp/prueba.pdf".to_string(),
-            5,
-        );
+/// Estadísticas generales de la biblioteca, para el panel de la UI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DbStats {
+    pub document_count: usize,
+    pub chunk_count: usize,
+    pub indexed_document_count: usize,
+    /// Suma de `char_count` de todos los chunks
+    pub total_chunk_chars: u64,
+    pub on_disk_size_bytes: u64,
+}
 
+/// Calcula las estadísticas de la biblioteca
+///
+/// Los conteos salen de `Tree::len`; solo se decodifican los documentos (para
+/// saber cuáles están indexados) y los chunks (para sumar caracteres).
+pub fn database_stats(db: &Arc<sled::Db>) -> Result<DbStats, DbError> {
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+
+    let mut indexed_document_count = 0;
+    for item in docs.iter() {
+        let (_k, v) = item?;
+        let doc: Document = decode_document(&v)?;
+        if doc.is_indexed {
+            indexed_document_count += 1;
+        }
+    }
+    let mut total_chunk_chars = 0u64;
+    for item in chunks.iter() {
+        let (_k, v) = item?;
+        total_chunk_chars += decode_chunk(&v)?.char_count as u64;
+    }
+
+    Ok(DbStats {
+        document_count: docs.len(),
+        chunk_count: chunks.len(),
+        indexed_document_count,
+        total_chunk_chars,
+        on_disk_size_bytes: db.size_on_disk()?,
+    })
+}
+
+/// Igual que `get_document`, pero un id inexistente es `DbError::NotFound`
+pub fn get_document_required(db: &Arc<sled::Db>, id: &str) -> Result<Document, DbError> {
+    get_document(db, id)?.ok_or_else(|| DbError::NotFound(id.to_string()))
+}
+
+/// Documentos de la biblioteca, sin los borrados con `soft_delete_document`
+pub fn get_all_documents(db: &Arc<sled::Db>) -> Result<Vec<Document>, DbError> {
+    Ok(get_all_documents_including_deleted(db)?
+        .into_iter()
+        .filter(|doc| !doc.is_deleted())
+        .collect())
+}
+
+/// Todos los documentos guardados, también los borrados
+pub(crate) fn get_all_documents_including_deleted(
+    db: &Arc<sled::Db>,
+) -> Result<Vec<Document>, DbError> {
+    let tree = open_documents_tree(db)?;
+    let mut out = Vec::new();
+    for item in tree.iter() {
+        let (_k, v) = item?;
+        out.push(decode_document(&v)?);
+    }
+    Ok(out)
+}
+
+/// Ids de los documentos borrados con `soft_delete_document`, para
+/// descartar sus chunks en las búsquedas
+pub(crate) fn deleted_document_ids(db: &Arc<sled::Db>) -> Result<HashSet<String>, DbError> {
+    Ok(get_all_documents_including_deleted(db)?
+        .into_iter()
+        .filter(|doc| doc.is_deleted())
+        .map(|doc| doc.id)
+        .collect())
+}
+
+/// Todos los documentos indexados por id, para buscarlos desde resultados
+/// que solo traen el `document_id`
+pub fn get_all_documents_map(db: &Arc<sled::Db>) -> Result<HashMap<String, Document>, DbError> {
+    Ok(get_all_documents(db)?
+        .into_iter()
+        .map(|doc| (doc.id.clone(), doc))
+        .collect())
+}
+
+/// Trae varios documentos abriendo el árbol una sola vez
+///
+/// Los ids que no existen se omiten; los encontrados conservan el orden de
+/// `ids`.
+pub fn get_documents_by_ids(db: &Arc<sled::Db>, ids: &[&str]) -> Result<Vec<Document>, DbError> {
+    let tree = open_documents_tree(db)?;
+    let mut out = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(bytes) = tree.get(id.as_bytes())? {
+            out.push(decode_document(&bytes)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Cantidad máxima de reintentos de `update_document_cas` antes de rendirse
+pub const MAX_CAS_RETRIES: usize = 64;
+
+/// Actualiza un documento con compare-and-swap, sin perder escrituras concurrentes
+///
+/// `f` recibe la versión más reciente del documento y retorna la nueva. Si
+/// otro hilo modificó el documento entre la lectura y la escritura, se vuelve
+/// a leer y se llama a `f` de nuevo (por eso debe ser `Fn` y sin efectos
+/// secundarios). Tras `MAX_CAS_RETRIES` intentos fallidos retorna
+/// `DbError::Conflict`. `f` no puede cambiar el id del documento.
+pub fn update_document_cas(
+    db: &Arc<sled::Db>,
+    id: &str,
+    f: impl Fn(Document) -> Document,
+) -> Result<Document, DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_documents_tree(db)?;
+
+    for _ in 0..MAX_CAS_RETRIES {
+        let current = tree
+            .get(id.as_bytes())?
+            .ok_or_else(|| DbError::NotFound(id.to_string()))?;
+        let old: Document = decode_document(&current)?;
+        let old_hash = old.sha256.clone();
+
+        let updated = f(old);
+        if updated.id != id {
+            return Err(DbError::InvalidInput(format!(
+                "update changed document id from {} to {}",
+                id, updated.id
+            )));
+        }
+
+        let swap = tree.compare_and_swap(id.as_bytes(), Some(current), Some(encode(&updated)?))?;
+        if swap.is_err() {
+            // Otro hilo escribió primero: reintentamos con la versión nueva
+            std::thread::yield_now();
+            continue;
+        }
+
+        if old_hash != updated.sha256 {
+            let by_hash = open_hash_index_tree(db)?;
+            if let Some(old) = &old_hash {
+                by_hash.remove(old.as_bytes())?;
+            }
+            if let Some(new) = &updated.sha256 {
+                by_hash.insert(new.as_bytes(), id.as_bytes())?;
+            }
+        }
+        flush_after_write(db)?;
+        return Ok(updated);
+    }
+
+    Err(DbError::Conflict(format!(
+        "document {} changed concurrently {} times",
+        id, MAX_CAS_RETRIES
+    )))
+}
+
+/// Marca un documento como indexado de forma segura ante escrituras concurrentes
+pub fn mark_document_indexed(db: &Arc<sled::Db>, id: &str) -> Result<Document, DbError> {
+    update_document_cas(db, id, |mut doc| {
+        doc.mark_as_indexed();
+        doc
+    })
+}
+
+/// Marca todos los documentos como no indexados, para forzar una reindexación
+///
+/// Incluye los borrados con `soft_delete_document`, que al restaurarse
+/// también necesitan reindexarse. Retorna cuántos documentos estaban
+/// indexados y se cambiaron.
+pub fn reset_index_flags(db: &Arc<sled::Db>) -> Result<usize, DbError> {
+    let mut reset = 0;
+    for doc in get_all_documents_including_deleted(db)? {
+        if !doc.is_indexed {
+            continue;
+        }
+        update_document_cas(db, &doc.id, |mut doc| {
+            doc.mark_as_unindexed();
+            doc
+        })?;
+        reset += 1;
+    }
+    Ok(reset)
+}
+
+/// Cambia el nombre visible de un documento
+///
+/// `file_path` no cambia, así que el vínculo al archivo original sigue
+/// funcionando. El nombre se guarda sin espacios al principio ni al final y
+/// no puede quedar vacío.
+pub fn rename_document(db: &Arc<sled::Db>, id: &str, new_name: &str) -> Result<(), DbError> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return Err(DbError::InvalidInput(
+            "document name cannot be empty".to_string(),
+        ));
+    }
+    update_document_cas(db, id, |mut doc| {
+        doc.name = new_name.to_string();
+        doc
+    })?;
+    Ok(())
+}
+
+/// Guarda (o borra, con `None`) el resumen de un documento
+///
+/// Un resumen vacío o solo con espacios equivale a `None`.
+pub fn set_document_summary(
+    db: &Arc<sled::Db>,
+    id: &str,
+    summary: Option<&str>,
+) -> Result<(), DbError> {
+    let summary = summary
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string);
+    update_document_cas(db, id, |mut doc| {
+        doc.summary = summary.clone();
+        doc
+    })?;
+    Ok(())
+}
+
+/// Guarda (o borra, con `None`) el idioma de un documento
+pub fn set_document_language(
+    db: &Arc<sled::Db>,
+    id: &str,
+    language: Option<&str>,
+) -> Result<(), DbError> {
+    update_document_cas(db, id, |mut doc| {
+        doc.language = language.map(str::to_string);
+        doc
+    })?;
+    Ok(())
+}
+
+/// Agrega `tag` a varios documentos a la vez
+///
+/// La etiqueta se guarda sin espacios en los bordes y no puede quedar
+/// vacía. Los documentos que ya la tenían no se tocan y los ids que no
+/// existen se ignoran. Retorna cuántos documentos cambiaron.
+pub fn add_tag_to_documents(db: &Arc<sled::Db>, ids: &[&str], tag: &str) -> Result<usize, DbError> {
+    let tag = clean_tag(tag)?;
+    update_documents_where(db, ids, |doc| doc.add_tag(tag))
+}
+
+/// Quita `tag` de varios documentos a la vez
+///
+/// Igual que `add_tag_to_documents`: retorna cuántos documentos la tenían
+/// y cambiaron.
+pub fn remove_tag_from_documents(
+    db: &Arc<sled::Db>,
+    ids: &[&str],
+    tag: &str,
+) -> Result<usize, DbError> {
+    let tag = clean_tag(tag)?;
+    update_documents_where(db, ids, |doc| doc.remove_tag(tag))
+}
+
+fn clean_tag(tag: &str) -> Result<&str, DbError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(DbError::InvalidInput("tag cannot be empty".to_string()));
+    }
+    Ok(tag)
+}
+
+/// Aplica `change` a los documentos de `ids` que existen y guarda solo
+/// aquellos en que retorna `true`; retorna cuántos se guardaron
+fn update_documents_where(
+    db: &Arc<sled::Db>,
+    ids: &[&str],
+    change: impl Fn(&mut Document) -> bool,
+) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let mut modified = 0;
+    for mut doc in get_documents_by_ids(db, ids)? {
+        if !change(&mut doc) {
+            continue;
+        }
+        update_document_cas(db, &doc.id, |mut doc| {
+            change(&mut doc);
+            doc
+        })?;
+        modified += 1;
+    }
+    Ok(modified)
+}
+
+/// Documentos que todavía no tienen resumen, para el resumidor en segundo
+/// plano
+pub fn get_documents_missing_summary(db: &Arc<sled::Db>) -> Result<Vec<Document>, DbError> {
+    Ok(get_all_documents(db)?
+        .into_iter()
+        .filter(|doc| doc.summary.is_none())
+        .collect())
+}
+
+/// Registra que el documento se abrió ahora (para "abiertos recientemente")
+pub fn touch_document(db: &Arc<sled::Db>, id: &str) -> Result<(), DbError> {
+    update_document_cas(db, id, |mut doc| {
+        doc.touch();
+        doc
+    })?;
+    Ok(())
+}
+
+/// Los `limit` documentos abiertos más recientemente
+///
+/// Ordena por `last_accessed` descendente; los que nunca se abrieron van al
+/// final, del más nuevo al más viejo según `created_at`.
+pub fn get_recent_documents(db: &Arc<sled::Db>, limit: usize) -> Result<Vec<Document>, DbError> {
+    let mut docs = get_all_documents(db)?;
+    docs.sort_by(|a, b| {
+        b.last_accessed
+            .cmp(&a.last_accessed)
+            .then(b.created_at.cmp(&a.created_at))
+    });
+    docs.truncate(limit);
+    Ok(docs)
+}
+
+/// Documentos cuyo archivo original ya no existe en disco
+///
+/// Permite ofrecer en la UI una limpieza de "archivos faltantes".
+pub fn find_stale_documents(db: &Arc<sled::Db>) -> Result<Vec<Document>, DbError> {
+    Ok(get_all_documents(db)?
+        .into_iter()
+        .filter(|doc| !doc.file_exists())
+        .collect())
+}
+
+/// Documentos cuyos embeddings son anteriores a `model_changed_at`
+///
+/// Sirve para sugerir "reindexar" tras cambiar el modelo de embeddings.
+pub fn get_documents_needing_reindex(
+    db: &Arc<sled::Db>,
+    model_changed_at: u64,
+) -> Result<Vec<Document>, DbError> {
+    Ok(get_all_documents(db)?
+        .into_iter()
+        .filter(|doc| doc.needs_reindex(model_changed_at))
+        .collect())
+}
+
+/// Borra un documento sin perder nada: solo registra `deleted_at`
+///
+/// Deja de aparecer en `get_all_documents` y en las búsquedas, pero sus
+/// chunks, embeddings y archivos quedan guardados hasta `purge_deleted`, así
+/// que se puede recuperar con `restore_document`. Borrarlo de nuevo conserva
+/// la fecha del primer borrado.
+pub fn soft_delete_document(db: &Arc<sled::Db>, id: &str) -> Result<Document, DbError> {
+    let now = unix_now();
+    update_document_cas(db, id, |mut doc| {
+        doc.deleted_at.get_or_insert(now);
+        doc
+    })
+}
+
+/// Deshace `soft_delete_document`
+///
+/// Un id inexistente (o ya purgado) es `DbError::NotFound`; un documento que
+/// no estaba borrado queda igual.
+pub fn restore_document(db: &Arc<sled::Db>, id: &str) -> Result<Document, DbError> {
+    update_document_cas(db, id, |mut doc| {
+        doc.deleted_at = None;
+        doc
+    })
+}
+
+/// Borra definitivamente, con `delete_document`, los documentos borrados hace
+/// al menos `older_than_secs`
+///
+/// Con `0` purga todos los borrados. Retorna cuántos documentos se eliminaron.
+pub fn purge_deleted(db: &Arc<sled::Db>, older_than_secs: u64) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let cutoff = unix_now().saturating_sub(older_than_secs);
+    let mut purged = 0;
+    for doc in get_all_documents_including_deleted(db)? {
+        if doc.deleted_at.is_some_and(|at| at <= cutoff) {
+            delete_document(db, &doc.id)?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Borra un documento para siempre, con sus chunks, embeddings y archivos
+///
+/// Para los borrados desde la UI está `soft_delete_document`.
+pub fn delete_document(db: &Arc<sled::Db>, id: &str) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_documents_tree(db)?;
+    if let Some(bytes) = tree.remove(id.as_bytes())? {
+        let doc: Document = decode_document(&bytes)?;
+        if let Some(hash) = doc.sha256 {
+            open_hash_index_tree(db)?.remove(hash.as_bytes())?;
+        }
+    }
+    delete_chunks_for_document(db, id)?;
+    blobs::delete_document_blob(db, id)?;
+    attachments::delete_attachments(db, id)?;
+    flush_after_write(db)
+}
+
+/// Borra TODOS los documentos y chunks de la biblioteca
+///
+/// Pensado para desarrollo y para el botón "reiniciar biblioteca". También
+/// vacía los índices, la papelera y los archivos guardados para no dejar datos colgando.
+/// Retorna la cantidad de documentos más chunks eliminados. Es una función
+/// aparte a propósito, para que no se pueda invocar por accidente.
+pub fn clear_all(db: &Arc<sled::Db>) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let removed = docs.len() + chunks.len();
+
+    docs.clear()?;
+    chunks.clear()?;
+    open_hash_index_tree(db)?.clear()?;
+    open_chunk_ids_tree(db)?.clear()?;
+    blobs::open_blobs_tree(db)?.clear()?;
+    attachments::open_attachments_tree(db)?.clear()?;
+    embeddings::open_embeddings_tree(db)?.clear()?;
+    vector_index::on_embeddings_cleared(db);
+    embeddings::open_embedding_cache_tree(db)?.clear()?;
+    trash::open_trash_tree(db)?.clear()?;
+    open_meta_tree(db)?.clear()?;
+
+    db.flush()?;
+    Ok(removed)
+}
+
+/// Guarda un chunk y actualiza el `chunk_count` de su documento
+///
+/// El documento debe existir y el chunk debe pasar `Chunk::validate` (si
+/// no, `DbError::InvalidChunk`). Si ya había un chunk con el mismo id se
+/// reemplaza (aunque haya cambiado su índice). Todo ocurre en una
+/// transacción sobre los árboles de documentos, chunks e índice de ids.
+/// El texto se comprime según `set_chunk_compression`.
+pub fn insert_chunk(db: &Arc<sled::Db>, chunk: &Chunk) -> Result<(), DbError> {
+    insert_chunk_with_compression(db, chunk, chunk_compression_enabled(db))
+}
+
+/// Igual que `insert_chunk`, pero eligiendo explícitamente si comprimir
+///
+/// Sirve, por ejemplo, para comprimir solo los chunks largos. `get_chunk` lee
+/// ambos formatos sin necesidad de saber cómo se guardó cada uno.
+pub fn insert_chunk_with_compression(
+    db: &Arc<sled::Db>,
+    chunk: &Chunk,
+    compress: bool,
+) -> Result<(), DbError> {
+    insert_chunks_with_compression(db, std::slice::from_ref(chunk), compress)
+}
+
+/// Guarda varios chunks en una sola transacción (y un solo flush)
+///
+/// Mismas reglas que `insert_chunk`: si algún documento no existe, algún
+/// chunk no es válido o algún id pertenece a otro documento, no se guarda
+/// ninguno.
+pub fn insert_chunks(db: &Arc<sled::Db>, chunks: &[Chunk]) -> Result<(), DbError> {
+    insert_chunks_with_compression(db, chunks, chunk_compression_enabled(db))
+}
+
+fn insert_chunks_with_compression(
+    db: &Arc<sled::Db>,
+    batch: &[Chunk],
+    compress: bool,
+) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+
+    let mut entries = Vec::with_capacity(batch.len());
+    for chunk in batch {
+        chunk.validate()?;
+        let key = chunk_key(&chunk.document_id, chunk.index, &chunk.id);
+        entries.push((chunk, key, encode_chunk_with(chunk, compress)?));
+    }
+
+    (&docs, &chunks, &ids).transaction(|(docs, chunks, ids)| {
+        // Chunks nuevos por documento, para actualizar `chunk_count` al final
+        let mut added: HashMap<&str, usize> = HashMap::new();
+        for (chunk, key, value) in &entries {
+            if docs.get(chunk.document_id.as_bytes())?.is_none() {
+                return Err(abort(DbError::NotFound(chunk.document_id.clone())));
+            }
+
+            let previous = ids.get(chunk.id.as_bytes())?;
+            if let Some(old_key) = &previous {
+                let belongs_to_doc = parse_chunk_key(old_key)
+                    .is_some_and(|parsed| parsed.document_id == chunk.document_id);
+                if !belongs_to_doc {
+                    return Err(abort(DbError::InvalidInput(format!(
+                        "chunk id {} already belongs to another document",
+                        chunk.id
+                    ))));
+                }
+                chunks.remove(old_key)?;
+            }
+
+            chunks.insert(key.as_bytes(), value.as_slice())?;
+            ids.insert(chunk.id.as_bytes(), key.as_bytes())?;
+            if previous.is_none() {
+                *added.entry(chunk.document_id.as_str()).or_default() += 1;
+            }
+        }
+
+        for (document_id, count) in added {
+            let Some(doc_bytes) = docs.get(document_id.as_bytes())? else {
+                continue;
+            };
+            let mut doc: Document = decode_document(&doc_bytes).map_err(abort)?;
+            doc.chunk_count += count;
+            docs.insert(doc.id.as_bytes(), encode(&doc).map_err(abort)?)?;
+        }
+        Ok(())
+    })?;
+
+    flush_after_write(db)
+}
+
+/// Guarda un documento nuevo junto con todos sus chunks, de forma atómica
+///
+/// Se usa al importar: o queda el documento con todos sus chunks, o no
+/// queda nada (por ejemplo, si la app se cierra a mitad de camino). El
+/// documento no debe existir todavía y todos los chunks deben pertenecerle;
+/// su `chunk_count` se fija a la cantidad de chunks recibidos. Se hace un
+/// solo flush al final (si la `FlushPolicy` lo pide).
+pub fn insert_document_with_chunks(
+    db: &Arc<sled::Db>,
+    doc: &Document,
+    chunks: &[Chunk],
+) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    if let Some(chunk) = chunks.iter().find(|c| c.document_id != doc.id) {
+        return Err(DbError::InvalidInput(format!(
+            "chunk {} belongs to document {}, not {}",
+            chunk.id, chunk.document_id, doc.id
+        )));
+    }
+    let docs = open_documents_tree(db)?;
+    let by_hash = open_hash_index_tree(db)?;
+    let chunks_tree = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+
+    let mut doc = doc.clone();
+    doc.chunk_count = chunks.len();
+    let doc_value = encode(&doc)?;
+    let compress = chunk_compression_enabled(db);
+    let mut entries = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        chunk.validate()?;
+        let key = chunk_key(&chunk.document_id, chunk.index, &chunk.id);
+        entries.push((chunk, key, encode_chunk_with(chunk, compress)?));
+    }
+
+    (&docs, &by_hash, &chunks_tree, &ids).transaction(|(docs, by_hash, chunks_tree, ids)| {
+        if docs.get(doc.id.as_bytes())?.is_some() {
+            return Err(abort(DbError::Conflict(format!(
+                "document {} already exists",
+                doc.id
+            ))));
+        }
+        docs.insert(doc.id.as_bytes(), doc_value.as_slice())?;
+        if let Some(hash) = &doc.sha256 {
+            by_hash.insert(hash.as_bytes(), doc.id.as_bytes())?;
+        }
+        for (chunk, key, value) in &entries {
+            // El documento es nuevo: un id ya usado es de otro documento
+            if ids.insert(chunk.id.as_bytes(), key.as_bytes())?.is_some() {
+                return Err(abort(DbError::InvalidInput(format!(
+                    "chunk id {} already belongs to another document",
+                    chunk.id
+                ))));
+            }
+            chunks_tree.insert(key.as_bytes(), value.as_slice())?;
+        }
+        Ok(())
+    })?;
+
+    flush_after_write(db)
+}
+
+/// Busca un chunk por su id
+pub fn get_chunk(db: &Arc<sled::Db>, chunk_id: &str) -> Result<Option<Chunk>, DbError> {
+    let ids = open_chunk_ids_tree(db)?;
+    let Some(key) = ids.get(chunk_id.as_bytes())? else {
+        return Ok(None);
+    };
+
+    let chunks = open_chunks_tree(db)?;
+    match chunks.get(&key)? {
+        Some(bytes) => Ok(Some(decode_chunk(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Retorna todos los chunks de un documento ordenados por `index`
+pub fn get_chunks_for_document(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+) -> Result<Vec<Chunk>, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let mut out = Vec::new();
+    for key in document_chunk_keys(&chunks, document_id)? {
+        if let Some(bytes) = chunks.get(&key)? {
+            out.push(decode_chunk(&bytes)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Recorre todos los chunks de la biblioteca sin cargarlos juntos en memoria
+///
+/// Cada chunk se deserializa recién cuando se pide al iterador, así que sirve
+/// para trabajos sobre toda la biblioteca (reindexar, búsquedas por fuerza
+/// bruta) aunque tenga millones de chunks. El orden es el de las claves:
+/// por documento y, dentro de cada uno, por `index`.
+pub fn iter_chunks(
+    db: &Arc<sled::Db>,
+) -> Result<impl Iterator<Item = Result<Chunk, DbError>>, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    Ok(chunks.iter().values().map(|v| decode_chunk(&v?)))
+}
+
+/// Reconstruye el texto completo de un documento uniendo sus chunks
+///
+/// Los chunks se unen en orden de `index` con `separator` (p. ej. "\n\n").
+/// Si se generaron con solapamiento, el texto solapado aparece duplicado;
+/// para evitarlo usar `join_chunks` con `ChunkOrder::PageOffset`.
+pub fn reconstruct_document_text(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    separator: &str,
+) -> Result<String, DbError> {
+    let chunks = get_chunks_for_document(db, document_id)?;
+    Ok(join_chunks(&chunks, ChunkOrder::Index, separator))
+}
+
+/// Chunks de un documento cuya página está entre `start_page` y `end_page`
+/// (ambas inclusive), ordenados por `index`
+///
+/// Pensado para mostrar el contexto de una cita. Un rango vacío
+/// (`start_page > end_page`) retorna un vec vacío.
+pub fn get_chunks_by_page_range(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    start_page: usize,
+    end_page: usize,
+) -> Result<Vec<Chunk>, DbError> {
+    if start_page > end_page {
+        return Ok(Vec::new());
+    }
+    Ok(get_chunks_for_document(db, document_id)?
+        .into_iter()
+        .filter(|c| (start_page..=end_page).contains(&c.page_number))
+        .collect())
+}
+
+/// El chunk `chunk_id` junto con hasta `window` chunks antes y después
+/// dentro del mismo documento, ordenados por `index`
+///
+/// Sirve para ampliar el contexto de un resultado de búsqueda antes de
+/// pasárselo al LLM. Cerca del principio o del final del documento se
+/// retornan los vecinos que haya. Falla con `DbError::NotFound` si el chunk
+/// no existe.
+pub fn get_chunk_neighbors(
+    db: &Arc<sled::Db>,
+    chunk_id: &str,
+    window: usize,
+) -> Result<Vec<Chunk>, DbError> {
+    let ids = open_chunk_ids_tree(db)?;
+    let not_found = || DbError::NotFound(chunk_id.to_string());
+    let key = ids.get(chunk_id.as_bytes())?.ok_or_else(not_found)?;
+    let document_id = parse_chunk_key(&key).ok_or_else(not_found)?.document_id;
+
+    let chunks = open_chunks_tree(db)?;
+    let keys = document_chunk_keys(&chunks, &document_id)?;
+    let position = keys.iter().position(|k| *k == key).ok_or_else(not_found)?;
+    let start = position.saturating_sub(window);
+    let end = position
+        .saturating_add(window)
+        .saturating_add(1)
+        .min(keys.len());
+
+    let mut out = Vec::with_capacity(end - start);
+    for key in &keys[start..end] {
+        if let Some(bytes) = chunks.get(key)? {
+            out.push(decode_chunk(&bytes)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Elimina un chunk por id; retorna `false` si no existía
+pub fn delete_chunk(db: &Arc<sled::Db>, chunk_id: &str) -> Result<bool, DbError> {
+    let _write = ensure_writable(db)?;
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+
+    let removed = (&docs, &chunks, &ids).transaction(|(docs, chunks, ids)| {
+        let Some(key) = ids.remove(chunk_id.as_bytes())? else {
+            return Ok(false);
+        };
+        chunks.remove(&key)?;
+
+        if let Some(parsed) = parse_chunk_key(&key) {
+            if let Some(doc_bytes) = docs.get(parsed.document_id.as_bytes())? {
+                let mut doc: Document = decode_document(&doc_bytes).map_err(abort)?;
+                doc.chunk_count = doc.chunk_count.saturating_sub(1);
+                docs.insert(doc.id.as_bytes(), encode(&doc).map_err(abort)?)?;
+            }
+        }
+        Ok(true)
+    })?;
+
+    if removed {
+        embeddings::delete_embeddings(db, &[chunk_id])?;
+    }
+    flush_after_write(db)?;
+    Ok(removed)
+}
+
+/// Elimina los chunks repetidos de un documento y retorna cuántos borró
+///
+/// Dos chunks son repetidos si su texto sin espacios en los bordes es igual
+/// (p. ej. encabezados o pies de página extraídos en cada hoja). De cada
+/// grupo se conserva el de menor `index`; los demás se borran con sus
+/// embeddings. Solo se comparan chunks del mismo documento.
+pub fn dedupe_document_chunks(db: &Arc<sled::Db>, document_id: &str) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let mut seen = HashSet::new();
+    let mut removed = 0;
+    for chunk in get_chunks_for_document(db, document_id)? {
+        if !seen.insert(chunk.text.trim().to_string()) && delete_chunk(db, &chunk.id)? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Reemplaza de una vez los chunks y embeddings de un documento
+///
+/// En una sola transacción borra los chunks anteriores (con sus entradas en
+/// el índice de ids y sus embeddings), guarda `chunks` con `embeddings`
+/// (vectores ya codificados, en el mismo orden; `None` si el chunk no tiene)
+/// y ajusta `chunk_count`. Si algo falla no cambia nada y el documento
+/// conserva sus datos anteriores.
+/// Retorna los ids de los chunks que se borraron.
+pub(crate) fn replace_document_chunks(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    batch: &[Chunk],
+    embeddings: &[Option<Vec<u8>>],
+) -> Result<Vec<String>, DbError> {
+    let _write = ensure_writable(db)?;
+    if embeddings.len() != batch.len() {
+        return Err(DbError::InvalidInput(format!(
+            "{} embeddings for {} chunks",
+            embeddings.len(),
+            batch.len()
+        )));
+    }
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+    let vectors = embeddings::open_embeddings_tree(db)?;
+
+    let old_keys = document_chunk_keys(&chunks, document_id)?;
+    let mut entries = Vec::with_capacity(batch.len());
+    for chunk in batch {
+        chunk.validate()?;
+        if chunk.document_id != document_id {
+            return Err(DbError::InvalidInput(format!(
+                "chunk {} belongs to document {}, not {}",
+                chunk.id, chunk.document_id, document_id
+            )));
+        }
+        let key = chunk_key(&chunk.document_id, chunk.index, &chunk.id);
+        entries.push((chunk, key, encode_chunk(db, chunk)?));
+    }
+
+    let removed =
+        (&docs, &chunks, &ids, &vectors).transaction(|(docs, chunks, ids, vectors)| {
+            let Some(doc_bytes) = docs.get(document_id.as_bytes())? else {
+                return Err(abort(DbError::NotFound(document_id.to_string())));
+            };
+            let mut removed = Vec::with_capacity(old_keys.len());
+            for key in &old_keys {
+                chunks.remove(key)?;
+                if let Some(parsed) = parse_chunk_key(key) {
+                    ids.remove(parsed.chunk_id.as_bytes())?;
+                    vectors.remove(parsed.chunk_id.as_bytes())?;
+                    removed.push(parsed.chunk_id);
+                }
+            }
+            for ((chunk, key, value), embedding) in entries.iter().zip(embeddings) {
+                if let Some(old_key) = ids.get(chunk.id.as_bytes())? {
+                    if parse_chunk_key(&old_key).is_some_and(|p| p.document_id != document_id) {
+                        return Err(abort(DbError::InvalidInput(format!(
+                            "chunk id {} already belongs to another document",
+                            chunk.id
+                        ))));
+                    }
+                }
+                chunks.insert(key.as_bytes(), value.as_slice())?;
+                ids.insert(chunk.id.as_bytes(), key.as_bytes())?;
+                if let Some(embedding) = embedding {
+                    vectors.insert(chunk.id.as_bytes(), embedding.as_slice())?;
+                }
+            }
+            let mut doc = decode_document(&doc_bytes).map_err(abort)?;
+            doc.chunk_count = entries.len();
+            docs.insert(doc.id.as_bytes(), encode(&doc).map_err(abort)?)?;
+            Ok(removed)
+        })?;
+    flush_after_write(db)?;
+    Ok(removed)
+}
+
+/// Elimina todos los chunks de un documento, sus entradas en el índice de ids y sus embeddings
+pub(crate) fn delete_chunks_for_document(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+) -> Result<usize, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+    let keys = document_chunk_keys(&chunks, document_id)?;
+
+    let mut chunk_batch = sled::Batch::default();
+    let mut id_batch = sled::Batch::default();
+    let mut chunk_ids = Vec::with_capacity(keys.len());
+    for key in &keys {
+        if let Some(parsed) = parse_chunk_key(key) {
+            id_batch.remove(parsed.chunk_id.as_bytes());
+            chunk_ids.push(parsed.chunk_id);
+        }
+        chunk_batch.remove(key);
+    }
+    ids.apply_batch(id_batch)?;
+    chunks.apply_batch(chunk_batch)?;
+    embeddings::delete_embeddings(db, &chunk_ids)?;
+    Ok(keys.len())
+}
+
+// TEST -------------------------------------------- TEST
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChunkValidationError;
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db};
+    use std::fs;
+    use std::sync::MutexGuard;
+
+    /// Serializa los tests que leen o cambian `LIBIA_DATA_DIR`
+    fn env_lock() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_get_db_dir() {
+        let _env = env_lock();
+        let dir = get_db_dir(None);
+
+        // Verificar que el path existe o puede ser creado
+        assert!(!dir.as_os_str().is_empty());
+
+        // Verificar que contiene el nombre de la app
+        let dir_str = dir.to_string_lossy();
+        assert!(dir_str.contains("libAi") || dir_str.contains("LibAI"));
+    }
+
+    #[test]
+    fn test_get_db_dir_custom_app_name() {
+        let _env = env_lock();
+        let custom_name = "test_app";
+        let dir = get_db_dir(Some(custom_name));
+        let dir_str = dir.to_string_lossy();
+
+        // Verificar que contiene el nombre personalizado
+        assert!(dir_str.contains(custom_name));
+    }
+
+    #[test]
+    fn test_get_db_dir_env_override() {
+        let _env = env_lock();
+        let base = std::env::temp_dir().join(format!("libia_data_{}", std::process::id()));
+        std::env::set_var(DATA_DIR_ENV, &base);
+        let dir = get_db_dir(Some("LibAI"));
+        let path = get_db_path(Some("LibAI"), Some("sled_db"));
+        std::env::remove_var(DATA_DIR_ENV);
+
+        assert_eq!(dir, base.join("LibAI"));
+        assert_eq!(path.unwrap(), base.join("LibAI").join("sled_db"));
+        assert!(!get_db_dir(Some("LibAI")).starts_with(&base));
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_get_db_path() {
+        let _env = env_lock();
+        // Usar un nombre de app único para tests
+        let test_app = format!("test_libai_{}", std::process::id());
+        let result = get_db_path(Some(&test_app), Some("test_db"));
+
+        assert!(result.is_ok());
+        let path = result.unwrap();
+
+        // Verificar que el directorio fue creado
+        assert!(path.exists(), "El directorio de BD debe existir");
+        assert!(path.is_dir(), "El path debe ser un directorio");
+
+        // Limpiar después del test
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_db_path_default_subdir() {
+        let _env = env_lock();
+        let test_app = format!("test_libai_default_{}", std::process::id());
+        let result = get_db_path(Some(&test_app), None);
+
+        assert!(result.is_ok());
+        let path = result.unwrap();
+
+        // Verificar que el subdirectorio por defecto es "sled_db"
+        assert!(path.ends_with("sled_db") || path.to_string_lossy().contains("sled_db"));
+        assert!(path.exists());
+
+        // Limpiar después del test
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_init_db() {
+        let _env = env_lock();
+        // Usar un nombre único para cada test
+        let test_app = format!("test_libai_init_{}", std::process::id());
+        let test_subdir = format!("test_init_db_{}", std::process::id());
+
+        // Inicializar la BD
+        let db_result = init_db(Some(&test_app), Some(&test_subdir));
+        assert!(db_result.is_ok(), "init_db debe retornar Ok");
+
+        let db = db_result.unwrap();
+
+        // Verificar que la BD está abierta (podemos hacer operaciones básicas)
+        // Intentar insertar y leer un valor de prueba
+        let test_key = b"test_key";
+        let test_value = b"test_value";
+
+        let insert_result = db.insert(test_key, test_value);
+        assert!(insert_result.is_ok(), "Debe poder insertar en la BD");
+
+        // Leer el valor insertado
+        let read_result = db.get(test_key);
+        assert!(read_result.is_ok(), "Debe poder leer de la BD");
+
+        let retrieved = read_result.unwrap();
+        assert!(retrieved.is_some(), "Debe encontrar el valor insertado");
+        assert_eq!(retrieved.unwrap().as_ref(), test_value);
+
+        // Limpiar: eliminar el test key
+        let _ = db.remove(test_key);
+
+        // Verificar que el directorio de BD existe en disco
+        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
+        assert!(
+            db_path.exists(),
+            "El directorio de BD debe existir en disco"
+        );
+
+        // Limpiar después del test
+        let _ = fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_init_db_open_and_close() {
+        let _env = env_lock();
+        let test_app = format!("test_libai_openclose_{}", std::process::id());
+        let test_subdir = format!("test_openclose_{}", std::process::id());
+
+        // Abrir la BD
+        let db1_result = init_db(Some(&test_app), Some(&test_subdir));
+        assert!(db1_result.is_ok());
+
+        let db1 = db1_result.unwrap();
+
+        // Insertar datos
+        let _ = db1.insert(b"key1", b"value1");
+        let _ = db1.insert(b"key2", b"value2");
+
+        // Cerrar la BD (drop)
+        drop(db1);
+
+        // Reabrir la BD (debe persistir los datos)
+        let db2_result = init_db(Some(&test_app), Some(&test_subdir));
+        assert!(db2_result.is_ok());
+
+        let db2 = db2_result.unwrap();
+
+        // Verificar que los datos persisten
+        let value1 = db2.get(b"key1").unwrap();
+        assert!(value1.is_some());
+        assert_eq!(value1.unwrap().as_ref(), b"value1");
+
+        let value2 = db2.get(b"key2").unwrap();
+        assert!(value2.is_some());
+        assert_eq!(value2.unwrap().as_ref(), b"value2");
+
+        // Limpiar
+        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
+        let _ = fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_db_path_correct_for_os() {
+        let _env = env_lock();
+        let test_app = "test_os_path";
+        let path = get_db_path(Some(test_app), Some("test")).unwrap();
+        let path_str = path.to_string_lossy().to_lowercase();
+
+        // Verificar que el path es correcto según el OS
+        #[cfg(windows)]
+        {
+            // En Windows debería estar en LocalAppData
+            assert!(
+                path_str.contains("appdata") || path_str.contains("local"),
+                "En Windows debe estar en AppData\\Local"
+            );
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            // En macOS debería estar en ~/Library/Application Support
+            assert!(
+                path_str.contains("library") || path_str.contains("application support"),
+                "En macOS debe estar en ~/Library/Application Support"
+            );
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            // En Linux debería estar en ~/.local/share
+            assert!(
+                path_str.contains(".local") || path_str.contains("share"),
+                "En Linux debe estar en ~/.local/share"
+            );
+        }
+
+        // Limpiar
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_multiple_db_instances() {
+        let test_app = format!("test_multi_{}", std::process::id());
+        let test_subdir = format!("test_multi_db_{}", std::process::id());
+
+        // Nota: Sled no permite abrir múltiples instancias de la misma BD simultáneamente
+        // debido a locks de archivo. Este test verifica que podemos usar Arc para compartir
+        // una única instancia entre múltiples referencias.
+
+        // Crear una instancia de BD
+        let db1 = init_db(Some(&test_app), Some(&test_subdir)).unwrap();
+
+        // Clonar la referencia Arc (no crea una nueva BD, solo otra referencia)
+        let db2 = Arc::clone(&db1);
+
+        // Insertar en una referencia
+        let _ = db1.insert(b"shared_key", b"shared_value");
+
+        // Leer desde la otra referencia (debe ver los mismos datos)
+        let value = db2.get(b"shared_key").unwrap();
+        assert!(value.is_some());
+        assert_eq!(value.unwrap().as_ref(), b"shared_value");
+
+        // Limpiar
+        drop(db1);
+        drop(db2);
+        let db_path = get_db_path(Some(&test_app), Some(&test_subdir)).unwrap();
+        let _ = fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_insert_and_get_document_minimal() {
+        let test_app = format!("test_insert_{}", std::process::id());
+        let test_sub = format!("test_db_{}", std::process::id());
+        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+
+        // Crear documento
+        let doc = Document::new(
+            "doc-1".to_string(),
+            "prueba.pdf".to_string(),
+            "/tmp/prueba.pdf".to_string(),
+            5,
+        );
+
+        assert!(insert_document(&db, &doc).is_ok());
+
+        // Cleanup
+        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
+        let _ = std::fs::remove_dir_all(&db_path);
+
+        let got = get_document(&db, &doc.id).unwrap();
+        assert!(got.is_some());
+        let got_doc = got.unwrap();
+        assert_eq!(got_doc.id, doc.id);
+        assert_eq!(got_doc.name, doc.name);
+    }
+
+    #[test]
+    fn test_get_all_documents() {
+        let test_app = format!("test_get_all_{}", std::process::id());
+        let test_sub = format!("test_get_all_db_{}", std::process::id());
+        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+
+        let d1 = Document::new(
+            "d1".to_string(),
+            "a.pdf".to_string(),
+            "/tmp/a.pdf".to_string(),
+            1,
+        );
+        let d2 = Document::new(
+            "d2".to_string(),
+            "b.pdf".to_string(),
+            "/tmp/b.pdf".to_string(),
+            1,
+        );
+
+        insert_document(&db, &d1).unwrap();
+        insert_document(&db, &d2).unwrap();
+
+        let all = get_all_documents(&db).unwrap();
+        let ids: Vec<String> = all.into_iter().map(|d| d.id).collect();
+        assert!(ids.contains(&"d1".to_string()));
+        assert!(ids.contains(&"d2".to_string()));
+
+        let map = get_all_documents_map(&db).unwrap();
+        let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["d1", "d2"]);
+        assert_eq!(map["d2"].name, "b.pdf");
+
+        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
+        let _ = std::fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_read_documents_from_older_versions() {
+        /// `Document` tal como estaba en la primera versión
+        #[derive(Serialize)]
+        struct DocumentV0 {
+            id: String,
+            name: String,
+            file_path: String,
+            page_count: usize,
+            created_at: u64,
+            is_indexed: bool,
+        }
+        /// La versión siguiente, con `sha256`
+        #[derive(Serialize)]
+        struct DocumentV1 {
+            id: String,
+            name: String,
+            file_path: String,
+            page_count: usize,
+            created_at: u64,
+            is_indexed: bool,
+            sha256: Option<String>,
+        }
+
+        let (db, path) = temp_db("test_legacy_docs");
+        let v0 = DocumentV0 {
+            id: "viejo".into(),
+            name: "viejo.pdf".into(),
+            file_path: "/tmp/viejo.pdf".into(),
+            page_count: 12,
+            created_at: 1_700_000_000,
+            is_indexed: true,
+        };
+        let v1 = DocumentV1 {
+            id: "con-hash".into(),
+            name: "hash.pdf".into(),
+            file_path: "/tmp/hash.pdf".into(),
+            page_count: 3,
+            created_at: 1_700_000_100,
+            is_indexed: false,
+            sha256: Some("abc".into()),
+        };
+        let docs = open_documents_tree(&db).unwrap();
+        docs.insert("viejo", bincode::serialize(&v0).unwrap())
+            .unwrap();
+        docs.insert("con-hash", bincode::serialize(&v1).unwrap())
+            .unwrap();
+
+        let old = get_document_required(&db, "viejo").unwrap();
+        assert_eq!(old.name, "viejo.pdf");
+        assert_eq!(old.page_count, 12);
+        assert_eq!(old.created_at, 1_700_000_000);
+        assert!(old.is_indexed);
+        assert!(old.sha256.is_none());
+        assert!(old.tags.is_empty());
+        assert!(old.doc_type.is_none());
+
+        let hashed = get_document_required(&db, "con-hash").unwrap();
+        assert_eq!(hashed.sha256.as_deref(), Some("abc"));
+        assert_eq!(get_all_documents(&db).unwrap().len(), 2);
+
+        // Al modificarlo se guarda con los campos actuales
+        rename_document(&db, "viejo", "Renombrado").unwrap();
+        let bytes = docs.get("viejo").unwrap().unwrap();
+        let current: Document = decode(&bytes).unwrap();
+        assert_eq!(current.name, "Renombrado");
+        assert_eq!(current.created_at, 1_700_000_000);
+
+        // Bytes que no son ningún documento siguen fallando
+        docs.insert("roto", &[1u8, 2, 3][..]).unwrap();
+        assert!(matches!(
+            get_document(&db, "roto"),
+            Err(DbError::Deserialize(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_or_insert_document() {
+        let test_app = format!("test_get_or_insert_{}", std::process::id());
+        let test_sub = format!("test_get_or_insert_db_{}", std::process::id());
+        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+
+        let first = Document::new(
+            "doc-a".to_string(),
+            "a.pdf".to_string(),
+            "/tmp/a.pdf".to_string(),
+            3,
+        )
+        .with_sha256("hash-123".to_string());
+
+        let (doc, inserted) = get_or_insert_document(&db, first).unwrap();
+        assert!(inserted);
+        assert_eq!(doc.id, "doc-a");
+
+        // Mismo contenido (mismo hash) pero con otro id: debe retornar el existente
+        let again = Document::new(
+            "doc-b".to_string(),
+            "copia.pdf".to_string(),
+            "/tmp/copia.pdf".to_string(),
+            3,
+        )
+        .with_sha256("hash-123".to_string());
+
+        let (doc, inserted) = get_or_insert_document(&db, again).unwrap();
+        assert!(!inserted);
+        assert_eq!(doc.id, "doc-a");
+        assert!(get_document(&db, "doc-b").unwrap().is_none());
+
+        // Al borrar el documento el hash deja de estar registrado
+        delete_document(&db, "doc-a").unwrap();
+        assert!(find_document_by_hash(&db, "hash-123").unwrap().is_none());
+
+        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
+        let _ = std::fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_maintenance_guard_blocks_writes() {
+        let test_app = format!("test_maintenance_{}", std::process::id());
+        let test_sub = format!("test_maintenance_db_{}", std::process::id());
+        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        {
+            let _guard = MaintenanceGuard::enter(&db);
+            assert!(is_read_only(&db));
+
+            // Las escrituras fallan durante el mantenimiento
+            let other = Document::new(
+                "doc-2".to_string(),
+                "b.pdf".to_string(),
+                "/tmp/b.pdf".to_string(),
+                1,
+            );
+            assert!(matches!(
+                insert_document(&db, &other),
+                Err(DbError::ReadOnly)
+            ));
+            assert!(matches!(
+                delete_document(&db, "doc-1"),
+                Err(DbError::ReadOnly)
+            ));
+
+            // Las lecturas siguen funcionando
+            assert!(get_document(&db, "doc-1").unwrap().is_some());
+        }
+
+        // Al soltar el guard se vuelve a poder escribir
+        assert!(!is_read_only(&db));
+        assert!(delete_document(&db, "doc-1").is_ok());
+
+        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
+        let _ = std::fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_maintenance_waits_for_writes_in_progress() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let (db, path) = temp_db("test_maint_wait");
+
+        let write = ensure_writable(&db).unwrap();
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let maintenance = std::thread::spawn({
+            let db = Arc::clone(&db);
+            move || {
+                let _guard = MaintenanceGuard::enter(&db);
+                entered_tx.send(()).unwrap();
+                let _ = release_rx.recv();
+            }
+        });
+
+        // El mantenimiento espera a la escritura en curso, pero ya no deja
+        // empezar otras
+        assert!(entered_rx.recv_timeout(Duration::from_millis(200)).is_err());
+        let doc = sample_document();
+        assert!(matches!(insert_document(&db, &doc), Err(DbError::ReadOnly)));
+
+        drop(write);
+        entered_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(is_read_only(&db));
+
+        drop(release_tx);
+        maintenance.join().unwrap();
         assert!(insert_document(&db, &doc).is_ok());
 
-        // Cleanup
-        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
-        let _ = std::fs::remove_dir_all(&db_path);
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_init_db_at_custom_path() {
+        let base = std::env::temp_dir().join(format!("test_init_db_at_{}", std::process::id()));
+        // Directorio anidado que todavía no existe
+        let path = base.join("perfil").join("sled_db");
+
+        let db = init_db_at(path.clone()).unwrap();
+        assert!(path.exists());
 
-        let got = get_document(&db, &doc.id).unwrap();
-        assert!(got.is_some());
-        let got_doc = got.unwrap();
-        assert_eq!(got_doc.id, doc.id);
-        assert_eq!(got_doc.name, doc.name);
+        db.insert(b"key", b"value").unwrap();
+        let value = db.get(b"key").unwrap();
+        assert_eq!(value.unwrap().as_ref(), b"value");
+
+        drop(db);
+        let _ = fs::remove_dir_all(&base);
     }
 
     #[test]
-    fn test_get_all_documents() {
-        let test_app = format!("test_get_all_{}", std::process::id());
-        let test_sub = format!("test_get_all_db_{}", std::process::id());
-        let db = init_db(Some(&test_app), Some(&test_sub)).unwrap();
+    fn test_insert_and_get_chunks() {
+        let (db, path) = temp_db("test_chunks");
 
-        let d1 = Document::new(
-            "d1".to_string(),
+        let doc = Document::new(
+            "doc-1".to_string(),
             "a.pdf".to_string(),
             "/tmp/a.pdf".to_string(),
+            2,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        // Insertamos desordenados; la lectura debe salir ordenada por índice
+        for (id, index) in [("c-2", 2), ("c-0", 0), ("c-1", 1)] {
+            let chunk = Chunk::new(
+                id.to_string(),
+                "doc-1".to_string(),
+                format!("texto {}", index),
+                index,
+                1,
+            );
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        let indices: Vec<usize> = chunks.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 3);
+
+        // Reinsertar un chunk existente no cambia el conteo
+        let updated = Chunk::new(
+            "c-1".to_string(),
+            "doc-1".to_string(),
+            "nuevo".to_string(),
             1,
+            2,
         );
-        let d2 = Document::new(
-            "d2".to_string(),
+        insert_chunk(&db, &updated).unwrap();
+        assert_eq!(get_chunk(&db, "c-1").unwrap().unwrap().text, "nuevo");
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 3);
+
+        // Un chunk de un documento inexistente se rechaza
+        let orphan = Chunk::new(
+            "x".to_string(),
+            "no-existe".to_string(),
+            "t".to_string(),
+            0,
+            1,
+        );
+        assert!(matches!(
+            insert_chunk(&db, &orphan),
+            Err(DbError::NotFound(_))
+        ));
+
+        assert!(delete_chunk(&db, "c-0").unwrap());
+        assert!(!delete_chunk(&db, "c-0").unwrap());
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 2);
+
+        // Borrar el documento borra sus chunks
+        delete_document(&db, "doc-1").unwrap();
+        assert!(get_chunk(&db, "c-1").unwrap().is_none());
+        assert!(get_chunks_for_document(&db, "doc-1").unwrap().is_empty());
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_dedupe_document_chunks() {
+        let (db, path) = temp_db("test_dedupe_chunks");
+        for id in ["doc-1", "doc-2"] {
+            let doc = Document::new(id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 2);
+            insert_document(&db, &doc).unwrap();
+        }
+        let texts = [
+            "Revista de Física, vol. 3",
+            "Contenido único",
+            " Revista de Física, vol. 3\n",
+        ];
+        for (i, text) in texts.iter().enumerate() {
+            let chunk = Chunk::new(
+                format!("c-{}", i),
+                "doc-1".into(),
+                text.to_string(),
+                i,
+                i + 1,
+            );
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        // El mismo texto en otro documento no cuenta como repetido
+        let other = Chunk::new("o-0".into(), "doc-2".into(), texts[0].into(), 0, 1);
+        insert_chunk(&db, &other).unwrap();
+
+        assert_eq!(dedupe_document_chunks(&db, "doc-1").unwrap(), 1);
+        let ids: Vec<String> = get_chunks_for_document(&db, "doc-1")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, ["c-0", "c-1"]);
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 2);
+        assert!(get_chunk(&db, "o-0").unwrap().is_some());
+        assert_eq!(dedupe_document_chunks(&db, "doc-1").unwrap(), 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_documents_needing_reindex() {
+        let (db, path) = temp_db("test_needs_reindex");
+
+        let mut indexed = Document::new(
+            "indexado".to_string(),
+            "a.pdf".to_string(),
+            "/tmp/a.pdf".to_string(),
+            1,
+        );
+        indexed.mark_as_indexed();
+        let pending = Document::new(
+            "pendiente".to_string(),
             "b.pdf".to_string(),
             "/tmp/b.pdf".to_string(),
             1,
         );
+        insert_document(&db, &indexed).unwrap();
+        insert_document(&db, &pending).unwrap();
 
-        insert_document(&db, &d1).unwrap();
-        insert_document(&db, &d2).unwrap();
+        let embedded_at = indexed.embedded_at.unwrap();
+        let ids = |docs: Vec<Document>| docs.into_iter().map(|d| d.id).collect::<Vec<_>>();
 
-        let all = get_all_documents(&db).unwrap();
-        let ids: Vec<String> = all.into_iter().map(|d| d.id).collect();
-        assert!(ids.contains(&"d1".to_string()));
-        assert!(ids.contains(&"d2".to_string()));
+        assert_eq!(
+            ids(get_documents_needing_reindex(&db, embedded_at).unwrap()),
+            vec!["pendiente"]
+        );
+        let mut all = ids(get_documents_needing_reindex(&db, embedded_at + 1).unwrap());
+        all.sort();
+        assert_eq!(all, vec!["indexado", "pendiente"]);
 
-        let db_path = get_db_path(Some(&test_app), Some(&test_sub)).unwrap();
-        let _ = std::fs::remove_dir_all(&db_path);
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_document_required() {
+        let (db, path) = temp_db("test_get_required");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        assert_eq!(get_document_required(&db, "doc-1").unwrap().id, "doc-1");
+        match get_document_required(&db, "no-existe") {
+            Err(DbError::NotFound(id)) => assert_eq!(id, "no-existe"),
+            other => panic!("se esperaba NotFound, se obtuvo {:?}", other),
+        }
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_update_document_cas_concurrent() {
+        let (db, path) = temp_db("test_update_cas");
+
+        let doc = Document::new(
+            "doc-1".to_string(),
+            "a.pdf".to_string(),
+            "/tmp/a.pdf".to_string(),
+            0,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        // Varios hilos incrementan page_count mientras otro marca el documento
+        // como indexado; ninguna actualización debe perderse
+        let threads = 4;
+        let increments = 25;
+        let mut handles = Vec::new();
+        for _ in 0..threads {
+            let db = Arc::clone(&db);
+            handles.push(std::thread::spawn(move || {
+                for _ in 0..increments {
+                    update_document_cas(&db, "doc-1", |mut d| {
+                        d.page_count += 1;
+                        d
+                    })
+                    .unwrap();
+                }
+            }));
+        }
+        let db_indexer = Arc::clone(&db);
+        handles.push(std::thread::spawn(move || {
+            mark_document_indexed(&db_indexer, "doc-1").unwrap();
+        }));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stored = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(stored.page_count, threads * increments);
+        assert!(stored.is_indexed);
+        assert!(stored.embedded_at.is_some());
+
+        assert!(matches!(
+            mark_document_indexed(&db, "no-existe"),
+            Err(DbError::NotFound(_))
+        ));
+        assert!(matches!(
+            update_document_cas(&db, "doc-1", |mut d| {
+                d.id = "otro".to_string();
+                d
+            }),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_clear_all() {
+        let (db, path) = temp_db("test_clear_all");
+
+        for d in 0..2 {
+            let doc = Document::new(format!("doc-{}", d), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for i in 0..3 {
+                let chunk = Chunk::new(
+                    format!("c-{}-{}", d, i),
+                    doc.id.clone(),
+                    "texto".into(),
+                    i,
+                    1,
+                );
+                insert_chunk(&db, &chunk).unwrap();
+            }
+        }
+
+        assert_eq!(clear_all(&db).unwrap(), 8);
+        assert!(open_documents_tree(&db).unwrap().is_empty());
+        assert!(open_chunks_tree(&db).unwrap().is_empty());
+        assert!(open_chunk_ids_tree(&db).unwrap().is_empty());
+        assert!(get_all_documents(&db).unwrap().is_empty());
+
+        // Una biblioteca vacía no da error
+        assert_eq!(clear_all(&db).unwrap(), 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_init_db_with_tiny_cache() {
+        let path = std::env::temp_dir().join(format!("test_tiny_cache_{}", std::process::id()));
+        let db = init_db_at_with_config(path.clone(), 4 * 1024, SledMode::LowSpace).unwrap();
+
+        for i in 0..50 {
+            let id = format!("doc-{}", i);
+            let doc = Document::new(id.clone(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            let text = format!("texto del documento {} ", i).repeat(20);
+            let chunk = Chunk::new(format!("{}-c", id), id, text, 0, 1);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        assert_eq!(count_documents(&db).unwrap(), 50);
+        let chunk = get_chunk(&db, "doc-42-c").unwrap().unwrap();
+        assert!(chunk.text.starts_with("texto del documento 42"));
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_exists_and_count_helpers() {
+        let (db, path) = temp_db("test_count_helpers");
+
+        // Biblioteca vacía
+        assert!(!document_exists(&db, "doc-1").unwrap());
+        assert_eq!(count_documents(&db).unwrap(), 0);
+        assert_eq!(count_chunks(&db, None).unwrap(), 0);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+
+        // Tras insertar
+        for (doc_id, n) in [("doc-1", 3), ("doc-10", 2)] {
+            let doc = Document::new(doc_id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for i in 0..n {
+                let chunk = Chunk::new(
+                    format!("{}-c{}", doc_id, i),
+                    doc_id.into(),
+                    "t".into(),
+                    i,
+                    1,
+                );
+                insert_chunk(&db, &chunk).unwrap();
+            }
+        }
+        assert!(document_exists(&db, "doc-1").unwrap());
+        // Un id desconocido, aunque sea prefijo de otros, no existe
+        assert!(!document_exists(&db, "doc").unwrap());
+        assert!(!document_exists(&db, "doc-2").unwrap());
+        assert_eq!(count_documents(&db).unwrap(), 2);
+        assert_eq!(count_chunks(&db, None).unwrap(), 5);
+        // "doc-1" es prefijo de "doc-10" pero no debe contar sus chunks
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 3);
+        assert_eq!(count_chunks(&db, Some("doc-10")).unwrap(), 2);
+
+        // Tras borrar
+        delete_document(&db, "doc-1").unwrap();
+        assert!(!document_exists(&db, "doc-1").unwrap());
+        assert_eq!(count_documents(&db).unwrap(), 1);
+        assert_eq!(count_chunks(&db, None).unwrap(), 2);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_count_chunks_matches_batch_insert() {
+        let (db, path) = temp_db("test_count_batch");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        let chunks: Vec<Chunk> = (0..5)
+            .map(|i| Chunk::new(format!("c{}", i), "doc-1".into(), "t".into(), i, 1))
+            .collect();
+        insert_chunks(&db, &chunks).unwrap();
+        assert_eq!(count_chunks_for_document(&db, "doc-1").unwrap(), 5);
+        assert_eq!(count_chunks_for_document(&db, "doc-2").unwrap(), 0);
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 5);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_touch_and_recent_documents() {
+        let (db, path) = temp_db("test_recent_docs");
+
+        for id in ["doc-1", "doc-2"] {
+            let doc = Document::new(id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+        }
+
+        touch_document(&db, "doc-2").unwrap();
+        let recent = get_recent_documents(&db, 10).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].id, "doc-2");
+        assert!(recent[0].last_accessed.is_some());
+        assert_eq!(recent[1].last_accessed, None);
+
+        assert_eq!(get_recent_documents(&db, 1).unwrap().len(), 1);
+        assert!(matches!(
+            touch_document(&db, "nope"),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_chunks_by_page_range() {
+        let (db, path) = temp_db("test_page_range");
+
+        let doc = Document::new("doc-1".into(), "a.pdf".into(), "/tmp/a.pdf".into(), 5);
+        insert_document(&db, &doc).unwrap();
+        // Dos chunks por página, de la 1 a la 5
+        for i in 0..10 {
+            let chunk = Chunk::new(
+                format!("c-{}", i),
+                "doc-1".into(),
+                "texto".into(),
+                i,
+                i / 2 + 1,
+            );
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        let chunks = get_chunks_by_page_range(&db, "doc-1", 2, 3).unwrap();
+        let ids: Vec<&str> = chunks.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["c-2", "c-3", "c-4", "c-5"]);
+
+        assert!(get_chunks_by_page_range(&db, "doc-1", 4, 2)
+            .unwrap()
+            .is_empty());
+        assert!(get_chunks_by_page_range(&db, "doc-1", 6, 9)
+            .unwrap()
+            .is_empty());
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_chunk_neighbors() {
+        let (db, path) = temp_db("test_neighbors");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for i in 0..3 {
+            let chunk = sample_chunk(i, "texto");
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        let ids = |chunks: Vec<Chunk>| chunks.into_iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(get_chunk_neighbors(&db, "c-1", 1).unwrap()),
+            ["c-0", "c-1", "c-2"]
+        );
+        // En los bordes se recorta la ventana
+        assert_eq!(
+            ids(get_chunk_neighbors(&db, "c-0", 1).unwrap()),
+            ["c-0", "c-1"]
+        );
+        assert_eq!(
+            ids(get_chunk_neighbors(&db, "c-2", 5).unwrap()),
+            ["c-0", "c-1", "c-2"]
+        );
+        assert_eq!(ids(get_chunk_neighbors(&db, "c-1", 0).unwrap()), ["c-1"]);
+        assert!(matches!(
+            get_chunk_neighbors(&db, "no-existe", 1),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_reconstruct_document_text() {
+        let (db, path) = temp_db("test_reconstruct");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        // Insertados al revés para comprobar que se ordenan por índice
+        let second = Chunk::new(
+            "c-1".into(),
+            "doc-1".into(),
+            "Segundo párrafo.".into(),
+            1,
+            1,
+        );
+        let first = sample_chunk(0, "Primer párrafo.");
+        insert_chunk(&db, &second).unwrap();
+        insert_chunk(&db, &first).unwrap();
+
+        assert_eq!(
+            reconstruct_document_text(&db, "doc-1", "\n\n").unwrap(),
+            "Primer párrafo.\n\nSegundo párrafo."
+        );
+        assert_eq!(reconstruct_document_text(&db, "otro", "\n").unwrap(), "");
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_chunk_compression() {
+        let (db, path) = temp_db("test_chunk_zstd");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        // Chunk viejo, sin comprimir
+        let legacy = sample_chunk(0, "texto corto");
+        insert_chunk(&db, &legacy).unwrap();
+
+        set_chunk_compression(&db, true);
+        let text = "La biblioteca guarda texto muy repetitivo. ".repeat(500);
+        let big = sample_chunk(1, text.clone());
+        insert_chunk(&db, &big).unwrap();
+
+        let stored = open_chunks_tree(&db)
+            .unwrap()
+            .get(chunk_key("doc-1", 1, "c-1"))
+            .unwrap()
+            .unwrap();
+        assert!(stored.starts_with(&CHUNK_ZSTD_HEADER));
+        assert!(stored.len() < text.len() / 10);
+
+        // Se leen ambos formatos
+        assert_eq!(get_chunk(&db, "c-1").unwrap(), Some(big.clone()));
+        assert_eq!(
+            get_chunks_for_document(&db, "doc-1").unwrap(),
+            vec![legacy, big]
+        );
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_find_stale_documents() {
+        let (db, path) = temp_db("test_stale_docs");
+
+        let file = std::env::temp_dir().join(format!("test_stale_{}.pdf", std::process::id()));
+        fs::write(&file, b"%PDF-1.4").unwrap();
+        let doc = Document::new(
+            "doc-1".into(),
+            "a.pdf".into(),
+            file.to_string_lossy().into_owned(),
+            1,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        assert!(doc.file_exists());
+        assert!(find_stale_documents(&db).unwrap().is_empty());
+
+        fs::remove_file(&file).unwrap();
+        assert!(!doc.file_exists());
+        let stale = find_stale_documents(&db).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].id, "doc-1");
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_insert_chunk_with_compression_roundtrip() {
+        let (db, path) = temp_db("test_chunk_compress_rt");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        // La compresión global está desactivada; se pide por parámetro
+        let text = "Capítulo 1. Introducción — ñandú, café, 日本語. ".repeat(2_000);
+        let chunk = sample_chunk(0, text.clone());
+        insert_chunk_with_compression(&db, &chunk, true).unwrap();
+
+        let stored = open_chunks_tree(&db)
+            .unwrap()
+            .get(chunk_key("doc-1", 0, "c-0"))
+            .unwrap()
+            .unwrap();
+        assert!(stored.starts_with(&CHUNK_ZSTD_HEADER));
+        assert!(stored.len() < text.len());
+
+        let read = get_chunk(&db, "c-0").unwrap().unwrap();
+        assert_eq!(read.text.as_bytes(), text.as_bytes());
+        assert_eq!(read, chunk);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let (db, path) = temp_db("test_iter_chunks");
+        assert_eq!(iter_chunks(&db).unwrap().count(), 0);
+
+        for (doc_id, n) in [("doc-1", 3), ("doc-2", 4)] {
+            let doc = Document::new(doc_id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for i in 0..n {
+                let chunk =
+                    Chunk::new(format!("{}-{}", doc_id, i), doc_id.into(), "t".into(), i, 1);
+                insert_chunk(&db, &chunk).unwrap();
+            }
+        }
+
+        let chunks: Vec<Chunk> = iter_chunks(&db).unwrap().map(Result::unwrap).collect();
+        assert_eq!(chunks.len(), 7);
+        assert_eq!(chunks[0].id, "doc-1-0");
+        assert_eq!(chunks[6].id, "doc-2-3");
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_reset_index_flags() {
+        let (db, path) = temp_db("test_reset_index");
+
+        for id in ["a", "b", "c"] {
+            let doc = Document::new(id.to_string(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+        }
+        mark_document_indexed(&db, "a").unwrap();
+        mark_document_indexed(&db, "b").unwrap();
+        assert!(get_document(&db, "a").unwrap().unwrap().is_indexed);
+
+        assert_eq!(reset_index_flags(&db).unwrap(), 2);
+        assert!(get_all_documents(&db)
+            .unwrap()
+            .iter()
+            .all(|d| !d.is_indexed));
+        assert_eq!(reset_index_flags(&db).unwrap(), 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_database_stats() {
+        let (db, path) = temp_db("test_db_stats");
+
+        for id in ["a", "b"] {
+            let doc = Document::new(id.to_string(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for i in 0..2 {
+                let chunk = Chunk::new(format!("{}-{}", id, i), id.into(), "hola".into(), i, 1);
+                insert_chunk(&db, &chunk).unwrap();
+            }
+        }
+        mark_document_indexed(&db, "a").unwrap();
+
+        let stats = database_stats(&db).unwrap();
+        assert_eq!(stats.document_count, 2);
+        assert_eq!(stats.chunk_count, 4);
+        assert_eq!(stats.indexed_document_count, 1);
+        assert_eq!(stats.total_chunk_chars, 16);
+        assert!(stats.on_disk_size_bytes > 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_get_documents_by_ids() {
+        let (db, path) = temp_db("test_docs_by_ids");
+
+        for id in ["a", "b", "c"] {
+            let doc = Document::new(id.to_string(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+        }
+
+        let docs = get_documents_by_ids(&db, &["c", "falta", "a"]).unwrap();
+        let ids: Vec<&str> = docs.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a"]);
+        assert!(get_documents_by_ids(&db, &[]).unwrap().is_empty());
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_insert_document_with_chunks_is_atomic() {
+        let (db, path) = temp_db("test_doc_with_chunks");
+
+        let doc = Document::new("doc-1".into(), "a.pdf".into(), "/tmp/a.pdf".into(), 2);
+        let chunks: Vec<Chunk> = (0..3)
+            .map(|i| sample_chunk(i, format!("texto {}", i)))
+            .collect();
+        insert_document_with_chunks(&db, &doc, &chunks).unwrap();
+
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 3);
+        for chunk in &chunks {
+            assert_eq!(get_chunk(&db, &chunk.id).unwrap().as_ref(), Some(chunk));
+        }
+
+        // Un id de chunk repetido deshace toda la importación
+        let other = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        let clashing = vec![
+            Chunk::new("d-0".into(), "doc-2".into(), "nuevo".into(), 0, 1),
+            Chunk::new("c-1".into(), "doc-2".into(), "repetido".into(), 1, 1),
+        ];
+        let err = insert_document_with_chunks(&db, &other, &clashing).unwrap_err();
+        assert!(matches!(err, DbError::InvalidInput(_)));
+        assert!(get_document(&db, "doc-2").unwrap().is_none());
+        assert!(get_chunk(&db, "d-0").unwrap().is_none());
+        assert_eq!(get_chunk(&db, "c-1").unwrap().unwrap().document_id, "doc-1");
+
+        // El documento no puede existir de antes
+        assert!(matches!(
+            insert_document_with_chunks(&db, &doc, &[]),
+            Err(DbError::Conflict(_))
+        ));
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_document_summary_storage() {
+        let (db, path) = temp_db("test_document_summary");
+        for id in ["doc-1", "doc-2"] {
+            let doc = Document::new(id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+        }
+        assert_eq!(get_documents_missing_summary(&db).unwrap().len(), 2);
+
+        set_document_summary(&db, "doc-1", Some("  Introducción a la óptica. ")).unwrap();
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(doc.summary.as_deref(), Some("Introducción a la óptica."));
+        let missing = get_documents_missing_summary(&db).unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].id, "doc-2");
+
+        set_document_summary(&db, "doc-1", Some("   ")).unwrap();
+        assert!(get_document(&db, "doc-1")
+            .unwrap()
+            .unwrap()
+            .summary
+            .is_none());
+        assert!(matches!(
+            set_document_summary(&db, "nope", Some("x")),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tag_documents_in_batch() {
+        let (db, path) = temp_db("test_tag_batch");
+        for id in ["doc-1", "doc-2", "doc-3"] {
+            let doc = Document::new(id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+        }
+        let tags = |id: &str| get_document(&db, id).unwrap().unwrap().tags;
+
+        assert_eq!(
+            add_tag_to_documents(&db, &["doc-1", "doc-3", "nope"], " física ").unwrap(),
+            2
+        );
+        assert_eq!(tags("doc-1"), ["física"]);
+        assert!(tags("doc-2").is_empty());
+        assert_eq!(tags("doc-3"), ["física"]);
+
+        // Los que ya la tenían no cuentan
+        assert_eq!(
+            add_tag_to_documents(&db, &["doc-1", "doc-2"], "física").unwrap(),
+            1
+        );
+        assert_eq!(
+            remove_tag_from_documents(&db, &["doc-1", "doc-2", "doc-3"], "química").unwrap(),
+            0
+        );
+        assert_eq!(
+            remove_tag_from_documents(&db, &["doc-2", "doc-3"], "física").unwrap(),
+            2
+        );
+        assert_eq!(tags("doc-1"), ["física"]);
+        assert!(tags("doc-3").is_empty());
+        assert!(matches!(
+            add_tag_to_documents(&db, &["doc-1"], "  "),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_insert_rejects_invalid_chunks() {
+        let (db, path) = temp_db("test_invalid_chunks");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        let valid = sample_chunk(0, "texto");
+        let mut stale = sample_chunk(1, "texto");
+        stale.char_count = 99;
+        let err = insert_chunks(&db, &[valid.clone(), stale.clone()]).unwrap_err();
+        assert!(matches!(
+            err,
+            DbError::InvalidChunk(ChunkValidationError::CharCountMismatch { ref chunk_id, .. })
+                if chunk_id == "c-1"
+        ));
+        // No se guardó ninguno, tampoco el válido
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+
+        let page_zero = Chunk::new("c-2".into(), "doc-1".into(), "texto".into(), 2, 0);
+        assert!(matches!(
+            insert_chunk(&db, &page_zero),
+            Err(DbError::InvalidChunk(
+                ChunkValidationError::InvalidPage { .. }
+            ))
+        ));
+        assert!(matches!(
+            insert_document_with_chunks(
+                &db,
+                &Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1),
+                &[Chunk::new("".into(), "doc-2".into(), "texto".into(), 0, 1)],
+            ),
+            Err(DbError::InvalidChunk(ChunkValidationError::EmptyId {
+                index: 0
+            }))
+        ));
+
+        // Un chunk guardado con un char_count viejo se corrige al leerlo
+        insert_chunk(&db, &valid).unwrap();
+        let key = chunk_key("doc-1", 1, "c-1");
+        open_chunks_tree(&db)
+            .unwrap()
+            .insert(key.as_bytes(), encode(&stale).unwrap())
+            .unwrap();
+        open_chunk_ids_tree(&db)
+            .unwrap()
+            .insert("c-1", key.as_bytes())
+            .unwrap();
+        let read = get_chunk(&db, "c-1").unwrap().unwrap();
+        assert_eq!(read.char_count, 5);
+        assert!(read.char_count_repaired);
+        assert!(!get_chunk(&db, "c-0").unwrap().unwrap().char_count_repaired);
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rename_document() {
+        let (db, path) = temp_db("test_rename_document");
+        let doc = Document::new(
+            "doc-1".into(),
+            "scan_0042.pdf".into(),
+            "/tmp/scan_0042.pdf".into(),
+            1,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        rename_document(&db, "doc-1", "  Apuntes de física ").unwrap();
+        let renamed = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(renamed.name, "Apuntes de física");
+        assert_eq!(renamed.file_path, "/tmp/scan_0042.pdf");
+
+        assert!(matches!(
+            rename_document(&db, "doc-1", "   "),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            rename_document(&db, "nope", "Otro"),
+            Err(DbError::NotFound(_))
+        ));
+        assert_eq!(
+            get_document(&db, "doc-1").unwrap().unwrap().name,
+            "Apuntes de física"
+        );
+
+        drop(db);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_deferred_flush_then_explicit_flush() {
+        // Todo en la misma instancia: al soltar la última referencia sled hace
+        // flush de todo, así que reabrir la BD no diría nada de la política
+        // (y los hilos de IO de sled pueden retener el lock un momento)
+        let path = std::env::temp_dir().join(format!("test_flush_policy_{}", std::process::id()));
+        let db = init_db_at_with_flush_policy(path.clone(), FlushPolicy::Never).unwrap();
+        assert_eq!(flush_policy(&db), FlushPolicy::Never);
+        let unflushed = |db: &Arc<sled::Db>| db_state(db).unflushed_writes.load(Ordering::SeqCst);
+
+        for i in 0..50 {
+            let doc = Document::new(format!("doc-{}", i), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            let chunk = Chunk::new(format!("c-{}", i), doc.id.clone(), "texto".into(), 0, 1);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        for i in 0..10 {
+            delete_document(&db, &format!("doc-{}", i)).unwrap();
+        }
+        // Con `Never` nada se escribió a disco todavía
+        assert!(unflushed(&db) >= 110);
+        // Tampoco fuerzan flush las demás escrituras por documento
+        let before = unflushed(&db);
+        set_document_language(&db, "doc-10", Some("es")).unwrap();
+        blobs::store_document_blob(&db, "doc-10", b"%PDF-1.4").unwrap();
+        assert_eq!(unflushed(&db), before + 2);
+        assert_eq!(count_documents(&db).unwrap(), 40);
+        assert_eq!(count_chunks(&db, None).unwrap(), 40);
+
+        flush_db(&db).unwrap();
+        assert_eq!(unflushed(&db), 0);
+
+        set_flush_policy(&db, FlushPolicy::EveryN(3));
+        for i in 0..2 {
+            let doc = Document::new(
+                format!("doc-x{}", i),
+                "x.pdf".into(),
+                "/tmp/x.pdf".into(),
+                1,
+            );
+            insert_document(&db, &doc).unwrap();
+        }
+        assert_eq!(unflushed(&db), 2);
+        let doc = Document::new("doc-x2".into(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+        insert_document(&db, &doc).unwrap();
+        assert_eq!(unflushed(&db), 0);
+
+        set_flush_policy(&db, FlushPolicy::Always);
+        rename_document(&db, "doc-x2", "Otro").unwrap();
+        assert_eq!(unflushed(&db), 0);
+
+        // Una BD recién abierta usa `Always`
+        let other = init_db_at(path.join("otra")).unwrap();
+        assert_eq!(flush_policy(&other), FlushPolicy::Always);
+
+        drop(db);
+        drop(other);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        let (db, path) = temp_db("test_soft_delete");
+        insert_document(&db, &sample_document()).unwrap();
+        insert_chunk(&db, &sample_chunk(0, "texto")).unwrap();
+        let other = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &other).unwrap();
+
+        let deleted = soft_delete_document(&db, "doc-1").unwrap();
+        let at = deleted.deleted_at.expect("Debe registrar el borrado");
+        let ids: Vec<String> = get_all_documents(&db)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        assert_eq!(ids, ["doc-2"]);
+        // Sigue guardado, con sus chunks
+        assert!(get_document(&db, "doc-1").unwrap().unwrap().is_deleted());
+        assert_eq!(count_chunks_for_document(&db, "doc-1").unwrap(), 1);
+        // Borrarlo de nuevo conserva la fecha original
+        assert_eq!(
+            soft_delete_document(&db, "doc-1").unwrap().deleted_at,
+            Some(at)
+        );
+
+        let restored = restore_document(&db, "doc-1").unwrap();
+        assert!(!restored.is_deleted());
+        assert_eq!(get_all_documents(&db).unwrap().len(), 2);
+        assert!(matches!(
+            restore_document(&db, "falta"),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_purge_deleted() {
+        let (db, path) = temp_db("test_purge_deleted");
+        insert_document(&db, &sample_document()).unwrap();
+        insert_chunk(&db, &sample_chunk(0, "texto")).unwrap();
+        let other = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &other).unwrap();
+        soft_delete_document(&db, "doc-1").unwrap();
+
+        // Recién borrado: no es más viejo que una hora
+        assert_eq!(purge_deleted(&db, 3600).unwrap(), 0);
+        assert_eq!(purge_deleted(&db, 0).unwrap(), 1);
+        assert!(get_document(&db, "doc-1").unwrap().is_none());
+        assert_eq!(count_chunks_for_document(&db, "doc-1").unwrap(), 0);
+        assert!(get_document(&db, "doc-2").unwrap().is_some());
+
+        drop(db);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_decode_document_without_deleted_at() {
+        // Registro guardado antes de que existiera `deleted_at`
+        let doc = sample_document();
+        let mut bytes = bincode::serialize(&doc).unwrap();
+        // `None` en bincode es un solo byte 0 al final
+        assert_eq!(bytes.pop(), Some(0));
+        assert_eq!(decode_document(&bytes).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_decode_chunk_without_token_count() {
+        // Chunk guardado en la primera versión: los 7 campos originales
+        let legacy = (
+            "c-0",
+            "doc-1",
+            "Hola mundo",
+            0usize,
+            2usize,
+            10usize,
+            None::<String>,
+        );
+        let bytes = bincode::serialize(&legacy).unwrap();
+        assert!(decode::<Chunk>(&bytes).is_err());
+
+        let chunk = decode_chunk(&bytes).unwrap();
+        assert_eq!(chunk.id, "c-0");
+        assert_eq!(chunk.text, "Hola mundo");
+        assert_eq!(chunk.page_number, 2);
+        assert_eq!(chunk.token_count, 0);
+        assert_eq!(chunk.start_offset, None);
+        assert_eq!(chunk.end_page, None);
+        assert_eq!(chunk.end_offset, None);
+        assert!(!chunk.char_count_repaired);
+
+        // Y también comprimido
+        let mut compressed = CHUNK_ZSTD_HEADER.to_vec();
+        compressed.extend(zstd::encode_all(bytes.as_slice(), CHUNK_ZSTD_LEVEL).unwrap());
+        assert_eq!(decode_chunk(&compressed).unwrap(), chunk);
+    }
+
+    #[test]
+    fn test_decode_chunk_without_end_page() {
+        // Chunk guardado con `start_offset` y `token_count`, antes de `end_page`
+        let legacy = (
+            "c-0",
+            "doc-1",
+            "Hola mundo",
+            0usize,
+            2usize,
+            10usize,
+            None::<String>,
+            Some(40usize),
+            3usize,
+        );
+        let chunk = decode_chunk(&bincode::serialize(&legacy).unwrap()).unwrap();
+        assert_eq!(chunk.start_offset, Some(40));
+        assert_eq!(chunk.token_count, 3);
+        assert_eq!(chunk.end_page, None);
+        assert_eq!(chunk.end_offset, None);
+        assert!(chunk.validate().is_ok());
+    }
+
+    #[test]
+    fn test_decode_chunk_without_end_offset() {
+        // Chunk guardado con `end_page`, antes de `end_offset`
+        let legacy = (
+            "c-0",
+            "doc-1",
+            "Hola mundo",
+            0usize,
+            2usize,
+            10usize,
+            None::<String>,
+            Some(40usize),
+            3usize,
+            Some(3usize),
+        );
+        let chunk = decode_chunk(&bincode::serialize(&legacy).unwrap()).unwrap();
+        assert_eq!(chunk.start_offset, Some(40));
+        assert_eq!(chunk.end_page, Some(3));
+        assert_eq!(chunk.end_offset, None);
+        assert_eq!(chunk.language, None);
+
+        // Un chunk actual con offsets sigue leyéndose completo
+        let current = chunk.clone().with_offsets(40, 50);
+        let stored = decode_chunk(&encode(&current).unwrap()).unwrap();
+        assert_eq!(stored, current);
+    }
+
+    #[test]
+    fn test_plain_chunk_starting_like_compressed() {
+        // Un id de 193 bytes hace que el chunk sin comprimir empiece con 0xC1
+        let chunk = Chunk::new("x".repeat(193), "doc-1".into(), "texto".into(), 0, 1);
+        let plain = encode_chunk_with(&chunk, false).unwrap();
+        assert_eq!(plain[0], CHUNK_ZSTD_HEADER[0]);
+        assert_eq!(decode_chunk(&plain).unwrap(), chunk);
+
+        let compressed = encode_chunk_with(&chunk, true).unwrap();
+        assert_eq!(decode_chunk(&compressed).unwrap(), chunk);
+
+        // Aunque coincida la firma completa, si no es zstd se lee sin comprimir
+        let id_len = u32::from_le_bytes(CHUNK_ZSTD_HEADER) as usize;
+        let mut forged = (id_len as u64).to_le_bytes().to_vec();
+        forged.extend(vec![b'x'; id_len]);
+        forged.extend(encode(&("doc-1", "texto", 0usize, 1usize, 5usize, None::<String>)).unwrap());
+        assert!(forged.starts_with(&CHUNK_ZSTD_HEADER));
+        let decoded = decode_chunk(&forged).unwrap();
+        assert_eq!(decoded.id.len(), id_len);
+        assert_eq!(decoded.text, "texto");
     }
 }
@@ -0,0 +1,1549 @@
+use crate::models::Chunk;
+use crate::services::database::{
+    document_chunk_keys, embedding_normalization_enabled, embedding_quantization_enabled,
+    ensure_writable, flush_after_write, get_chunks_for_document, open_chunk_ids_tree,
+    open_chunks_tree, open_meta_tree, open_tree, replace_document_chunks, DbError,
+};
+use crate::services::error::EmbedError;
+use crate::services::keys::{
+    parse_chunk_key, EMBEDDINGS_TREE, EMBEDDING_CACHE_TREE, EMBEDDING_DIMENSION_KEY,
+    EMBEDDING_MODEL_KEY,
+};
+use crate::services::ollama::{OllamaEmbedder, DEFAULT_OLLAMA_URL};
+use crate::services::openai::{OpenAiEmbedder, DEFAULT_OPENAI_URL};
+use crate::services::vector_index;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled;
+use std::collections::HashSet;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Backend que genera embeddings (vectores) a partir de textos
+///
+/// Permite cambiar de proveedor (modelo local, API externa, hashing para
+/// tests) sin tocar el resto del pipeline, que solo recibe un
+/// `&dyn EmbeddingProvider`.
+pub trait EmbeddingProvider: Send + Sync {
+    /// Calcula un vector por cada texto, en el mismo orden
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError>;
+
+    /// Dimensión de los vectores que produce
+    fn dimension(&self) -> usize;
+
+    /// Nombre del modelo, para registrar con qué se generaron los vectores
+    fn model_name(&self) -> String;
+}
+
+/// Dimensión por defecto del `HashingEmbedder`
+pub const DEFAULT_HASH_DIMENSION: usize = 256;
+
+/// Proveedor determinista basado en hashing de palabras (feature hashing)
+///
+/// Cada palabra (en minúsculas) se asigna a un bucket del vector mediante un
+/// hash FNV-1a estable, con signo +1/-1 según otro bit del hash, y el vector
+/// final se normaliza. No necesita modelo ni red, y el mismo texto produce
+/// siempre el mismo vector en cualquier máquina.
+///
+/// Solo captura coincidencia de palabras, no significado: sirve para
+/// desarrollo, CI y modo offline, pero la calidad de recuperación es muy
+/// inferior a la de un modelo real.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashingEmbedder {
+    dimension: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(1),
+        }
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimension];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            let hash = fnv1a(word.to_lowercase().as_bytes());
+            let bucket = (hash % self.dimension as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign;
+        }
+
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_DIMENSION)
+    }
+}
+
+impl EmbeddingProvider for HashingEmbedder {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        Ok(texts.iter().map(|t| self.embed_one(t)).collect())
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_name(&self) -> String {
+        format!("hash-{}", self.dimension)
+    }
+}
+
+/// Hash FNV-1a de 64 bits (estable entre versiones de Rust, a diferencia de
+/// `DefaultHasher`)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Configuración del backend de embeddings, serializable para guardarla en
+/// los ajustes de la app
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmbedderConfig {
+    /// `HashingEmbedder` determinista, sin modelo (offline/tests)
+    Hash { dimension: usize },
+    /// Servidor Ollama (ver `OllamaEmbedder`)
+    Ollama {
+        #[serde(default = "default_ollama_url")]
+        base_url: String,
+        model: String,
+        dimension: usize,
+    },
+    /// API compatible con OpenAI (ver `OpenAiEmbedder`)
+    #[serde(rename = "openai")]
+    OpenAi {
+        #[serde(default = "default_openai_url")]
+        base_url: String,
+        #[serde(default)]
+        api_key: Option<String>,
+        model: String,
+        dimension: usize,
+    },
+}
+
+fn default_openai_url() -> String {
+    DEFAULT_OPENAI_URL.to_string()
+}
+
+fn default_ollama_url() -> String {
+    DEFAULT_OLLAMA_URL.to_string()
+}
+
+impl Default for EmbedderConfig {
+    fn default() -> Self {
+        EmbedderConfig::Hash {
+            dimension: DEFAULT_HASH_DIMENSION,
+        }
+    }
+}
+
+impl EmbedderConfig {
+    /// Construye el embedder configurado
+    pub fn build(&self) -> Box<dyn EmbeddingProvider> {
+        match self {
+            EmbedderConfig::Hash { dimension } => Box::new(HashingEmbedder::new(*dimension)),
+            EmbedderConfig::Ollama {
+                base_url,
+                model,
+                dimension,
+            } => Box::new(OllamaEmbedder::new(base_url, model, *dimension)),
+            EmbedderConfig::OpenAi {
+                base_url,
+                api_key,
+                model,
+                dimension,
+            } => Box::new(OpenAiEmbedder::new(
+                base_url,
+                api_key.as_deref(),
+                model,
+                *dimension,
+            )),
+        }
+    }
+}
+
+/// Similitud coseno entre dos vectores (0 si alguno es nulo o difieren en largo)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Producto punto (0 si los largos no coinciden)
+///
+/// Para vectores de norma 1 equivale a la similitud coseno.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Escala el vector a norma 1 (los vectores nulos quedan igual)
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Árbol chunk_id -> vector del chunk
+///
+/// Se guarda aparte del texto para que listar chunks no tenga que leer vectores.
+pub(crate) fn open_embeddings_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, EMBEDDINGS_TREE)
+}
+
+/// Versión del formato binario de los embeddings guardados
+const EMBEDDING_FORMAT_VERSION: u8 = 1;
+
+/// Flag del header: el vector se guardó con norma 1
+const FLAG_NORMALIZED: u8 = 0b0000_0001;
+
+/// Flag del header: el vector se guardó cuantizado a 8 bits
+const FLAG_QUANTIZED: u8 = 0b0000_0010;
+
+/// Embedding guardado junto con el modelo que lo generó
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEmbedding {
+    pub model: String,
+    pub dimension: usize,
+    pub vector: Vec<f32>,
+    /// `true` si el vector se normalizó al guardarlo
+    pub normalized: bool,
+    /// `true` si se guardó cuantizado; `vector` es entonces una aproximación
+    pub quantized: bool,
+}
+
+/// Serializa un vector como f32 little-endian consecutivos
+pub(crate) fn vector_to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Inversa de `vector_to_bytes`
+pub(crate) fn bytes_to_vector(bytes: &[u8]) -> Result<Vec<f32>, DbError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(DbError::Deserialize(format!(
+            "embedding has {} bytes, not a multiple of 4",
+            bytes.len()
+        )));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+/// Cuantiza un vector a 8 bits: retorna `(min, scale, códigos)`
+///
+/// Cada componente se lleva linealmente de `[min, max]` a `0..=255` y se
+/// guarda como i8 (restando 128). Error de a lo sumo `scale / 2` por
+/// componente.
+fn quantize(vector: &[f32]) -> Result<(f32, f32, Vec<i8>), DbError> {
+    let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if vector.is_empty() {
+        return Ok((0.0, 0.0, Vec::new()));
+    }
+    let scale = (max - min) / 255.0;
+    if !scale.is_finite() {
+        return Err(DbError::InvalidInput(
+            "cannot quantize a non-finite embedding".to_string(),
+        ));
+    }
+    let codes = vector
+        .iter()
+        .map(|x| {
+            let level = if scale > 0.0 {
+                ((x - min) / scale).round()
+            } else {
+                0.0
+            };
+            (level as i16 - 128) as i8
+        })
+        .collect();
+    Ok((min, scale, codes))
+}
+
+/// Inversa (aproximada) de `quantize`
+fn dequantize(min: f32, scale: f32, codes: &[u8]) -> Vec<f32> {
+    codes
+        .iter()
+        .map(|c| min + (*c as i8 as i16 + 128) as f32 * scale)
+        .collect()
+}
+
+/// Formato: versión (u8), flags (u8), largo del nombre del modelo (u16),
+/// nombre del modelo (UTF-8), dimensión (u32) y el vector. El vector va en
+/// f32 o, con `FLAG_QUANTIZED`, como min (f32), scale (f32) y un i8 por
+/// componente. Todos los números en little-endian.
+fn encode_embedding(
+    model: &str,
+    vector: &[f32],
+    normalized: bool,
+    quantized: bool,
+) -> Result<Vec<u8>, DbError> {
+    let model_len = u16::try_from(model.len())
+        .map_err(|_| DbError::InvalidInput(format!("model name too long: {}", model)))?;
+    let dimension = u32::try_from(vector.len())
+        .map_err(|_| DbError::InvalidInput("embedding too large".to_string()))?;
+
+    let mut flags = 0;
+    if normalized {
+        flags |= FLAG_NORMALIZED;
+    }
+    if quantized {
+        flags |= FLAG_QUANTIZED;
+    }
+    let mut out = Vec::with_capacity(8 + model.len() + vector.len() * 4);
+    out.push(EMBEDDING_FORMAT_VERSION);
+    out.push(flags);
+    out.extend(model_len.to_le_bytes());
+    out.extend(model.as_bytes());
+    out.extend(dimension.to_le_bytes());
+    if quantized {
+        let (min, scale, codes) = quantize(vector)?;
+        out.extend(min.to_le_bytes());
+        out.extend(scale.to_le_bytes());
+        out.extend(codes.iter().map(|c| *c as u8));
+    } else {
+        out.extend(vector_to_bytes(vector));
+    }
+    Ok(out)
+}
+
+pub(crate) fn decode_embedding(bytes: &[u8]) -> Result<StoredEmbedding, DbError> {
+    let corrupt = || DbError::Deserialize("truncated embedding header".to_string());
+    if bytes.first() != Some(&EMBEDDING_FORMAT_VERSION) {
+        return Err(DbError::Deserialize(format!(
+            "unsupported embedding format {:?}",
+            bytes.first()
+        )));
+    }
+    let flags = *bytes.get(1).ok_or_else(corrupt)?;
+    let model_len = u16::from_le_bytes(bytes.get(2..4).ok_or_else(corrupt)?.try_into().unwrap());
+    let model_end = 4 + model_len as usize;
+    let model = std::str::from_utf8(bytes.get(4..model_end).ok_or_else(corrupt)?)
+        .map_err(|e| DbError::Deserialize(e.to_string()))?
+        .to_string();
+    let dimension = u32::from_le_bytes(
+        bytes
+            .get(model_end..model_end + 4)
+            .ok_or_else(corrupt)?
+            .try_into()
+            .unwrap(),
+    ) as usize;
+
+    let data = &bytes[model_end + 4..];
+    let quantized = flags & FLAG_QUANTIZED != 0;
+    let vector = if quantized {
+        let params = bytes_to_vector(data.get(..8).ok_or_else(corrupt)?)?;
+        dequantize(params[0], params[1], &data[8..])
+    } else {
+        bytes_to_vector(data)?
+    };
+    if vector.len() != dimension {
+        return Err(DbError::Deserialize(format!(
+            "embedding header says {} dimensions but has {}",
+            dimension,
+            vector.len()
+        )));
+    }
+    Ok(StoredEmbedding {
+        model,
+        dimension,
+        vector,
+        normalized: flags & FLAG_NORMALIZED != 0,
+        quantized,
+    })
+}
+
+/// Guarda (o reemplaza) el embedding de un chunk existente
+///
+/// `dim` es la dimensión que produce el modelo; un vector de otro largo se
+/// rechaza con `DbError::InvalidInput` en vez de guardar datos inservibles.
+/// Lo mismo si `dim` no coincide con la de los embeddings ya guardados (ver
+/// `embedding_dimension`), para no mezclar vectores de modelos distintos.
+/// Si `model` no es el modelo de la biblioteca (ver
+/// `get_library_embedding_info`) falla con `EmbedError::ModelMismatch`.
+/// Si está activada `set_embedding_normalization`, se guarda normalizado, y
+/// con `set_embedding_quantization`, cuantizado a 8 bits.
+pub fn insert_embedding(
+    db: &Arc<sled::Db>,
+    chunk_id: &str,
+    vector: &[f32],
+    model: &str,
+    dim: usize,
+) -> Result<(), EmbedError> {
+    insert_embedding_with_model_override(db, chunk_id, vector, model, dim, false)
+}
+
+/// Igual que `insert_embedding`, pero eligiendo si se acepta un modelo
+/// distinto del de la biblioteca
+///
+/// Con `allow_model_change` el vector se guarda aunque el modelo o la
+/// dimensión no coincidan con los de la biblioteca, y pasan a ser los de
+/// `model` y `dim`; sirve para reindexar todo con un modelo nuevo. Mientras
+/// tanto los vectores viejos de otra dimensión puntúan 0 en las búsquedas.
+pub fn insert_embedding_with_model_override(
+    db: &Arc<sled::Db>,
+    chunk_id: &str,
+    vector: &[f32],
+    model: &str,
+    dim: usize,
+    allow_model_change: bool,
+) -> Result<(), EmbedError> {
+    let _write = ensure_writable(db)?;
+    if vector.len() != dim {
+        return Err(DbError::InvalidInput(format!(
+            "embedding for chunk {} has {} dimensions, expected {}",
+            chunk_id,
+            vector.len(),
+            dim
+        ))
+        .into());
+    }
+    if !open_chunk_ids_tree(db)?.contains_key(chunk_id.as_bytes())? {
+        return Err(DbError::NotFound(chunk_id.to_string()).into());
+    }
+    claim_library_meta(db, &[chunk_id], model, dim, allow_model_change)?;
+
+    let value = encode_for_storage(db, model, vector)?;
+    let tree = open_embeddings_tree(db)?;
+    let previous = tree.insert(chunk_id.as_bytes(), value.as_slice())?;
+    if !allow_model_change {
+        // Otra escritura pudo reemplazar el modelo de la biblioteca entre el
+        // registro y el insert (solo pasa si no había otros vectores): este
+        // vector ya no vale y se deshace
+        if let Err(e) = check_library_meta(db, model, dim) {
+            let _ = tree.compare_and_swap(chunk_id.as_bytes(), Some(value.as_slice()), previous)?;
+            return Err(e);
+        }
+    }
+    vector_index::on_embedding_stored(db, chunk_id, vector);
+    flush_after_write(db)?;
+    Ok(())
+}
+
+/// Guarda los chunks nuevos de un documento con sus vectores (`None` para
+/// los que no tienen), reemplazando de una vez los anteriores (ver
+/// `replace_document_chunks`)
+///
+/// Modelo y dimensión se validan y registran como en
+/// `insert_embedding_with_model_override`; los vectores que se reemplazan no
+/// cuentan como de otro modelo. Si algo falla el documento conserva sus
+/// chunks y embeddings anteriores.
+pub(crate) fn replace_document_embeddings(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    chunks: &[Chunk],
+    vectors: &[Option<Vec<f32>>],
+    model: &str,
+    dim: usize,
+    allow_model_change: bool,
+) -> Result<(), EmbedError> {
+    let _write = ensure_writable(db)?;
+    if let Some(vector) = vectors.iter().flatten().find(|v| v.len() != dim) {
+        return Err(DbError::InvalidInput(format!(
+            "embedding has {} dimensions, expected {}",
+            vector.len(),
+            dim
+        ))
+        .into());
+    }
+    let old_ids: Vec<String> = document_chunk_keys(&open_chunks_tree(db)?, document_id)?
+        .iter()
+        .filter_map(|key| parse_chunk_key(key).map(|parsed| parsed.chunk_id))
+        .collect();
+    let replaced: Vec<&str> = old_ids
+        .iter()
+        .map(String::as_str)
+        .chain(chunks.iter().map(|c| c.id.as_str()))
+        .collect();
+    if vectors.iter().any(Option::is_some) {
+        claim_library_meta(db, &replaced, model, dim, allow_model_change)?;
+    }
+
+    let encoded = vectors
+        .iter()
+        .map(|vector| {
+            vector
+                .as_ref()
+                .map(|vector| encode_for_storage(db, model, vector))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let removed = replace_document_chunks(db, document_id, chunks, &encoded)?;
+    vector_index::on_embeddings_removed(db, &removed);
+    for (chunk, vector) in chunks.iter().zip(vectors) {
+        if let Some(vector) = vector {
+            vector_index::on_embedding_stored(db, &chunk.id, vector);
+        }
+    }
+    Ok(())
+}
+
+/// Codifica un vector según las opciones de normalización y cuantización de
+/// la BD
+fn encode_for_storage(db: &Arc<sled::Db>, model: &str, vector: &[f32]) -> Result<Vec<u8>, DbError> {
+    let quantized = embedding_quantization_enabled(db);
+    if embedding_normalization_enabled(db) {
+        let mut vector = vector.to_vec();
+        normalize(&mut vector);
+        encode_embedding(model, &vector, true, quantized)
+    } else {
+        encode_embedding(model, vector, false, quantized)
+    }
+}
+
+/// Verifica que "meta" siga teniendo `model` y `dim` como los de la biblioteca
+fn check_library_meta(db: &Arc<sled::Db>, model: &str, dim: usize) -> Result<(), EmbedError> {
+    let meta = open_meta_tree(db)?;
+    if let Some(stored) = meta.get(EMBEDDING_DIMENSION_KEY)? {
+        let stored = decode_dimension(&stored)?;
+        if stored != dim {
+            return Err(DbError::InvalidInput(format!(
+                "embedding has {} dimensions, but the library uses {}",
+                dim, stored
+            ))
+            .into());
+        }
+    }
+    match meta.get(EMBEDDING_MODEL_KEY)? {
+        Some(stored) if stored.as_ref() != model.as_bytes() => Err(EmbedError::ModelMismatch {
+            stored: String::from_utf8_lossy(&stored).into_owned(),
+            requested: model.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Dimensión de los embeddings de la biblioteca, si ya se guardó alguno
+pub fn embedding_dimension(db: &Arc<sled::Db>) -> Result<Option<usize>, DbError> {
+    match open_meta_tree(db)?.get(EMBEDDING_DIMENSION_KEY)? {
+        Some(bytes) => Ok(Some(decode_dimension(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+fn decode_dimension(bytes: &[u8]) -> Result<usize, DbError> {
+    let bytes: [u8; 8] = bytes
+        .try_into()
+        .map_err(|_| DbError::Deserialize(format!("invalid {} value", EMBEDDING_DIMENSION_KEY)))?;
+    Ok(u64::from_le_bytes(bytes) as usize)
+}
+
+/// Modelo y dimensión con los que se calcularon los embeddings de la biblioteca
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LibraryEmbeddingInfo {
+    pub model: String,
+    pub dimension: usize,
+}
+
+/// Modelo de embeddings de la biblioteca, para mostrarlo en la configuración
+///
+/// Se registra en "meta" con el primer embedding guardado. En bibliotecas
+/// anteriores a ese registro se toma el modelo de algún vector guardado.
+/// Retorna `None` si todavía no hay embeddings.
+pub fn get_library_embedding_info(
+    db: &Arc<sled::Db>,
+) -> Result<Option<LibraryEmbeddingInfo>, DbError> {
+    let Some(dimension) = embedding_dimension(db)? else {
+        return Ok(None);
+    };
+    let model = match open_meta_tree(db)?.get(EMBEDDING_MODEL_KEY)? {
+        Some(bytes) => String::from_utf8(bytes.to_vec())
+            .map_err(|_| DbError::Deserialize(format!("invalid {} value", EMBEDDING_MODEL_KEY)))?,
+        None => match iter_embeddings(db)?.next() {
+            Some(item) => item?.1.model,
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(LibraryEmbeddingInfo { model, dimension }))
+}
+
+/// Indica si hay embeddings guardados además de los de `replaced`
+fn other_embeddings_exist(db: &Arc<sled::Db>, replaced: &[&str]) -> Result<bool, DbError> {
+    let tree = open_embeddings_tree(db)?;
+    let mut own = HashSet::new();
+    for &chunk_id in replaced {
+        if tree.contains_key(chunk_id.as_bytes())? {
+            own.insert(chunk_id);
+        }
+    }
+    Ok(tree.len() > own.len())
+}
+
+/// Registra modelo y dimensión para guardar vectores de `replaced`
+///
+/// Con `allow_model_change` pasan a ser los de la biblioteca; si no, falla
+/// cuando no coinciden con los registrados (ver `claim_library_model`).
+fn claim_library_meta(
+    db: &Arc<sled::Db>,
+    replaced: &[&str],
+    model: &str,
+    dim: usize,
+    allow_model_change: bool,
+) -> Result<(), EmbedError> {
+    if allow_model_change {
+        let meta = open_meta_tree(db)?;
+        meta.insert(EMBEDDING_DIMENSION_KEY, &(dim as u64).to_le_bytes())?;
+        meta.insert(EMBEDDING_MODEL_KEY, model.as_bytes())?;
+        return Ok(());
+    }
+    claim_embedding_dimension(db, replaced, dim)?;
+    claim_library_model(db, replaced, model)
+}
+
+/// Registra `model` como el modelo de la biblioteca, o falla con
+/// `EmbedError::ModelMismatch` si ya es otro
+///
+/// Como con la dimensión, si no queda ningún otro vector guardado se acepta
+/// cualquier modelo.
+fn claim_library_model(
+    db: &Arc<sled::Db>,
+    replaced: &[&str],
+    model: &str,
+) -> Result<(), EmbedError> {
+    let mismatch = |stored: String| EmbedError::ModelMismatch {
+        stored,
+        requested: model.to_string(),
+    };
+    // Bibliotecas anteriores al registro: el modelo es el de sus vectores
+    if open_meta_tree(db)?.get(EMBEDDING_MODEL_KEY)?.is_none() {
+        if let Some(info) = get_library_embedding_info(db)? {
+            if info.model != model && other_embeddings_exist(db, replaced)? {
+                return Err(mismatch(info.model));
+            }
+        }
+    }
+    match claim_meta_value(db, EMBEDDING_MODEL_KEY, model.as_bytes(), replaced)? {
+        Some(stored) => Err(mismatch(String::from_utf8_lossy(&stored).into_owned())),
+        None => Ok(()),
+    }
+}
+
+/// Registra `dim` como la dimensión de la biblioteca, o falla si ya es otra
+///
+/// El primer embedding fija la dimensión. Si no queda ningún otro vector
+/// guardado (biblioteca vacía, o solo se reemplazan los de estos chunks)
+/// se acepta la nueva dimensión, para poder cambiar de modelo tras borrar
+/// los vectores viejos.
+fn claim_embedding_dimension(
+    db: &Arc<sled::Db>,
+    replaced: &[&str],
+    dim: usize,
+) -> Result<(), DbError> {
+    let value = (dim as u64).to_le_bytes();
+    match claim_meta_value(db, EMBEDDING_DIMENSION_KEY, &value, replaced)? {
+        Some(stored) => Err(DbError::InvalidInput(format!(
+            "embedding has {} dimensions, but the library uses {}",
+            dim,
+            decode_dimension(&stored)?
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Guarda `value` en la clave `key` de "meta" si está libre o si ya tiene
+/// ese valor; si tiene otro, solo lo reemplaza cuando no hay más vectores
+/// que los de `replaced`. Retorna el valor guardado que impidió escribir.
+///
+/// Se escribe con `compare_and_swap` sobre el valor leído: si otra
+/// escritura cambió la clave en el medio se vuelve a revisar, así dos
+/// embeddings simultáneos de modelos distintos no registran cada uno el suyo.
+/// Si al reemplazar otro valor aparece un vector nuevo, se devuelve el
+/// valor anterior (el del vector que ganó).
+fn claim_meta_value(
+    db: &Arc<sled::Db>,
+    key: &str,
+    value: &[u8],
+    replaced: &[&str],
+) -> Result<Option<sled::IVec>, DbError> {
+    let meta = open_meta_tree(db)?;
+    loop {
+        let current = meta.get(key)?;
+        if current.as_deref() == Some(value) {
+            return Ok(None);
+        }
+        if let Some(stored) = &current {
+            if other_embeddings_exist(db, replaced)? {
+                return Ok(Some(stored.clone()));
+            }
+        }
+        if meta
+            .compare_and_swap(key, current.clone(), Some(value))?
+            .is_err()
+        {
+            continue;
+        }
+        if let Some(previous) = current {
+            if other_embeddings_exist(db, replaced)? {
+                let _ = meta.compare_and_swap(key, Some(value), Some(previous.clone()))?;
+                return Ok(Some(previous));
+            }
+        }
+        return Ok(None);
+    }
+}
+
+/// Retorna el embedding de un chunk con su modelo y dimensión, si existe
+pub fn get_embedding_record(
+    db: &Arc<sled::Db>,
+    chunk_id: &str,
+) -> Result<Option<StoredEmbedding>, DbError> {
+    match open_embeddings_tree(db)?.get(chunk_id.as_bytes())? {
+        Some(bytes) => Ok(Some(decode_embedding(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Retorna el vector de un chunk, si ya fue calculado
+pub fn get_embedding(db: &Arc<sled::Db>, chunk_id: &str) -> Result<Option<Vec<f32>>, DbError> {
+    Ok(get_embedding_record(db, chunk_id)?.map(|e| e.vector))
+}
+
+/// Recorre todos los embeddings guardados como `(chunk_id, embedding)`
+pub(crate) fn iter_embeddings(
+    db: &sled::Db,
+) -> Result<impl Iterator<Item = Result<(String, StoredEmbedding), DbError>>, DbError> {
+    Ok(open_embeddings_tree(db)?.iter().map(|item| {
+        let (k, v) = item?;
+        Ok((
+            String::from_utf8_lossy(&k).into_owned(),
+            decode_embedding(&v)?,
+        ))
+    }))
+}
+
+/// Normaliza en el lugar los embeddings que aún no lo estaban
+///
+/// Migra una biblioteca existente para que la búsqueda pueda usar el producto
+/// punto. `progress` recibe `(procesados, total)` tras cada embedding.
+/// Retorna cuántos se convirtieron; los ya normalizados se saltan.
+pub fn normalize_embeddings(
+    db: &Arc<sled::Db>,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_embeddings_tree(db)?;
+    let total = tree.len();
+
+    let mut converted = 0;
+    for (done, item) in tree.iter().enumerate() {
+        let (k, v) = item?;
+        let mut record = decode_embedding(&v)?;
+        if !record.normalized {
+            normalize(&mut record.vector);
+            let value = encode_embedding(&record.model, &record.vector, true, record.quantized)?;
+            tree.insert(k, value)?;
+            converted += 1;
+        }
+        progress(done + 1, total);
+    }
+    tree.flush()?;
+    Ok(converted)
+}
+
+/// Elimina los embeddings de los chunks indicados (los que no existan se ignoran)
+pub(crate) fn delete_embeddings<S: AsRef<str>>(
+    db: &Arc<sled::Db>,
+    chunk_ids: &[S],
+) -> Result<(), DbError> {
+    let mut batch = sled::Batch::default();
+    for id in chunk_ids {
+        batch.remove(id.as_ref().as_bytes());
+    }
+    open_embeddings_tree(db)?.apply_batch(batch)?;
+    vector_index::on_embeddings_removed(db, chunk_ids);
+    Ok(())
+}
+
+/// Árbol sha256(modelo + texto) -> vector (f32 LE)
+///
+/// Permite reindexar un documento apenas editado sin volver a pedir al
+/// backend los vectores de los chunks que no cambiaron.
+pub(crate) fn open_embedding_cache_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, EMBEDDING_CACHE_TREE)
+}
+
+/// Clave del cache para `text` embebido con `model`
+fn embedding_cache_key(model: &str, text: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    // Separador para que ("ab", "c") y ("a", "bc") no compartan clave
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Vector cacheado de `text` con `model`, si hay
+pub(crate) fn get_cached_embedding(
+    db: &Arc<sled::Db>,
+    model: &str,
+    text: &str,
+) -> Result<Option<Vec<f32>>, DbError> {
+    match open_embedding_cache_tree(db)?.get(embedding_cache_key(model, text))? {
+        Some(bytes) => Ok(Some(bytes_to_vector(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Guarda en el cache el vector de `text` con `model`
+pub(crate) fn cache_embedding(
+    db: &Arc<sled::Db>,
+    model: &str,
+    text: &str,
+    vector: &[f32],
+) -> Result<(), DbError> {
+    open_embedding_cache_tree(db)?
+        .insert(embedding_cache_key(model, text), vector_to_bytes(vector))?;
+    Ok(())
+}
+
+/// Vectores de `texts` con el modelo de `provider`, en el mismo orden
+///
+/// Los que están en el cache de embeddings no se piden; el resto se pide en
+/// una sola llamada y se agrega al cache. Retorna también cuántos salieron
+/// del cache.
+pub(crate) fn embed_texts_cached(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    texts: &[String],
+) -> Result<(Vec<Vec<f32>>, usize), EmbedError> {
+    let model = provider.model_name();
+    let dim = provider.dimension();
+    let mut vectors = Vec::with_capacity(texts.len());
+    let mut missing = Vec::new();
+    for (i, text) in texts.iter().enumerate() {
+        let cached = get_cached_embedding(db, &model, text)?.filter(|v| v.len() == dim);
+        if cached.is_none() {
+            missing.push(i);
+        }
+        vectors.push(cached);
+    }
+    let hits = texts.len() - missing.len();
+
+    if !missing.is_empty() {
+        let pending: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+        let computed = provider.embed(&pending)?;
+        if computed.len() != pending.len() {
+            return Err(EmbedError::InvalidOutput(format!(
+                "{} vectors for {} texts",
+                computed.len(),
+                pending.len()
+            )));
+        }
+        for (&i, vector) in missing.iter().zip(computed) {
+            cache_embedding(db, &model, &texts[i], &vector)?;
+            vectors[i] = Some(vector);
+        }
+    }
+    Ok((vectors.into_iter().flatten().collect(), hits))
+}
+
+/// Vacía el cache de embeddings; retorna cuántas entradas tenía
+///
+/// Los embeddings ya guardados de los chunks no se tocan.
+pub fn clear_embedding_cache(db: &Arc<sled::Db>) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let tree = open_embedding_cache_tree(db)?;
+    let removed = tree.len();
+    tree.clear()?;
+    tree.flush()?;
+    Ok(removed)
+}
+
+/// Cantidad de textos que se envían al embedder en cada llamada
+pub const EMBED_BATCH_SIZE: usize = 32;
+
+/// Genera embeddings solo para los chunks del documento que aún no tienen
+///
+/// Permite retomar una indexación interrumpida sin volver a calcular lo que
+/// ya estaba hecho. Retorna cuántos chunks se embebieron (0 si no faltaba
+/// ninguno). Cada lote se guarda apenas se calcula.
+pub fn embed_missing_chunks(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    document_id: &str,
+) -> Result<usize, EmbedError> {
+    let tree = open_embeddings_tree(db)?;
+    let mut missing = Vec::new();
+    for chunk in get_chunks_for_document(db, document_id)? {
+        if !tree.contains_key(chunk.id.as_bytes())? {
+            missing.push(chunk);
+        }
+    }
+    embed_chunks(db, provider, &missing, None)
+}
+
+/// Avance de `embed_document_chunks`: chunks embebidos sobre el total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EmbedProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Calcula y guarda los embeddings de todos los chunks de un documento
+///
+/// Los vectores que ya existían se reemplazan. Si se pasa `progress`, tras
+/// cada lote de `EMBED_BATCH_SIZE` se envía un `EmbedProgress` (el último
+/// con `done == total`; un documento sin chunks envía `{ 0, 0 }`), así un
+/// comando de Tauri puede reenviarlos al frontend desde otro hilo. Si el
+/// receptor ya no existe los avisos se descartan sin cortar el trabajo.
+/// Retorna cuántos chunks se embebieron.
+pub fn embed_document_chunks(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    document_id: &str,
+    progress: Option<&Sender<EmbedProgress>>,
+) -> Result<usize, EmbedError> {
+    let chunks = get_chunks_for_document(db, document_id)?;
+    embed_chunks(db, provider, &chunks, progress)
+}
+
+/// Embebe `chunks` por lotes, guardando cada lote apenas se calcula
+fn embed_chunks(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    chunks: &[Chunk],
+    progress: Option<&Sender<EmbedProgress>>,
+) -> Result<usize, EmbedError> {
+    let total = chunks.len();
+    let report = |done| {
+        if let Some(sender) = progress {
+            let _ = sender.send(EmbedProgress { done, total });
+        }
+    };
+    if chunks.is_empty() {
+        report(0);
+    }
+
+    let mut done = 0;
+    for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+        done += embed_batch(db, provider, batch)?;
+        report(done);
+    }
+    Ok(total)
+}
+
+/// Embebe un lote y lo guarda; retorna cuántos chunks se embebieron
+///
+/// Los textos que están en el cache de embeddings no se piden al
+/// proveedor; el resto se pide en una sola llamada (ver
+/// `embed_texts_cached`).
+fn embed_batch(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    batch: &[Chunk],
+) -> Result<usize, EmbedError> {
+    let texts: Vec<String> = batch.iter().map(|c| c.text.clone()).collect();
+    let (vectors, _) = embed_texts_cached(db, provider, &texts)?;
+    for (chunk, vector) in batch.iter().zip(&vectors) {
+        insert_embedding(
+            db,
+            &chunk.id,
+            vector,
+            &provider.model_name(),
+            provider.dimension(),
+        )?;
+    }
+    Ok(batch.len())
+}
+
+/// Igual que `embed_document_chunks`, pero con hasta `concurrency` lotes
+/// pidiéndose al proveedor a la vez
+///
+/// Sirve para backends que atienden varias peticiones en paralelo (un
+/// servidor local con varios núcleos, una API externa). Cada lote corre en
+/// un hilo de `spawn_blocking` y se guarda apenas termina, en cualquier
+/// orden. Con `concurrency` 0 se usa 1. Si un lote falla se devuelve ese
+/// error y no se lanzan más; los lotes ya guardados quedan. Retorna cuántos
+/// chunks se embebieron.
+pub async fn embed_document_chunks_parallel(
+    db: &Arc<sled::Db>,
+    provider: Arc<dyn EmbeddingProvider>,
+    document_id: &str,
+    concurrency: usize,
+) -> Result<usize, EmbedError> {
+    let chunks = get_chunks_for_document(db, document_id)?;
+    let concurrency = concurrency.max(1);
+    let mut tasks = JoinSet::new();
+    let mut embedded = 0;
+    for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+        if tasks.len() >= concurrency {
+            embedded += join_batch(&mut tasks).await?;
+        }
+        let (db, provider, batch) = (Arc::clone(db), Arc::clone(&provider), batch.to_vec());
+        tasks.spawn_blocking(move || embed_batch(&db, provider.as_ref(), &batch));
+    }
+    while !tasks.is_empty() {
+        embedded += join_batch(&mut tasks).await?;
+    }
+    Ok(embedded)
+}
+
+/// Espera el próximo lote de `embed_document_chunks_parallel` que termine
+async fn join_batch(tasks: &mut JoinSet<Result<usize, EmbedError>>) -> Result<usize, EmbedError> {
+    match tasks.join_next().await {
+        Some(Ok(result)) => result,
+        Some(Err(e)) => Err(EmbedError::Backend(format!("embedding task failed: {}", e))),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{get_chunks_for_document, insert_chunk, insert_document};
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db, TestProvider};
+
+    #[test]
+    fn test_hash_embedder_is_deterministic() {
+        let embedder = HashingEmbedder::new(64);
+        let texts = vec!["Redes neuronales profundas".to_string()];
+
+        let a = embedder.embed(&texts).unwrap();
+        let b = HashingEmbedder::new(64).embed(&texts).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a[0].len(), 64);
+
+        // Normalizado: norma 1
+        let norm = a[0].iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+
+        // Texto vacío produce un vector nulo, no NaN
+        let empty = embedder.embed(&["".to_string()]).unwrap();
+        assert!(empty[0].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_embedder_config_builds_hash_embedder() {
+        let config: EmbedderConfig =
+            serde_json::from_str(r#"{"type":"hash","dimension":32}"#).unwrap();
+        let embedder = config.build();
+        assert_eq!(embedder.dimension(), 32);
+        assert_eq!(embedder.model_name(), "hash-32");
+
+        let config: EmbedderConfig =
+            serde_json::from_str(r#"{"type":"ollama","model":"nomic-embed-text","dimension":768}"#)
+                .unwrap();
+        assert_eq!(
+            config,
+            EmbedderConfig::Ollama {
+                base_url: DEFAULT_OLLAMA_URL.to_string(),
+                model: "nomic-embed-text".to_string(),
+                dimension: 768,
+            }
+        );
+        let embedder = config.build();
+        assert_eq!(embedder.dimension(), 768);
+        assert_eq!(embedder.model_name(), "nomic-embed-text");
+
+        let config: EmbedderConfig = serde_json::from_str(
+            r#"{"type":"openai","api_key":"sk-x","model":"text-embedding-3-small","dimension":1536}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            &config,
+            EmbedderConfig::OpenAi { base_url, .. } if base_url == DEFAULT_OPENAI_URL
+        ));
+        assert_eq!(config.build().dimension(), 1536);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 2.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn test_embedding_roundtrip_and_cascade() {
+        let (db, path) = temp_db("test_embedding_rt");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        let vector: Vec<f32> = (0..384).map(|i| (i as f32 * 0.37).sin() * 1e3).collect();
+        insert_embedding(&db, "c-0", &vector, "nomic-embed-text", 384).unwrap();
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), Some(vector.clone()));
+        let record = get_embedding_record(&db, "c-0").unwrap().unwrap();
+        assert_eq!(record.model, "nomic-embed-text");
+        assert_eq!(record.dimension, 384);
+        assert_eq!(record.vector, vector);
+
+        // Valores extremos
+        let extremes = vec![0.25, -1.5, 3.0e-7, f32::MAX, f32::MIN_POSITIVE, -0.0];
+        insert_embedding(&db, "c-0", &extremes, "m", 6).unwrap();
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), Some(extremes));
+
+        // No se guardan vectores de chunks inexistentes
+        assert!(matches!(
+            insert_embedding(&db, "nope", &[1.0], "m", 1),
+            Err(EmbedError::Db(DbError::NotFound(_)))
+        ));
+
+        // Borrar el chunk elimina su embedding
+        let other = sample_chunk(1, "otro");
+        insert_chunk(&db, &other).unwrap();
+        insert_embedding(&db, "c-1", &[1.0; 6], "m", 6).unwrap();
+        crate::services::database::delete_chunk(&db, "c-1").unwrap();
+        assert_eq!(get_embedding(&db, "c-1").unwrap(), None);
+
+        // Borrar el documento elimina también sus embeddings
+        crate::services::database::delete_document(&db, "doc-1").unwrap();
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), None);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_insert_embedding_normalized() {
+        let (db, path) = temp_db("test_embedding_norm");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        crate::services::database::set_embedding_normalization(&db, true);
+        insert_embedding(&db, "c-0", &[3.0, 4.0], "m", 2).unwrap();
+        let record = get_embedding_record(&db, "c-0").unwrap().unwrap();
+        assert!(record.normalized);
+        assert_eq!(record.vector, vec![0.6, 0.8]);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_insert_embedding_dimension_mismatch() {
+        let (db, path) = temp_db("test_embedding_dim");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        let err = insert_embedding(&db, "c-0", &[1.0, 2.0, 3.0], "m", 4).unwrap_err();
+        assert!(matches!(err, EmbedError::Db(DbError::InvalidInput(_))));
+        assert!(err.to_string().contains("3 dimensions, expected 4"));
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), None);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_quantized_embedding_storage() {
+        let (db, path) = temp_db("test_embedding_q8");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for i in 0..2 {
+            let chunk = sample_chunk(i, "texto");
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let vector: Vec<f32> = (0..384).map(|i| (i as f32 * 0.37).sin()).collect();
+
+        insert_embedding(&db, "c-0", &vector, "m", 384).unwrap();
+        crate::services::database::set_embedding_quantization(&db, true);
+        insert_embedding(&db, "c-1", &vector, "m", 384).unwrap();
+
+        // Header (1 + 1 + 2 + 1 + 4) + datos: 4 bytes por componente en f32,
+        // 8 bytes de parámetros más 1 por componente cuantizado
+        let tree = open_embeddings_tree(&db).unwrap();
+        assert_eq!(tree.get("c-0").unwrap().unwrap().len(), 9 + 384 * 4);
+        assert_eq!(tree.get("c-1").unwrap().unwrap().len(), 9 + 8 + 384);
+
+        // La biblioteca mixta se lee igual; el cuantizado es una aproximación
+        let exact = get_embedding_record(&db, "c-0").unwrap().unwrap();
+        assert!(!exact.quantized);
+        assert_eq!(exact.vector, vector);
+        let approx = get_embedding_record(&db, "c-1").unwrap().unwrap();
+        assert!(approx.quantized);
+        assert_eq!(approx.dimension, 384);
+        let step = 2.0 / 255.0;
+        for (a, b) in approx.vector.iter().zip(&vector) {
+            assert!((a - b).abs() <= step / 2.0 + 1e-6);
+        }
+        assert!(cosine_similarity(&approx.vector, &vector) > 0.999);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_embedding_dimension_is_fixed_by_first_insert() {
+        let (db, path) = temp_db("test_embedding_meta");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for i in 0..2 {
+            let chunk = sample_chunk(i, "texto");
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        assert_eq!(embedding_dimension(&db).unwrap(), None);
+
+        insert_embedding(&db, "c-0", &vec![0.1; 384], "small", 384).unwrap();
+        assert_eq!(embedding_dimension(&db).unwrap(), Some(384));
+
+        let err = insert_embedding(&db, "c-1", &vec![0.1; 768], "large", 768).unwrap_err();
+        assert!(matches!(err, EmbedError::Db(DbError::InvalidInput(_))));
+        let message = err.to_string();
+        assert!(message.contains("768") && message.contains("384"));
+        assert_eq!(get_embedding(&db, "c-1").unwrap(), None);
+
+        // Sin vectores viejos se puede cambiar de modelo
+        delete_embeddings(&db, &["c-0"]).unwrap();
+        insert_embedding(&db, "c-1", &vec![0.1; 768], "large", 768).unwrap();
+        assert_eq!(embedding_dimension(&db).unwrap(), Some(768));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_embed_missing_chunks_only_embeds_new() {
+        let (db, path) = temp_db("test_embed_missing");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for i in 0..2 {
+            let chunk = sample_chunk(i, format!("texto {}", i));
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let embedder = HashingEmbedder::new(8);
+        let existing = vec![9.0; 8];
+        insert_embedding(&db, "c-0", &existing, &embedder.model_name(), 8).unwrap();
+
+        assert_eq!(embed_missing_chunks(&db, &embedder, "doc-1").unwrap(), 1);
+
+        // El vector que ya estaba no se recalcula
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), Some(existing));
+        assert_eq!(get_embedding(&db, "c-1").unwrap().unwrap().len(), 8);
+
+        // Segunda pasada: no falta nada
+        assert_eq!(embed_missing_chunks(&db, &embedder, "doc-1").unwrap(), 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_embed_document_chunks_reports_progress() {
+        let (db, path) = temp_db("test_embed_progress");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let total = EMBED_BATCH_SIZE * 2 + 5;
+        for i in 0..total {
+            let chunk = sample_chunk(i, format!("t {}", i));
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let embedder = HashingEmbedder::new(8);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let embedded = embed_document_chunks(&db, &embedder, "doc-1", Some(&sender)).unwrap();
+        drop(sender);
+        assert_eq!(embedded, total);
+        let updates: Vec<EmbedProgress> = receiver.iter().collect();
+        let done: Vec<usize> = updates.iter().map(|p| p.done).collect();
+        assert_eq!(done, [EMBED_BATCH_SIZE, EMBED_BATCH_SIZE * 2, total]);
+        assert!(updates.iter().all(|p| p.total == total));
+        let last = updates.last().unwrap();
+        assert_eq!(last.done, last.total);
+
+        // Sin canal también funciona, y un documento vacío avisa { 0, 0 }
+        assert_eq!(
+            embed_document_chunks(&db, &embedder, "doc-1", None).unwrap(),
+            total
+        );
+        let (sender, receiver) = std::sync::mpsc::channel();
+        assert_eq!(
+            embed_document_chunks(&db, &embedder, "vacío", Some(&sender)).unwrap(),
+            0
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            EmbedProgress { done: 0, total: 0 }
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_end_to_end_with_hash_embedder() {
+        let (db, path) = temp_db("test_hash_search");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let texts = [
+            "La fotosíntesis convierte la luz en energía química",
+            "El motor de combustión quema gasolina",
+            "Las redes neuronales aprenden de los datos",
+        ];
+        for (i, text) in texts.iter().enumerate() {
+            let chunk = sample_chunk(i, *text);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        let embedder = EmbedderConfig::default().build();
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        let vectors = embedder
+            .embed(&chunks.iter().map(|c| c.text.clone()).collect::<Vec<_>>())
+            .unwrap();
+        let query = embedder
+            .embed(&["¿cómo aprenden las redes neuronales?".to_string()])
+            .unwrap()
+            .remove(0);
+
+        for (chunk, vector) in chunks.iter().zip(&vectors) {
+            insert_embedding(
+                &db,
+                &chunk.id,
+                vector,
+                &embedder.model_name(),
+                embedder.dimension(),
+            )
+            .unwrap();
+        }
+
+        let hits = crate::services::search::search_similar(&db, &query, 3).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].0.id, "c-2");
+        assert!(hits.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_embed_through_provider_then_search() {
+        let (db, path) = temp_db("test_index_provider");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let texts = [
+            "La fotosíntesis convierte la luz en energía química",
+            "El motor de combustión quema gasolina",
+            "Las redes neuronales aprenden de los datos",
+        ];
+        for (i, text) in texts.iter().enumerate() {
+            let chunk = sample_chunk(i, *text);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        let provider: &dyn EmbeddingProvider = &HashingEmbedder::new(128);
+        assert_eq!(embed_missing_chunks(&db, provider, "doc-1").unwrap(), 3);
+
+        let query = provider
+            .embed(&["¿cómo aprenden las redes neuronales?".to_string()])
+            .unwrap()
+            .remove(0);
+        let hits = crate::services::search::search_similar(&db, &query, 1).unwrap();
+        assert_eq!(hits[0].0.id, "c-2");
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_embed_rejects_bad_provider_output() {
+        let (db, path) = temp_db("test_index_bad");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        assert!(matches!(
+            embed_missing_chunks(&db, &TestProvider::new(4).with_missing_vector(), "doc-1"),
+            Err(EmbedError::InvalidOutput(_))
+        ));
+        assert_eq!(get_embedding(&db, "c-0").unwrap(), None);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_library_embedding_model_is_recorded_and_enforced() {
+        let (db, path) = temp_db("test_embedding_model");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for i in 0..3 {
+            let chunk = sample_chunk(i, "texto");
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        assert_eq!(get_library_embedding_info(&db).unwrap(), None);
+
+        // El primer embedding registra modelo y dimensión
+        insert_embedding(&db, "c-0", &[1.0, 0.0], "modelo-a", 2).unwrap();
+        let info = get_library_embedding_info(&db).unwrap().unwrap();
+        assert_eq!(
+            info,
+            LibraryEmbeddingInfo {
+                model: "modelo-a".into(),
+                dimension: 2
+            }
+        );
+
+        // Otro modelo con la misma dimensión se rechaza
+        let err = insert_embedding(&db, "c-1", &[0.0, 1.0], "modelo-b", 2).unwrap_err();
+        match err {
+            EmbedError::ModelMismatch { stored, requested } => {
+                assert_eq!(
+                    (stored.as_str(), requested.as_str()),
+                    ("modelo-a", "modelo-b")
+                );
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(get_embedding(&db, "c-1").unwrap(), None);
+
+        // Reemplazar el único vector guardado no mezcla modelos
+        insert_embedding(&db, "c-0", &[1.0, 0.0], "modelo-b", 2).unwrap();
+        assert_eq!(
+            get_library_embedding_info(&db).unwrap().unwrap().model,
+            "modelo-b"
+        );
+
+        // Con override se acepta y pasa a ser el modelo de la biblioteca,
+        // aunque cambie la dimensión
+        insert_embedding_with_model_override(&db, "c-1", &[1.0, 0.0, 0.0], "modelo-c", 3, true)
+            .unwrap();
+        let info = get_library_embedding_info(&db).unwrap().unwrap();
+        assert_eq!((info.model.as_str(), info.dimension), ("modelo-c", 3));
+        insert_embedding(&db, "c-2", &[0.0, 0.0, 1.0], "modelo-c", 3).unwrap();
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_concurrent_first_embeddings_agree_on_model() {
+        let (db, path) = temp_db("test_embedding_model_race");
+        insert_document(&db, &sample_document()).unwrap();
+        let threads = 8;
+        for i in 0..threads {
+            insert_chunk(&db, &sample_chunk(i, "texto")).unwrap();
+        }
+
+        // Cada hilo quiere estrenar la biblioteca con su propio modelo
+        let barrier = std::sync::Barrier::new(threads);
+        std::thread::scope(|scope| {
+            for i in 0..threads {
+                let (db, barrier) = (&db, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    let model = format!("modelo-{}", i % 2);
+                    let _ = insert_embedding(db, &format!("c-{}", i), &[1.0, 0.0], &model, 2);
+                });
+            }
+        });
+
+        let library = get_library_embedding_info(&db).unwrap().unwrap().model;
+        let stored: Vec<StoredEmbedding> = iter_embeddings(&db)
+            .unwrap()
+            .map(|item| item.unwrap().1)
+            .collect();
+        assert!(!stored.is_empty());
+        assert!(stored.iter().all(|e| e.model == library), "{:?}", stored);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[tokio::test]
+    async fn test_embed_document_chunks_parallel() {
+        let (db, path) = temp_db("test_embed_parallel");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let total = EMBED_BATCH_SIZE * 6 + 3;
+        for i in 0..total {
+            let chunk = sample_chunk(i, format!("t {}", i));
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let embedder =
+            Arc::new(TestProvider::new(8).with_delay(std::time::Duration::from_millis(30)));
+
+        let embedded = embed_document_chunks_parallel(&db, embedder.clone(), "doc-1", 4)
+            .await
+            .unwrap();
+        assert_eq!(embedded, total);
+        for chunk in get_chunks_for_document(&db, "doc-1").unwrap() {
+            assert_eq!(
+                get_embedding(&db, &chunk.id).unwrap(),
+                Some(HashingEmbedder::new(8).embed_one(&chunk.text))
+            );
+        }
+        let max = embedder.max_in_flight();
+        assert!((2..=4).contains(&max), "max in flight: {}", max);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_paths_use_the_cache() {
+        let (db, path) = temp_db("test_embed_cache_paths");
+        insert_document(&db, &sample_document()).unwrap();
+        for i in 0..3 {
+            insert_chunk(&db, &sample_chunk(i, format!("texto {}", i))).unwrap();
+        }
+
+        let provider = TestProvider::new(8);
+        assert_eq!(
+            embed_document_chunks(&db, &provider, "doc-1", None).unwrap(),
+            3
+        );
+        assert_eq!(provider.calls(), 1);
+
+        // Los mismos textos salen del cache en todos los caminos
+        let cached = Arc::new(TestProvider::new(8));
+        embed_document_chunks(&db, cached.as_ref(), "doc-1", None).unwrap();
+        delete_embeddings(&db, &["c-1"]).unwrap();
+        assert_eq!(
+            embed_missing_chunks(&db, cached.as_ref(), "doc-1").unwrap(),
+            1
+        );
+        embed_document_chunks_parallel(&db, cached.clone(), "doc-1", 2)
+            .await
+            .unwrap();
+        assert_eq!(cached.calls(), 0);
+        assert_eq!(
+            get_embedding(&db, "c-1").unwrap(),
+            Some(HashingEmbedder::new(8).embed_one("texto 1"))
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,418 @@
+// Lectura de libros EPUB: un zip con el paquete OPF (metadatos, manifiesto
+// y orden de lectura) y un XHTML por capítulo. El paquete lo interpreta el
+// crate `epub`; acá se saca el texto de cada capítulo
+
+use crate::services::html::{collapse_whitespace, is_block_element, is_heading, PlainText};
+use epub::doc::{EpubDoc, ResourceItem};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+/// Algoritmos de `encryption.xml` que solo ofuscan fuentes incrustadas; no
+/// impiden leer el texto
+const FONT_OBFUSCATION: [&str; 2] = [
+    "http://www.idpf.org/2008/embedding",
+    "http://ns.adobe.com/pdf/enc#RC",
+];
+
+/// Entidades HTML comunes que los XHTML usan sin declarar
+const HTML_ENTITIES: [(&str, &str); 7] = [
+    ("nbsp", "\u{a0}"),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("hellip", "…"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("copy", "©"),
+];
+
+/// Libro EPUB ya leído
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpubBook {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Un capítulo por entrada del `spine`, en orden de lectura
+    pub chapters: Vec<EpubChapter>,
+}
+
+/// Texto de un documento del `spine`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpubChapter {
+    /// Primer encabezado del capítulo; si no tiene, su entrada en el índice
+    /// NCX o su `<title>`
+    pub title: Option<String>,
+    /// Texto plano, con los bloques separados por una línea en blanco
+    pub text: String,
+}
+
+/// Motivo por el que no se pudo leer un EPUB
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EpubError {
+    /// El libro tiene DRM (contenido cifrado)
+    Drm,
+    /// No es un EPUB válido
+    Invalid(String),
+}
+
+/// Lee un EPUB desde sus bytes, con el crate `epub`
+///
+/// Falla con `EpubError::Drm` si el libro declara contenido cifrado
+/// (`META-INF/rights.xml` o un `encryption.xml` que no sea solo ofuscación
+/// de fuentes), en vez de devolver capítulos ilegibles.
+///
+/// Hay un capítulo por cada entrada del `spine`, aunque no se pueda leer:
+/// las que no están en el manifiesto, apuntan a un archivo que falta o son
+/// el índice de navegación (`properties="nav"`) quedan vacías, así la
+/// posición de cada capítulo sigue siendo la del `spine`.
+pub fn read_epub(bytes: &[u8]) -> Result<EpubBook, EpubError> {
+    let mut doc =
+        EpubDoc::from_reader(Cursor::new(bytes)).map_err(|e| EpubError::Invalid(e.to_string()))?;
+
+    if doc.get_resource_by_path("META-INF/rights.xml").is_some() {
+        return Err(EpubError::Drm);
+    }
+    if let Some(encryption) = doc.get_resource_by_path("META-INF/encryption.xml") {
+        if has_drm_encryption(&String::from_utf8_lossy(&encryption))? {
+            return Err(EpubError::Drm);
+        }
+    }
+
+    let toc_labels = toc_labels(&mut doc)?;
+    let spine: Vec<Option<ResourceItem>> = doc
+        .spine
+        .iter()
+        .map(|item| doc.resources.get(&item.idref).cloned())
+        .collect();
+    let mut chapters = Vec::with_capacity(spine.len());
+    for resource in spine {
+        let Some(resource) = resource.filter(|r| !is_nav(r)) else {
+            chapters.push(EpubChapter::default());
+            continue;
+        };
+        let path = normalize_path(&resource.path);
+        let Some(xhtml) = doc.get_resource_by_path(&path) else {
+            chapters.push(EpubChapter::default());
+            continue;
+        };
+        let chapter = chapter_text(&String::from_utf8_lossy(&xhtml))?;
+        chapters.push(EpubChapter {
+            title: chapter
+                .heading
+                .or_else(|| toc_labels.get(&path).cloned())
+                .or(chapter.head_title),
+            text: chapter.text,
+        });
+    }
+
+    let metadata = |property: &str| {
+        doc.mdata(property)
+            .map(|item| item.value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    Ok(EpubBook {
+        title: metadata("title"),
+        author: metadata("creator"),
+        chapters,
+    })
+}
+
+/// Indica si `encryption.xml` cifra algo más que fuentes
+fn has_drm_encryption(encryption: &str) -> Result<bool, EpubError> {
+    Ok(elements(encryption)?
+        .into_iter()
+        .filter(|(name, _, _)| name == "EncryptionMethod")
+        .filter_map(|(_, attributes, _)| attribute(&attributes, "Algorithm"))
+        .any(|algorithm| !FONT_OBFUSCATION.contains(&algorithm.as_str())))
+}
+
+/// Documento de navegación de EPUB 3: es el índice, no texto del libro
+fn is_nav(resource: &ResourceItem) -> bool {
+    resource
+        .properties
+        .as_deref()
+        .is_some_and(|p| p.split_whitespace().any(|p| p == "nav"))
+}
+
+/// Primera etiqueta del índice NCX de cada archivo, por ruta dentro del zip
+///
+/// El NCX se lee acá y no con el `toc` de `epub`: ese resuelve los `src`
+/// desde la carpeta del OPF (son relativos al NCX) y queda vacío si el
+/// `href` del NCX tiene `..`.
+fn toc_labels(doc: &mut EpubDoc<Cursor<&[u8]>>) -> Result<HashMap<String, String>, EpubError> {
+    let Some(ncx_path) = doc
+        .resources
+        .values()
+        .find(|r| r.mime == "application/x-dtbncx+xml")
+        .map(|r| normalize_path(&r.path))
+    else {
+        return Ok(HashMap::new());
+    };
+    let Some(ncx) = doc.get_resource_by_path(&ncx_path) else {
+        return Ok(HashMap::new());
+    };
+    let ncx_dir = Path::new(&ncx_path).parent().unwrap_or(Path::new(""));
+
+    // Cada `<navPoint>` tiene `<navLabel><text>` y después `<content src>`
+    let mut labels = HashMap::new();
+    let mut label = None;
+    let mut in_nav_map = false;
+    for (name, attributes, text) in elements(&String::from_utf8_lossy(&ncx))? {
+        match name.as_str() {
+            "navMap" => in_nav_map = true,
+            "text" if in_nav_map => label = Some(collapse_whitespace(&text)),
+            "content" => {
+                let (Some(src), Some(label)) = (attribute(&attributes, "src"), label.take()) else {
+                    continue;
+                };
+                if !label.is_empty() {
+                    labels
+                        .entry(normalize_path(&ncx_dir.join(src)))
+                        .or_insert(label);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(labels)
+}
+
+/// Ruta dentro del zip, sin `.`, `..` ni fragmento (`#...`)
+///
+/// `epub` une el `href` a la carpeta del OPF tal cual, así que
+/// `OEBPS/../texto/cap1.xhtml` no se encontraría en el zip.
+fn normalize_path(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let path = path.split('#').next().unwrap_or_default();
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Cada elemento del XML con sus atributos y el texto que tiene adentro
+/// (sin contar el de elementos hijos)
+fn elements(xml: &str) -> Result<Vec<(String, Vec<OwnedAttribute>, String)>, EpubError> {
+    let mut out = Vec::new();
+    // Posición en `out` de los elementos abiertos
+    let mut open = Vec::new();
+    for event in reader(xml) {
+        match event.map_err(|e| EpubError::Invalid(e.to_string()))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                open.push(out.len());
+                out.push((name.local_name, attributes, String::new()));
+            }
+            XmlEvent::EndElement { .. } => {
+                open.pop();
+            }
+            XmlEvent::Characters(text) => {
+                if let Some(&current) = open.last() {
+                    out[current].2.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+fn attribute(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+}
+
+fn reader(xml: &str) -> EventReader<&[u8]> {
+    let config = ParserConfig::new()
+        .trim_whitespace(false)
+        .whitespace_to_characters(true)
+        .cdata_to_characters(true)
+        .ignore_comments(true)
+        .replace_unknown_entity_references(true)
+        .add_entities(HTML_ENTITIES);
+    EventReader::new_with_config(xml.as_bytes(), config)
+}
+
+/// Lo que se saca de un XHTML del `spine`
+struct ChapterText {
+    /// Primer encabezado (`<h1>`…`<h6>`) con texto
+    heading: Option<String>,
+    /// `<title>` del `<head>`
+    head_title: Option<String>,
+    text: String,
+}
+
+/// Texto plano de un capítulo XHTML
+///
+/// Los espacios de cada bloque se colapsan (salvo en `<pre>`), `<br>` es un
+/// salto de línea y se descartan `<head>`, `<script>` y `<style>`.
+fn chapter_text(xhtml: &str) -> Result<ChapterText, EpubError> {
+    let mut title = None;
+    let mut head_title: Option<String> = None;
+    let mut text = PlainText::default();
+    // Elementos abiertos, para saber si el texto es de un encabezado, de un
+    // `<pre>` o de algo que se descarta
+    let mut open: Vec<String> = Vec::new();
+    let in_pre = |open: &[String]| open.iter().any(|e| e == "pre");
+    for event in reader(xhtml) {
+        match event.map_err(|e| EpubError::Invalid(e.to_string()))? {
+            XmlEvent::StartElement { name, .. } => {
+                let name = name.local_name.to_ascii_lowercase();
+                if name == "br" {
+                    text.line_break();
+                } else if is_block_element(&name) {
+                    text.end_block(in_pre(&open));
+                }
+                open.push(name);
+            }
+            XmlEvent::EndElement { .. } => {
+                let preformatted = in_pre(&open);
+                let name = open.pop().unwrap_or_default();
+                if is_block_element(&name) {
+                    if is_heading(&name) && title.is_none() {
+                        title = Some(text.current_block()).filter(|t| !t.is_empty());
+                    }
+                    text.end_block(preformatted);
+                }
+            }
+            XmlEvent::Characters(chars) => {
+                if open.iter().any(|e| e == "title") {
+                    head_title.get_or_insert_with(String::new).push_str(&chars);
+                } else if !open
+                    .iter()
+                    .any(|e| matches!(e.as_str(), "head" | "script" | "style"))
+                {
+                    text.push_str(&chars);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(ChapterText {
+        heading: title,
+        head_title: head_title
+            .map(|t| collapse_whitespace(&t))
+            .filter(|t| !t.is_empty()),
+        text: text.finish(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/{}", FIXTURES, name)).unwrap()
+    }
+
+    #[test]
+    fn test_read_epub() {
+        let book = read_epub(&fixture("tiny.epub")).unwrap();
+        assert_eq!(book.title.as_deref(), Some("Apuntes de óptica"));
+        assert_eq!(book.author.as_deref(), Some("Ana Pérez"));
+
+        // El índice (nav.xhtml) no está en el spine
+        let titles: Vec<Option<&str>> = book.chapters.iter().map(|c| c.title.as_deref()).collect();
+        assert_eq!(
+            titles,
+            [Some("La luz"), Some("Refracción"), Some("Ejercicios")]
+        );
+        assert_eq!(
+            book.chapters[0].text,
+            "La luz\n\nLa luz viaja en línea recta por el vacío.\n\n\
+             Su velocidad es de unos 300 000 km/s."
+        );
+        // `<br>` es un salto de línea; los scripts se descartan
+        assert_eq!(
+            book.chapters[1].text,
+            "Refracción\n\nLa luz cambia de dirección\nal pasar de un medio a otro."
+        );
+        assert_eq!(
+            book.chapters[2].text,
+            "Calcular el índice del agua.\n\nDibujar el rayo refractado."
+        );
+    }
+
+    #[test]
+    fn test_drm_is_rejected() {
+        assert_eq!(read_epub(&fixture("drm.epub")), Err(EpubError::Drm));
+
+        // La ofuscación de fuentes no es DRM
+        let fonts = r#"<encryption xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+            <enc:EncryptedData>
+              <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+            </enc:EncryptedData></encryption>"#;
+        assert!(!has_drm_encryption(fonts).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_epub() {
+        assert!(matches!(
+            read_epub(b"no es un zip"),
+            Err(EpubError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("OEBPS/text/cap1.xhtml")),
+            "OEBPS/text/cap1.xhtml"
+        );
+        assert_eq!(
+            normalize_path(Path::new("OEBPS/text/../img/./a.xhtml#s1")),
+            "OEBPS/img/a.xhtml"
+        );
+        assert_eq!(normalize_path(Path::new("cap1.xhtml")), "cap1.xhtml");
+    }
+
+    #[test]
+    fn test_nested_paths_and_ncx_titles() {
+        let book = read_epub(&fixture("nested.epub")).unwrap();
+        assert_eq!(book.title.as_deref(), Some("Cuentos del río"));
+        assert_eq!(book.author.as_deref(), Some("Luis Gómez"));
+
+        // El OPF está en `OPS/paquete/` y los hrefs suben con `..`; el
+        // primero además está codificado (`parte%201/cap%201.xhtml`)
+        let chapters: Vec<(Option<&str>, &str)> = book
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_deref(), c.text.as_str()))
+            .collect();
+        assert_eq!(
+            chapters,
+            [
+                // Sin encabezados: el título sale del índice NCX
+                (Some("La crecida"), "El agua subió durante la noche."),
+                (Some("El puente"), "Nadie cruzó el puente."),
+                // Archivo que falta, item sin href y idref sin manifiesto:
+                // quedan vacíos para no correr los números de capítulo
+                (None, ""),
+                (None, ""),
+                (None, ""),
+                // El encabezado gana sobre el NCX y el `<title>`
+                (Some("Después"), "Después\n\nEl río volvió a su cauce."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nav_document_is_not_text() {
+        let book = read_epub(&fixture("nav_only.epub")).unwrap();
+        assert_eq!(book.title.as_deref(), Some("Solo índice"));
+        assert_eq!(book.chapters, [EpubChapter::default()]);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::models::ChunkValidationError;
+use std::fmt;
+
+/// Errores de la capa de base de datos
+///
+/// Permite a los llamadores distinguir, por ejemplo, un documento inexistente
+/// de un fallo de disco. Los comandos de Tauri pueden seguir enviando un
+/// `String` al frontend con `to_string()` (o `String::from`).
+#[derive(Debug)]
+pub enum DbError {
+    /// No se pudo abrir la BD o uno de sus árboles
+    Open(String),
+    /// Error de E/S (directorios, archivos)
+    Io(std::io::Error),
+    /// Error interno de sled al leer o escribir
+    Storage(sled::Error),
+    /// No se pudo serializar un registro
+    Serialize(String),
+    /// Un registro guardado no se pudo deserializar
+    Deserialize(String),
+    /// El registro pedido no existe
+    NotFound(String),
+    /// La BD está en modo mantenimiento y no acepta escrituras
+    ReadOnly,
+    /// Los datos recibidos no son válidos para la operación
+    InvalidInput(String),
+    /// Una actualización concurrente impidió completar la operación
+    Conflict(String),
+    /// Un chunk no pasó `Chunk::validate`
+    InvalidChunk(ChunkValidationError),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Open(msg) => write!(f, "failed to open database: {}", msg),
+            DbError::Io(e) => write!(f, "io error: {}", e),
+            DbError::Storage(e) => write!(f, "sled error: {}", e),
+            DbError::Serialize(msg) => write!(f, "serialize error: {}", msg),
+            DbError::Deserialize(msg) => write!(f, "deserialize error: {}", msg),
+            DbError::NotFound(what) => write!(f, "not found: {}", what),
+            DbError::ReadOnly => write!(f, "database is in maintenance mode (read-only)"),
+            DbError::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+            DbError::Conflict(msg) => write!(f, "conflict: {}", msg),
+            DbError::InvalidChunk(e) => write!(f, "invalid chunk: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbError::Io(e) => Some(e),
+            DbError::Storage(e) => Some(e),
+            DbError::InvalidChunk(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sled::Error> for DbError {
+    fn from(e: sled::Error) -> Self {
+        match e {
+            sled::Error::Io(e) => DbError::Io(e),
+            other => DbError::Storage(other),
+        }
+    }
+}
+
+/// bincode usa el mismo tipo de error para ambos sentidos; con `?` se asume
+/// lectura. Las escrituras usan `DbError::Serialize` explícitamente.
+impl From<bincode::Error> for DbError {
+    fn from(e: bincode::Error) -> Self {
+        DbError::Deserialize(e.to_string())
+    }
+}
+
+impl From<ChunkValidationError> for DbError {
+    fn from(e: ChunkValidationError) -> Self {
+        DbError::InvalidChunk(e)
+    }
+}
+
+impl From<std::io::Error> for DbError {
+    fn from(e: std::io::Error) -> Self {
+        DbError::Io(e)
+    }
+}
+
+impl From<sled::transaction::TransactionError<DbError>> for DbError {
+    fn from(e: sled::transaction::TransactionError<DbError>) -> Self {
+        match e {
+            sled::transaction::TransactionError::Abort(e) => e,
+            sled::transaction::TransactionError::Storage(e) => e.into(),
+        }
+    }
+}
+
+impl From<DbError> for String {
+    fn from(e: DbError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Errores al generar embeddings
+#[derive(Debug)]
+pub enum EmbedError {
+    /// El backend no pudo calcular los vectores (modelo, red, etc.)
+    Backend(String),
+    /// El backend devolvió algo inesperado (cantidad o largo de vectores)
+    InvalidOutput(String),
+    /// No se pudieron leer o guardar los embeddings
+    Db(DbError),
+    /// La biblioteca se indexó con otro modelo de embeddings
+    ModelMismatch { stored: String, requested: String },
+}
+
+impl fmt::Display for EmbedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedError::Backend(msg) => write!(f, "embedding backend error: {}", msg),
+            EmbedError::InvalidOutput(msg) => write!(f, "invalid embedder output: {}", msg),
+            EmbedError::Db(e) => write!(f, "{}", e),
+            EmbedError::ModelMismatch { stored, requested } => write!(
+                f,
+                "library embeddings were built with model {}, not {}",
+                stored, requested
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmbedError::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DbError> for EmbedError {
+    fn from(e: DbError) -> Self {
+        EmbedError::Db(e)
+    }
+}
+
+impl From<sled::Error> for EmbedError {
+    fn from(e: sled::Error) -> Self {
+        EmbedError::Db(e.into())
+    }
+}
+
+impl From<EmbedError> for String {
+    fn from(e: EmbedError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Errores del pipeline de indexación (ver `indexing::index_document`)
+#[derive(Debug)]
+pub enum IndexError {
+    /// No se pudo extraer el texto del archivo del documento
+    Extract(String),
+    /// Falló el cálculo de embeddings
+    Embed(EmbedError),
+    /// Falló la lectura o escritura en la BD
+    Db(DbError),
+    /// Se canceló la indexación antes de terminar
+    Cancelled,
+    /// El documento no tiene guardada una copia del archivo original
+    MissingBlob(String),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Extract(msg) => write!(f, "text extraction failed: {}", msg),
+            IndexError::Embed(e) => write!(f, "{}", e),
+            IndexError::Db(e) => write!(f, "{}", e),
+            IndexError::Cancelled => write!(f, "indexing cancelled"),
+            IndexError::MissingBlob(id) => write!(f, "no stored file for document {}", id),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexError::Embed(e) => Some(e),
+            IndexError::Db(e) => Some(e),
+            IndexError::Extract(_) | IndexError::Cancelled | IndexError::MissingBlob(_) => None,
+        }
+    }
+}
+
+impl From<EmbedError> for IndexError {
+    fn from(e: EmbedError) -> Self {
+        match e {
+            EmbedError::Db(e) => IndexError::Db(e),
+            other => IndexError::Embed(other),
+        }
+    }
+}
+
+impl From<DbError> for IndexError {
+    fn from(e: DbError) -> Self {
+        IndexError::Db(e)
+    }
+}
+
+impl From<IndexError> for String {
+    fn from(e: IndexError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Errores al extraer el texto de un archivo (ver `extract::extract`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// Ningún extractor reconoce el archivo
+    Unsupported(String),
+    /// El archivo no tiene texto
+    Empty(String),
+    /// El archivo tiene DRM y no se puede leer
+    DrmProtected(String),
+    /// El extractor no pudo leer el archivo
+    Invalid(String),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Unsupported(path) => write!(f, "unsupported file format: {}", path),
+            ExtractError::Empty(path) => write!(f, "file has no text: {}", path),
+            ExtractError::DrmProtected(path) => write!(f, "file is DRM-protected: {}", path),
+            ExtractError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<ExtractError> for IndexError {
+    fn from(e: ExtractError) -> Self {
+        IndexError::Extract(e.to_string())
+    }
+}
+
+/// Errores al incorporar un archivo a la biblioteca (`ingest_document`)
+#[derive(Debug)]
+pub enum IngestError {
+    /// La ruta no existe o no es un archivo
+    FileNotFound(String),
+    /// No se pudo leer el archivo
+    Io(std::io::Error),
+    /// No se pudo extraer el texto del archivo
+    Extract(ExtractError),
+    /// Ya hay un documento con el mismo contenido
+    Duplicate { existing_id: String },
+    /// Prácticamente todas las páginas son escaneos sin capa de texto; hace
+    /// falta OCR
+    NoExtractableText { scanned_pages: usize },
+    /// Falló la lectura o escritura en la BD
+    Db(DbError),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::FileNotFound(path) => write!(f, "file not found: {}", path),
+            IngestError::Io(e) => write!(f, "io error: {}", e),
+            IngestError::Extract(e) => write!(f, "text extraction failed: {}", e),
+            IngestError::Duplicate { existing_id } => {
+                write!(f, "document already in library as {}", existing_id)
+            }
+            IngestError::NoExtractableText { scanned_pages } => write!(
+                f,
+                "no extractable text: {} scanned pages need OCR",
+                scanned_pages
+            ),
+            IngestError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for IngestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IngestError::Io(e) => Some(e),
+            IngestError::Extract(e) => Some(e),
+            IngestError::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for IngestError {
+    fn from(e: std::io::Error) -> Self {
+        IngestError::Io(e)
+    }
+}
+
+impl From<ExtractError> for IngestError {
+    fn from(e: ExtractError) -> Self {
+        IngestError::Extract(e)
+    }
+}
+
+impl From<DbError> for IngestError {
+    fn from(e: DbError) -> Self {
+        IngestError::Db(e)
+    }
+}
+
+impl From<IngestError> for String {
+    fn from(e: IngestError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_error_display() {
+        assert_eq!(
+            DbError::NotFound("doc-1".to_string()).to_string(),
+            "not found: doc-1"
+        );
+        assert_eq!(
+            String::from(DbError::ReadOnly),
+            "database is in maintenance mode (read-only)"
+        );
+    }
+
+    #[test]
+    fn test_db_error_from_bincode() {
+        let err: DbError = bincode::deserialize::<String>(&[1, 2]).unwrap_err().into();
+        assert!(matches!(err, DbError::Deserialize(_)));
+    }
+
+    #[test]
+    fn test_db_error_from_io() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "sin archivo");
+        let err: DbError = sled::Error::Io(io).into();
+        assert!(matches!(err, DbError::Io(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_embed_error_display() {
+        let err = EmbedError::Backend("sin conexión".to_string());
+        assert_eq!(err.to_string(), "embedding backend error: sin conexión");
+
+        let err: EmbedError = DbError::NotFound("c-1".to_string()).into();
+        assert_eq!(String::from(err), "not found: c-1");
+    }
+}
@@ -0,0 +1,792 @@
+use crate::models::{Chunk, Document};
+use crate::services::attachments::delete_attachments;
+use crate::services::blobs::delete_document_blob;
+use crate::services::database::{
+    delete_document, document_exists, get_all_documents, get_chunks_for_document,
+    get_document_required, insert_document, DbError,
+};
+use crate::services::embeddings::{
+    bytes_to_vector, get_embedding_record, replace_document_embeddings, vector_to_bytes,
+};
+use crate::services::error::EmbedError;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Versión del formato del archivo exportado
+///
+/// Se incrementa ante cambios incompatibles; los archivos de versiones más
+/// nuevas que la soportada se rechazan al importar.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Qué hacer al importar un documento cuyo id ya existe en la biblioteca
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportConflict {
+    /// Dejar el documento existente y no importar nada
+    Skip,
+    /// Borrar el documento existente (con sus chunks, blobs y adjuntos) y
+    /// reemplazarlo por el importado
+    Overwrite,
+    /// Importar como un documento nuevo con un id recién generado
+    NewId,
+}
+
+/// Contenido de un archivo exportado: un documento completo y autocontenido
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentBundle {
+    pub format_version: u32,
+    pub document: Document,
+    pub chunks: Vec<BundleChunk>,
+}
+
+/// Chunk exportado, con su embedding (si tiene) en base64
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleChunk {
+    #[serde(flatten)]
+    pub chunk: Chunk,
+    /// Vector en f32 little-endian codificado en base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<String>,
+    /// Modelo que generó el embedding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+}
+
+/// Arma el bundle de un documento (metadata, chunks y embeddings)
+pub fn build_bundle(db: &Arc<sled::Db>, doc_id: &str) -> Result<DocumentBundle, DbError> {
+    let document = get_document_required(db, doc_id)?;
+    let mut chunks = Vec::with_capacity(document.chunk_count);
+    for chunk in get_chunks_for_document(db, doc_id)? {
+        let record = get_embedding_record(db, &chunk.id)?;
+        chunks.push(BundleChunk {
+            chunk,
+            embedding: record
+                .as_ref()
+                .map(|e| BASE64.encode(vector_to_bytes(&e.vector))),
+            embedding_model: record.map(|e| e.model),
+        });
+    }
+    Ok(DocumentBundle {
+        format_version: BUNDLE_FORMAT_VERSION,
+        document,
+        chunks,
+    })
+}
+
+/// Exporta un documento a un único archivo JSON en `path`
+pub fn export_document(
+    db: &Arc<sled::Db>,
+    doc_id: &str,
+    path: impl AsRef<Path>,
+) -> Result<(), DbError> {
+    let bundle = build_bundle(db, doc_id)?;
+    let json = serde_json::to_vec_pretty(&bundle).map_err(|e| DbError::Serialize(e.to_string()))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// Exporta el texto de un documento a Markdown en `out_path`
+///
+/// El nombre del documento va como título (`#`) y los chunks, en orden de
+/// `index`, debajo de un encabezado `## Página N`; chunks seguidos de la
+/// misma página comparten encabezado.
+pub fn export_document_markdown(
+    db: &Arc<sled::Db>,
+    doc_id: &str,
+    out_path: impl AsRef<Path>,
+) -> Result<(), DbError> {
+    let document = get_document_required(db, doc_id)?;
+    let mut chunks = get_chunks_for_document(db, doc_id)?;
+    chunks.sort_by_key(|c| c.index);
+
+    let mut out = format!("# {}\n", document.name);
+    let mut current_page = None;
+    for chunk in &chunks {
+        if current_page != Some(chunk.page_number) {
+            out.push_str(&format!("\n## Página {}\n", chunk.page_number));
+            current_page = Some(chunk.page_number);
+        }
+        out.push('\n');
+        out.push_str(chunk.text.trim());
+        out.push('\n');
+    }
+    std::fs::write(out_path, out)?;
+    Ok(())
+}
+
+/// Importa un documento exportado con `export_document`
+///
+/// Retorna el id con el que quedó guardado, o `None` si ya existía y la
+/// política era `ImportConflict::Skip`. Con `NewId` se cambia el id del
+/// documento y de sus chunks; la copia no conserva el hash de contenido para
+/// que la deduplicación siga apuntando al original. Si falla no queda nada
+/// a medias: un documento nuevo no se guarda y, con `Overwrite`, el
+/// existente queda como estaba.
+pub fn import_document(
+    db: &Arc<sled::Db>,
+    path: impl AsRef<Path>,
+    on_conflict: ImportConflict,
+) -> Result<Option<String>, DbError> {
+    let bytes = std::fs::read(path)?;
+    let bundle: DocumentBundle =
+        serde_json::from_slice(&bytes).map_err(|e| DbError::Deserialize(e.to_string()))?;
+    import_bundle(db, bundle, on_conflict)
+}
+
+/// Igual que `import_document`, pero a partir de un bundle ya leído
+pub fn import_bundle(
+    db: &Arc<sled::Db>,
+    mut bundle: DocumentBundle,
+    on_conflict: ImportConflict,
+) -> Result<Option<String>, DbError> {
+    if bundle.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(DbError::InvalidInput(format!(
+            "bundle format version {} is newer than supported version {}",
+            bundle.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    // Decodificar todo antes de escribir para no dejar imports a medias por un vector roto
+    let mut vectors = Vec::with_capacity(bundle.chunks.len());
+    for entry in &bundle.chunks {
+        let vector = match &entry.embedding {
+            Some(encoded) => {
+                let raw = BASE64
+                    .decode(encoded)
+                    .map_err(|e| DbError::Deserialize(e.to_string()))?;
+                Some(bytes_to_vector(&raw)?)
+            }
+            None => None,
+        };
+        vectors.push(vector);
+    }
+
+    // Un documento guarda vectores de un solo modelo (el de la biblioteca)
+    let mut models = bundle
+        .chunks
+        .iter()
+        .filter(|entry| entry.embedding.is_some())
+        .map(|entry| entry.embedding_model.as_deref().unwrap_or("unknown"));
+    let model = models.next().unwrap_or_default().to_string();
+    if let Some(other) = models.find(|m| *m != model) {
+        return Err(DbError::InvalidInput(format!(
+            "bundle mixes embedding models {} and {}",
+            model, other
+        )));
+    }
+    let dim = vectors.iter().flatten().map(Vec::len).next().unwrap_or(0);
+
+    let overwrite = if document_exists(db, &bundle.document.id)? {
+        match on_conflict {
+            ImportConflict::Skip => return Ok(None),
+            ImportConflict::Overwrite => true,
+            ImportConflict::NewId => {
+                rekey(&mut bundle);
+                false
+            }
+        }
+    } else {
+        false
+    };
+
+    let mut document = bundle.document;
+    let chunks: Vec<Chunk> = bundle.chunks.into_iter().map(|entry| entry.chunk).collect();
+    if !overwrite {
+        // `replace_document_embeddings` cuenta los chunks
+        document.chunk_count = 0;
+        insert_document(db, &document)?;
+    }
+
+    // Chunks y vectores se cambian de una vez: si el modelo o la dimensión
+    // no son los de la biblioteca falla sin tocar nada, y con `Overwrite` el
+    // documento anterior sigue entero hasta que el nuevo quedó escrito
+    let replaced =
+        replace_document_embeddings(db, &document.id, &chunks, &vectors, &model, dim, false)
+            .map_err(|e| match e {
+                EmbedError::Db(e) => e,
+                // Vectores de otro modelo no sirven en esta biblioteca
+                other => DbError::InvalidInput(other.to_string()),
+            });
+    if let Err(e) = replaced {
+        if !overwrite {
+            let _ = delete_document(db, &document.id);
+        }
+        return Err(e);
+    }
+
+    if overwrite {
+        // El archivo y los adjuntos del documento anterior no vienen en el
+        // bundle: ya no corresponden
+        document.chunk_count = chunks.len();
+        insert_document(db, &document)?;
+        delete_document_blob(db, &document.id)?;
+        delete_attachments(db, &document.id)?;
+    }
+    Ok(Some(document.id))
+}
+
+/// Formato del respaldo de la biblioteca completa (`export_database`)
+///
+/// - `JsonPlain`: JSON legible, el más portable.
+/// - `JsonGzip`: el mismo JSON comprimido con gzip.
+/// - `BincodeZstd`: bincode comprimido con zstd, el más chico y rápido de
+///   leer, pero solo lo entiende LibIA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupFormat {
+    #[default]
+    JsonPlain,
+    JsonGzip,
+    BincodeZstd,
+}
+
+/// Primeros bytes de un archivo gzip
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Primeros bytes de un frame zstd
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Nivel gzip por defecto (el de la herramienta `gzip`)
+const GZIP_DEFAULT_LEVEL: i32 = 6;
+/// Nivel zstd por defecto
+const ZSTD_DEFAULT_LEVEL: i32 = 3;
+
+impl BackupFormat {
+    /// Reconoce el formato de un respaldo por sus primeros bytes
+    ///
+    /// gzip y zstd tienen firmas propias; cualquier otra cosa se toma como
+    /// JSON sin comprimir.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            BackupFormat::JsonGzip
+        } else if bytes.starts_with(&ZSTD_MAGIC) {
+            BackupFormat::BincodeZstd
+        } else {
+            BackupFormat::JsonPlain
+        }
+    }
+}
+
+/// Respaldo de la biblioteca: un bundle por documento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryBackup {
+    pub format_version: u32,
+    pub documents: Vec<DocumentBundle>,
+}
+
+/// `LibraryBackup` para bincode, que no soporta el `#[serde(flatten)]` de
+/// `BundleChunk`
+#[derive(Serialize, Deserialize)]
+struct PackedBackup {
+    format_version: u32,
+    documents: Vec<PackedBundle>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackedBundle {
+    format_version: u32,
+    document: Document,
+    chunks: Vec<PackedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackedChunk {
+    chunk: Chunk,
+    embedding: Option<String>,
+    embedding_model: Option<String>,
+}
+
+impl From<LibraryBackup> for PackedBackup {
+    fn from(backup: LibraryBackup) -> Self {
+        let documents = backup
+            .documents
+            .into_iter()
+            .map(|bundle| PackedBundle {
+                format_version: bundle.format_version,
+                document: bundle.document,
+                chunks: bundle
+                    .chunks
+                    .into_iter()
+                    .map(|c| PackedChunk {
+                        chunk: c.chunk,
+                        embedding: c.embedding,
+                        embedding_model: c.embedding_model,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            format_version: backup.format_version,
+            documents,
+        }
+    }
+}
+
+impl From<PackedBackup> for LibraryBackup {
+    fn from(packed: PackedBackup) -> Self {
+        let documents = packed
+            .documents
+            .into_iter()
+            .map(|bundle| DocumentBundle {
+                format_version: bundle.format_version,
+                document: bundle.document,
+                chunks: bundle
+                    .chunks
+                    .into_iter()
+                    .map(|c| BundleChunk {
+                        chunk: c.chunk,
+                        embedding: c.embedding,
+                        embedding_model: c.embedding_model,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            format_version: packed.format_version,
+            documents,
+        }
+    }
+}
+
+/// Exporta toda la biblioteca (documentos, chunks y embeddings) a `path`
+///
+/// `level` es el nivel de compresión: 0 a 9 para gzip (6 por defecto) y el
+/// rango de zstd (3 por defecto); con `JsonPlain` se ignora. Retorna
+/// cuántos documentos se exportaron.
+pub fn export_database(
+    db: &Arc<sled::Db>,
+    path: impl AsRef<Path>,
+    format: BackupFormat,
+    level: Option<i32>,
+) -> Result<usize, DbError> {
+    let documents = get_all_documents(db)?
+        .iter()
+        .map(|doc| build_bundle(db, &doc.id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let count = documents.len();
+    let backup = LibraryBackup {
+        format_version: BUNDLE_FORMAT_VERSION,
+        documents,
+    };
+
+    let serialize_error = |e: &dyn std::fmt::Display| DbError::Serialize(e.to_string());
+    let bytes = match format {
+        BackupFormat::JsonPlain => {
+            serde_json::to_vec_pretty(&backup).map_err(|e| serialize_error(&e))?
+        }
+        BackupFormat::JsonGzip => {
+            let level = level.unwrap_or(GZIP_DEFAULT_LEVEL);
+            if !(0..=9).contains(&level) {
+                return Err(DbError::InvalidInput(format!(
+                    "gzip level {} out of range 0..=9",
+                    level
+                )));
+            }
+            let json = serde_json::to_vec(&backup).map_err(|e| serialize_error(&e))?;
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level as u32));
+            encoder.write_all(&json)?;
+            encoder.finish()?
+        }
+        BackupFormat::BincodeZstd => {
+            let level = level.unwrap_or(ZSTD_DEFAULT_LEVEL);
+            if !zstd::compression_level_range().contains(&level) {
+                return Err(DbError::InvalidInput(format!(
+                    "zstd level {} out of range",
+                    level
+                )));
+            }
+            let packed =
+                bincode::serialize(&PackedBackup::from(backup)).map_err(|e| serialize_error(&e))?;
+            zstd::encode_all(packed.as_slice(), level)?
+        }
+    };
+    std::fs::write(path, bytes)?;
+    Ok(count)
+}
+
+/// Resultado de importar un documento dentro de `import_database`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportEntry {
+    /// Id del documento en el respaldo
+    pub document_id: String,
+    /// El id con que quedó guardado (`None` si se saltó por
+    /// `ImportConflict::Skip`), o el mensaje de error
+    pub result: Result<Option<String>, String>,
+}
+
+/// Importa un respaldo hecho con `export_database`
+///
+/// El formato se detecta por los primeros bytes del archivo (ver
+/// `BackupFormat::detect`), no por la extensión. Cada documento se importa
+/// con `import_bundle` y `on_conflict`; un error en un documento queda en su
+/// entrada y se sigue con el próximo. Solo falla entero si no se puede leer
+/// el respaldo.
+pub fn import_database(
+    db: &Arc<sled::Db>,
+    path: impl AsRef<Path>,
+    on_conflict: ImportConflict,
+) -> Result<Vec<ImportEntry>, DbError> {
+    let bytes = std::fs::read(path)?;
+    let deserialize_error = |e: &dyn std::fmt::Display| DbError::Deserialize(e.to_string());
+    let backup: LibraryBackup = match BackupFormat::detect(&bytes) {
+        BackupFormat::JsonPlain => {
+            serde_json::from_slice(&bytes).map_err(|e| deserialize_error(&e))?
+        }
+        BackupFormat::JsonGzip => {
+            let mut json = Vec::new();
+            GzDecoder::new(bytes.as_slice())
+                .read_to_end(&mut json)
+                .map_err(|e| deserialize_error(&e))?;
+            serde_json::from_slice(&json).map_err(|e| deserialize_error(&e))?
+        }
+        BackupFormat::BincodeZstd => {
+            let packed = zstd::decode_all(bytes.as_slice()).map_err(|e| deserialize_error(&e))?;
+            bincode::deserialize::<PackedBackup>(&packed)
+                .map_err(|e| deserialize_error(&e))?
+                .into()
+        }
+    };
+    if backup.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(DbError::InvalidInput(format!(
+            "backup format version {} is newer than supported version {}",
+            backup.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    Ok(backup
+        .documents
+        .into_iter()
+        .map(|bundle| ImportEntry {
+            document_id: bundle.document.id.clone(),
+            result: import_bundle(db, bundle, on_conflict).map_err(|e| e.to_string()),
+        })
+        .collect())
+}
+
+/// Cambia el id del documento del bundle y de todos sus chunks
+///
+/// Los ids de chunk que empiezan con el id viejo del documento conservan el
+/// resto; los demás se prefijan con el id nuevo.
+fn rekey(bundle: &mut DocumentBundle) {
+    let old_id = bundle.document.id.clone();
+    let new_id = fresh_id(&old_id);
+
+    for entry in &mut bundle.chunks {
+        let chunk = &mut entry.chunk;
+        chunk.id = match chunk.id.strip_prefix(old_id.as_str()) {
+            Some(rest) => format!("{}{}", new_id, rest),
+            None => format!("{}:{}", new_id, chunk.id),
+        };
+        chunk.document_id = new_id.clone();
+    }
+    bundle.document.id = new_id;
+    bundle.document.sha256 = None;
+}
+
+/// Genera un id nuevo (hex de 64 caracteres, como los ids por hash)
+fn fresh_id(seed: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let mut hasher = Sha256::new();
+    hasher.update(seed.as_bytes());
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{count_chunks, get_document, insert_chunk};
+    use crate::services::embeddings::{get_embedding, insert_embedding};
+    use crate::services::test_support::temp_db;
+
+    fn seed(db: &Arc<sled::Db>) {
+        let doc = Document::new("doc-1".into(), "a.pdf".into(), "/tmp/a.pdf".into(), 2)
+            .with_sha256("abc".into());
+        insert_document(db, &doc).unwrap();
+        for i in 0..3 {
+            let chunk = Chunk::new(
+                format!("doc-1-{}", i),
+                "doc-1".into(),
+                format!("texto {}", i),
+                i,
+                1,
+            );
+            insert_chunk(db, &chunk).unwrap();
+        }
+        insert_embedding(db, "doc-1-0", &[0.5, -0.25, 1.0e-3], "hash-3", 3).unwrap();
+    }
+
+    #[test]
+    fn test_export_markdown_groups_pages() {
+        let (db, path) = temp_db("test_export_markdown");
+        let file = path.with_extension("md");
+        let doc = Document::new("doc-1".into(), "Apuntes".into(), "/tmp/a.pdf".into(), 2);
+        insert_document(&db, &doc).unwrap();
+        let pages = [(2, 1), (0, 1), (1, 1), (3, 2)];
+        for (index, page) in pages {
+            let text = format!("chunk {}", index);
+            let chunk = Chunk::new(format!("c-{}", index), "doc-1".into(), text, index, page);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+
+        export_document_markdown(&db, "doc-1", &file).unwrap();
+        let markdown = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(
+            markdown,
+            "# Apuntes\n\n## Página 1\n\nchunk 0\n\nchunk 1\n\nchunk 2\n\n## Página 2\n\nchunk 3\n"
+        );
+        assert_eq!(markdown.matches("## Página").count(), 2);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let (db, path) = temp_db("test_export_roundtrip");
+        let (other, other_path) = temp_db("test_export_roundtrip_dest");
+        let file = path.with_extension("json");
+        seed(&db);
+
+        export_document(&db, "doc-1", &file).unwrap();
+        let json = std::fs::read_to_string(&file).unwrap();
+        assert!(json.contains("\"format_version\": 1"));
+
+        let id = import_document(&other, &file, ImportConflict::Skip).unwrap();
+        assert_eq!(id.as_deref(), Some("doc-1"));
+        assert_eq!(
+            get_document(&other, "doc-1").unwrap(),
+            get_document(&db, "doc-1").unwrap()
+        );
+        assert_eq!(
+            get_chunks_for_document(&other, "doc-1").unwrap(),
+            get_chunks_for_document(&db, "doc-1").unwrap()
+        );
+        assert_eq!(
+            get_embedding(&other, "doc-1-0").unwrap(),
+            Some(vec![0.5, -0.25, 1.0e-3])
+        );
+        assert_eq!(get_embedding(&other, "doc-1-1").unwrap(), None);
+        assert_eq!(
+            get_embedding_record(&other, "doc-1-0")
+                .unwrap()
+                .unwrap()
+                .model,
+            "hash-3"
+        );
+
+        // Skip no toca lo existente; Overwrite lo reemplaza
+        assert_eq!(
+            import_document(&other, &file, ImportConflict::Skip).unwrap(),
+            None
+        );
+        let id = import_document(&other, &file, ImportConflict::Overwrite).unwrap();
+        assert_eq!(id.as_deref(), Some("doc-1"));
+        assert_eq!(count_chunks(&other, Some("doc-1")).unwrap(), 3);
+        assert_eq!(
+            get_document(&other, "doc-1").unwrap().unwrap().chunk_count,
+            3
+        );
+
+        drop(db);
+        drop(other);
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_dir_all(&other_path);
+    }
+
+    #[test]
+    fn test_import_with_new_id_remaps_chunks() {
+        let (db, path) = temp_db("test_export_new_id");
+        let file = path.with_extension("json");
+        seed(&db);
+        export_document(&db, "doc-1", &file).unwrap();
+
+        let new_id = import_document(&db, &file, ImportConflict::NewId)
+            .unwrap()
+            .unwrap();
+        assert_ne!(new_id, "doc-1");
+
+        let copy = get_document(&db, &new_id).unwrap().unwrap();
+        assert_eq!(copy.name, "a.pdf");
+        assert_eq!(copy.chunk_count, 3);
+        assert_eq!(copy.sha256, None);
+
+        let chunks = get_chunks_for_document(&db, &new_id).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.document_id == new_id));
+        assert!(chunks.iter().all(|c| c.id.starts_with(&new_id)));
+        assert_eq!(
+            get_embedding(&db, &chunks[0].id).unwrap(),
+            Some(vec![0.5, -0.25, 1.0e-3])
+        );
+
+        // El original queda intacto
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 3);
+
+        drop(db);
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_import_rejects_newer_format() {
+        let (db, path) = temp_db("test_export_version");
+        seed(&db);
+        let mut bundle = build_bundle(&db, "doc-1").unwrap();
+        bundle.format_version = BUNDLE_FORMAT_VERSION + 1;
+
+        assert!(matches!(
+            import_bundle(&db, bundle, ImportConflict::NewId),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_database_backup_roundtrip_per_format() {
+        let (db, path) = temp_db("test_backup_roundtrip");
+        seed(&db);
+        let doc = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &doc).unwrap();
+        let chunk = Chunk::new("doc-2-0".into(), "doc-2".into(), "otro texto".into(), 0, 1)
+            .with_metadata(r#"{"language": "es"}"#.to_string());
+        insert_chunk(&db, &chunk).unwrap();
+
+        let formats = [
+            (BackupFormat::JsonPlain, None),
+            (BackupFormat::JsonGzip, Some(9)),
+            (BackupFormat::BincodeZstd, None),
+        ];
+        for (i, (format, level)) in formats.into_iter().enumerate() {
+            // La extensión no dice nada del formato: se detecta por el contenido
+            let file = path.with_extension(format!("backup{}", i));
+            assert_eq!(export_database(&db, &file, format, level).unwrap(), 2);
+            assert_eq!(BackupFormat::detect(&std::fs::read(&file).unwrap()), format);
+
+            let (other, other_path) = temp_db(&format!("test_backup_roundtrip_dest{}", i));
+            let mut ids: Vec<String> = import_database(&other, &file, ImportConflict::Skip)
+                .unwrap()
+                .into_iter()
+                .filter_map(|entry| entry.result.unwrap())
+                .collect();
+            ids.sort();
+            assert_eq!(ids, ["doc-1", "doc-2"]);
+            for id in ["doc-1", "doc-2"] {
+                assert_eq!(
+                    get_document(&other, id).unwrap(),
+                    get_document(&db, id).unwrap()
+                );
+                assert_eq!(
+                    get_chunks_for_document(&other, id).unwrap(),
+                    get_chunks_for_document(&db, id).unwrap()
+                );
+            }
+            assert_eq!(
+                get_embedding(&other, "doc-1-0").unwrap(),
+                Some(vec![0.5, -0.25, 1.0e-3])
+            );
+
+            drop(other);
+            let _ = std::fs::remove_file(&file);
+            let _ = std::fs::remove_dir_all(&other_path);
+        }
+
+        let file = path.with_extension("backup");
+        assert!(matches!(
+            export_database(&db, &file, BackupFormat::JsonGzip, Some(10)),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_overwrite_keeps_document_when_model_differs() {
+        let (db, path) = temp_db("test_export_overwrite_model");
+        seed(&db);
+        let doc = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &doc).unwrap();
+        let chunk = Chunk::new("doc-2-0".into(), "doc-2".into(), "otro".into(), 0, 1);
+        insert_chunk(&db, &chunk).unwrap();
+        insert_embedding(&db, "doc-2-0", &[1.0, 0.0, 0.0], "hash-3", 3).unwrap();
+        let before = get_chunks_for_document(&db, "doc-1").unwrap();
+
+        // Vectores de otro modelo: se rechaza antes de borrar nada
+        let mut bundle = build_bundle(&db, "doc-1").unwrap();
+        bundle.chunks.truncate(1);
+        bundle.chunks[0].chunk.text = "texto nuevo".into();
+        bundle.chunks[0].chunk.char_count = "texto nuevo".chars().count();
+        bundle.chunks[0].embedding_model = Some("otro-modelo".into());
+        assert!(matches!(
+            import_bundle(&db, bundle.clone(), ImportConflict::Overwrite),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert_eq!(get_chunks_for_document(&db, "doc-1").unwrap(), before);
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 3);
+        assert!(get_embedding(&db, "doc-1-0").unwrap().is_some());
+
+        // Con el modelo correcto reemplaza chunks y vectores
+        bundle.chunks[0].embedding_model = Some("hash-3".into());
+        import_bundle(&db, bundle, ImportConflict::Overwrite).unwrap();
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "texto nuevo");
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 1);
+        assert!(get_embedding(&db, "doc-1-1").unwrap().is_none());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_import_database_reports_each_document() {
+        let (db, path) = temp_db("test_backup_partial");
+        seed(&db);
+        let doc = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &doc).unwrap();
+        let file = path.with_extension("backup");
+        export_database(&db, &file, BackupFormat::JsonPlain, None).unwrap();
+
+        // La biblioteca destino ya usa otro modelo: doc-1 (con vectores)
+        // falla, doc-2 (sin vectores) se importa igual
+        let (other, other_path) = temp_db("test_backup_partial_dest");
+        let doc = Document::new("doc-x".into(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+        insert_document(&other, &doc).unwrap();
+        let chunk = Chunk::new("doc-x-0".into(), "doc-x".into(), "x".into(), 0, 1);
+        insert_chunk(&other, &chunk).unwrap();
+        insert_embedding(&other, "doc-x-0", &[1.0, 0.0], "otro-modelo", 2).unwrap();
+
+        let mut entries = import_database(&other, &file, ImportConflict::Skip).unwrap();
+        entries.sort_by(|a, b| a.document_id.cmp(&b.document_id));
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].result.is_err(), "{:?}", entries[0]);
+        assert_eq!(entries[1].result, Ok(Some("doc-2".to_string())));
+        assert!(get_document(&other, "doc-1").unwrap().is_none());
+        assert!(get_document(&other, "doc-2").unwrap().is_some());
+
+        drop(db);
+        drop(other);
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_dir_all(&other_path);
+    }
+}
@@ -0,0 +1,627 @@
+// Extracción de texto según el formato del archivo
+//
+// Cada formato tiene su `Extractor`; `extract` elige el que corresponde por
+// la extensión del archivo y, si no la reconoce, mirando su contenido. Todos
+// producen un `ExtractedDocument` con el texto dividido en "páginas".
+
+use crate::models::{Chunk, ChunkMetadata, DocType, Document};
+use crate::services::chunker::{build_chunks_with, ChunkingConfig};
+use crate::services::epub::{self, EpubError};
+use crate::services::error::ExtractError;
+use crate::services::html;
+use crate::services::pdf::{self, PdfInfo};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::path::Path;
+
+/// Bytes iniciales que se miran para adivinar el formato
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Fracción de páginas escaneadas a partir de la cual se considera que todo
+/// el documento es un escaneo (p. ej. un libro escaneado con la portada en
+/// texto)
+const SCANNED_THRESHOLD: f64 = 0.9;
+
+/// Resultado de extraer un archivo, igual para todos los formatos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedDocument {
+    pub doc_type: DocType,
+    /// Texto de cada página, en orden; los formatos sin páginas tienen una
+    pub pages: Vec<String>,
+    /// Encabezados bajo los que está cada página, del más general al más
+    /// cercano; vacío si el formato no tiene secciones
+    pub page_headings: Vec<Vec<String>>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    /// Páginas sin texto pero con imágenes (escaneos sin capa de texto); solo
+    /// los PDFs pueden tenerlas
+    pub scanned_page_count: usize,
+}
+
+impl ExtractedDocument {
+    /// Documento de una sola página, sin título ni autor
+    fn single_page(doc_type: DocType, text: String) -> Self {
+        Self {
+            doc_type,
+            pages: vec![text],
+            page_headings: Vec::new(),
+            title: None,
+            author: None,
+            subject: None,
+            scanned_page_count: 0,
+        }
+    }
+
+    /// Indica si prácticamente todas las páginas son escaneos, así que no
+    /// hay texto que indexar sin pasar antes por OCR
+    pub fn is_scanned(&self) -> bool {
+        self.scanned_page_count > 0
+            && self.scanned_page_count as f64 >= self.pages.len() as f64 * SCANNED_THRESHOLD
+    }
+
+    /// Números (desde 1) de las páginas sin texto
+    pub fn empty_pages(&self) -> Vec<usize> {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| text.trim().is_empty())
+            .map(|(index, _)| index + 1)
+            .collect()
+    }
+
+    /// Título, autor y tema, como los del `/Info` de un PDF
+    pub fn info(&self) -> PdfInfo {
+        PdfInfo {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            subject: self.subject.clone(),
+        }
+    }
+
+    /// Copia en `doc` el formato y, con `PdfInfo::apply_to`, el título, autor
+    /// y tema presentes
+    pub fn apply_to(&self, doc: &mut Document) {
+        doc.doc_type = Some(self.doc_type);
+        self.info().apply_to(doc);
+    }
+
+    /// Divide las páginas en chunks con `build_chunks_with` y guarda en
+    /// `ChunkMetadata::section_title` el encabezado más cercano de cada una
+    pub fn build_chunks(&self, document_id: &str, config: &ChunkingConfig) -> Vec<Chunk> {
+        let mut chunks = build_chunks_with(document_id, &self.pages, config);
+        for chunk in &mut chunks {
+            let heading = self
+                .page_headings
+                .get(chunk.page_number - 1)
+                .and_then(|headings| headings.last());
+            if let Some(heading) = heading {
+                chunk
+                    .metadata
+                    .get_or_insert_with(ChunkMetadata::default)
+                    .section_title = Some(heading.clone());
+            }
+        }
+        chunks
+    }
+}
+
+/// Extrae el texto de un formato de archivo
+pub trait Extractor {
+    /// `path` solo se usa en los mensajes de error
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError>;
+}
+
+/// PDFs, con `pdf::read_text_from_bytes`; el `/Info` aporta título, autor y
+/// tema
+///
+/// Las páginas escaneadas quedan vacías y se cuentan en
+/// `scanned_page_count`; decidir qué hacer con ellas es cosa de quien llama.
+/// Un PDF sin texto que no es un escaneo (ver `is_scanned`) se rechaza con
+/// `ExtractError::Empty`, como en los demás formatos.
+pub struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+        let text = pdf::read_text_from_bytes(bytes)
+            .map_err(|e| ExtractError::Invalid(format!("{}: {}", path, e)))?;
+        // Un `/Info` ilegible no impide extraer el texto
+        let info = pdf::read_info_from_bytes(bytes).unwrap_or_default();
+        let extracted = ExtractedDocument {
+            doc_type: DocType::Pdf,
+            pages: text.pages,
+            page_headings: Vec::new(),
+            title: info.title,
+            author: info.author,
+            subject: info.subject,
+            scanned_page_count: text.scanned_pages.len(),
+        };
+        if !extracted.is_scanned() && extracted.pages.iter().all(|p| p.trim().is_empty()) {
+            return Err(ExtractError::Empty(path.to_string()));
+        }
+        Ok(extracted)
+    }
+}
+
+/// Texto plano como una sola página
+///
+/// Se lee como UTF-8 (sin BOM); si no es UTF-8 válido se asume Latin-1, que
+/// nunca falla. Un archivo vacío o solo con espacios se rechaza.
+pub struct TxtExtractor;
+
+impl Extractor for TxtExtractor {
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+        let text = decode_text(bytes);
+        if text.trim().is_empty() {
+            return Err(ExtractError::Empty(path.to_string()));
+        }
+        Ok(ExtractedDocument::single_page(DocType::Text, text))
+    }
+}
+
+/// Markdown como texto plano, con una página por sección
+///
+/// Cada encabezado abre una página nueva, así ningún chunk mezcla dos
+/// secciones. Los bloques de código quedan tal cual; de los enlaces queda
+/// solo el texto y las imágenes se descartan. El texto se decodifica igual
+/// que en `TxtExtractor`.
+pub struct MarkdownExtractor;
+
+impl Extractor for MarkdownExtractor {
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+        let (pages, page_headings) = markdown_sections(&decode_text(bytes));
+        if pages.is_empty() {
+            return Err(ExtractError::Empty(path.to_string()));
+        }
+        Ok(ExtractedDocument {
+            doc_type: DocType::Markdown,
+            pages,
+            page_headings,
+            title: None,
+            author: None,
+            subject: None,
+            scanned_page_count: 0,
+        })
+    }
+}
+
+/// Libros EPUB, con un capítulo del `spine` por página
+///
+/// Así `page_number` es la posición del capítulo en el orden de lectura
+/// (desde 1) y su título queda como encabezado de la página. Los libros con
+/// DRM se rechazan con `ExtractError::DrmProtected`.
+pub struct EpubExtractor;
+
+impl Extractor for EpubExtractor {
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+        let book = epub::read_epub(bytes).map_err(|e| match e {
+            EpubError::Drm => ExtractError::DrmProtected(path.to_string()),
+            EpubError::Invalid(msg) => ExtractError::Invalid(format!("{}: {}", path, msg)),
+        })?;
+        if book.chapters.iter().all(|c| c.text.trim().is_empty()) {
+            return Err(ExtractError::Empty(path.to_string()));
+        }
+        let (pages, page_headings) = book
+            .chapters
+            .into_iter()
+            .map(|c| (c.text, c.title.into_iter().collect()))
+            .unzip();
+        Ok(ExtractedDocument {
+            doc_type: DocType::Epub,
+            pages,
+            page_headings,
+            title: book.title,
+            author: book.author,
+            subject: None,
+            scanned_page_count: 0,
+        })
+    }
+}
+
+/// Páginas web guardadas, como una sola página sin menús, pies ni scripts
+/// (ver `html::read_html`); el `<title>` pasa a ser el título del documento
+pub struct HtmlExtractor;
+
+impl Extractor for HtmlExtractor {
+    fn extract(&self, path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+        let page = html::read_html(bytes);
+        if page.text.trim().is_empty() {
+            return Err(ExtractError::Empty(path.to_string()));
+        }
+        Ok(ExtractedDocument {
+            title: page.title,
+            ..ExtractedDocument::single_page(DocType::Html, page.text)
+        })
+    }
+}
+
+/// Texto como UTF-8 (sin BOM) o, si no es UTF-8 válido, como Latin-1
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// Texto plano de cada sección de `source` y los encabezados que la
+/// contienen; las secciones sin texto se omiten
+fn markdown_sections(source: &str) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut pages = Vec::new();
+    let mut page_headings = Vec::new();
+    // Encabezados abiertos, del más general al más cercano
+    let mut open: Vec<(HeadingLevel, String)> = Vec::new();
+    let mut page = String::new();
+    // Texto del encabezado que se está leyendo
+    let mut heading: Option<String> = None;
+    let mut image_depth = 0usize;
+
+    let mut flush = |page: &mut String, open: &[(HeadingLevel, String)]| {
+        let text = page.trim_end();
+        if !text.trim_start().is_empty() {
+            pages.push(text.to_string());
+            page_headings.push(open.iter().map(|(_, title)| title.clone()).collect());
+        }
+        page.clear();
+    };
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    for event in Parser::new_ext(source, options) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                flush(&mut page, &open);
+                heading = Some(String::new());
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                let title = heading.take().unwrap_or_default().trim().to_string();
+                while open.last().is_some_and(|(l, _)| *l >= level) {
+                    open.pop();
+                }
+                page.push_str(&title);
+                end_block(&mut page);
+                open.push((level, title));
+            }
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            _ if image_depth > 0 => {}
+            Event::Text(text) | Event::Code(text) => {
+                heading.as_mut().unwrap_or(&mut page).push_str(&text)
+            }
+            Event::SoftBreak => heading.as_mut().unwrap_or(&mut page).push(' '),
+            Event::HardBreak => page.push('\n'),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::List(_)
+                | TagEnd::Table,
+            )
+            | Event::Rule => end_block(&mut page),
+            Event::End(TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow) => end_line(&mut page),
+            Event::End(TagEnd::TableCell) => page.push('\t'),
+            // HTML, notas al pie, marcadores de tareas, etc.
+            _ => {}
+        }
+    }
+    flush(&mut page, &open);
+    (pages, page_headings)
+}
+
+/// Termina la línea actual, si hay una empezada
+fn end_line(page: &mut String) {
+    if !page.is_empty() && !page.ends_with('\n') {
+        page.push('\n');
+    }
+}
+
+/// Cierra un bloque con una línea en blanco
+fn end_block(page: &mut String) {
+    let len = page.trim_end_matches('\n').len();
+    page.truncate(len);
+    if !page.is_empty() {
+        page.push_str("\n\n");
+    }
+}
+
+/// Formato según la extensión de `path`, si es conocida
+pub fn doc_type_from_path(path: &str) -> Option<DocType> {
+    let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "pdf" => Some(DocType::Pdf),
+        "txt" | "text" => Some(DocType::Text),
+        "md" | "markdown" => Some(DocType::Markdown),
+        "epub" => Some(DocType::Epub),
+        "html" | "htm" | "xhtml" => Some(DocType::Html),
+        _ => None,
+    }
+}
+
+/// Formato adivinado por el contenido: la firma `%PDF-`, el `mimetype` que
+/// un EPUB guarda primero en su zip, HTML si empieza con `<!doctype html` o
+/// `<html`, o texto si el comienzo no tiene bytes nulos ni otros caracteres
+/// de control binarios
+pub fn sniff_doc_type(bytes: &[u8]) -> Option<DocType> {
+    if bytes.starts_with(b"%PDF-") {
+        return Some(DocType::Pdf);
+    }
+    if bytes.starts_with(b"PK\x03\x04")
+        && bytes.get(30..58) == Some(b"mimetypeapplication/epub+zip".as_slice())
+    {
+        return Some(DocType::Epub);
+    }
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let binary = head
+        .iter()
+        .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C));
+    if binary {
+        return None;
+    }
+    let start = String::from_utf8_lossy(&head[..head.len().min(64)]).to_ascii_lowercase();
+    let start = start.trim_start_matches('\u{feff}').trim_start();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        return Some(DocType::Html);
+    }
+    Some(DocType::Text)
+}
+
+/// Extractor de un formato
+pub fn extractor_for(doc_type: DocType) -> &'static dyn Extractor {
+    match doc_type {
+        DocType::Pdf => &PdfExtractor,
+        DocType::Text => &TxtExtractor,
+        DocType::Markdown => &MarkdownExtractor,
+        DocType::Epub => &EpubExtractor,
+        DocType::Html => &HtmlExtractor,
+    }
+}
+
+/// Extrae el texto de un archivo ya leído, eligiendo el extractor por la
+/// extensión de `path` o, si no la reconoce, por el contenido
+pub fn extract(path: &str, bytes: &[u8]) -> Result<ExtractedDocument, ExtractError> {
+    let doc_type = doc_type_from_path(path)
+        .or_else(|| sniff_doc_type(bytes))
+        .ok_or_else(|| ExtractError::Unsupported(path.to_string()))?;
+    extractor_for(doc_type).extract(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREE_PAGES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/three_pages.pdf");
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+    #[test]
+    fn test_txt_utf8() {
+        let text = "Apuntes de óptica\n\nLa luz se refracta.";
+        let extracted = extract("notas.txt", text.as_bytes()).unwrap();
+        assert_eq!(extracted.doc_type, DocType::Text);
+        assert_eq!(extracted.pages, [text]);
+
+        // El BOM no queda en el texto
+        let with_bom = [b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat();
+        assert_eq!(extract("notas.txt", &with_bom).unwrap().pages, [text]);
+    }
+
+    #[test]
+    fn test_txt_latin1() {
+        // "Óptica y difracción" en Latin-1: no es UTF-8 válido
+        let bytes = b"\xD3ptica y difracci\xF3n";
+        let extracted = extract("apuntes.TXT", bytes).unwrap();
+        assert_eq!(extracted.pages, ["Óptica y difracción"]);
+    }
+
+    #[test]
+    fn test_txt_empty_is_rejected() {
+        for bytes in [b"".as_slice(), b" \n\t\n"] {
+            let err = extract("vacio.txt", bytes).unwrap_err();
+            assert_eq!(err, ExtractError::Empty("vacio.txt".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_dispatch_and_sniffing() {
+        assert_eq!(doc_type_from_path("a/b/Libro.PDF"), Some(DocType::Pdf));
+        assert_eq!(doc_type_from_path("notas.txt"), Some(DocType::Text));
+        assert_eq!(doc_type_from_path("sin_extension"), None);
+
+        // Sin extensión conocida se mira el contenido
+        let pdf = std::fs::read(format!("{}/text_only.pdf", FIXTURES)).unwrap();
+        let extracted = extract("descarga", &pdf).unwrap();
+        assert_eq!(extracted.doc_type, DocType::Pdf);
+        assert_eq!(extracted.pages.len(), 2);
+        assert_eq!(extract("LEEME", b"hola").unwrap().doc_type, DocType::Text);
+
+        // Binario sin formato conocido
+        let err = extract("imagen.bin", b"\x89PNG\r\n\x1a\n\0\0").unwrap_err();
+        assert!(matches!(err, ExtractError::Unsupported(_)));
+    }
+
+    const NOTES: &str = "Apuntes del curso.
+
+# Óptica
+
+La luz viaja en línea recta.
+
+## Refracción
+
+La luz cambia de dirección al pasar de un medio a otro (ver [la figura](https://ejemplo.com/fig)).
+![diagrama del prisma](prisma.png)
+
+```rust
+let n = 1.5;   // índice del vidrio
+```
+
+### Ley de Snell
+
+El seno del ángulo es proporcional al índice.
+
+## Reflexión
+
+El ángulo de incidencia es igual al de **reflexión**.
+
+# Mecánica
+
+Las fuerzas cambian el movimiento.
+";
+
+    #[test]
+    fn test_markdown_sections() {
+        let extracted = extract("notas.md", NOTES.as_bytes()).unwrap();
+        assert_eq!(extracted.doc_type, DocType::Markdown);
+        let headings: Vec<Vec<&str>> = extracted
+            .page_headings
+            .iter()
+            .map(|h| h.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                vec![],
+                vec!["Óptica"],
+                vec!["Óptica", "Refracción"],
+                vec!["Óptica", "Refracción", "Ley de Snell"],
+                vec!["Óptica", "Reflexión"],
+                vec!["Mecánica"],
+            ]
+        );
+
+        let refraction = &extracted.pages[2];
+        assert!(refraction.starts_with("Refracción\n\n"));
+        // Del enlace queda el texto; la imagen y las URLs se descartan
+        assert!(refraction.contains("(ver la figura)."));
+        assert!(!refraction.contains("https://") && !refraction.contains("prisma"));
+        // El código queda tal cual
+        assert!(refraction.contains("let n = 1.5;   // índice del vidrio"));
+        assert_eq!(
+            extracted.pages[4],
+            "Reflexión\n\nEl ángulo de incidencia es igual al de reflexión."
+        );
+
+        // Solo imágenes o espacios: no hay texto
+        let err = extract("vacio.md", b"![foto](a.png)\n\n   \n").unwrap_err();
+        assert!(matches!(err, ExtractError::Empty(_)));
+    }
+
+    #[test]
+    fn test_markdown_section_titles_reach_chunks() {
+        let extracted = extract("notas.md", NOTES.as_bytes()).unwrap();
+        let chunks = extracted.build_chunks("doc", &ChunkingConfig::default());
+
+        let section_of = |needle: &str| {
+            let chunk = chunks.iter().find(|c| c.text.contains(needle)).unwrap();
+            chunk
+                .metadata
+                .as_ref()
+                .and_then(|m| m.section_title.clone())
+        };
+        assert_eq!(section_of("Apuntes del curso"), None);
+        assert_eq!(section_of("línea recta").as_deref(), Some("Óptica"));
+        assert_eq!(section_of("medio a otro").as_deref(), Some("Refracción"));
+        assert_eq!(
+            section_of("seno del ángulo").as_deref(),
+            Some("Ley de Snell")
+        );
+        assert_eq!(section_of("incidencia").as_deref(), Some("Reflexión"));
+        assert_eq!(section_of("fuerzas").as_deref(), Some("Mecánica"));
+        // Ningún chunk mezcla secciones
+        assert!(chunks
+            .iter()
+            .all(|c| !(c.text.contains("recta") && c.text.contains("Refracción"))));
+    }
+
+    #[test]
+    fn test_epub_chapters_are_pages() {
+        let epub =
+            std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/tiny.epub")).unwrap();
+        let extracted = extract("libro.epub", &epub).unwrap();
+        assert_eq!(sniff_doc_type(&epub), Some(DocType::Epub));
+        assert_eq!(extracted.doc_type, DocType::Epub);
+        assert_eq!(extracted.pages.len(), 3);
+        assert_eq!(extracted.title.as_deref(), Some("Apuntes de óptica"));
+        assert_eq!(extracted.author.as_deref(), Some("Ana Pérez"));
+
+        let chunks = extracted.build_chunks("doc", &ChunkingConfig::default());
+        let chunk = chunks
+            .iter()
+            .find(|c| c.text.contains("índice del agua"))
+            .unwrap();
+        assert_eq!(chunk.page_number, 3);
+        assert_eq!(
+            chunk.metadata.as_ref().unwrap().section_title.as_deref(),
+            Some("Ejercicios")
+        );
+
+        let drm = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/drm.epub")).unwrap();
+        let err = extract("protegido.epub", &drm).unwrap_err();
+        assert_eq!(
+            err,
+            ExtractError::DrmProtected("protegido.epub".to_string())
+        );
+
+        // Un libro cuyo spine es solo el índice no tiene texto
+        let nav_only = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/nav_only.epub"
+        ))
+        .unwrap();
+        assert_eq!(
+            extract("indice.epub", &nav_only).unwrap_err(),
+            ExtractError::Empty("indice.epub".to_string())
+        );
+    }
+
+    #[test]
+    fn test_html_extractor() {
+        let article = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/article.html"
+        ))
+        .unwrap();
+        let extracted = extract("guardado.html", &article).unwrap();
+        assert_eq!(extracted.doc_type, DocType::Html);
+        assert_eq!(extracted.pages.len(), 1);
+        assert!(!extracted.pages[0].contains("Suscribite"));
+
+        let mut doc = Document::new("d".into(), "guardado.html".into(), "/tmp/g.html".into(), 1);
+        extracted.apply_to(&mut doc);
+        assert_eq!(doc.title.as_deref(), Some("Cómo funciona la refracción"));
+
+        // Sin extensión se reconoce por el contenido
+        assert_eq!(sniff_doc_type(&article), Some(DocType::Html));
+        let err = extract("vacia.html", b"<html><nav>Inicio</nav></html>").unwrap_err();
+        assert!(matches!(err, ExtractError::Empty(_)));
+    }
+
+    #[test]
+    fn test_pdf_scanned_pages() {
+        let read = |name: &str| {
+            let bytes = std::fs::read(format!("{}/{}", FIXTURES, name)).unwrap();
+            extract(name, &bytes).unwrap()
+        };
+
+        let text = read("text_only.pdf");
+        assert_eq!(text.scanned_page_count, 0);
+        assert!(!text.is_scanned());
+        assert!(text.empty_pages().is_empty());
+
+        let scanned = read("scanned.pdf");
+        assert_eq!(scanned.scanned_page_count, 2);
+        assert!(scanned.is_scanned());
+
+        let mixed = read("mixed_scan.pdf");
+        assert_eq!(mixed.scanned_page_count, 1);
+        assert!(!mixed.is_scanned());
+        assert_eq!(mixed.empty_pages(), [2]);
+
+        // En blanco no es lo mismo que escaneado: sin texto se rechaza, como
+        // en los demás formatos
+        let bytes = std::fs::read(THREE_PAGES).unwrap();
+        assert_eq!(
+            extract("blanco.pdf", &bytes).unwrap_err(),
+            ExtractError::Empty("blanco.pdf".to_string())
+        );
+    }
+}
@@ -0,0 +1,268 @@
+// Texto plano a partir de HTML: páginas web guardadas (`read_html`) y las
+// piezas que comparten con los capítulos XHTML de los EPUB
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Marca de `<br>` dentro de un bloque, distinta de los saltos de línea del
+/// código fuente (que se colapsan como cualquier espacio)
+const LINE_BREAK: char = '\u{2028}';
+
+/// Elementos que cortan el texto en bloques
+const BLOCK_ELEMENTS: [&str; 25] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "aside",
+    "header",
+    "footer",
+    "nav",
+    "blockquote",
+    "pre",
+    "li",
+    "ul",
+    "ol",
+    "dt",
+    "dd",
+    "tr",
+    "table",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+/// Elementos de una página web que no son parte del contenido: código,
+/// estilos y la "decoración" del sitio (menús, pies, barras laterales)
+const BOILERPLATE: [&str; 8] = [
+    "head", "script", "style", "noscript", "template", "nav", "footer", "aside",
+];
+
+/// Bytes iniciales donde se busca la declaración `<meta charset>`
+const CHARSET_SCAN_LEN: usize = 1024;
+
+pub(crate) fn is_block_element(name: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&name)
+}
+
+pub(crate) fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Colapsa los espacios (incluido el de no separación) en uno solo
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Arma texto plano por bloques, separados por una línea en blanco
+///
+/// Los espacios de cada bloque se colapsan, salvo en los preformateados
+/// (`<pre>`); `line_break` es un salto de línea que sí se conserva.
+#[derive(Debug, Default)]
+pub(crate) struct PlainText {
+    text: String,
+    block: String,
+}
+
+impl PlainText {
+    pub(crate) fn push_str(&mut self, text: &str) {
+        self.block.push_str(text);
+    }
+
+    pub(crate) fn line_break(&mut self) {
+        self.block.push(LINE_BREAK);
+    }
+
+    /// Texto del bloque en curso, con los espacios colapsados
+    pub(crate) fn current_block(&self) -> String {
+        collapse_whitespace(&self.block)
+    }
+
+    /// Cierra el bloque en curso; los bloques vacíos se descartan
+    pub(crate) fn end_block(&mut self, preformatted: bool) {
+        let content = if preformatted {
+            self.block
+                .replace(LINE_BREAK, "\n")
+                .trim_matches('\n')
+                .to_string()
+        } else {
+            self.block
+                .split(LINE_BREAK)
+                .map(collapse_whitespace)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        self.block.clear();
+        if content.trim().is_empty() {
+            return;
+        }
+        if !self.text.is_empty() {
+            self.text.push_str("\n\n");
+        }
+        self.text.push_str(&content);
+    }
+
+    pub(crate) fn finish(mut self) -> String {
+        self.end_block(false);
+        self.text
+    }
+}
+
+/// Página web ya leída
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HtmlPage {
+    /// Contenido de `<title>`
+    pub title: Option<String>,
+    /// Texto legible, con los párrafos separados por una línea en blanco
+    pub text: String,
+}
+
+/// Lee una página HTML desde sus bytes
+///
+/// La codificación sale del BOM o del `<meta charset>` (o
+/// `http-equiv="Content-Type"`); sin declaración se usa UTF-8 si es válido
+/// y Windows-1252 si no, como hacen los navegadores. Se descartan el código,
+/// los estilos, los menús (`<nav>`), los pies (`<footer>`) y las barras
+/// laterales (`<aside>`).
+pub fn read_html(bytes: &[u8]) -> HtmlPage {
+    let html = Html::parse_document(&decode_html(bytes));
+    let title = Selector::parse("title").ok().and_then(|selector| {
+        let title = collapse_whitespace(&html.select(&selector).next()?.text().collect::<String>());
+        Some(title).filter(|t| !t.is_empty())
+    });
+    let mut text = PlainText::default();
+    walk(html.root_element(), &mut text, false);
+    HtmlPage {
+        title,
+        text: text.finish(),
+    }
+}
+
+/// Agrega a `out` el texto de los hijos de `element`
+fn walk(element: ElementRef, out: &mut PlainText, preformatted: bool) {
+    for child in element.children() {
+        if let Some(child) = ElementRef::wrap(child) {
+            let name = child.value().name();
+            if BOILERPLATE.contains(&name) {
+                continue;
+            }
+            if name == "br" {
+                out.line_break();
+                continue;
+            }
+            let block = is_block_element(name);
+            let inner = preformatted || name == "pre";
+            if block {
+                out.end_block(preformatted);
+            }
+            walk(child, out, inner);
+            if block {
+                out.end_block(inner);
+            }
+        } else if let Node::Text(text) = child.value() {
+            out.push_str(text);
+        }
+    }
+}
+
+/// Texto de la página según su codificación
+fn decode_html(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_len..])
+            .0
+            .into_owned();
+    }
+    let encoding = declared_charset(bytes).unwrap_or_else(|| {
+        if std::str::from_utf8(bytes).is_ok() {
+            UTF_8
+        } else {
+            WINDOWS_1252
+        }
+    });
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Codificación declarada en un `<meta>` al comienzo de la página
+fn declared_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(CHARSET_SCAN_LEN)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + "<meta".len()..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let Some(at) = tag.find("charset=") else {
+            continue;
+        };
+        let label = tag[at + "charset=".len()..]
+            .trim_start_matches(['"', '\'', ' '])
+            .split(['"', '\'', ';', ' ', '/'])
+            .next()
+            .unwrap_or_default();
+        // Como en los navegadores, una página que dice ser UTF-16 se lee
+        // como UTF-8 (`output_encoding`)
+        if let Some(encoding) = Encoding::for_label(label.as_bytes()) {
+            return Some(encoding.output_encoding());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/article.html");
+
+    #[test]
+    fn test_read_html_strips_boilerplate() {
+        let page = read_html(&std::fs::read(ARTICLE).unwrap());
+        assert_eq!(page.title.as_deref(), Some("Cómo funciona la refracción"));
+        assert_eq!(
+            page.text,
+            "Cómo funciona la refracción\n\n\
+             La luz cambia de dirección al pasar del aire al agua.\n\n\
+             Por eso una cuchara parece quebrada dentro de un vaso.\n\
+             El efecto depende del índice de refracción.\n\n\
+             n1 · sen(θ1) = n2 · sen(θ2)"
+        );
+        for noise in [
+            "Inicio",
+            "Suscribite",
+            "Todos los derechos",
+            "Artículos relacionados",
+            "analytics",
+            "color:",
+        ] {
+            assert!(!page.text.contains(noise), "{}", noise);
+        }
+    }
+
+    #[test]
+    fn test_read_html_respects_charset() {
+        // "Óptica" en Latin-1, declarado de las dos formas
+        let latin1 = b"<html><head><meta charset=\"iso-8859-1\"><title>\xD3ptica</title></head>\
+            <body><p>\xD3ptica</p></body></html>";
+        let page = read_html(latin1);
+        assert_eq!(page.title.as_deref(), Some("Óptica"));
+        assert_eq!(page.text, "Óptica");
+
+        let http_equiv = b"<html><head><meta http-equiv=\"Content-Type\" \
+            content=\"text/html; charset=windows-1252\"></head><body>\x93Hola\x94</body></html>";
+        assert_eq!(read_html(http_equiv).text, "\u{201c}Hola\u{201d}");
+
+        // Sin declaración: UTF-8 si es válido
+        let utf8 = "<p>Óptica</p>".as_bytes();
+        assert_eq!(read_html(utf8).text, "Óptica");
+        assert_eq!(read_html(utf8).title, None);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::services::error::EmbedError;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+/// Tiempo máximo por request a un backend de embeddings
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Cliente HTTP con el timeout indicado (conexión, lectura y escritura)
+pub(crate) fn agent(timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(timeout).build()
+}
+
+/// Convierte un error de ureq en `EmbedError::Backend`
+///
+/// Si el servidor respondió con un error se incluye el cuerpo de la
+/// respuesta, que suele explicar el problema (modelo inexistente, etc.).
+pub(crate) fn backend_error(url: &str, error: ureq::Error) -> EmbedError {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            EmbedError::Backend(format!("{} returned {}: {}", url, code, body.trim()))
+        }
+        ureq::Error::Transport(e) => EmbedError::Backend(format!("{}: {}", url, e)),
+    }
+}
+
+/// Lee el cuerpo JSON de una respuesta exitosa
+pub(crate) fn read_json<T: DeserializeOwned>(
+    url: &str,
+    response: ureq::Response,
+) -> Result<T, EmbedError> {
+    let body = response
+        .into_string()
+        .map_err(|e| EmbedError::Backend(format!("{}: {}", url, e)))?;
+    serde_json::from_str(&body)
+        .map_err(|e| EmbedError::InvalidOutput(format!("malformed response from {}: {}", url, e)))
+}
+
+/// Servidor HTTP mínimo para probar los clientes sin red
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+
+    /// Respuesta que el servidor devuelve a una conexión
+    pub struct MockResponse {
+        pub status: u16,
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+        /// Espera antes de responder (para simular timeouts)
+        pub delay: Duration,
+    }
+
+    impl MockResponse {
+        pub fn json(status: u16, body: &str) -> Self {
+            Self {
+                status,
+                headers: Vec::new(),
+                body: body.to_string(),
+                delay: Duration::ZERO,
+            }
+        }
+
+        pub fn with_header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+
+        pub fn with_delay(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+    }
+
+    /// Request recibido, con los nombres de header en minúsculas
+    #[derive(Debug, Clone)]
+    pub struct RecordedRequest {
+        pub path: String,
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    impl RecordedRequest {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    pub struct MockServer {
+        pub url: String,
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl MockServer {
+        /// Atiende una conexión por respuesta, en orden, y luego termina
+        pub fn serve(responses: Vec<MockResponse>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&requests);
+
+            let handle = std::thread::spawn(move || {
+                for response in responses {
+                    let Ok((stream, _)) = listener.accept() else {
+                        return;
+                    };
+                    let mut reader = BufReader::new(stream);
+                    if let Some(request) = read_request(&mut reader) {
+                        recorded.lock().unwrap().push(request);
+                    }
+                    std::thread::sleep(response.delay);
+                    let mut out = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    for (name, value) in &response.headers {
+                        out.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    out.push_str("\r\n");
+                    out.push_str(&response.body);
+                    // El cliente pudo haberse ido por timeout
+                    let _ = reader.get_mut().write_all(out.as_bytes());
+                }
+            });
+
+            Self {
+                url,
+                requests,
+                handle: Some(handle),
+            }
+        }
+
+        /// Espera a que se atiendan todas las respuestas y retorna los requests
+        pub fn finish(mut self) -> Vec<RecordedRequest> {
+            if let Some(handle) = self.handle.take() {
+                handle.join().unwrap();
+            }
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn read_request(reader: &mut impl BufRead) -> Option<RecordedRequest> {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let path = line.split_whitespace().nth(1)?.to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            let trimmed = line.trim_end();
+            if trimmed.is_empty() {
+                break;
+            }
+            let (name, value) = trimmed.split_once(':')?;
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+
+        let length = headers
+            .iter()
+            .find(|(n, _)| n == "content-length")
+            .and_then(|(_, v)| v.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+        Some(RecordedRequest {
+            path,
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+}
@@ -0,0 +1,863 @@
+use crate::models::Chunk;
+use crate::services::blobs::get_document_blob;
+use crate::services::chunker::{dedupe_chunks, ChunkStrategy, ChunkingConfig};
+use crate::services::database::{
+    delete_chunks_for_document, flush_db, get_all_documents, get_document_required, insert_chunks,
+    mark_document_indexed, set_document_language, update_document_cas, DbError,
+};
+use crate::services::embeddings::{
+    embed_texts_cached, replace_document_embeddings, EmbeddingProvider,
+};
+use crate::services::error::IndexError;
+use crate::services::extract::{self, ExtractedDocument};
+use crate::services::language::majority_language;
+use crate::services::pdf::PdfInfo;
+use serde::Serialize;
+use sled;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Resultado de `index_document`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexReport {
+    pub chunk_count: usize,
+    pub embedding_count: usize,
+    /// Chunks cuyo vector salió del cache de embeddings
+    pub cache_hits: usize,
+    /// Chunks que hubo que pedir al proveedor
+    pub cache_misses: usize,
+    /// Chunks repetidos dentro del documento que no se guardaron (ver
+    /// `ChunkingConfig::dedupe`)
+    pub duplicates_skipped: usize,
+    /// Duración total en milisegundos
+    pub elapsed_ms: u64,
+}
+
+/// Señal para cancelar una indexación desde otro hilo (p. ej. el botón
+/// "Cancelar" de la UI): basta con guardar `true`
+pub type CancellationToken = Arc<AtomicBool>;
+
+/// Etapa de la indexación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexStage {
+    /// Lectura del texto del archivo; `total` es la cantidad de páginas
+    Extracting,
+    /// Cálculo y guardado de embeddings; `total` es la cantidad de chunks
+    Embedding,
+    /// Escritura a disco y marca del documento como indexado
+    Finalizing,
+}
+
+/// Avance reportado por `index_document`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct IndexProgress {
+    pub stage: IndexStage,
+    pub current: usize,
+    pub total: usize,
+}
+
+/// Indexa un documento guardado: extrae su texto, lo divide, calcula los
+/// embeddings y guarda todo
+///
+/// Los chunks anteriores del documento se reemplazan. Los embeddings se
+/// calculan por lotes de `config.batch_size` y recién con todos listos se
+/// cambian los chunks y embeddings viejos por los nuevos, de una vez; el
+/// documento se marca como indexado recién cuando todo quedó en disco (se
+/// hace flush sea cual sea la `FlushPolicy`). Si algo falla a mitad de
+/// camino no se escribe nada y el documento conserva sus datos anteriores.
+/// Con `config.dedupe` los chunks de texto repetido se guardan una sola vez
+/// y se cuentan en `IndexReport::duplicates_skipped`.
+///
+/// `progress` se llama al terminar la extracción, después de cada lote y al
+/// finalizar. `cancel` se revisa entre lotes: si está en `true` se deja todo
+/// como estaba, igual que ante un error, y se retorna
+/// `IndexError::Cancelled`.
+///
+/// Si el modelo de `provider` no es el de la biblioteca falla con
+/// `EmbedError::ModelMismatch`; para cambiar de modelo está `reindex_all`.
+pub fn index_document(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    doc_id: &str,
+    config: &ChunkingConfig,
+    progress: impl Fn(IndexProgress),
+    cancel: &CancellationToken,
+) -> Result<IndexReport, IndexError> {
+    index_document_with(db, provider, doc_id, config, progress, cancel, false)
+}
+
+fn index_document_with(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    doc_id: &str,
+    config: &ChunkingConfig,
+    progress: impl Fn(IndexProgress),
+    cancel: &CancellationToken,
+    allow_model_change: bool,
+) -> Result<IndexReport, IndexError> {
+    let started = Instant::now();
+    let doc = get_document_required(db, doc_id)?;
+    let extracted = read_extracted(&doc.file_path)?;
+    progress(IndexProgress {
+        stage: IndexStage::Extracting,
+        current: extracted.pages.len(),
+        total: extracted.pages.len(),
+    });
+    let chunks = extracted.build_chunks(
+        doc_id,
+        &ChunkingConfig {
+            dedupe: false,
+            ..config.clone()
+        },
+    );
+    let (chunks, duplicates_skipped) = if config.dedupe {
+        dedupe_chunks(chunks)
+    } else {
+        (chunks, 0)
+    };
+
+    // Todo se calcula antes de tocar el documento: si falla o se cancela,
+    // quedan sus chunks y embeddings anteriores
+    let (vectors, stats) =
+        embed_chunks(db, provider, &chunks, config.batch_size, &progress, cancel)?;
+    let vectors: Vec<Option<Vec<f32>>> = vectors.into_iter().map(Some).collect();
+    replace_document_embeddings(
+        db,
+        doc_id,
+        &chunks,
+        &vectors,
+        &provider.model_name(),
+        provider.dimension(),
+        allow_model_change,
+    )?;
+    store_document_info(db, doc_id, &extracted)?;
+    set_document_language(db, doc_id, majority_language(&chunks).as_deref())?;
+    // Con `FlushPolicy::Never` nada garantiza que los chunks estén en disco:
+    // el documento no puede quedar marcado como indexado sin ellos
+    flush_db(db)?;
+    mark_document_indexed(db, doc_id)?;
+    progress(IndexProgress {
+        stage: IndexStage::Finalizing,
+        current: 1,
+        total: 1,
+    });
+
+    Ok(IndexReport {
+        chunk_count: chunks.len(),
+        embedding_count: vectors.len(),
+        cache_hits: stats.cache_hits,
+        cache_misses: stats.cache_misses,
+        duplicates_skipped,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Resultado de indexar un documento dentro de `reindex_all`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReindexEntry {
+    pub document_id: String,
+    /// El reporte si se indexó, o el mensaje de error si falló
+    pub result: Result<IndexReport, String>,
+}
+
+/// Reindexa varios documentos seguidos, p. ej. después de cambiar el modelo
+/// de embeddings
+///
+/// Con `force` se reindexan todos los documentos; si no, solo los que no
+/// están indexados. Cada uno pasa por `index_document`, que reemplaza sus
+/// chunks y embeddings anteriores; con `force` se acepta además que
+/// `provider` use otro modelo que la biblioteca, que pasa a ser el suyo. Un
+/// error en un documento queda en su entrada y se sigue con el próximo.
+/// `cancel` se revisa entre documentos (y dentro de cada uno): al cancelar se
+/// retorna lo hecho hasta ese momento.
+pub fn reindex_all(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    config: &ChunkingConfig,
+    force: bool,
+    cancel: &CancellationToken,
+) -> Result<Vec<ReindexEntry>, DbError> {
+    let pending = get_all_documents(db)?
+        .into_iter()
+        .filter(|doc| force || !doc.is_indexed);
+
+    let mut entries = Vec::new();
+    for doc in pending {
+        if cancel.load(Ordering::SeqCst) {
+            break;
+        }
+        let result = index_document_with(db, provider, &doc.id, config, |_| {}, cancel, force);
+        entries.push(ReindexEntry {
+            document_id: doc.id,
+            result: result.map_err(String::from),
+        });
+    }
+    Ok(entries)
+}
+
+/// Vuelve a dividir un documento a partir de la copia guardada de su archivo
+///
+/// Sirve para cambiar la estrategia de chunking sin reimportar, aunque el
+/// archivo original ya no exista. Reemplaza los chunks (y embeddings) del
+/// documento por los nuevos y lo deja sin indexar, a la espera de
+/// `index_document`. Falla con `IndexError::MissingBlob` si no hay copia
+/// guardada (ver `set_blob_storage`). Retorna la cantidad de chunks nuevos.
+pub fn rechunk_document(
+    db: &Arc<sled::Db>,
+    doc_id: &str,
+    strategy: &ChunkStrategy,
+) -> Result<usize, IndexError> {
+    let doc = get_document_required(db, doc_id)?;
+    let blob =
+        get_document_blob(db, doc_id)?.ok_or_else(|| IndexError::MissingBlob(doc_id.into()))?;
+    let config = ChunkingConfig {
+        strategy: strategy.clone(),
+        ..ChunkingConfig::default()
+    };
+    let chunks = extract::extract(&doc.file_path, &blob)?.build_chunks(doc_id, &config);
+
+    reset_document(db, doc_id)?;
+    insert_chunks(db, &chunks)?;
+    set_document_language(db, doc_id, majority_language(&chunks).as_deref())?;
+    Ok(chunks.len())
+}
+
+/// Texto del archivo, con el extractor de su formato (ver `extract::extract`)
+fn read_extracted(file_path: &str) -> Result<ExtractedDocument, IndexError> {
+    let bytes = std::fs::read(file_path)
+        .map_err(|e| IndexError::Extract(format!("{}: {}", file_path, e)))?;
+    Ok(extract::extract(file_path, &bytes)?)
+}
+
+/// Guarda en el documento el título, autor y tema que trajo la extracción
+/// (en un PDF, los de su `/Info`)
+fn store_document_info(
+    db: &Arc<sled::Db>,
+    doc_id: &str,
+    extracted: &ExtractedDocument,
+) -> Result<(), DbError> {
+    let info = extracted.info();
+    if info == PdfInfo::default() {
+        return Ok(());
+    }
+    update_document_cas(db, doc_id, |mut doc| {
+        info.apply_to(&mut doc);
+        doc
+    })?;
+    Ok(())
+}
+
+/// Borra los chunks (y embeddings) del documento y lo marca sin indexar
+fn reset_document(db: &Arc<sled::Db>, doc_id: &str) -> Result<(), DbError> {
+    update_document_cas(db, doc_id, |mut doc| {
+        doc.mark_as_unindexed();
+        doc
+    })?;
+    delete_chunks_for_document(db, doc_id)?;
+    update_document_cas(db, doc_id, |mut doc| {
+        doc.chunk_count = 0;
+        doc
+    })?;
+    Ok(())
+}
+
+/// Conteos del cache de embeddings en `embed_chunks`
+#[derive(Default)]
+struct CacheStats {
+    cache_hits: usize,
+    cache_misses: usize,
+}
+
+/// Calcula por lotes los embeddings de los chunks, sin guardar nada en el
+/// documento
+///
+/// Los vectores se buscan primero en el cache de embeddings; solo los que
+/// faltan se piden al proveedor, y se agregan al cache.
+fn embed_chunks(
+    db: &Arc<sled::Db>,
+    provider: &dyn EmbeddingProvider,
+    chunks: &[Chunk],
+    batch_size: usize,
+    progress: &impl Fn(IndexProgress),
+    cancel: &CancellationToken,
+) -> Result<(Vec<Vec<f32>>, CacheStats), IndexError> {
+    let mut vectors = Vec::with_capacity(chunks.len());
+    let mut stats = CacheStats::default();
+    for batch in chunks.chunks(batch_size.max(1)) {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(IndexError::Cancelled);
+        }
+        let texts: Vec<String> = batch.iter().map(|c| c.text.clone()).collect();
+        let (computed, hits) = embed_texts_cached(db, provider, &texts)?;
+        stats.cache_hits += hits;
+        stats.cache_misses += batch.len() - hits;
+        vectors.extend(computed);
+        progress(IndexProgress {
+            stage: IndexStage::Embedding,
+            current: vectors.len(),
+            total: chunks.len(),
+        });
+    }
+    if cancel.load(Ordering::SeqCst) {
+        return Err(IndexError::Cancelled);
+    }
+    Ok((vectors, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Document;
+    use crate::services::chunker::ChunkStrategy;
+    use crate::services::database::{
+        count_chunks, get_chunks_for_document, get_document, insert_document,
+    };
+    use crate::services::embeddings::{
+        clear_embedding_cache, get_embedding, get_library_embedding_info, HashingEmbedder,
+    };
+    use crate::services::error::EmbedError;
+    use crate::services::search::search_similar;
+    use crate::services::test_support::{temp_db, TestProvider};
+    use std::path::PathBuf;
+
+    const TEXT: &str = "La fotosíntesis convierte la luz en energía química.\n\n\
+        El motor de combustión quema gasolina.\n\n\
+        Las redes neuronales aprenden de los datos.";
+
+    fn setup(name: &str) -> (Arc<sled::Db>, PathBuf, PathBuf) {
+        let (db, path) = temp_db(name);
+        let file = add_text_document(&db, name, "doc-1", TEXT);
+        (db, path, file)
+    }
+
+    /// Escribe `text` en un archivo temporal y registra el documento
+    fn add_text_document(db: &Arc<sled::Db>, name: &str, id: &str, text: &str) -> PathBuf {
+        let file = std::env::temp_dir().join(format!("{}_{}_{}.txt", name, id, std::process::id()));
+        std::fs::write(&file, text).unwrap();
+        let doc = Document::new(
+            id.into(),
+            format!("{}.txt", id),
+            file.to_string_lossy().into_owned(),
+            1,
+        );
+        insert_document(db, &doc).unwrap();
+        file
+    }
+
+    fn by_paragraph() -> ChunkingConfig {
+        ChunkingConfig {
+            strategy: ChunkStrategy::ByParagraph,
+            batch_size: 2,
+            ..ChunkingConfig::default()
+        }
+    }
+
+    /// `index_document` sin progreso ni cancelación
+    fn index(
+        db: &Arc<sled::Db>,
+        provider: &dyn EmbeddingProvider,
+        doc_id: &str,
+        config: &ChunkingConfig,
+    ) -> Result<IndexReport, IndexError> {
+        index_document(
+            db,
+            provider,
+            doc_id,
+            config,
+            |_| {},
+            &CancellationToken::default(),
+        )
+    }
+
+    #[test]
+    fn test_index_document_pipeline() {
+        let (db, path, file) = setup("test_indexing_pipeline");
+        let provider = HashingEmbedder::new(128);
+
+        let report = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!(report.chunk_count, 3);
+        assert_eq!(report.embedding_count, 3);
+
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert!(doc.is_indexed);
+        assert_eq!(doc.chunk_count, 3);
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        assert_eq!(chunks[2].id, "doc-1-chunk-2");
+        assert!(get_embedding(&db, "doc-1-chunk-2").unwrap().is_some());
+
+        let query = provider
+            .embed(&["¿cómo aprenden las redes neuronales?".to_string()])
+            .unwrap()
+            .remove(0);
+        let hits = search_similar(&db, &query, 1).unwrap();
+        assert_eq!(hits[0].0.id, "doc-1-chunk-2");
+
+        // Reindexar con otra estrategia reemplaza los chunks anteriores
+        let config = ChunkingConfig {
+            strategy: ChunkStrategy::FixedChars {
+                size: 1_000,
+                overlap: 0,
+            },
+            ..by_paragraph()
+        };
+        let report = index(&db, &provider, "doc-1", &config).unwrap();
+        assert_eq!(report.chunk_count, 1);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 1);
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().chunk_count, 1);
+        assert!(get_embedding(&db, "doc-1-chunk-2").unwrap().is_none());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_document_failure_writes_nothing() {
+        let (db, path, file) = setup("test_indexing_failure");
+        let provider = TestProvider::new(16).with_failure_after(1);
+
+        let err = index(&db, &provider, "doc-1", &by_paragraph()).unwrap_err();
+        assert!(matches!(err, IndexError::Embed(EmbedError::Backend(_))));
+
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert!(!doc.is_indexed);
+        assert_eq!(doc.chunk_count, 0);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+        assert!(get_embedding(&db, "doc-1-chunk-0").unwrap().is_none());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_document_missing_file() {
+        let (db, path, file) = setup("test_indexing_missing");
+        std::fs::remove_file(&file).unwrap();
+
+        let err = index(&db, &HashingEmbedder::new(16), "doc-1", &by_paragraph());
+        assert!(matches!(err, Err(IndexError::Extract(_))));
+        assert!(matches!(
+            index(&db, &HashingEmbedder::new(16), "nope", &by_paragraph()),
+            Err(IndexError::Db(DbError::NotFound(_)))
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_index_document_reports_progress() {
+        let (db, path, file) = setup("test_indexing_progress");
+        let events = std::sync::Mutex::new(Vec::new());
+
+        index_document(
+            &db,
+            &HashingEmbedder::new(16),
+            "doc-1",
+            &by_paragraph(),
+            |p| events.lock().unwrap().push(p),
+            &CancellationToken::default(),
+        )
+        .unwrap();
+
+        let events = events.into_inner().unwrap();
+        let summary: Vec<(IndexStage, usize, usize)> = events
+            .iter()
+            .map(|p| (p.stage, p.current, p.total))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (IndexStage::Extracting, 1, 1),
+                (IndexStage::Embedding, 2, 3),
+                (IndexStage::Embedding, 3, 3),
+                (IndexStage::Finalizing, 1, 1),
+            ]
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_document_cancelled_after_first_progress() {
+        let (db, path, file) = setup("test_indexing_cancel_first");
+        let cancel = CancellationToken::default();
+
+        let err = index_document(
+            &db,
+            &HashingEmbedder::new(16),
+            "doc-1",
+            &by_paragraph(),
+            |_| cancel.store(true, Ordering::SeqCst),
+            &cancel,
+        )
+        .unwrap_err();
+        assert!(matches!(err, IndexError::Cancelled));
+
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert!(!doc.is_indexed);
+        assert_eq!(doc.chunk_count, 0);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_document_cancelled_mid_embedding_writes_nothing() {
+        let (db, path, file) = setup("test_indexing_cancel_mid");
+        let cancel = CancellationToken::default();
+        let config = ChunkingConfig {
+            batch_size: 1,
+            ..by_paragraph()
+        };
+
+        // El primer lote ya estaba calculado cuando llega la cancelación
+        let err = index_document(
+            &db,
+            &HashingEmbedder::new(16),
+            "doc-1",
+            &config,
+            |p| {
+                if p.stage == IndexStage::Embedding {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            },
+            &cancel,
+        )
+        .unwrap_err();
+        assert!(matches!(err, IndexError::Cancelled));
+
+        assert!(!get_document(&db, "doc-1").unwrap().unwrap().is_indexed);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 0);
+        assert!(get_embedding(&db, "doc-1-chunk-0").unwrap().is_none());
+
+        // Un nuevo intento empieza de cero
+        let report = index(&db, &HashingEmbedder::new(16), "doc-1", &config).unwrap();
+        assert_eq!(report.chunk_count, 3);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_failed_reindex_keeps_previous_data() {
+        let (db, path, file) = setup("test_indexing_keep_previous");
+        index(&db, &HashingEmbedder::new(16), "doc-1", &by_paragraph()).unwrap();
+        let chunks_before = get_chunks_for_document(&db, "doc-1").unwrap();
+        let vector_before = get_embedding(&db, "doc-1-chunk-1").unwrap();
+
+        // Un párrafo nuevo (fuera del cache) y un proveedor que falla
+        std::fs::write(&file, TEXT.replace("gasolina", "diésel")).unwrap();
+        let failing = TestProvider::new(16).with_failure_after(0);
+        let err = index(&db, &failing, "doc-1", &by_paragraph()).unwrap_err();
+        assert!(matches!(err, IndexError::Embed(EmbedError::Backend(_))));
+
+        // Cancelado después del primer lote
+        let cancel = CancellationToken::default();
+        let err = index_document(
+            &db,
+            &HashingEmbedder::new(16),
+            "doc-1",
+            &by_paragraph(),
+            |p| {
+                if p.stage == IndexStage::Embedding {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            },
+            &cancel,
+        )
+        .unwrap_err();
+        assert!(matches!(err, IndexError::Cancelled));
+
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert!(doc.is_indexed);
+        assert_eq!(doc.chunk_count, 3);
+        assert_eq!(
+            get_chunks_for_document(&db, "doc-1").unwrap(),
+            chunks_before
+        );
+        assert_eq!(get_embedding(&db, "doc-1-chunk-1").unwrap(), vector_before);
+        assert!(chunks_before[1].text.contains("gasolina"));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_reindex_all_continues_after_failures() {
+        let (db, path, first) = setup("test_reindex_all");
+        let second = add_text_document(&db, "test_reindex_all", "doc-2", "texto ilegible");
+        let third = add_text_document(&db, "test_reindex_all", "doc-3", "Otro documento.");
+        let provider = TestProvider::new(16).with_failure_on("ilegible");
+        let cancel = CancellationToken::default();
+
+        let mut entries = reindex_all(&db, &provider, &by_paragraph(), false, &cancel).unwrap();
+        entries.sort_by(|a, b| a.document_id.cmp(&b.document_id));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].result.as_ref().unwrap().chunk_count, 3);
+        assert!(entries[1]
+            .result
+            .as_ref()
+            .unwrap_err()
+            .contains("cannot embed"));
+        assert_eq!(entries[2].result.as_ref().unwrap().chunk_count, 1);
+
+        let indexed = |id: &str| get_document(&db, id).unwrap().unwrap().is_indexed;
+        assert!(indexed("doc-1") && !indexed("doc-2") && indexed("doc-3"));
+
+        // Sin `force` solo se reintenta el que falló; con `force`, todos
+        let entries = reindex_all(&db, &provider, &by_paragraph(), false, &cancel).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].document_id, "doc-2");
+        let entries = reindex_all(&db, &provider, &by_paragraph(), true, &cancel).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 3);
+
+        // Cancelado de antemano no procesa nada
+        cancel.store(true, Ordering::SeqCst);
+        assert!(reindex_all(&db, &provider, &by_paragraph(), true, &cancel)
+            .unwrap()
+            .is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        for file in [first, second, third] {
+            let _ = std::fs::remove_file(&file);
+        }
+    }
+
+    #[test]
+    fn test_model_change_requires_forced_reindex() {
+        let (db, path, first) = setup("test_reindex_model_change");
+        let second = add_text_document(&db, "test_reindex_model_change", "doc-2", "Otro.");
+        let cancel = CancellationToken::default();
+        reindex_all(
+            &db,
+            &HashingEmbedder::new(16),
+            &by_paragraph(),
+            false,
+            &cancel,
+        )
+        .unwrap();
+
+        // Un modelo nuevo no puede mezclarse con los vectores existentes
+        let new_model = TestProvider::new(16).with_model("otro-modelo");
+        let err = index(&db, &new_model, "doc-1", &by_paragraph()).unwrap_err();
+        assert!(matches!(
+            err,
+            IndexError::Embed(EmbedError::ModelMismatch { .. })
+        ));
+        // y el documento se queda con lo que tenía
+        assert!(get_document(&db, "doc-1").unwrap().unwrap().is_indexed);
+        assert_eq!(
+            get_embedding(&db, "doc-1-chunk-0").unwrap().unwrap().len(),
+            16
+        );
+
+        // Reindexar todo con `force` cambia el modelo (y la dimensión)
+        let entries = reindex_all(
+            &db,
+            &HashingEmbedder::new(32),
+            &by_paragraph(),
+            true,
+            &cancel,
+        )
+        .unwrap();
+        assert!(entries.iter().all(|e| e.result.is_ok()));
+        let info = get_library_embedding_info(&db).unwrap().unwrap();
+        assert_eq!((info.model.as_str(), info.dimension), ("hash-32", 32));
+        assert_eq!(
+            get_embedding(&db, "doc-2-chunk-0").unwrap().unwrap().len(),
+            32
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&first);
+        let _ = std::fs::remove_file(&second);
+    }
+
+    #[test]
+    fn test_embedding_cache_skips_provider_on_reindex() {
+        let (db, path, file) = setup("test_indexing_cache");
+        let provider = TestProvider::new(16);
+
+        let first = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!((first.cache_hits, first.cache_misses), (0, 3));
+        let calls = provider.calls();
+        assert_eq!(calls, 2);
+
+        // Mismo texto: todo sale del cache y no se llama al proveedor
+        let second = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!((second.cache_hits, second.cache_misses), (3, 0));
+        assert_eq!(second.embedding_count, 3);
+        assert_eq!(provider.calls(), calls);
+        assert!(get_embedding(&db, "doc-1-chunk-1").unwrap().is_some());
+
+        // Tras editar un párrafo solo se pide ese
+        std::fs::write(&file, TEXT.replace("gasolina", "diésel")).unwrap();
+        let edited = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!((edited.cache_hits, edited.cache_misses), (2, 1));
+        assert_eq!(provider.texts(), 4);
+
+        assert_eq!(clear_embedding_cache(&db).unwrap(), 4);
+        let cleared = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!(cleared.cache_misses, 3);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_rechunk_document_from_blob() {
+        let (db, path) = temp_db("test_rechunk");
+        let doc = Document::new(
+            "doc-1".into(),
+            "notas.txt".into(),
+            "/no/existe.txt".into(),
+            1,
+        );
+        insert_document(&db, &doc).unwrap();
+
+        let err = rechunk_document(&db, "doc-1", &ChunkStrategy::ByParagraph).unwrap_err();
+        assert!(matches!(err, IndexError::MissingBlob(ref id) if id == "doc-1"));
+
+        crate::services::blobs::store_document_blob(&db, "doc-1", TEXT.as_bytes()).unwrap();
+        let fixed = |size| ChunkStrategy::FixedChars { size, overlap: 0 };
+        let coarse = rechunk_document(&db, "doc-1", &fixed(200)).unwrap();
+        mark_document_indexed(&db, "doc-1").unwrap();
+        let fine = rechunk_document(&db, "doc-1", &fixed(40)).unwrap();
+
+        assert!(fine > coarse, "{} vs {}", fine, coarse);
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), fine);
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(doc.chunk_count, fine);
+        assert!(!doc.is_indexed);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_index_document_skips_repeated_footers() {
+        let (db, path) = temp_db("test_index_dedupe");
+        let text: String = (1..=10)
+            .map(|page| {
+                format!(
+                    "Contenido propio de la página {}.\n\nCapítulo 3 —  Apuntes de física\n\n",
+                    page
+                )
+            })
+            .collect();
+        let file = add_text_document(&db, "test_index_dedupe", "doc-1", &text);
+        let provider = HashingEmbedder::new(16);
+
+        let config = ChunkingConfig {
+            dedupe: true,
+            ..by_paragraph()
+        };
+        let report = index(&db, &provider, "doc-1", &config).unwrap();
+        assert_eq!(report.chunk_count, 11);
+        assert_eq!(report.duplicates_skipped, 9);
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        let footers = chunks
+            .iter()
+            .filter(|c| c.text.starts_with("Capítulo 3"))
+            .count();
+        assert_eq!(footers, 1);
+        let indices: Vec<usize> = chunks.iter().map(|c| c.index).collect();
+        assert_eq!(indices, (0..11).collect::<Vec<_>>());
+
+        // Sin dedupe (por defecto) se guarda el texto tal cual
+        let report = index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        assert_eq!((report.chunk_count, report.duplicates_skipped), (20, 0));
+        assert_eq!(count_chunks(&db, Some("doc-1")).unwrap(), 20);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_detects_languages() {
+        let (db, path) = temp_db("test_index_language");
+        let text = "La biblioteca guarda los documentos en una base de datos local y permite \
+            buscar pasajes por significado.\n\n\
+            The library stores every document in a local database and lets you search \
+            passages by meaning.\n\n\
+            1234 5678 91011\n\n\
+            Los resultados se ordenan por similitud y se muestran con la página de origen.";
+        let file = add_text_document(&db, "test_index_language", "doc-1", text);
+        let provider = HashingEmbedder::new(64);
+
+        let config = ChunkingConfig {
+            detect_language: true,
+            ..by_paragraph()
+        };
+        index(&db, &provider, "doc-1", &config).unwrap();
+        let languages: Vec<Option<String>> = get_chunks_for_document(&db, "doc-1")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.language)
+            .collect();
+        assert_eq!(
+            languages,
+            [
+                Some("es".into()),
+                Some("en".into()),
+                None,
+                Some("es".into())
+            ]
+        );
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(doc.language.as_deref(), Some("es"));
+
+        // Sin detección (por defecto) los chunks quedan sin idioma
+        index(&db, &provider, "doc-1", &by_paragraph()).unwrap();
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+        assert!(chunks.iter().all(|c| c.language.is_none()));
+        assert_eq!(get_document(&db, "doc-1").unwrap().unwrap().language, None);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_index_stores_pdf_info() {
+        let (db, path) = temp_db("test_index_pdf_info");
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+        for (id, file) in [("doc-1", "with_metadata.pdf"), ("doc-2", "text_only.pdf")] {
+            let file_path = format!("{}/{}", fixtures, file);
+            let doc = Document::new(id.into(), file.into(), file_path, 1);
+            insert_document(&db, &doc).unwrap();
+            index(&db, &HashingEmbedder::new(16), id, &by_paragraph()).unwrap();
+        }
+
+        let doc = get_document(&db, "doc-1").unwrap().unwrap();
+        assert_eq!(doc.title.as_deref(), Some("Óptica — Introducción"));
+        assert_eq!(doc.author.as_deref(), Some("Ana Pérez"));
+        assert_eq!(doc.display_name(), "Óptica — Introducción");
+
+        // Sin metadata se muestra el nombre del archivo
+        let doc = get_document(&db, "doc-2").unwrap().unwrap();
+        assert_eq!((doc.title, doc.author, doc.subject), (None, None, None));
+        assert_eq!(
+            get_document(&db, "doc-2").unwrap().unwrap().display_name(),
+            "text_only.pdf"
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
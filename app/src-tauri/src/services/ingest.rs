@@ -0,0 +1,303 @@
+// Importación de archivos a la biblioteca: de una ruta a un `Document`
+// guardado, opcionalmente ya dividido en chunks (sin embeddings; eso queda
+// para `indexing::index_document`)
+
+use crate::models::Document;
+use crate::services::blobs::store_document_blob_if_enabled;
+use crate::services::chunker::ChunkingConfig;
+use crate::services::database::{
+    delete_document, find_document_by_hash, get_document, get_document_required,
+    insert_document_with_chunks, DbError,
+};
+use crate::services::error::IngestError;
+use crate::services::extract::extract;
+use crate::services::language::majority_language;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Opciones de `ingest_document`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IngestOptions {
+    /// Si está, el documento se divide en chunks con esta configuración al
+    /// importarlo
+    pub chunking: Option<ChunkingConfig>,
+}
+
+/// Importa el archivo en `path` como documento nuevo
+///
+/// El id es el hash SHA-256 del contenido (igual que `Document::id_from_file`);
+/// si ya hay un documento con ese contenido retorna
+/// `IngestError::Duplicate` sin tocar la BD. El texto sale del extractor
+/// del formato del archivo (ver `extract::extract`), que también puede aportar
+/// título, autor y tema. Con `options.chunking` también se guardan los chunks
+/// y el idioma del documento.
+///
+/// Un PDF escaneado sin capa de texto se rechaza con
+/// `IngestError::NoExtractableText`; si solo algunas páginas lo son, se
+/// importa igual y las páginas vacías quedan en `Document::empty_pages`.
+///
+/// Si algo falla no queda nada guardado, así que se puede reintentar.
+pub fn ingest_document(
+    db: &Arc<sled::Db>,
+    path: impl AsRef<Path>,
+    options: &IngestOptions,
+) -> Result<Document, IngestError> {
+    ingest_with(db, path.as_ref(), options, store_document_blob_if_enabled)
+}
+
+/// Guarda la copia del archivo original (ver `store_document_blob_if_enabled`)
+type StoreBlob = fn(&Arc<sled::Db>, &str, &[u8]) -> Result<bool, DbError>;
+
+/// `ingest_document` con la función que guarda la copia del archivo, para
+/// poder simular que falla en los tests
+fn ingest_with(
+    db: &Arc<sled::Db>,
+    path: &Path,
+    options: &IngestOptions,
+    store_blob: StoreBlob,
+) -> Result<Document, IngestError> {
+    if !path.is_file() {
+        return Err(IngestError::FileNotFound(path.display().to_string()));
+    }
+    let path = path.canonicalize()?;
+    let file_path = path.to_string_lossy().into_owned();
+    let bytes = std::fs::read(&path)?;
+
+    let id = format!("{:x}", Sha256::digest(&bytes));
+    if let Some(existing) = find_document_by_hash(db, &id)? {
+        return Err(IngestError::Duplicate {
+            existing_id: existing.id,
+        });
+    }
+    if get_document(db, &id)?.is_some() {
+        return Err(IngestError::Duplicate { existing_id: id });
+    }
+
+    let extracted = extract(&file_path, &bytes)?;
+    if extracted.is_scanned() {
+        return Err(IngestError::NoExtractableText {
+            scanned_pages: extracted.scanned_page_count,
+        });
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_path.clone());
+    let mut doc =
+        Document::new(id.clone(), name, file_path, extracted.pages.len()).with_sha256(id.clone());
+    doc.file_size = Some(bytes.len() as u64);
+    doc.empty_pages = extracted.empty_pages();
+    extracted.apply_to(&mut doc);
+    let chunks = match &options.chunking {
+        Some(config) => extracted.build_chunks(&id, config),
+        None => Vec::new(),
+    };
+    doc.language = majority_language(&chunks);
+
+    // El documento y sus chunks se guardan juntos; la copia del archivo va en
+    // otro árbol, así que si falla se deshace lo anterior para que el archivo
+    // se pueda volver a importar
+    insert_document_with_chunks(db, &doc, &chunks)?;
+    if let Err(e) = store_blob(db, &id, &bytes) {
+        delete_document(db, &id)?;
+        return Err(e.into());
+    }
+
+    Ok(get_document_required(db, &id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DocType;
+    use crate::services::database::get_chunks_for_document;
+    use crate::services::error::ExtractError;
+    use crate::services::test_support::temp_db;
+    use std::path::PathBuf;
+
+    const TEXT: &str = "La fotosíntesis convierte la luz en energía química.\n\n\
+        El motor de combustión quema gasolina.\n\n\
+        Las redes neuronales aprenden de los datos.";
+
+    fn write_file(name: &str, text: &str) -> PathBuf {
+        let file = std::env::temp_dir().join(format!("{}_{}.txt", name, std::process::id()));
+        std::fs::write(&file, text).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_ingest_text_file() {
+        let (db, path) = temp_db("test_ingest_text");
+        let file = write_file("test_ingest_text", TEXT);
+
+        let doc = ingest_document(&db, &file, &IngestOptions::default()).unwrap();
+        assert_eq!(doc.id, Document::id_from_file(&file).unwrap());
+        assert_eq!(doc.sha256.as_deref(), Some(doc.id.as_str()));
+        assert_eq!(doc.file_size, Some(TEXT.len() as u64));
+        assert_eq!(doc.page_count, 1);
+        assert_eq!(doc.doc_type, Some(DocType::Text));
+        assert!(doc.name.ends_with(".txt"));
+        // Sin chunking no se guardan chunks
+        assert!(get_chunks_for_document(&db, &doc.id).unwrap().is_empty());
+
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_with_chunking() {
+        let (db, path) = temp_db("test_ingest_chunking");
+        let file = write_file("test_ingest_chunking", TEXT);
+
+        let options = IngestOptions {
+            chunking: Some(ChunkingConfig {
+                detect_language: true,
+                ..ChunkingConfig::default()
+            }),
+        };
+        let doc = ingest_document(&db, &file, &options).unwrap();
+        let chunks = get_chunks_for_document(&db, &doc.id).unwrap();
+        assert!(!chunks.is_empty());
+        assert_eq!(doc.language.as_deref(), Some("es"));
+
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_pdf_reads_info() {
+        let (db, path) = temp_db("test_ingest_pdf");
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/with_metadata.pdf");
+
+        let doc = ingest_document(&db, fixture, &IngestOptions::default()).unwrap();
+        assert_eq!(doc.name, "with_metadata.pdf");
+        assert_eq!(doc.doc_type, Some(DocType::Pdf));
+        assert_eq!(doc.author.as_deref(), Some("Ana Pérez"));
+        assert_eq!(
+            doc.file_size,
+            Some(std::fs::metadata(fixture).unwrap().len())
+        );
+
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_duplicate() {
+        let (db, path) = temp_db("test_ingest_duplicate");
+        let file = write_file("test_ingest_duplicate", TEXT);
+        let copy = write_file("test_ingest_duplicate_copy", TEXT);
+
+        let doc = ingest_document(&db, &file, &IngestOptions::default()).unwrap();
+        // Mismo contenido con otro nombre: duplicado
+        match ingest_document(&db, &copy, &IngestOptions::default()) {
+            Err(IngestError::Duplicate { existing_id }) => assert_eq!(existing_id, doc.id),
+            other => panic!("expected duplicate, got {:?}", other),
+        }
+
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_file(&copy);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_empty_file() {
+        let (db, path) = temp_db("test_ingest_empty");
+        let file = write_file("test_ingest_empty", "");
+
+        let err = ingest_document(&db, &file, &IngestOptions::default()).unwrap_err();
+        assert!(matches!(err, IngestError::Extract(ExtractError::Empty(_))));
+        assert!(err.to_string().contains("has no text"));
+        // No queda nada guardado
+        assert!(get_document(&db, &Document::id_from_file(&file).unwrap())
+            .unwrap()
+            .is_none());
+
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_missing_file() {
+        let (db, path) = temp_db("test_ingest_missing");
+        let missing = std::env::temp_dir().join("no_existe_libia.pdf");
+
+        let err = ingest_document(&db, &missing, &IngestOptions::default()).unwrap_err();
+        assert!(matches!(err, IngestError::FileNotFound(_)));
+        // Un directorio tampoco es un archivo
+        let err = ingest_document(&db, &path, &IngestOptions::default()).unwrap_err();
+        assert!(matches!(err, IngestError::FileNotFound(_)));
+
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_scanned_pdf() {
+        let (db, path) = temp_db("test_ingest_scanned");
+        let fixture = |name: &str| format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+
+        let scanned = fixture("scanned.pdf");
+        let err = ingest_document(&db, &scanned, &IngestOptions::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            IngestError::NoExtractableText { scanned_pages: 2 }
+        ));
+        assert!(err.to_string().contains("OCR"));
+        assert!(
+            get_document(&db, &Document::id_from_file(&scanned).unwrap())
+                .unwrap()
+                .is_none()
+        );
+
+        let options = IngestOptions {
+            chunking: Some(ChunkingConfig::default()),
+        };
+        let text = ingest_document(&db, fixture("text_only.pdf"), &options).unwrap();
+        assert!(text.empty_pages.is_empty());
+        assert!(!get_chunks_for_document(&db, &text.id).unwrap().is_empty());
+
+        // Con algunas páginas escaneadas se importa y se registran las vacías
+        let mixed = ingest_document(&db, fixture("mixed_scan.pdf"), &options).unwrap();
+        assert_eq!(mixed.page_count, 2);
+        assert_eq!(mixed.empty_pages, [2]);
+        let chunks = get_chunks_for_document(&db, &mixed.id).unwrap();
+        assert!(chunks.iter().all(|c| c.page_number == 1));
+
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_ingest_failure_leaves_nothing_behind() {
+        let (db, path) = temp_db("test_ingest_rollback");
+        let file = write_file("test_ingest_rollback", TEXT);
+        let options = IngestOptions {
+            chunking: Some(ChunkingConfig::default()),
+        };
+
+        // Falla después de guardar el documento y sus chunks
+        fn failing_blob(_: &Arc<sled::Db>, _: &str, _: &[u8]) -> Result<bool, DbError> {
+            Err(DbError::Io(std::io::Error::other("disco lleno")))
+        }
+        let err = ingest_with(&db, &file, &options, failing_blob).unwrap_err();
+        assert!(matches!(err, IngestError::Db(DbError::Io(_))));
+        let id = Document::id_from_file(&file).unwrap();
+        assert!(get_document(&db, &id).unwrap().is_none());
+        assert!(find_document_by_hash(&db, &id).unwrap().is_none());
+        assert!(get_chunks_for_document(&db, &id).unwrap().is_empty());
+
+        // Se puede volver a importar
+        let doc = ingest_document(&db, &file, &options).unwrap();
+        assert_eq!(doc.id, id);
+        assert_eq!(
+            doc.chunk_count,
+            get_chunks_for_document(&db, &id).unwrap().len()
+        );
+
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
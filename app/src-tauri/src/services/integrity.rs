@@ -0,0 +1,389 @@
+use crate::models::Document;
+use crate::services::database::{
+    decode_chunk, decode_document, document_chunk_keys, encode, ensure_writable,
+    open_chunk_ids_tree, open_chunks_tree, open_documents_tree, open_hash_index_tree, DbError,
+};
+use crate::services::keys::parse_chunk_key;
+use serde::Serialize;
+use sled;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Cantidad máxima de ids de ejemplo que se incluyen en cada resumen
+pub const INTEGRITY_SAMPLE_SIZE: usize = 20;
+
+/// Resumen de un tipo de problema: cuántos hay y algunos ids de ejemplo
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct IssueSummary {
+    pub count: usize,
+    pub sample_ids: Vec<String>,
+}
+
+impl IssueSummary {
+    fn record(&mut self, id: String) {
+        self.count += 1;
+        if self.sample_ids.len() < INTEGRITY_SAMPLE_SIZE {
+            self.sample_ids.push(id);
+        }
+    }
+}
+
+/// Documento cuyo `chunk_count` no coincide con los chunks guardados
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ChunkCountMismatch {
+    pub document_id: String,
+    pub stored: usize,
+    pub actual: usize,
+}
+
+/// Resultado de `check_integrity`
+///
+/// Los resúmenes sirven para que la UI muestre qué se va a borrar antes de
+/// llamar a `repair`. Las claves exactas afectadas se guardan aparte (no se
+/// serializan) para que `repair` actúe solo sobre lo reportado.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    /// Chunks cuyo `document_id` ya no existe
+    pub orphan_chunks: IssueSummary,
+    /// Entradas de índices (ids de chunk, hashes) que apuntan a registros inexistentes
+    pub dangling_index_entries: IssueSummary,
+    /// Documentos cuyo conteo de chunks no coincide con la realidad
+    pub chunk_count_mismatches: Vec<ChunkCountMismatch>,
+
+    #[serde(skip)]
+    orphan_chunk_keys: Vec<sled::IVec>,
+    #[serde(skip)]
+    dangling_chunk_ids: Vec<sled::IVec>,
+    #[serde(skip)]
+    dangling_hashes: Vec<sled::IVec>,
+}
+
+impl IntegrityReport {
+    /// Indica si no se encontró ningún problema
+    pub fn is_clean(&self) -> bool {
+        self.orphan_chunks.count == 0
+            && self.dangling_index_entries.count == 0
+            && self.chunk_count_mismatches.is_empty()
+    }
+}
+
+/// Revisa la BD en busca de chunks huérfanos, índices colgantes y conteos
+/// de chunks desactualizados, sin modificar nada
+pub fn check_integrity(db: &Arc<sled::Db>) -> Result<IntegrityReport, DbError> {
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+    let by_hash = open_hash_index_tree(db)?;
+
+    let mut report = IntegrityReport::default();
+
+    let mut documents: HashMap<String, usize> = HashMap::new();
+    for item in docs.iter() {
+        let (_k, v) = item?;
+        let doc: Document = decode_document(&v)?;
+        documents.insert(doc.id, doc.chunk_count);
+    }
+
+    // Chunks cuyo documento no existe
+    let mut actual_counts: HashMap<String, usize> = HashMap::new();
+    let mut chunk_keys: HashSet<sled::IVec> = HashSet::new();
+    for item in chunks.iter() {
+        let (k, v) = item?;
+        let document_id = match parse_chunk_key(&k) {
+            Some(parsed) => parsed.document_id,
+            None => {
+                let chunk = decode_chunk(&v)?;
+                chunk.document_id
+            }
+        };
+
+        if documents.contains_key(&document_id) {
+            *actual_counts.entry(document_id).or_default() += 1;
+            chunk_keys.insert(k);
+        } else {
+            report
+                .orphan_chunks
+                .record(String::from_utf8_lossy(&k).into_owned());
+            report.orphan_chunk_keys.push(k);
+        }
+    }
+
+    // Entradas del índice de ids que apuntan a chunks inexistentes o huérfanos
+    for item in ids.iter() {
+        let (chunk_id, key) = item?;
+        if !chunk_keys.contains(&key) {
+            report
+                .dangling_index_entries
+                .record(String::from_utf8_lossy(&chunk_id).into_owned());
+            report.dangling_chunk_ids.push(chunk_id);
+        }
+    }
+
+    // Entradas del índice de hashes que apuntan a documentos inexistentes
+    for item in by_hash.iter() {
+        let (hash, doc_id) = item?;
+        if !documents.contains_key(String::from_utf8_lossy(&doc_id).as_ref()) {
+            report
+                .dangling_index_entries
+                .record(String::from_utf8_lossy(&hash).into_owned());
+            report.dangling_hashes.push(hash);
+        }
+    }
+
+    for (document_id, stored) in &documents {
+        let actual = actual_counts.get(document_id).copied().unwrap_or(0);
+        if *stored != actual {
+            report.chunk_count_mismatches.push(ChunkCountMismatch {
+                document_id: document_id.clone(),
+                stored: *stored,
+                actual,
+            });
+        }
+    }
+    report
+        .chunk_count_mismatches
+        .sort_by(|a, b| a.document_id.cmp(&b.document_id));
+
+    Ok(report)
+}
+
+/// Corrige los problemas de un reporte generado por `check_integrity`
+///
+/// Borra los chunks huérfanos y las entradas de índice colgantes, y ajusta
+/// `chunk_count` al valor real. Retorna la cantidad de registros corregidos.
+pub fn repair(db: &Arc<sled::Db>, report: &IntegrityReport) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let docs = open_documents_tree(db)?;
+    let chunks = open_chunks_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+    let by_hash = open_hash_index_tree(db)?;
+
+    let mut fixed = 0;
+
+    for key in &report.orphan_chunk_keys {
+        if chunks.remove(key)?.is_some() {
+            fixed += 1;
+        }
+        // El índice de ids también puede apuntar al chunk huérfano
+        if let Some(parsed) = parse_chunk_key(key) {
+            ids.remove(parsed.chunk_id.as_bytes())?;
+        }
+    }
+
+    for chunk_id in &report.dangling_chunk_ids {
+        if ids.remove(chunk_id)?.is_some() {
+            fixed += 1;
+        }
+    }
+
+    for hash in &report.dangling_hashes {
+        if by_hash.remove(hash)?.is_some() {
+            fixed += 1;
+        }
+    }
+
+    for mismatch in &report.chunk_count_mismatches {
+        let Some(bytes) = docs.get(mismatch.document_id.as_bytes())? else {
+            continue;
+        };
+        let mut doc: Document = decode_document(&bytes)?;
+        doc.chunk_count = mismatch.actual;
+        let v = encode(&doc)?;
+        docs.insert(doc.id.as_bytes(), v)?;
+        fixed += 1;
+    }
+
+    db.flush()?;
+    Ok(fixed)
+}
+
+/// Resultado de `verify_chunk_indices`
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ChunkIndexReport {
+    pub document_id: String,
+    /// Cantidad de chunks guardados del documento
+    pub chunk_count: usize,
+    /// Índices entre 0 y el mayor guardado que no tienen chunk, en orden
+    pub missing: Vec<usize>,
+    /// Índices usados por más de un chunk, en orden
+    pub duplicated: Vec<usize>,
+}
+
+impl ChunkIndexReport {
+    /// Indica si los índices van de 0 a `chunk_count - 1` sin huecos ni
+    /// repetidos
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty()
+    }
+}
+
+/// Revisa que los chunks de un documento tengan índices contiguos desde 0
+///
+/// Un hueco o un índice repetido rompe `reconstruct_document_text`; el
+/// reporte dice cuáles faltan y cuáles sobran para poder repararlos. Solo
+/// lee las claves de los chunks, sin deserializarlos. Un documento sin
+/// chunks da un reporte limpio.
+pub fn verify_chunk_indices(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+) -> Result<ChunkIndexReport, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut chunk_count = 0;
+    for key in document_chunk_keys(&chunks, document_id)? {
+        let index = match parse_chunk_key(&key) {
+            Some(parsed) => parsed.index,
+            None => match chunks.get(&key)? {
+                Some(bytes) => decode_chunk(&bytes)?.index,
+                None => continue,
+            },
+        };
+        *counts.entry(index).or_default() += 1;
+        chunk_count += 1;
+    }
+
+    let last = counts.keys().next_back().copied();
+    Ok(ChunkIndexReport {
+        document_id: document_id.to_string(),
+        chunk_count,
+        missing: last
+            .map(|last| (0..last).filter(|i| !counts.contains_key(i)).collect())
+            .unwrap_or_default(),
+        duplicated: counts
+            .iter()
+            .filter(|(_, &n)| n > 1)
+            .map(|(&index, _)| index)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+    use crate::services::database::{insert_chunk, insert_document};
+    use crate::services::keys::chunk_key;
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db};
+
+    #[test]
+    fn test_check_integrity_clean_library() {
+        let (db, path) = temp_db("test_integrity_clean");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        let report = check_integrity(&db).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_detect_and_repair_orphans() {
+        let (db, path) = temp_db("test_integrity_repair");
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunk = sample_chunk(0, "texto");
+        insert_chunk(&db, &chunk).unwrap();
+
+        // Chunk huérfano escrito directamente (simula un borrado manual del documento)
+        let orphan = Chunk::new("c-x".into(), "borrado".into(), "huérfano".into(), 0, 1);
+        let orphan_key = chunk_key("borrado", 0, "c-x");
+        open_chunks_tree(&db)
+            .unwrap()
+            .insert(orphan_key.as_bytes(), bincode::serialize(&orphan).unwrap())
+            .unwrap();
+
+        // Entrada de índice que apunta a un chunk que no existe
+        open_chunk_ids_tree(&db)
+            .unwrap()
+            .insert(
+                b"c-fantasma",
+                chunk_key("doc-1", 9, "c-fantasma").as_bytes(),
+            )
+            .unwrap();
+
+        let report = check_integrity(&db).unwrap();
+        assert_eq!(report.orphan_chunks.count, 1);
+        assert_eq!(report.orphan_chunks.sample_ids, vec![orphan_key.clone()]);
+        assert_eq!(report.dangling_index_entries.count, 1);
+        assert_eq!(report.dangling_index_entries.sample_ids, vec!["c-fantasma"]);
+        assert!(report.chunk_count_mismatches.is_empty());
+
+        let fixed = repair(&db, &report).unwrap();
+        assert_eq!(fixed, 2);
+
+        assert!(check_integrity(&db).unwrap().is_clean());
+        // El chunk válido sigue intacto
+        assert_eq!(
+            crate::services::database::get_chunk(&db, "c-0")
+                .unwrap()
+                .unwrap()
+                .text,
+            "texto"
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_detect_and_repair_chunk_count_mismatch() {
+        let (db, path) = temp_db("test_integrity_count");
+
+        let mut doc = sample_document();
+        doc.chunk_count = 5;
+        insert_document(&db, &doc).unwrap();
+
+        let report = check_integrity(&db).unwrap();
+        assert_eq!(
+            report.chunk_count_mismatches,
+            vec![ChunkCountMismatch {
+                document_id: "doc-1".to_string(),
+                stored: 5,
+                actual: 0,
+            }]
+        );
+
+        repair(&db, &report).unwrap();
+        assert!(check_integrity(&db).unwrap().is_clean());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_verify_chunk_indices() {
+        let (db, path) = temp_db("test_verify_chunk_indices");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+
+        for (id, index) in [("c-0", 0), ("c-1", 1)] {
+            let chunk = Chunk::new(id.into(), "doc-1".into(), "texto".into(), index, 1);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let report = verify_chunk_indices(&db, "doc-1").unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+
+        for (id, index) in [("c-1b", 1), ("c-3", 3)] {
+            let chunk = Chunk::new(id.into(), "doc-1".into(), "texto".into(), index, 1);
+            insert_chunk(&db, &chunk).unwrap();
+        }
+        let report = verify_chunk_indices(&db, "doc-1").unwrap();
+        assert_eq!(report.chunk_count, 4);
+        assert_eq!(report.missing, [2]);
+        assert_eq!(report.duplicated, [1]);
+        assert!(!report.is_clean());
+
+        let empty = verify_chunk_indices(&db, "sin-chunks").unwrap();
+        assert!(empty.is_clean());
+        assert_eq!(empty.chunk_count, 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,202 @@
+// Nombres de árboles y formatos de clave de la BD
+//
+// Todas las claves se arman y se leen con las funciones de este módulo para
+// que cada formato esté definido en un solo lugar. Los números van con ceros
+// a la izquierda para que el orden lexicográfico de sled coincida con el
+// orden numérico.
+
+/// Árbol id -> `Document`
+pub const DOCUMENTS_TREE: &str = "documents";
+/// Índice sha256 -> id de documento
+pub const DOCUMENTS_BY_HASH_TREE: &str = "documents_by_hash";
+/// Árbol `chunk_key` -> `Chunk`
+pub const CHUNKS_TREE: &str = "chunks";
+/// Índice chunk_id -> `chunk_key`
+pub const CHUNK_IDS_TREE: &str = "chunk_ids";
+/// Árbol `segment_key` -> bytes del archivo original
+pub const BLOBS_TREE: &str = "blobs";
+/// Árbol document_id -> adjuntos
+pub const ATTACHMENTS_TREE: &str = "attachments";
+/// Árbol chunk_id -> embedding
+pub const EMBEDDINGS_TREE: &str = "embeddings";
+/// Árbol document_id -> documento enviado a la papelera (con sus chunks)
+pub const TRASH_TREE: &str = "trash";
+/// Árbol sha256(modelo + texto) -> vector, para no recalcular embeddings
+pub const EMBEDDING_CACHE_TREE: &str = "embedding_cache";
+/// Árbol de metadatos de la biblioteca (clave fija -> valor)
+pub const META_TREE: &str = "meta";
+
+/// Clave en "meta" con la dimensión de los embeddings guardados (u64 LE)
+pub const EMBEDDING_DIMENSION_KEY: &str = "embedding_dimension";
+
+/// Clave en "meta" con el modelo de los embeddings guardados (UTF-8)
+pub const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+
+/// Ancho del índice en la clave del chunk
+pub const CHUNK_INDEX_WIDTH: usize = 10;
+/// Ancho del número de página en las claves del índice por página
+pub const PAGE_WIDTH: usize = 8;
+/// Ancho del número de segmento en las claves de blobs
+pub const SEGMENT_WIDTH: usize = 8;
+
+/// Componentes de una clave de chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkKey {
+    pub document_id: String,
+    pub index: usize,
+    pub chunk_id: String,
+}
+
+/// Componentes de una clave del índice por página
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageIndexKey {
+    pub document_id: String,
+    pub page: usize,
+    pub chunk_id: String,
+}
+
+/// Prefijo común a todas las claves de un documento: `{document_id}:`
+pub fn document_prefix(document_id: &str) -> String {
+    format!("{}:", document_id)
+}
+
+/// Clave de un chunk: `{document_id}:{index}:{chunk_id}`
+///
+/// Agrupa los chunks de cada documento bajo un prefijo común y los ordena por
+/// índice. Incluir el id permite detectar índices duplicados en vez de pisarlos.
+pub fn chunk_key(document_id: &str, index: usize, chunk_id: &str) -> String {
+    format!(
+        "{}:{:0width$}:{}",
+        document_id,
+        index,
+        chunk_id,
+        width = CHUNK_INDEX_WIDTH
+    )
+}
+
+/// Inversa de `chunk_key`
+pub fn parse_chunk_key(key: &[u8]) -> Option<ChunkKey> {
+    let (document_id, index, chunk_id) = split_numbered_key(key, CHUNK_INDEX_WIDTH)?;
+    Some(ChunkKey {
+        document_id,
+        index,
+        chunk_id,
+    })
+}
+
+/// Clave del índice por página: `{document_id}:{page}:{chunk_id}`
+pub fn page_index_key(document_id: &str, page: usize, chunk_id: &str) -> String {
+    format!(
+        "{}:{:0width$}:{}",
+        document_id,
+        page,
+        chunk_id,
+        width = PAGE_WIDTH
+    )
+}
+
+/// Inversa de `page_index_key`
+pub fn parse_page_index_key(key: &[u8]) -> Option<PageIndexKey> {
+    let (document_id, page, chunk_id) = split_numbered_key(key, PAGE_WIDTH)?;
+    Some(PageIndexKey {
+        document_id,
+        page,
+        chunk_id,
+    })
+}
+
+/// Clave de un segmento de blob: `{document_id}:{segment}`
+pub fn segment_key(document_id: &str, segment: usize) -> String {
+    format!("{}:{:0width$}", document_id, segment, width = SEGMENT_WIDTH)
+}
+
+/// Inversa de `segment_key`: retorna `(document_id, segment)`
+pub fn parse_segment_key(key: &[u8]) -> Option<(String, usize)> {
+    let key = std::str::from_utf8(key).ok()?;
+    let (document_id, segment) = key.rsplit_once(':')?;
+    if !is_number(segment, SEGMENT_WIDTH) {
+        return None;
+    }
+    Some((document_id.to_string(), segment.parse().ok()?))
+}
+
+fn is_number(s: &str, width: usize) -> bool {
+    s.len() == width && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Separa `{id}:{número}:{resto}` cuando el id puede contener ':'
+///
+/// Se busca el número desde el final, porque es el único tramo de ancho fijo.
+fn split_numbered_key(key: &[u8], width: usize) -> Option<(String, usize, String)> {
+    let key = std::str::from_utf8(key).ok()?;
+    let mut seps = key.match_indices(':').map(|(i, _)| i).collect::<Vec<_>>();
+    seps.reverse();
+    for sep in seps {
+        let (id, rest) = (&key[..sep], &key[sep + 1..]);
+        let Some((number, tail)) = rest.split_once(':') else {
+            continue;
+        };
+        if is_number(number, width) {
+            return Some((id.to_string(), number.parse().ok()?, tail.to_string()));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_key_roundtrip() {
+        let key = chunk_key("doc:raro", 42, "chunk:1");
+        assert_eq!(
+            parse_chunk_key(key.as_bytes()),
+            Some(ChunkKey {
+                document_id: "doc:raro".to_string(),
+                index: 42,
+                chunk_id: "chunk:1".to_string(),
+            })
+        );
+        assert!(parse_chunk_key(b"sin-formato").is_none());
+    }
+
+    #[test]
+    fn test_page_and_segment_key_roundtrip() {
+        let key = page_index_key("doc-1", 7, "c-3");
+        let parsed = parse_page_index_key(key.as_bytes()).unwrap();
+        assert_eq!(parsed.document_id, "doc-1");
+        assert_eq!(parsed.page, 7);
+        assert_eq!(parsed.chunk_id, "c-3");
+
+        let key = segment_key("a:b", 12);
+        assert_eq!(
+            parse_segment_key(key.as_bytes()),
+            Some(("a:b".to_string(), 12))
+        );
+        assert!(parse_segment_key(b"a:12").is_none());
+    }
+
+    #[test]
+    fn test_key_order_matches_numeric_order() {
+        let numbers = [0, 1, 2, 9, 10, 11, 99, 100, 1_000, 123_456];
+
+        let chunk_keys: Vec<String> = numbers.iter().map(|i| chunk_key("d", *i, "c")).collect();
+        let mut sorted = chunk_keys.clone();
+        sorted.sort();
+        assert_eq!(chunk_keys, sorted);
+
+        let page_keys: Vec<String> = numbers
+            .iter()
+            .map(|p| page_index_key("d", *p, "c"))
+            .collect();
+        let mut sorted = page_keys.clone();
+        sorted.sort();
+        assert_eq!(page_keys, sorted);
+
+        let segments: Vec<String> = numbers.iter().map(|s| segment_key("d", *s)).collect();
+        let mut sorted = segments.clone();
+        sorted.sort();
+        assert_eq!(segments, sorted);
+    }
+}
@@ -0,0 +1,181 @@
+// Detección del idioma de chunks y documentos
+//
+// Usa whatlang, que funciona sin modelos externos. Los códigos son ISO 639-1
+// ("es", "en"); whatlang trabaja con ISO 639-3 y se traducen con `iso_639_1`.
+
+use crate::models::Chunk;
+use std::collections::HashMap;
+use whatlang::Lang;
+
+/// Letras mínimas para intentar detectar el idioma; con menos (números de
+/// página, títulos sueltos) el resultado no es confiable
+const MIN_LETTERS: usize = 20;
+
+/// Confianza mínima de whatlang para aceptar el idioma. `is_reliable` es
+/// demasiado estricto con párrafos cortos en español (los confunde con
+/// portugués o catalán)
+const MIN_CONFIDENCE: f64 = 0.3;
+
+/// Idioma de `text` como código ISO 639-1, o `None` si es ambiguo
+///
+/// Un texto demasiado corto, sin letras o en el que whatlang tiene poca
+/// confianza retorna `None`; nunca falla.
+pub fn detect_language(text: &str) -> Option<String> {
+    if text.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+    let info = whatlang::detect(text)?;
+    (info.confidence() >= MIN_CONFIDENCE).then(|| iso_639_1(info.lang()).to_string())
+}
+
+/// Código ISO 639-1 de un idioma de whatlang (todos los que soporta tienen uno)
+fn iso_639_1(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Epo => "eo",
+        Lang::Eng => "en",
+        Lang::Rus => "ru",
+        Lang::Cmn => "zh",
+        Lang::Spa => "es",
+        Lang::Por => "pt",
+        Lang::Ita => "it",
+        Lang::Ben => "bn",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Ukr => "uk",
+        Lang::Kat => "ka",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Jpn => "ja",
+        Lang::Heb => "he",
+        Lang::Yid => "yi",
+        Lang::Pol => "pl",
+        Lang::Amh => "am",
+        Lang::Jav => "jv",
+        Lang::Kor => "ko",
+        Lang::Nob => "nb",
+        Lang::Dan => "da",
+        Lang::Swe => "sv",
+        Lang::Fin => "fi",
+        Lang::Tur => "tr",
+        Lang::Nld => "nl",
+        Lang::Hun => "hu",
+        Lang::Ces => "cs",
+        Lang::Ell => "el",
+        Lang::Bul => "bg",
+        Lang::Bel => "be",
+        Lang::Mar => "mr",
+        Lang::Kan => "kn",
+        Lang::Ron => "ro",
+        Lang::Slv => "sl",
+        Lang::Hrv => "hr",
+        Lang::Srp => "sr",
+        Lang::Mkd => "mk",
+        Lang::Lit => "lt",
+        Lang::Lav => "lv",
+        Lang::Est => "et",
+        Lang::Tam => "ta",
+        Lang::Vie => "vi",
+        Lang::Urd => "ur",
+        Lang::Tha => "th",
+        Lang::Guj => "gu",
+        Lang::Uzb => "uz",
+        Lang::Pan => "pa",
+        Lang::Aze => "az",
+        Lang::Ind => "id",
+        Lang::Tel => "te",
+        Lang::Pes => "fa",
+        Lang::Mal => "ml",
+        Lang::Ori => "or",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Sin => "si",
+        Lang::Khm => "km",
+        Lang::Tuk => "tk",
+        Lang::Aka => "ak",
+        Lang::Zul => "zu",
+        Lang::Sna => "sn",
+        Lang::Afr => "af",
+        Lang::Lat => "la",
+        Lang::Slk => "sk",
+        Lang::Cat => "ca",
+        Lang::Tgl => "tl",
+        Lang::Hye => "hy",
+    }
+}
+
+/// Guarda en `Chunk::language` el idioma detectado de cada chunk
+///
+/// Los chunks en que la detección es ambigua quedan sin idioma.
+pub fn tag_chunk_languages(chunks: &mut [Chunk]) {
+    for chunk in chunks {
+        chunk.language = detect_language(&chunk.text);
+    }
+}
+
+/// Idioma más frecuente entre los chunks que tienen uno
+///
+/// En caso de empate gana el que aparece primero. `None` si ningún chunk
+/// tiene idioma.
+pub fn majority_language(chunks: &[Chunk]) -> Option<String> {
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    let languages = chunks.iter().filter_map(|c| c.language.as_deref());
+    for (position, language) in languages.enumerate() {
+        counts.entry(language).or_insert((0, position)).0 += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, (count, first))| (count, std::cmp::Reverse(first)))
+        .map(|(language, _)| language.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPANISH: &str = "La biblioteca guarda los documentos en una base de datos local y \
+        permite buscar pasajes por significado, no solo por palabras exactas.";
+    const ENGLISH: &str = "The library stores every document in a local database and lets \
+        you search passages by meaning instead of matching exact words.";
+
+    fn chunk(index: usize, text: &str) -> Chunk {
+        Chunk::new(format!("c-{}", index), "doc".into(), text.into(), index, 1)
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language(SPANISH).as_deref(), Some("es"));
+        assert_eq!(detect_language(ENGLISH).as_deref(), Some("en"));
+        // Ambiguos: solo números, o muy corto
+        assert_eq!(detect_language("12 345 6789 10.11 2024"), None);
+        assert_eq!(detect_language("Hola"), None);
+        assert_eq!(detect_language(""), None);
+    }
+
+    #[test]
+    fn test_codes_are_iso_639_1() {
+        for &lang in Lang::all() {
+            let code = iso_639_1(lang);
+            assert_eq!(code.len(), 2, "{:?}", lang);
+            assert!(code.chars().all(|c| c.is_ascii_lowercase()));
+        }
+    }
+
+    #[test]
+    fn test_tag_chunk_languages_and_majority() {
+        let mut chunks = vec![
+            chunk(0, ENGLISH),
+            chunk(1, SPANISH),
+            chunk(2, "3.14 2.71 1.41 42"),
+            chunk(3, SPANISH),
+        ];
+        tag_chunk_languages(&mut chunks);
+
+        let languages: Vec<Option<&str>> = chunks.iter().map(|c| c.language.as_deref()).collect();
+        assert_eq!(languages, [Some("en"), Some("es"), None, Some("es")]);
+        assert_eq!(majority_language(&chunks).as_deref(), Some("es"));
+
+        // Empate: gana el primero; sin idiomas: None
+        assert_eq!(majority_language(&chunks[..2]).as_deref(), Some("en"));
+        assert_eq!(majority_language(&chunks[2..3]), None);
+    }
+}
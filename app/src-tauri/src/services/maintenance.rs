@@ -0,0 +1,267 @@
+use crate::services::database::{init_db_at, DbError};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sled;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Resultado de `vacuum`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VacuumReport {
+    /// Tamaño en disco antes del flush (bytes)
+    pub size_before: u64,
+    /// Tamaño en disco después del flush (bytes)
+    pub size_after: u64,
+    /// Bytes de claves y valores vivos en todos los árboles
+    pub live_bytes: u64,
+    /// `true` si el archivo ocupa más que los datos vivos, es decir, si al
+    /// recolector de sled todavía le queda espacio por recuperar
+    pub gc_pending: bool,
+}
+
+impl VacuumReport {
+    /// Espacio que se podría recuperar con `rebuild_library` (aproximado)
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.size_after.saturating_sub(self.live_bytes)
+    }
+}
+
+/// Resultado de `rebuild_library`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RebuildReport {
+    /// Registros copiados (todas las claves de todos los árboles)
+    pub records: usize,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// Fuerza un flush y reporta cuánto espacio ocupa la BD
+///
+/// sled recupera espacio en segundo plano y no expone el estado de su
+/// recolector, así que `gc_pending` se estima comparando el tamaño en disco
+/// con los bytes vivos. Si queda mucho por recuperar, `rebuild_library` lo
+/// libera de inmediato.
+pub fn vacuum(db: &Arc<sled::Db>) -> Result<VacuumReport, DbError> {
+    let size_before = db.size_on_disk()?;
+    db.flush()?;
+    let size_after = db.size_on_disk()?;
+    let live_bytes = live_bytes(db)?;
+
+    Ok(VacuumReport {
+        size_before,
+        size_after,
+        live_bytes,
+        gc_pending: size_after > live_bytes,
+    })
+}
+
+fn live_bytes(db: &sled::Db) -> Result<u64, DbError> {
+    let mut total = 0u64;
+    for name in db.tree_names() {
+        for item in db.open_tree(&name)?.iter() {
+            let (k, v) = item?;
+            total += (k.len() + v.len()) as u64;
+        }
+    }
+    Ok(total)
+}
+
+/// Hash del contenido lógico de la BD (nombres de árboles, claves y valores)
+///
+/// Dos BDs con los mismos datos dan el mismo hash aunque sus archivos sean
+/// distintos.
+pub fn library_checksum(db: &sled::Db) -> Result<String, DbError> {
+    let mut names = db.tree_names();
+    names.sort();
+
+    let mut hasher = Sha256::new();
+    for name in names {
+        let tree = db.open_tree(&name)?;
+        // Los árboles vacíos no aportan contenido (sled crea algunos al abrir)
+        if tree.is_empty() {
+            continue;
+        }
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(&name);
+        for item in tree.iter() {
+            let (k, v) = item?;
+            hasher.update((k.len() as u64).to_le_bytes());
+            hasher.update(&k);
+            hasher.update((v.len() as u64).to_le_bytes());
+            hasher.update(&v);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Reconstruye la biblioteca copiando los registros vivos a una BD nueva
+///
+/// La copia se arma en `new_path` (que no debe existir) y se verifica con
+/// `library_checksum`; solo si coincide se intercambian los directorios, de
+/// modo que al terminar la biblioteca sigue en `old_path` pero compactada.
+/// Si algún registro no se puede copiar, o la verificación falla, se borra
+/// la copia y `old_path` queda intacto.
+///
+/// La BD en `old_path` no debe estar abierta en el proceso: sled bloquea el
+/// directorio. La app debe cerrar su instancia antes y reabrirla después.
+pub fn rebuild_library(old_path: &Path, new_path: &Path) -> Result<RebuildReport, DbError> {
+    if new_path.exists() {
+        return Err(DbError::InvalidInput(format!(
+            "{} already exists",
+            new_path.display()
+        )));
+    }
+
+    let copied = copy_library(old_path, new_path);
+    let (records, size_before, size_after) = match copied {
+        Ok(stats) => stats,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(new_path);
+            return Err(e);
+        }
+    };
+
+    swap_dirs(old_path, new_path)?;
+    Ok(RebuildReport {
+        records,
+        size_before,
+        size_after,
+    })
+}
+
+/// Copia todos los árboles de `old_path` a `new_path` y verifica el contenido
+///
+/// Retorna (registros, tamaño viejo, tamaño nuevo). Ambas BDs quedan cerradas.
+fn copy_library(old_path: &Path, new_path: &Path) -> Result<(usize, u64, u64), DbError> {
+    let old = sled::open(old_path)
+        .map_err(|e| DbError::Open(format!("{}: {}", old_path.display(), e)))?;
+    let new = init_db_at(new_path.to_path_buf())?;
+
+    let mut records = 0;
+    for name in old.tree_names() {
+        let source = old.open_tree(&name)?;
+        let target = new.open_tree(&name)?;
+        let mut batch = sled::Batch::default();
+        for item in source.iter() {
+            let (k, v) = item?;
+            batch.insert(k, v);
+            records += 1;
+        }
+        target.apply_batch(batch)?;
+    }
+    new.flush()?;
+
+    if library_checksum(&old)? != library_checksum(&new)? {
+        return Err(DbError::Conflict(
+            "rebuilt library does not match the original".to_string(),
+        ));
+    }
+    Ok((records, old.size_on_disk()?, new.size_on_disk()?))
+}
+
+/// Reemplaza `old_path` por `new_path` usando renombres
+///
+/// El original se mueve primero a un respaldo; si el segundo renombre falla
+/// se restaura, así que nunca queda la biblioteca a medio reemplazar.
+fn swap_dirs(old_path: &Path, new_path: &Path) -> Result<(), DbError> {
+    let backup = backup_path(old_path);
+    if backup.exists() {
+        std::fs::remove_dir_all(&backup)?;
+    }
+    std::fs::rename(old_path, &backup)?;
+    if let Err(e) = std::fs::rename(new_path, old_path) {
+        std::fs::rename(&backup, old_path)?;
+        return Err(e.into());
+    }
+    std::fs::remove_dir_all(&backup)?;
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".rebuild-old");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Chunk, Document};
+    use crate::services::database::{
+        count_chunks, delete_document, get_all_documents, insert_chunk, insert_document,
+    };
+    use crate::services::test_support::temp_db;
+
+    fn fill(db: &Arc<sled::Db>) {
+        let text = "contenido de relleno ".repeat(50);
+        for d in 0..40 {
+            let id = format!("doc-{}", d);
+            let doc = Document::new(id.clone(), format!("{}.pdf", id), "/tmp/x.pdf".into(), 1);
+            insert_document(db, &doc).unwrap();
+            for c in 0..10 {
+                let chunk = Chunk::new(format!("{}-{}", id, c), id.clone(), text.clone(), c, 1);
+                insert_chunk(db, &chunk).unwrap();
+            }
+        }
+        // Borrar la mayoría para dejar espacio recuperable
+        for d in 0..35 {
+            delete_document(db, &format!("doc-{}", d)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_vacuum_reports_sizes() {
+        let (db, path) = temp_db("test_vacuum");
+        fill(&db);
+
+        let report = vacuum(&db).unwrap();
+        assert!(report.size_after > 0);
+        assert!(report.live_bytes > 0);
+        assert!(report.gc_pending);
+        assert!(report.reclaimable_bytes() > 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_rebuild_library_keeps_content_and_shrinks() {
+        let (db, path) = temp_db("test_rebuild");
+        fill(&db);
+        db.flush().unwrap();
+        let checksum = library_checksum(&db).unwrap();
+        let docs = get_all_documents(&db).unwrap();
+        drop(db);
+
+        let staging = path.with_file_name(format!("test_rebuild_new_{}", std::process::id()));
+        let report = rebuild_library(&path, &staging).unwrap();
+        assert!(report.records > 0);
+        assert!(report.size_after < report.size_before);
+        assert!(!staging.exists());
+
+        let db = init_db_at(path.clone()).unwrap();
+        assert_eq!(library_checksum(&db).unwrap(), checksum);
+        assert_eq!(get_all_documents(&db).unwrap(), docs);
+        assert_eq!(count_chunks(&db, None).unwrap(), 50);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_rebuild_refuses_existing_target() {
+        let (db, path) = temp_db("test_rebuild_target");
+        drop(db);
+        let (other, other_path) = temp_db("test_rebuild_target_new");
+        drop(other);
+
+        assert!(matches!(
+            rebuild_library(&path, &other_path),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert!(path.exists());
+
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_dir_all(&other_path);
+    }
+}
@@ -1 +1,26 @@
+pub mod attachments;
+pub mod blobs;
+pub mod chunker;
 pub mod database;
+pub mod embeddings;
+pub mod epub;
+pub mod error;
+pub mod export;
+pub mod extract;
+pub mod html;
+pub mod http;
+pub mod indexing;
+pub mod ingest;
+pub mod integrity;
+pub mod keys;
+pub mod language;
+pub mod maintenance;
+pub mod ollama;
+pub mod openai;
+pub mod pdf;
+pub mod rag;
+pub mod search;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod trash;
+pub mod vector_index;
@@ -0,0 +1,213 @@
+use crate::services::embeddings::EmbeddingProvider;
+use crate::services::error::EmbedError;
+use crate::services::http::{agent, backend_error, read_json, DEFAULT_TIMEOUT};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// URL por defecto de un servidor Ollama local
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Textos por request, por defecto
+pub const DEFAULT_OLLAMA_BATCH_SIZE: usize = 32;
+
+/// Cliente de embeddings para Ollama (`POST /api/embed`)
+///
+/// Los textos se envían en lotes (`input: [...]`) de hasta `batch_size`
+/// por request. La dimensión se declara al crearlo y cada vector recibido
+/// se valida contra ella: un modelo distinto del esperado produce un error,
+/// no datos inservibles.
+pub struct OllamaEmbedder {
+    base_url: String,
+    model: String,
+    dimension: usize,
+    batch_size: usize,
+    agent: ureq::Agent,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl OllamaEmbedder {
+    /// `base_url` sin la ruta del endpoint, p. ej. `DEFAULT_OLLAMA_URL`
+    pub fn new(base_url: &str, model: &str, dimension: usize) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            dimension,
+            batch_size: DEFAULT_OLLAMA_BATCH_SIZE,
+            agent: agent(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// Cambia la cantidad máxima de textos por request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Cambia el tiempo máximo de cada request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = agent(timeout);
+        self
+    }
+
+    fn embed_batch(&self, url: &str, batch: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let request = EmbedRequest {
+            model: &self.model,
+            input: batch,
+        };
+        let response = self
+            .agent
+            .post(url)
+            .send_json(&request)
+            .map_err(|e| backend_error(url, e))?;
+        let parsed: EmbedResponse = read_json(url, response)?;
+        if parsed.embeddings.len() != batch.len() {
+            return Err(EmbedError::InvalidOutput(format!(
+                "{} vectors for {} texts",
+                parsed.embeddings.len(),
+                batch.len()
+            )));
+        }
+        if let Some(vector) = parsed.embeddings.iter().find(|v| v.len() != self.dimension) {
+            return Err(EmbedError::InvalidOutput(format!(
+                "model {} returned {} dimensions, expected {}",
+                self.model,
+                vector.len(),
+                self.dimension
+            )));
+        }
+        Ok(parsed.embeddings)
+    }
+}
+
+impl EmbeddingProvider for OllamaEmbedder {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let url = format!("{}/api/embed", self.base_url);
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            vectors.extend(self.embed_batch(&url, batch)?);
+        }
+        Ok(vectors)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_name(&self) -> String {
+        self.model.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http::mock::{MockResponse, MockServer};
+
+    fn texts(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("texto {}", i)).collect()
+    }
+
+    #[test]
+    fn test_ollama_embeds_in_batches() {
+        let server = MockServer::serve(vec![
+            MockResponse::json(200, r#"{"embeddings": [[0.1, 0.2], [0.3, 0.4]]}"#),
+            MockResponse::json(200, r#"{"embeddings": [[0.5, 0.6], [0.7, 0.8]]}"#),
+            MockResponse::json(200, r#"{"embeddings": [[0.9, 1.0]]}"#),
+        ]);
+        let embedder = OllamaEmbedder::new(&server.url, "nomic-embed-text", 2).with_batch_size(2);
+
+        let vectors = embedder.embed(&texts(5)).unwrap();
+        assert_eq!(vectors.len(), 5);
+        assert_eq!(vectors[0], vec![0.1, 0.2]);
+        assert_eq!(vectors[4], vec![0.9, 1.0]);
+
+        // 5 textos en lotes de 2: 3 requests
+        let requests = server.finish();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|r| r.path == "/api/embed"));
+        let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["model"], "nomic-embed-text");
+        assert_eq!(body["input"], serde_json::json!(["texto 2", "texto 3"]));
+        let last: serde_json::Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(last["input"], serde_json::json!(["texto 4"]));
+    }
+
+    #[test]
+    fn test_ollama_rejects_missing_vectors() {
+        let server = MockServer::serve(vec![MockResponse::json(
+            200,
+            r#"{"embeddings": [[0.1, 0.2, 0.3]]}"#,
+        )]);
+        let embedder = OllamaEmbedder::new(&server.url, "m", 3);
+
+        let err = embedder.embed(&texts(2)).unwrap_err();
+        assert!(matches!(err, EmbedError::InvalidOutput(_)));
+        assert!(err.to_string().contains("1 vectors for 2 texts"));
+        server.finish();
+    }
+
+    #[test]
+    fn test_ollama_reports_server_errors_with_body() {
+        let server = MockServer::serve(vec![MockResponse::json(
+            404,
+            r#"{"error": "model \"nada\" not found"}"#,
+        )]);
+        let embedder = OllamaEmbedder::new(&server.url, "nada", 3);
+
+        let err = embedder.embed(&texts(1)).unwrap_err();
+        assert!(matches!(err, EmbedError::Backend(_)));
+        let message = err.to_string();
+        assert!(message.contains("404") && message.contains("not found"));
+        server.finish();
+    }
+
+    #[test]
+    fn test_ollama_rejects_unexpected_dimension() {
+        let server = MockServer::serve(vec![MockResponse::json(
+            200,
+            r#"{"embeddings": [[0.1, 0.2]]}"#,
+        )]);
+        let embedder = OllamaEmbedder::new(&server.url, "m", 3);
+
+        let err = embedder.embed(&texts(1)).unwrap_err();
+        assert!(matches!(err, EmbedError::InvalidOutput(_)));
+        assert!(err.to_string().contains("2 dimensions, expected 3"));
+        server.finish();
+    }
+
+    #[test]
+    fn test_ollama_malformed_json() {
+        let server = MockServer::serve(vec![MockResponse::json(200, r#"{"embeddings": [[0.1,"#)]);
+        let embedder = OllamaEmbedder::new(&server.url, "m", 3);
+
+        let err = embedder.embed(&texts(1)).unwrap_err();
+        assert!(matches!(err, EmbedError::InvalidOutput(_)));
+        assert!(err.to_string().contains("malformed response"));
+        server.finish();
+    }
+
+    #[test]
+    fn test_ollama_timeout() {
+        let server = MockServer::serve(vec![MockResponse::json(
+            200,
+            r#"{"embeddings": [[0.1, 0.2, 0.3]]}"#,
+        )
+        .with_delay(Duration::from_millis(500))]);
+        let embedder =
+            OllamaEmbedder::new(&server.url, "m", 3).with_timeout(Duration::from_millis(100));
+
+        let err = embedder.embed(&texts(1)).unwrap_err();
+        assert!(matches!(err, EmbedError::Backend(_)));
+        server.finish();
+    }
+}
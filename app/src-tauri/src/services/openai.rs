@@ -0,0 +1,281 @@
+use crate::services::embeddings::EmbeddingProvider;
+use crate::services::error::EmbedError;
+use crate::services::http::{agent, backend_error, read_json, DEFAULT_TIMEOUT};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// URL base de la API de OpenAI
+pub const DEFAULT_OPENAI_URL: &str = "https://api.openai.com/v1";
+
+/// Textos por request, por defecto
+pub const DEFAULT_OPENAI_BATCH_SIZE: usize = 64;
+
+/// Caracteres por request, por defecto (bien por debajo de los límites de
+/// tokens por request de los servidores compatibles)
+pub const DEFAULT_OPENAI_BATCH_CHARS: usize = 100_000;
+
+/// Espera máxima ante un 429, aunque `retry-after` pida más
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Cliente de embeddings para APIs compatibles con OpenAI (`POST /embeddings`)
+///
+/// Sirve para OpenAI y para servidores locales con la misma API (LM Studio,
+/// vLLM). Los textos se envían en lotes (`input: [...]`) acotados por
+/// cantidad y por caracteres. Ante un 429 se reintenta una sola vez, después
+/// de lo que indique `retry-after`.
+pub struct OpenAiEmbedder {
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    dimension: usize,
+    batch_size: usize,
+    batch_chars: usize,
+    agent: ureq::Agent,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: Option<usize>,
+}
+
+impl OpenAiEmbedder {
+    /// `base_url` incluye la versión de la API, p. ej. `DEFAULT_OPENAI_URL`;
+    /// `api_key` puede omitirse para servidores locales
+    pub fn new(base_url: &str, api_key: Option<&str>, model: &str, dimension: usize) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(str::to_string),
+            model: model.to_string(),
+            dimension,
+            batch_size: DEFAULT_OPENAI_BATCH_SIZE,
+            batch_chars: DEFAULT_OPENAI_BATCH_CHARS,
+            agent: agent(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// Límites de cada request: cantidad de textos y suma de caracteres
+    ///
+    /// Un texto que por sí solo supera `batch_chars` se envía solo.
+    pub fn with_batch_limits(mut self, batch_size: usize, batch_chars: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.batch_chars = batch_chars.max(1);
+        self
+    }
+
+    /// Cambia el tiempo máximo de cada request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = agent(timeout);
+        self
+    }
+
+    /// Parte `texts` en lotes según los límites configurados
+    fn batches<'a>(&self, texts: &'a [String]) -> Vec<&'a [String]> {
+        let mut out = Vec::new();
+        let mut start = 0;
+        let mut chars = 0;
+        for (i, text) in texts.iter().enumerate() {
+            let len = text.chars().count();
+            let full =
+                i - start == self.batch_size || (i > start && chars + len > self.batch_chars);
+            if full {
+                out.push(&texts[start..i]);
+                start = i;
+                chars = 0;
+            }
+            chars += len;
+        }
+        if start < texts.len() {
+            out.push(&texts[start..]);
+        }
+        out
+    }
+
+    /// Envía un lote; ante un 429 espera lo indicado y reintenta una vez
+    fn send(&self, url: &str, batch: &[String]) -> Result<ureq::Response, EmbedError> {
+        let mut retried = false;
+        loop {
+            let mut request = self.agent.post(url);
+            if let Some(key) = &self.api_key {
+                request = request.set("Authorization", &format!("Bearer {}", key));
+            }
+            let result = request.send_json(EmbeddingsRequest {
+                model: &self.model,
+                input: batch,
+            });
+            match result {
+                Err(ureq::Error::Status(429, response)) if !retried => {
+                    std::thread::sleep(retry_after(&response));
+                    retried = true;
+                }
+                other => return other.map_err(|e| backend_error(url, e)),
+            }
+        }
+    }
+
+    fn embed_batch(&self, url: &str, batch: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let response = self.send(url, batch)?;
+
+        let mut parsed: EmbeddingsResponse = read_json(url, response)?;
+        if parsed.data.len() != batch.len() {
+            return Err(EmbedError::InvalidOutput(format!(
+                "{} vectors for {} texts",
+                parsed.data.len(),
+                batch.len()
+            )));
+        }
+        // La API no garantiza el orden; `index` indica a qué texto corresponde
+        if parsed.data.iter().all(|d| d.index.is_some()) {
+            parsed.data.sort_by_key(|d| d.index);
+        }
+        let mut vectors = Vec::with_capacity(batch.len());
+        for data in parsed.data {
+            if data.embedding.len() != self.dimension {
+                return Err(EmbedError::InvalidOutput(format!(
+                    "model {} returned {} dimensions, expected {}",
+                    self.model,
+                    data.embedding.len(),
+                    self.dimension
+                )));
+            }
+            vectors.push(data.embedding);
+        }
+        Ok(vectors)
+    }
+}
+
+/// Espera pedida por el servidor en `retry-after` (segundos); 1 s si no la indica
+fn retry_after(response: &ureq::Response) -> Duration {
+    response
+        .header("retry-after")
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .unwrap_or(Duration::from_secs(1))
+        .min(MAX_RETRY_AFTER)
+}
+
+impl EmbeddingProvider for OpenAiEmbedder {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let url = format!("{}/embeddings", self.base_url);
+        let mut out = Vec::with_capacity(texts.len());
+        for batch in self.batches(texts) {
+            out.extend(self.embed_batch(&url, batch)?);
+        }
+        Ok(out)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_name(&self) -> String {
+        self.model.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http::mock::{MockResponse, MockServer};
+
+    fn texts(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("texto {}", i)).collect()
+    }
+
+    /// Respuesta con un vector `[i, i]` por texto, en orden inverso
+    fn response_for(first: usize, count: usize) -> MockResponse {
+        let data: Vec<String> = (0..count)
+            .rev()
+            .map(|i| {
+                let v = (first + i) as f32;
+                format!(r#"{{"embedding": [{}, {}], "index": {}}}"#, v, v, i)
+            })
+            .collect();
+        MockResponse::json(200, &format!(r#"{{"data": [{}]}}"#, data.join(",")))
+    }
+
+    #[test]
+    fn test_openai_batches_and_auth() {
+        let server = MockServer::serve(vec![
+            response_for(0, 2),
+            response_for(2, 2),
+            response_for(4, 1),
+        ]);
+        let embedder = OpenAiEmbedder::new(&server.url, Some("sk-test"), "text-embedding-3", 2)
+            .with_batch_limits(2, 1_000);
+
+        let vectors = embedder.embed(&texts(5)).unwrap();
+        let expected: Vec<Vec<f32>> = (0..5).map(|i| vec![i as f32; 2]).collect();
+        assert_eq!(vectors, expected);
+
+        let requests = server.finish();
+        assert_eq!(requests.len(), 3);
+        let sizes: Vec<usize> = requests
+            .iter()
+            .map(|r| {
+                let body: serde_json::Value = serde_json::from_str(&r.body).unwrap();
+                assert_eq!(body["model"], "text-embedding-3");
+                body["input"].as_array().unwrap().len()
+            })
+            .collect();
+        assert_eq!(sizes, [2, 2, 1]);
+        assert_eq!(requests[0].path, "/embeddings");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+    }
+
+    #[test]
+    fn test_openai_batches_by_chars() {
+        let embedder = OpenAiEmbedder::new("http://x", None, "m", 2).with_batch_limits(100, 10);
+        let texts = vec![
+            "aaaa".to_string(),
+            "bbbb".to_string(),
+            "cccc".to_string(),
+            "d".repeat(30),
+            "e".to_string(),
+        ];
+        let sizes: Vec<usize> = embedder.batches(&texts).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, [2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_openai_retries_once_on_429() {
+        let server = MockServer::serve(vec![
+            MockResponse::json(429, r#"{"error": "rate limited"}"#).with_header("Retry-After", "0"),
+            response_for(0, 1),
+        ]);
+        let embedder = OpenAiEmbedder::new(&server.url, None, "m", 2);
+
+        assert_eq!(embedder.embed(&texts(1)).unwrap(), vec![vec![0.0, 0.0]]);
+        let requests = server.finish();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header("authorization"), None);
+    }
+
+    #[test]
+    fn test_openai_gives_up_after_second_429() {
+        let server = MockServer::serve(vec![
+            MockResponse::json(429, r#"{"error": "rate limited"}"#).with_header("Retry-After", "0"),
+            MockResponse::json(429, r#"{"error": "still limited"}"#)
+                .with_header("Retry-After", "0"),
+        ]);
+        let embedder = OpenAiEmbedder::new(&server.url, None, "m", 2);
+
+        let err = embedder.embed(&texts(1)).unwrap_err();
+        assert!(matches!(err, EmbedError::Backend(_)));
+        assert!(err.to_string().contains("still limited"));
+        server.finish();
+    }
+}
@@ -0,0 +1,302 @@
+use crate::models::Document;
+use crate::services::error::ExtractError;
+use lopdf;
+
+/// Abre el PDF en `file_path`
+fn load(file_path: &str) -> Result<lopdf::Document, ExtractError> {
+    lopdf::Document::load(file_path)
+        .map_err(|e| ExtractError::Invalid(format!("cannot open pdf {}: {}", file_path, e)))
+}
+
+/// Abre un PDF desde sus bytes
+fn load_mem(bytes: &[u8]) -> Result<lopdf::Document, ExtractError> {
+    lopdf::Document::load_mem(bytes)
+        .map_err(|e| ExtractError::Invalid(format!("cannot open pdf: {}", e)))
+}
+
+/// Cantidad real de páginas del PDF en `file_path`
+pub fn read_page_count(file_path: &str) -> Result<usize, ExtractError> {
+    Ok(load(file_path)?.get_pages().len())
+}
+
+/// Texto de cada página del PDF, en orden
+///
+/// Las páginas sin texto extraíble (p. ej. escaneadas o dañadas) quedan
+/// vacías; solo falla si no se puede abrir el archivo.
+pub fn extract_pages(file_path: &str) -> Result<Vec<String>, ExtractError> {
+    Ok(pages_text(&load(file_path)?))
+}
+
+/// Igual que `extract_pages`, pero desde los bytes del PDF (p. ej. el blob
+/// guardado del documento)
+pub fn extract_pages_from_bytes(bytes: &[u8]) -> Result<Vec<String>, ExtractError> {
+    Ok(pages_text(&load_mem(bytes)?))
+}
+
+/// Texto de un PDF junto con las páginas escaneadas
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PdfText {
+    /// Texto de cada página, en orden
+    pub pages: Vec<String>,
+    /// Números (desde 1) de las páginas sin texto pero con imágenes: escaneos
+    /// sin capa de texto, que necesitan OCR
+    pub scanned_pages: Vec<usize>,
+}
+
+/// Texto de cada página del PDF y cuáles son escaneadas, desde sus bytes
+///
+/// Una página vacía sin imágenes (p. ej. en blanco) no cuenta como escaneada.
+pub fn read_text_from_bytes(bytes: &[u8]) -> Result<PdfText, ExtractError> {
+    let doc = load_mem(bytes)?;
+    let pages = pages_text(&doc);
+    let scanned_pages = doc
+        .get_pages()
+        .values()
+        .zip(&pages)
+        .enumerate()
+        .filter(|(_, (&page_id, text))| text.trim().is_empty() && has_images(&doc, page_id))
+        .map(|(index, _)| index + 1)
+        .collect();
+    Ok(PdfText {
+        pages,
+        scanned_pages,
+    })
+}
+
+/// Indica si la página tiene imágenes (XObjects `/Image`) entre sus recursos,
+/// incluidos los heredados de `/Pages`
+fn has_images(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> bool {
+    let Ok((direct, inherited)) = doc.get_page_resources(page_id) else {
+        return false;
+    };
+    direct
+        .into_iter()
+        .chain(
+            inherited
+                .iter()
+                .filter_map(|id| doc.get_dictionary(*id).ok()),
+        )
+        .filter_map(|resources| {
+            let xobjects = resources.get(b"XObject").ok()?;
+            doc.dereference(xobjects).ok()?.1.as_dict().ok()
+        })
+        .flat_map(|xobjects| xobjects.iter())
+        .filter_map(|(_, xobject)| doc.dereference(xobject).ok()?.1.as_stream().ok())
+        .any(|stream| {
+            stream
+                .dict
+                .get(b"Subtype")
+                .and_then(|subtype| subtype.as_name())
+                .is_ok_and(|subtype| subtype == b"Image")
+        })
+}
+
+/// Texto de cada página de `doc`
+///
+/// Una página que no se puede leer (contenido o fuentes dañadas) queda vacía
+/// en vez de hacer fallar todo el documento.
+fn pages_text(doc: &lopdf::Document) -> Vec<String> {
+    doc.get_pages()
+        .keys()
+        .map(|number| doc.extract_text(&[*number]).unwrap_or_default())
+        .collect()
+}
+
+/// Datos del diccionario de información (`/Info`) de un PDF
+///
+/// Cada campo es `None` si falta o queda vacío.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PdfInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+}
+
+impl PdfInfo {
+    /// Copia en `doc` los campos presentes; los ausentes no borran lo que
+    /// `doc` ya tenía
+    pub fn apply_to(&self, doc: &mut Document) {
+        for (value, field) in [
+            (&self.title, &mut doc.title),
+            (&self.author, &mut doc.author),
+            (&self.subject, &mut doc.subject),
+        ] {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        }
+    }
+}
+
+/// Título, autor y tema del PDF en `file_path`
+pub fn read_info(file_path: &str) -> Result<PdfInfo, ExtractError> {
+    Ok(info_of(&load(file_path)?))
+}
+
+/// Igual que `read_info`, pero desde los bytes del PDF
+pub fn read_info_from_bytes(bytes: &[u8]) -> Result<PdfInfo, ExtractError> {
+    Ok(info_of(&load_mem(bytes)?))
+}
+
+fn info_of(doc: &lopdf::Document) -> PdfInfo {
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|object| doc.dereference(object))
+        .and_then(|(_, object)| object.as_dict());
+    let Ok(info) = info else {
+        return PdfInfo::default();
+    };
+    let field = |key: &[u8]| {
+        let bytes = info.get(key).and_then(|o| doc.dereference(o)).ok()?.1;
+        let text = decode_text_string(bytes.as_str().ok()?);
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    };
+    PdfInfo {
+        title: field(b"Title"),
+        author: field(b"Author"),
+        subject: field(b"Subject"),
+    }
+}
+
+/// Decodifica un "text string" de PDF
+///
+/// Con BOM `FE FF` es UTF-16BE y con `EF BB BF`, UTF-8; si no, es
+/// PDFDocEncoding, que se lee como Latin-1 (coinciden en casi todo).
+fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).into_owned();
+    }
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+/// Compara el `page_count` informado con las páginas reales del PDF
+///
+/// Retorna `Ok(false)` si no coinciden, para que la importación pueda avisar
+/// de archivos dañados o mal reportados. Si el archivo no se puede abrir o no
+/// es un PDF válido retorna un error.
+pub fn verify_page_count(file_path: &str, claimed: usize) -> Result<bool, ExtractError> {
+    Ok(read_page_count(file_path)? == claimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/three_pages.pdf");
+    /// Un PDF de una página con título en UTF-16BE y autor y tema en
+    /// PDFDocEncoding
+    const WITH_METADATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/with_metadata.pdf");
+
+    #[test]
+    fn test_verify_page_count() {
+        assert_eq!(read_page_count(FIXTURE).unwrap(), 3);
+        assert!(verify_page_count(FIXTURE, 3).unwrap());
+        assert!(!verify_page_count(FIXTURE, 5).unwrap());
+    }
+
+    #[test]
+    fn test_extract_pages_of_blank_pdf() {
+        let pages = extract_pages(FIXTURE).unwrap();
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(|p| p.trim().is_empty()));
+    }
+
+    #[test]
+    fn test_extract_pages_from_bytes() {
+        let bytes = std::fs::read(FIXTURE).unwrap();
+        assert_eq!(extract_pages_from_bytes(&bytes).unwrap().len(), 3);
+        assert!(extract_pages_from_bytes(b"no es un pdf").is_err());
+    }
+
+    #[test]
+    fn test_scanned_pages() {
+        let fixture = |name: &str| {
+            std::fs::read(format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+        };
+
+        let text = read_text_from_bytes(&fixture("text_only.pdf")).unwrap();
+        assert!(text.pages[0].contains("La luz viaja en linea recta."));
+        assert!(text.scanned_pages.is_empty());
+
+        // Solo imágenes, sin capa de texto
+        let scanned = read_text_from_bytes(&fixture("scanned.pdf")).unwrap();
+        assert_eq!(scanned.pages.len(), 2);
+        assert_eq!(scanned.scanned_pages, [1, 2]);
+
+        let mixed = read_text_from_bytes(&fixture("mixed_scan.pdf")).unwrap();
+        assert_eq!(mixed.scanned_pages, [2]);
+
+        // Las páginas en blanco no son escaneos
+        let blank = read_text_from_bytes(&fixture("three_pages.pdf")).unwrap();
+        assert!(blank.scanned_pages.is_empty());
+    }
+
+    #[test]
+    fn test_broken_page_does_not_fail_document() {
+        // La página 2 usa `Tf` sin nombre de fuente
+        let bytes = std::fs::read(format!(
+            "{}/fixtures/broken_page.pdf",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let pages = extract_pages_from_bytes(&bytes).unwrap();
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].contains("La luz viaja en linea recta."));
+        assert!(pages[1].trim().is_empty());
+        assert!(pages[2].contains("Su velocidad"));
+    }
+
+    #[test]
+    fn test_read_info() {
+        let info = read_info(WITH_METADATA).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Óptica — Introducción"));
+        assert_eq!(info.author.as_deref(), Some("Ana Pérez"));
+        assert_eq!(info.subject.as_deref(), Some("Apuntes de física"));
+        let bytes = std::fs::read(WITH_METADATA).unwrap();
+        assert_eq!(read_info_from_bytes(&bytes).unwrap(), info);
+
+        // Sin diccionario /Info
+        assert_eq!(read_info(FIXTURE).unwrap(), PdfInfo::default());
+    }
+
+    #[test]
+    fn test_decode_text_string() {
+        assert_eq!(decode_text_string(b"Caf\xe9"), "Café");
+        assert_eq!(
+            decode_text_string(&[0xFE, 0xFF, 0x00, 0x41, 0x20, 0x14]),
+            "A—"
+        );
+        assert_eq!(decode_text_string("\u{feff}Año".as_bytes()), "Año");
+    }
+
+    #[test]
+    fn test_pdf_info_keeps_missing_fields() {
+        let mut doc = Document::new("d".into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+        doc.author = Some("Autor previo".into());
+        let info = PdfInfo {
+            title: Some("Título".into()),
+            ..PdfInfo::default()
+        };
+        info.apply_to(&mut doc);
+        assert_eq!(doc.title.as_deref(), Some("Título"));
+        assert_eq!(doc.author.as_deref(), Some("Autor previo"));
+        assert_eq!(doc.display_name(), "Título");
+    }
+
+    #[test]
+    fn test_verify_page_count_missing_file() {
+        let path = std::env::temp_dir().join("no_existe_libia.pdf");
+        let err = verify_page_count(path.to_str().unwrap(), 1).unwrap_err();
+        assert!(matches!(err, ExtractError::Invalid(_)));
+        assert!(err.to_string().contains("cannot open pdf"));
+    }
+}
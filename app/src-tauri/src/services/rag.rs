@@ -0,0 +1,152 @@
+// Armado del prompt para responder preguntas con los fragmentos de la
+// biblioteca (RAG): los resultados de búsqueda pasan a ser el contexto que
+// el LLM debe citar
+
+use crate::services::search::SearchHit;
+
+/// Instrucción que va al final del prompt, después de la pregunta
+const INSTRUCTION: &str = "Responde la pregunta usando solo los fragmentos anteriores y cita \
+    cada dato con el número de su fragmento entre corchetes, p. ej. [1]. Si los fragmentos no \
+    alcanzan para responder, dilo.";
+
+/// Texto del contexto cuando ningún fragmento entra en el presupuesto
+const NO_CONTEXT: &str = "(no hay fragmentos relevantes)";
+
+/// Arma el prompt para responder `question` con los fragmentos de `hits`
+///
+/// Los fragmentos van de mayor a menor puntaje, numerados para citarlos y
+/// con su documento y página. El contexto (fragmentos con sus encabezados)
+/// ocupa como mucho `max_context_chars` caracteres: un fragmento que no
+/// entra se descarta y se prueba con el siguiente. Después van la pregunta
+/// y la instrucción.
+pub fn build_rag_prompt(question: &str, hits: &[SearchHit], max_context_chars: usize) -> String {
+    let context = context_block(hits, max_context_chars);
+    let context = if context.is_empty() {
+        NO_CONTEXT
+    } else {
+        &context
+    };
+    format!(
+        "Fragmentos de la biblioteca:\n\n{}\n\nPregunta: {}\n\n{}",
+        context,
+        question.trim(),
+        INSTRUCTION
+    )
+}
+
+/// Fragmentos que entran en `max_chars`, separados por una línea en blanco
+fn context_block(hits: &[SearchHit], max_chars: usize) -> String {
+    let mut ranked: Vec<&SearchHit> = hits.iter().collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut context = String::new();
+    let mut used = 0;
+    let mut included = 0;
+    for hit in ranked {
+        let separator = if included == 0 { "" } else { "\n\n" };
+        let entry = format!(
+            "{}[{}] {}\n{}",
+            separator,
+            included + 1,
+            citation(hit),
+            hit.chunk.text.trim()
+        );
+        let len = entry.chars().count();
+        if used + len > max_chars {
+            continue;
+        }
+        used += len;
+        included += 1;
+        context.push_str(&entry);
+    }
+    context
+}
+
+/// Documento y página(s) de un resultado, p. ej. "apuntes.pdf, p. 3"
+fn citation(hit: &SearchHit) -> String {
+    match hit.chunk.end_page.filter(|&end| end > hit.page_number) {
+        Some(end) => format!("{}, pp. {}-{}", hit.document_name, hit.page_number, end),
+        None => format!("{}, p. {}", hit.document_name, hit.page_number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+
+    fn hit(id: &str, document: &str, page: usize, text: &str, score: f32) -> SearchHit {
+        SearchHit {
+            chunk: Chunk::new(id.into(), "doc".into(), text.into(), 0, page),
+            score,
+            document_name: document.into(),
+            page_number: page,
+        }
+    }
+
+    fn hits() -> Vec<SearchHit> {
+        vec![
+            hit(
+                "c1",
+                "mecanica.pdf",
+                7,
+                "Las fuerzas cambian el movimiento.",
+                0.2,
+            ),
+            hit(
+                "c2",
+                "optica.pdf",
+                3,
+                "La luz se refracta al cambiar de medio.",
+                0.9,
+            ),
+            hit("c3", "optica.pdf", 4, "El índice del agua es 1,33.", 0.7),
+        ]
+    }
+
+    #[test]
+    fn test_prompt_has_question_and_citations() {
+        let prompt = build_rag_prompt(" ¿Por qué se refracta la luz? ", &hits(), 10_000);
+        assert!(prompt.contains("Pregunta: ¿Por qué se refracta la luz?\n\n"));
+        assert!(prompt.ends_with(INSTRUCTION));
+
+        // De mayor a menor puntaje, numerados
+        let first = prompt
+            .find("[1] optica.pdf, p. 3\nLa luz se refracta")
+            .unwrap();
+        let second = prompt.find("[2] optica.pdf, p. 4\nEl índice").unwrap();
+        let third = prompt.find("[3] mecanica.pdf, p. 7\nLas fuerzas").unwrap();
+        assert!(first < second && second < third);
+    }
+
+    #[test]
+    fn test_prompt_respects_budget() {
+        let all = context_block(&hits(), 10_000);
+        // Solo entra el mejor resultado
+        let budget = all.find("\n\n[2]").unwrap();
+        let context = context_block(&hits(), budget);
+        assert!(context.chars().count() <= budget);
+        assert!(context.starts_with("[1] optica.pdf, p. 3"));
+        assert!(!context.contains("[2]"));
+
+        // Un fragmento largo que no entra se salta, pero los siguientes sí
+        let mut with_long = hits();
+        with_long.push(hit("c4", "libro.pdf", 1, &"palabra ".repeat(500), 0.95));
+        let context = context_block(&with_long, 200);
+        assert!(context.chars().count() <= 200);
+        assert!(!context.contains("libro.pdf"));
+        assert!(context.starts_with("[1] optica.pdf, p. 3"));
+
+        // Sin espacio para ningún fragmento
+        let prompt = build_rag_prompt("¿Qué es la luz?", &hits(), 10);
+        assert!(prompt.contains(NO_CONTEXT));
+        assert!(prompt.contains("Pregunta: ¿Qué es la luz?"));
+    }
+
+    #[test]
+    fn test_citation_spans_pages() {
+        let mut spanning = hit("c", "optica.pdf", 3, "texto", 1.0);
+        spanning.chunk.end_page = Some(4);
+        assert_eq!(citation(&spanning), "optica.pdf, pp. 3-4");
+    }
+}
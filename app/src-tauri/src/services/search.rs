@@ -0,0 +1,1521 @@
+use crate::models::{Chunk, Document};
+use crate::services::database::{
+    decode_chunk, deleted_document_ids, document_chunk_keys, get_chunk, get_document,
+    get_documents_by_ids, iter_chunks, open_chunk_ids_tree, open_chunks_tree, DbError,
+};
+use crate::services::embeddings::{
+    cosine_similarity, dot, get_embedding_record, get_library_embedding_info, iter_embeddings,
+    normalize, StoredEmbedding,
+};
+use crate::services::error::EmbedError;
+use crate::services::keys::parse_chunk_key;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sled;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Indica si el texto contiene todos los términos de la búsqueda
+///
+/// La comparación ignora mayúsculas/minúsculas. Una búsqueda sin términos
+/// no coincide con nada.
+pub(crate) fn keyword_matches(text: &str, query: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut terms = query.split_whitespace().peekable();
+    if terms.peek().is_none() {
+        return false;
+    }
+    terms.all(|term| text.contains(&term.to_lowercase()))
+}
+
+/// Cuenta los chunks que coinciden con una búsqueda por palabras clave
+///
+/// Recorre los chunks una sola vez sin armar la lista de resultados, así la UI
+/// puede mostrar "N coincidencias" antes del ranking completo. El conteo es
+/// exacto.
+pub fn count_keyword_matches(db: &Arc<sled::Db>, query: &str) -> Result<usize, DbError> {
+    let deleted = deleted_document_ids(db)?;
+    let mut count = 0;
+    for chunk in iter_chunks(db)? {
+        let chunk = chunk?;
+        if !deleted.contains(&chunk.document_id) && keyword_matches(&chunk.text, query) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Chunk que coincide con una búsqueda por palabras clave
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeywordHit {
+    pub chunk_id: String,
+    pub document_id: String,
+    pub page_number: usize,
+    /// Fragmento del texto alrededor de la primera coincidencia
+    pub snippet: String,
+}
+
+/// Chunks que contienen todos los términos de `query`, en orden de
+/// documento, con un fragmento de `context_chars` a cada lado de la primera
+/// coincidencia
+pub fn search_chunks_by_keyword(
+    db: &Arc<sled::Db>,
+    query: &str,
+    limit: usize,
+    context_chars: usize,
+) -> Result<Vec<KeywordHit>, DbError> {
+    let deleted = deleted_document_ids(db)?;
+    let mut hits = Vec::new();
+    for chunk in iter_chunks(db)? {
+        if hits.len() >= limit {
+            break;
+        }
+        let chunk = chunk?;
+        if !deleted.contains(&chunk.document_id) && keyword_matches(&chunk.text, query) {
+            hits.push(KeywordHit {
+                snippet: make_snippet(&chunk.text, query, context_chars),
+                chunk_id: chunk.id,
+                document_id: chunk.document_id,
+                page_number: chunk.page_number,
+            });
+        }
+    }
+    Ok(hits)
+}
+
+/// Fragmento de `text` alrededor de la primera aparición de algún término
+/// de `query`
+///
+/// Se conservan la coincidencia completa y hasta `context_chars` caracteres
+/// a cada lado; si se recorta algo se agrega "…" al principio o al final. La
+/// búsqueda ignora mayúsculas/minúsculas. Sin coincidencias se devuelve el
+/// comienzo del texto.
+pub fn make_snippet(text: &str, query: &str, context_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    // Minúsculas carácter a carácter para que los índices sigan alineados
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let (start, len) = query
+        .split_whitespace()
+        .filter_map(|term| {
+            let term: Vec<char> = term
+                .chars()
+                .map(|c| c.to_lowercase().next().unwrap_or(c))
+                .collect();
+            lower
+                .windows(term.len())
+                .position(|w| w == term.as_slice())
+                .map(|pos| (pos, term.len()))
+        })
+        .min()
+        .unwrap_or((0, 0));
+
+    let from = start.saturating_sub(context_chars);
+    let to = (start + len + context_chars).min(chars.len());
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(&chars[from..to]);
+    if to < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Posiciones (en bytes) de cada aparición de `query` en el texto del chunk
+///
+/// La comparación ignora mayúsculas/minúsculas carácter a carácter, así que
+/// los offsets siempre caen en límites de carácter UTF-8 de `chunk.text`.
+/// Se incluyen apariciones superpuestas ("aa" aparece dos veces en "aaa").
+/// Una búsqueda vacía no encuentra nada.
+pub fn find_in_chunk(chunk: &Chunk, query: &str) -> Vec<usize> {
+    let query: Vec<char> = query.chars().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let text = &chunk.text;
+    text.char_indices()
+        .filter(|(start, _)| {
+            let mut rest = text[*start..].chars();
+            query.iter().all(|q| {
+                rest.next()
+                    .is_some_and(|c| c == *q || c.to_lowercase().eq(q.to_lowercase()))
+            })
+        })
+        .map(|(start, _)| start)
+        .collect()
+}
+
+/// Puntaje con orden total, para guardarlo en un `BinaryHeap`
+#[derive(Debug, Clone, PartialEq)]
+struct Scored(f32, String);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .total_cmp(&other.0)
+            .then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+/// Opciones de búsqueda vectorial
+///
+/// Se puede recibir tal cual desde el frontend (los campos omitidos toman su
+/// valor por defecto).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Cantidad máxima de resultados
+    pub k: usize,
+    /// Similitud mínima; los chunks por debajo se descartan aunque queden
+    /// menos de `k` resultados
+    pub min_score: Option<f32>,
+    /// Si se indica, solo se buscan chunks de ese documento
+    pub doc_filter: Option<String>,
+    /// Si se indica, los resultados se re-ordenan con `mmr_rerank` usando
+    /// este `lambda` (1.0 = solo relevancia, 0.0 = solo diversidad)
+    pub diversify: Option<f32>,
+    /// Hilos para calcular los puntajes; `None` usa todos los núcleos y
+    /// `Some(1)` busca en serie (útil si el LLM también está corriendo)
+    pub threads: Option<usize>,
+    /// Modelo con el que se calculó la consulta; si no es el de la
+    /// biblioteca la búsqueda falla con `EmbedError::ModelMismatch`. Sin él
+    /// igual falla si la consulta no tiene la dimensión de la biblioteca
+    pub model: Option<String>,
+    /// Buscar igual aunque la consulta no sea del modelo de la biblioteca
+    pub allow_model_mismatch: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            k: 5,
+            min_score: None,
+            doc_filter: None,
+            diversify: None,
+            threads: None,
+            model: None,
+            allow_model_mismatch: false,
+        }
+    }
+}
+
+/// Chunk encontrado junto con su similitud con la consulta
+pub type ScoredChunk = (Chunk, f32);
+
+/// Con `diversify`, cuántos candidatos por resultado pedido se re-ordenan
+pub const MMR_CANDIDATE_FACTOR: usize = 4;
+
+/// Busca los `top_k` chunks más parecidos a `query` por similitud coseno
+///
+/// Atajo de `search_similar_with` sin umbral ni filtro; una consulta de otro
+/// modelo que la biblioteca falla con `EmbedError::ModelMismatch`.
+pub fn search_similar(
+    db: &Arc<sled::Db>,
+    query: &[f32],
+    top_k: usize,
+) -> Result<Vec<(Chunk, f32)>, EmbedError> {
+    let options = SearchOptions {
+        k: top_k,
+        ..SearchOptions::default()
+    };
+    search_similar_with(db, query, &options)
+}
+
+/// Busca los `top_k` chunks de un solo documento más parecidos a `query`
+///
+/// Para la lectura enfocada en un documento abierto: los chunks de otros
+/// documentos no se puntúan, aunque estén más cerca de la consulta. Atajo
+/// de `search_similar_with` con `doc_filter`.
+pub fn search_in_document(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+    query: &[f32],
+    top_k: usize,
+) -> Result<Vec<ScoredChunk>, EmbedError> {
+    let options = SearchOptions {
+        k: top_k,
+        doc_filter: Some(document_id.to_string()),
+        ..SearchOptions::default()
+    };
+    search_similar_with(db, query, &options)
+}
+
+/// Resultado de búsqueda listo para la UI: el chunk con su documento
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub chunk: Chunk,
+    pub score: f32,
+    /// Nombre del documento, o `UNKNOWN_DOCUMENT_NAME` si ya no existe
+    pub document_name: String,
+    pub page_number: usize,
+}
+
+/// Nombre que se muestra para chunks cuyo documento no está en la BD
+pub const UNKNOWN_DOCUMENT_NAME: &str = "<unknown>";
+
+/// Igual que `search_similar`, pero cada resultado trae el nombre del documento
+///
+/// Los documentos se leen todos juntos con `get_documents_by_ids`.
+pub fn search(
+    db: &Arc<sled::Db>,
+    query_embedding: &[f32],
+    top_k: usize,
+) -> Result<Vec<SearchHit>, EmbedError> {
+    let results = search_similar(db, query_embedding, top_k)?;
+    Ok(with_document_names(db, results)?)
+}
+
+/// Convierte resultados puntuados en `SearchHit`, leyendo los documentos de
+/// una sola vez
+fn with_document_names(
+    db: &Arc<sled::Db>,
+    results: Vec<ScoredChunk>,
+) -> Result<Vec<SearchHit>, DbError> {
+    let mut ids: Vec<&str> = results
+        .iter()
+        .map(|(c, _)| c.document_id.as_str())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let names: HashMap<String, String> = get_documents_by_ids(db, &ids)?
+        .into_iter()
+        .map(|d| (d.id, d.name))
+        .collect();
+
+    Ok(results
+        .into_iter()
+        .map(|(chunk, score)| SearchHit {
+            document_name: names
+                .get(&chunk.document_id)
+                .cloned()
+                .unwrap_or_else(|| UNKNOWN_DOCUMENT_NAME.to_string()),
+            page_number: chunk.page_number,
+            chunk,
+            score,
+        })
+        .collect())
+}
+
+/// Peso por defecto del puntaje semántico en `hybrid_search`
+pub const HYBRID_SEMANTIC_WEIGHT: f32 = 0.7;
+
+/// Búsqueda híbrida con `HYBRID_SEMANTIC_WEIGHT`
+///
+/// Ver `hybrid_search_with`.
+pub fn hybrid_search(
+    db: &Arc<sled::Db>,
+    query_text: &str,
+    query_embedding: &[f32],
+    top_k: usize,
+) -> Result<Vec<SearchHit>, DbError> {
+    hybrid_search_with(
+        db,
+        query_text,
+        query_embedding,
+        top_k,
+        HYBRID_SEMANTIC_WEIGHT,
+    )
+}
+
+/// Busca combinando similitud vectorial y coincidencia de palabras clave
+///
+/// Cada chunk recibe `semantic_weight * coseno + (1 - semantic_weight) *
+/// keyword_score`, donde el coseno negativo cuenta como 0 y
+/// `keyword_score` es la fracción de términos de `query_text` que aparecen
+/// en el chunk. Así un chunk con la palabra exacta pero un vector mediocre
+/// puede superar a uno solo parecido en el espacio vectorial. Los chunks
+/// sin embedding compiten solo con su puntaje de palabras clave. El peso se
+/// limita a [0, 1]; los chunks con puntaje 0 no se retornan. Retorna hasta
+/// `top_k` resultados de mayor a menor puntaje combinado.
+pub fn hybrid_search_with(
+    db: &Arc<sled::Db>,
+    query_text: &str,
+    query_embedding: &[f32],
+    top_k: usize,
+    semantic_weight: f32,
+) -> Result<Vec<SearchHit>, DbError> {
+    if top_k == 0 {
+        return Ok(Vec::new());
+    }
+    let weight = semantic_weight.clamp(0.0, 1.0);
+    let mut terms: Vec<String> = query_text
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    terms.sort_unstable();
+    terms.dedup();
+    let mut unit_query = query_embedding.to_vec();
+    normalize(&mut unit_query);
+    let embeddings: HashMap<String, StoredEmbedding> =
+        iter_embeddings(db)?.collect::<Result<_, _>>()?;
+    let deleted = deleted_document_ids(db)?;
+
+    let mut heap = TopK::new();
+    for chunk in iter_chunks(db)? {
+        let chunk = chunk?;
+        if deleted.contains(&chunk.document_id) {
+            continue;
+        }
+        let semantic = embeddings
+            .get(&chunk.id)
+            .map(|e| similarity(query_embedding, &unit_query, e).max(0.0))
+            .unwrap_or(0.0);
+        let score = weight * semantic + (1.0 - weight) * keyword_score(&chunk.text, &terms);
+        if score <= 0.0 {
+            continue;
+        }
+        heap = push_bounded(heap, Scored(score, chunk.id), top_k);
+    }
+
+    let mut ranked: Vec<Scored> = heap.into_iter().map(|Reverse(s)| s).collect();
+    ranked.sort_by(|a, b| b.cmp(a));
+    let mut results = Vec::with_capacity(ranked.len());
+    for Scored(score, chunk_id) in ranked {
+        if let Some(chunk) = get_chunk(db, &chunk_id)? {
+            results.push((chunk, score));
+        }
+    }
+    with_document_names(db, results)
+}
+
+/// Fracción de `terms` (ya en minúsculas) que aparecen en `text`
+fn keyword_score(text: &str, terms: &[String]) -> f32 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let text = text.to_lowercase();
+    let found = terms.iter().filter(|t| text.contains(t.as_str())).count();
+    found as f32 / terms.len() as f32
+}
+
+/// Pool de rayon con `threads` hilos para las búsquedas
+///
+/// Se crea la primera vez que se pide cada tamaño y después se reutiliza, para
+/// no levantar y cerrar hilos en cada consulta.
+fn thread_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, DbError> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| DbError::InvalidInput(format!("search thread pool: {}", e)))?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, Arc::clone(&pool));
+    Ok(pool)
+}
+
+/// Busca los chunks más parecidos a `query` según `options`
+///
+/// Compara contra todos los embeddings guardados (fuerza bruta), repartiendo
+/// el cálculo entre hilos con rayon a medida que se leen del árbol (no se
+/// cargan todos en memoria), y mantiene solo los `k` mejores. Los
+/// vectores guardados normalizados se comparan con un producto punto contra
+/// la consulta normalizada, que da el mismo puntaje sin recalcular normas;
+/// si toda la biblioteca está normalizada (ver `normalize_embeddings`) nunca
+/// se calcula un coseno completo. Con `doc_filter` solo se leen los
+/// embeddings de ese documento. Retorna los chunks de mayor a menor
+/// similitud; puede ser vacío si nada supera `min_score`. Con `diversify` se
+/// toman `k * MMR_CANDIDATE_FACTOR` candidatos y se eligen `k` con
+/// `mmr_rerank`.
+pub fn search_similar_with(
+    db: &Arc<sled::Db>,
+    query: &[f32],
+    options: &SearchOptions,
+) -> Result<Vec<ScoredChunk>, EmbedError> {
+    if !options.allow_model_mismatch {
+        check_query_model(db, query, options.model.as_deref())?;
+    }
+    if options.k == 0 {
+        return Ok(Vec::new());
+    }
+    let pool = match options.diversify {
+        Some(_) => options.k.saturating_mul(MMR_CANDIDATE_FACTOR),
+        None => options.k,
+    };
+    let mut unit_query = query.to_vec();
+    normalize(&mut unit_query);
+
+    let candidates: Candidates = match &options.doc_filter {
+        Some(doc_id) => Box::new(document_embeddings(db, doc_id)?.into_iter().map(Ok)),
+        None => Box::new(iter_embeddings(db)?),
+    };
+    let hidden = hidden_chunk_ids(db)?;
+
+    let score = |heap: TopK, candidate: Result<(String, StoredEmbedding), DbError>| {
+        let (chunk_id, embedding) = candidate?;
+        if hidden.contains(&chunk_id) {
+            return Ok(heap);
+        }
+        let score = similarity(query, &unit_query, &embedding);
+        if options.min_score.is_some_and(|min| score < min) {
+            return Ok(heap);
+        }
+        Ok::<_, DbError>(push_bounded(heap, Scored(score, chunk_id), pool))
+    };
+    // Cada hilo arma su propio top-k con lo que le toca del árbol y al final
+    // se combinan; como el orden de `Scored` es total, el resultado es el
+    // mismo que recorriendo en serie
+    let parallel = |candidates: Candidates| {
+        candidates
+            .par_bridge()
+            .try_fold(TopK::new, score)
+            .try_reduce(TopK::new, |a, b| {
+                Ok(b.into_iter()
+                    .fold(a, |heap, Reverse(s)| push_bounded(heap, s, pool)))
+            })
+    };
+    let heap = match options.threads {
+        Some(1) => candidates.into_iter().try_fold(TopK::new(), score)?,
+        Some(threads) => thread_pool(threads)?.install(|| parallel(candidates))?,
+        None => parallel(candidates)?,
+    };
+
+    let mut ranked: Vec<Scored> = heap.into_iter().map(|Reverse(s)| s).collect();
+    ranked.sort_by(|a, b| b.cmp(a));
+
+    let mut out = Vec::with_capacity(ranked.len());
+    for Scored(score, chunk_id) in ranked {
+        if let Some(chunk) = get_chunk(db, &chunk_id)? {
+            out.push((chunk, score));
+        }
+    }
+    match options.diversify {
+        Some(lambda) => Ok(mmr_rerank(db, &out, query, lambda, options.k)?),
+        None => Ok(out),
+    }
+}
+
+/// Verifica que la consulta venga del modelo de la biblioteca (el registrado
+/// en "meta", ver `get_library_embedding_info`)
+///
+/// Sin `model` solo se puede comparar la dimensión del vector; una consulta
+/// de otra dimensión es de otro modelo. Una biblioteca sin embeddings acepta
+/// cualquier consulta.
+fn check_query_model(
+    db: &Arc<sled::Db>,
+    query: &[f32],
+    model: Option<&str>,
+) -> Result<(), EmbedError> {
+    let Some(info) = get_library_embedding_info(db)? else {
+        return Ok(());
+    };
+    let requested = match model {
+        Some(model) if model != info.model => model.to_string(),
+        _ if query.len() != info.dimension => format!(
+            "{} ({} dimensions)",
+            model.unwrap_or("unknown model"),
+            query.len()
+        ),
+        _ => return Ok(()),
+    };
+    Err(EmbedError::ModelMismatch {
+        stored: info.model,
+        requested,
+    })
+}
+
+/// Ids de los chunks de documentos borrados con `soft_delete_document`
+///
+/// Solo recorre claves, y nada si no hay documentos borrados.
+fn hidden_chunk_ids(db: &Arc<sled::Db>) -> Result<HashSet<String>, DbError> {
+    let deleted = deleted_document_ids(db)?;
+    let mut hidden = HashSet::new();
+    if deleted.is_empty() {
+        return Ok(hidden);
+    }
+    let chunks = open_chunks_tree(db)?;
+    for document_id in &deleted {
+        for key in document_chunk_keys(&chunks, document_id)? {
+            if let Some(parsed) = parse_chunk_key(&key) {
+                hidden.insert(parsed.chunk_id);
+            }
+        }
+    }
+    Ok(hidden)
+}
+
+/// Embeddings a comparar en `search_similar_with`, leídos de a uno
+type Candidates = Box<dyn Iterator<Item = Result<(String, StoredEmbedding), DbError>> + Send>;
+
+/// Re-ordena candidatos con Maximal Marginal Relevance
+///
+/// Elige de a uno el candidato que maximiza
+/// `lambda * relevancia - (1 - lambda) * similitud máxima con los ya elegidos`,
+/// así un párrafo repetido en varias páginas no ocupa todos los resultados.
+/// La relevancia es el puntaje que trae cada candidato; la similitud entre
+/// candidatos se calcula con sus embeddings guardados (un candidato sin
+/// embedding no penaliza a nadie). `lambda` se limita a [0, 1]. Retorna
+/// hasta `k` candidatos en el orden de selección, con su puntaje original.
+pub fn mmr_rerank(
+    db: &Arc<sled::Db>,
+    candidates: &[ScoredChunk],
+    query: &[f32],
+    lambda: f32,
+    k: usize,
+) -> Result<Vec<ScoredChunk>, DbError> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let mut vectors = Vec::with_capacity(candidates.len());
+    for (chunk, _) in candidates {
+        let vector = match get_embedding_record(db, &chunk.id)? {
+            Some(record) => record.vector,
+            // Sin vector guardado: basta con que sea nulo para no penalizar
+            None => vec![0.0; query.len()],
+        };
+        vectors.push(vector);
+    }
+
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut selected: Vec<usize> = Vec::with_capacity(k.min(candidates.len()));
+    while selected.len() < k && !remaining.is_empty() {
+        let mut best = 0;
+        let mut best_score = f32::NEG_INFINITY;
+        for (pos, &i) in remaining.iter().enumerate() {
+            let redundancy = selected
+                .iter()
+                .map(|&j| cosine_similarity(&vectors[i], &vectors[j]))
+                .fold(0.0f32, f32::max);
+            let score = lambda * candidates[i].1 - (1.0 - lambda) * redundancy;
+            if score > best_score {
+                best = pos;
+                best_score = score;
+            }
+        }
+        selected.push(remaining.remove(best));
+    }
+    Ok(selected
+        .into_iter()
+        .map(|i| candidates[i].clone())
+        .collect())
+}
+
+/// Mejores puntajes vistos hasta ahora (el peor queda arriba para descartarlo)
+type TopK = BinaryHeap<Reverse<Scored>>;
+
+fn push_bounded(mut heap: TopK, scored: Scored, limit: usize) -> TopK {
+    heap.push(Reverse(scored));
+    if heap.len() > limit {
+        heap.pop();
+    }
+    heap
+}
+
+/// Embeddings de los chunks de un documento (los chunks sin vector se omiten)
+fn document_embeddings(
+    db: &Arc<sled::Db>,
+    document_id: &str,
+) -> Result<Vec<(String, StoredEmbedding)>, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let mut out = Vec::new();
+    for key in document_chunk_keys(&chunks, document_id)? {
+        let Some(parsed) = parse_chunk_key(&key) else {
+            continue;
+        };
+        if let Some(embedding) = get_embedding_record(db, &parsed.chunk_id)? {
+            out.push((parsed.chunk_id, embedding));
+        }
+    }
+    Ok(out)
+}
+
+/// Similitud entre la consulta y un embedding guardado
+///
+/// `unit_query` es `query` ya normalizada, para el atajo del producto punto.
+fn similarity(query: &[f32], unit_query: &[f32], embedding: &StoredEmbedding) -> f32 {
+    if embedding.normalized {
+        dot(unit_query, &embedding.vector)
+    } else {
+        cosine_similarity(query, &embedding.vector)
+    }
+}
+
+/// Cómo se combina el puntaje de los chunks de un documento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreAggregation {
+    /// El mejor chunk decide
+    #[default]
+    Max,
+    /// Promedio de los mejores chunks del documento (los que se retornan)
+    Mean,
+}
+
+/// Documento encontrado por `search_similar_grouped`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentHit {
+    pub document: Document,
+    /// Puntaje combinado del documento
+    pub score: f32,
+    /// Mejores chunks del documento, de mayor a menor similitud
+    pub chunks: Vec<(Chunk, f32)>,
+}
+
+/// Busca los `k_docs` documentos más relevantes para `query`
+///
+/// Puntúa todos los chunks con embedding, los agrupa por documento y combina
+/// los `chunks_per_doc` mejores de cada uno según `aggregation`. Un documento
+/// con un solo chunk puntúa igual con ambos criterios. Los empates se ordenan
+/// por id de documento para que el resultado sea estable. Los chunks de
+/// documentos que ya no existen o están borrados se ignoran.
+pub fn search_similar_grouped(
+    db: &Arc<sled::Db>,
+    query: &[f32],
+    k_docs: usize,
+    chunks_per_doc: usize,
+    aggregation: ScoreAggregation,
+) -> Result<Vec<DocumentHit>, DbError> {
+    if k_docs == 0 || chunks_per_doc == 0 {
+        return Ok(Vec::new());
+    }
+    let mut unit_query = query.to_vec();
+    normalize(&mut unit_query);
+
+    let ids = open_chunk_ids_tree(db)?;
+    let mut by_document: HashMap<String, Vec<Scored>> = HashMap::new();
+    for item in iter_embeddings(db)? {
+        let (chunk_id, embedding) = item?;
+        let Some(key) = ids.get(chunk_id.as_bytes())? else {
+            continue;
+        };
+        let Some(parsed) = parse_chunk_key(&key) else {
+            continue;
+        };
+        let score = similarity(query, &unit_query, &embedding);
+        by_document
+            .entry(parsed.document_id)
+            .or_default()
+            .push(Scored(score, chunk_id));
+    }
+
+    let mut ranked: Vec<(Scored, Vec<Scored>)> = by_document
+        .into_iter()
+        .map(|(document_id, mut scores)| {
+            scores.sort_by(|a, b| b.cmp(a));
+            scores.truncate(chunks_per_doc);
+            let score = match aggregation {
+                ScoreAggregation::Max => scores[0].0,
+                ScoreAggregation::Mean => {
+                    scores.iter().map(|s| s.0).sum::<f32>() / scores.len() as f32
+                }
+            };
+            (Scored(score, document_id), scores)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut out = Vec::with_capacity(k_docs);
+    for (Scored(score, document_id), scores) in ranked {
+        if out.len() == k_docs {
+            break;
+        }
+        let Some(document) = get_document(db, &document_id)? else {
+            continue;
+        };
+        if document.is_deleted() {
+            continue;
+        }
+        let mut chunks = Vec::with_capacity(scores.len());
+        for Scored(chunk_score, chunk_id) in scores {
+            if let Some(chunk) = get_chunk(db, &chunk_id)? {
+                chunks.push((chunk, chunk_score));
+            }
+        }
+        out.push(DocumentHit {
+            document,
+            score,
+            chunks,
+        });
+    }
+    Ok(out)
+}
+
+/// Resultado de una estimación por muestreo
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct MatchEstimate {
+    /// Cantidad estimada de chunks que coinciden
+    pub estimate: usize,
+    /// Total de chunks en la biblioteca
+    pub total: usize,
+    /// Cantidad de chunks evaluados
+    pub sampled: usize,
+    /// Margen de error aproximado (intervalo de confianza del 95%), en chunks
+    pub margin: usize,
+}
+
+/// Estima cuántos chunks cumplen `predicate` evaluando una muestra aleatoria
+///
+/// Pensado para búsquedas caras de evaluar en toda la biblioteca, como la
+/// búsqueda vectorial (p. ej. "similitud >= umbral"). Se toma una muestra
+/// uniforme de `sample_size` chunks (reservoir sampling sobre las claves, así
+/// solo se deserializan los chunks muestreados) y se escala la proporción
+/// observada `p` al total `N`.
+///
+/// Precisión: con `n` muestras el margen al 95% es aproximadamente
+/// `1.96 * sqrt(p * (1 - p) / n) * sqrt((N - n) / (N - 1)) * N`. Con
+/// `n = 400` el error queda por debajo de ±5% del total; si `n >= N` el
+/// resultado es exacto (margen 0).
+pub fn estimate_match_count_sampled(
+    db: &Arc<sled::Db>,
+    sample_size: usize,
+    predicate: impl Fn(&Chunk) -> bool,
+) -> Result<MatchEstimate, DbError> {
+    let chunks = open_chunks_tree(db)?;
+    let mut rng = SplitMix64::from_time();
+
+    // Reservoir sampling (algoritmo R) sobre las claves
+    let mut reservoir: Vec<sled::IVec> = Vec::with_capacity(sample_size);
+    let mut total = 0;
+    for key in chunks.iter().keys() {
+        let key = key?;
+        total += 1;
+        if reservoir.len() < sample_size {
+            reservoir.push(key);
+        } else {
+            let j = rng.next_below(total);
+            if j < sample_size {
+                reservoir[j] = key;
+            }
+        }
+    }
+
+    let mut hits = 0;
+    let mut sampled = 0;
+    for key in &reservoir {
+        if let Some(bytes) = chunks.get(key)? {
+            let chunk = decode_chunk(&bytes)?;
+            sampled += 1;
+            if predicate(&chunk) {
+                hits += 1;
+            }
+        }
+    }
+
+    if sampled == 0 {
+        return Ok(MatchEstimate {
+            estimate: 0,
+            total,
+            sampled,
+            margin: 0,
+        });
+    }
+
+    let p = hits as f64 / sampled as f64;
+    let n = total as f64;
+    let margin = if sampled >= total {
+        0.0
+    } else {
+        let correction = ((n - sampled as f64) / (n - 1.0)).sqrt();
+        1.96 * (p * (1.0 - p) / sampled as f64).sqrt() * correction * n
+    };
+
+    Ok(MatchEstimate {
+        estimate: (p * n).round() as usize,
+        total,
+        sampled,
+        margin: margin.ceil() as usize,
+    })
+}
+
+/// Generador pseudoaleatorio mínimo (SplitMix64) para el muestreo
+///
+/// No necesita calidad criptográfica; evita sumar una dependencia solo para esto.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0x9E37_79B9_7F4A_7C15);
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Número uniforme en `0..bound`
+    fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{
+        get_chunks_for_document, insert_chunk, insert_document, restore_document,
+        soft_delete_document,
+    };
+    use crate::services::embeddings::{insert_embedding, normalize_embeddings};
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db};
+
+    fn seed_library(db: &Arc<sled::Db>) {
+        let texts = [
+            "El aprendizaje automático usa datos",
+            "Redes neuronales y aprendizaje profundo",
+            "Historia de la imprenta",
+            "APRENDIZAJE por refuerzo",
+            "Recetas de cocina",
+        ];
+        let doc = sample_document();
+        insert_document(db, &doc).unwrap();
+        for (i, text) in texts.iter().enumerate() {
+            let chunk = sample_chunk(i, *text);
+            insert_chunk(db, &chunk).unwrap();
+        }
+    }
+
+    /// Vector pseudoaleatorio en [-1, 1) con una escala distinta por vector
+    fn random_vector(rng: &mut SplitMix64, dim: usize) -> Vec<f32> {
+        let scale = 1.0 + rng.next_below(100) as f32;
+        (0..dim)
+            .map(|_| (rng.next_below(2_000) as f32 / 1_000.0 - 1.0) * scale)
+            .collect()
+    }
+
+    fn seed_vectors(db: &Arc<sled::Db>, n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = SplitMix64(seed);
+        let doc = Document::new("doc-v".into(), "v.pdf".into(), "/tmp/v.pdf".into(), 1);
+        insert_document(db, &doc).unwrap();
+        let mut vectors = Vec::new();
+        for i in 0..n {
+            let id = format!("v-{}", i);
+            insert_chunk(
+                db,
+                &Chunk::new(id.clone(), "doc-v".into(), "t".into(), i, 1),
+            )
+            .unwrap();
+            let v = random_vector(&mut rng, dim);
+            insert_embedding(db, &id, &v, "test", dim).unwrap();
+            vectors.push(v);
+        }
+        vectors
+    }
+
+    #[test]
+    fn test_search_similar_finds_exact_vector() {
+        let (db, path) = temp_db("test_search_similar");
+        let vectors = seed_vectors(&db, 30, 8, 7);
+
+        let hits = search_similar(&db, &vectors[12], 3).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].0.id, "v-12");
+        assert!((hits[0].1 - 1.0).abs() < 1e-5);
+        assert!(hits[0].1 >= hits[1].1 && hits[1].1 >= hits[2].1);
+
+        assert!(search_similar(&db, &vectors[0], 0).unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_min_score_drops_unrelated_chunks() {
+        let (db, path) = temp_db("test_search_min_score");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for (i, v) in [[0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].iter().enumerate() {
+            let id = format!("c-{}", i);
+            insert_chunk(
+                &db,
+                &Chunk::new(id.clone(), "doc-1".into(), "t".into(), i, 1),
+            )
+            .unwrap();
+            insert_embedding(&db, &id, v, "test", 3).unwrap();
+        }
+
+        // Consulta ortogonal a todo: sin umbral igual devuelve k resultados
+        let query = [1.0, 0.0, 0.0];
+        assert_eq!(search_similar(&db, &query, 2).unwrap().len(), 2);
+
+        let options = SearchOptions {
+            k: 2,
+            min_score: Some(0.9),
+            ..SearchOptions::default()
+        };
+        assert!(search_similar_with(&db, &query, &options)
+            .unwrap()
+            .is_empty());
+
+        let hits = search_similar_with(&db, &[0.0, 1.0, 0.1], &options).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.id, "c-0");
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_similar_grouped() {
+        let (db, path) = temp_db("test_search_grouped");
+        let library: [(&str, &[[f32; 3]]); 3] = [
+            ("doc-a", &[[1.0, 0.3, 0.0], [1.0, 0.35, 0.0]]),
+            ("doc-b", &[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]),
+            ("doc-c", &[[0.0, 0.0, 1.0]]),
+        ];
+        for (doc_id, vectors) in library {
+            let doc = Document::new(doc_id.into(), "x.pdf".into(), "/tmp/x.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for (i, v) in vectors.iter().enumerate() {
+                let id = format!("{}-{}", doc_id, i);
+                insert_chunk(
+                    &db,
+                    &Chunk::new(id.clone(), doc_id.into(), "t".into(), i, 1),
+                )
+                .unwrap();
+                insert_embedding(&db, &id, v, "test", 3).unwrap();
+            }
+        }
+        let query = [1.0, 0.0, 0.0];
+        let ids = |hits: &[DocumentHit]| -> Vec<String> {
+            hits.iter().map(|h| h.document.id.clone()).collect()
+        };
+
+        // Con el máximo gana el documento con el chunk idéntico
+        let hits = search_similar_grouped(&db, &query, 3, 2, ScoreAggregation::Max).unwrap();
+        assert_eq!(ids(&hits), ["doc-b", "doc-a", "doc-c"]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        assert_eq!(hits[0].chunks[0].0.id, "doc-b-0");
+        let best_a: Vec<&str> = hits[1].chunks.iter().map(|(c, _)| c.id.as_str()).collect();
+        assert_eq!(best_a, ["doc-a-0", "doc-a-1"]);
+        assert_eq!(hits[2].chunks.len(), 1);
+
+        // Con el promedio gana el documento consistentemente relevante
+        let hits = search_similar_grouped(&db, &query, 2, 2, ScoreAggregation::Mean).unwrap();
+        assert_eq!(ids(&hits), ["doc-a", "doc-b"]);
+        assert!((hits[1].score - 0.5).abs() < 1e-6);
+
+        // Con un solo chunk por documento ambos criterios coinciden
+        let max = search_similar_grouped(&db, &query, 3, 1, ScoreAggregation::Max).unwrap();
+        let mean = search_similar_grouped(&db, &query, 3, 1, ScoreAggregation::Mean).unwrap();
+        assert_eq!(max, mean);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_mmr_skips_near_duplicates() {
+        let (db, path) = temp_db("test_search_mmr");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        // Tres copias casi idénticas del mismo párrafo y un chunk distinto
+        let vectors = [
+            [1.0, 0.1, 0.0],
+            [1.0, 0.1, 0.001],
+            [1.0, 0.11, 0.0],
+            [0.7, -0.7, 0.1],
+            [0.0, 1.0, 0.0],
+        ];
+        for (i, v) in vectors.iter().enumerate() {
+            let id = format!("c-{}", i);
+            insert_chunk(
+                &db,
+                &Chunk::new(id.clone(), "doc-1".into(), "t".into(), i, 1),
+            )
+            .unwrap();
+            insert_embedding(&db, &id, v, "test", 3).unwrap();
+        }
+        let query = [1.0, 0.0, 0.0];
+        let ids = |hits: &[ScoredChunk]| -> Vec<String> {
+            hits.iter().map(|(c, _)| c.id.clone()).collect()
+        };
+
+        let plain = search_similar(&db, &query, 2).unwrap();
+        assert!(ids(&plain).iter().all(|id| id != "c-3"));
+
+        let options = SearchOptions {
+            k: 2,
+            diversify: Some(0.5),
+            ..SearchOptions::default()
+        };
+        let diverse = search_similar_with(&db, &query, &options).unwrap();
+        assert_eq!(diverse.len(), 2);
+        assert_eq!(diverse[0].0.id, plain[0].0.id);
+        assert_eq!(diverse[1].0.id, "c-3");
+
+        // Con lambda = 1 solo cuenta la relevancia: mismo orden que sin MMR
+        let candidates = search_similar(&db, &query, 5).unwrap();
+        let reranked = mmr_rerank(&db, &candidates, &query, 1.0, 5).unwrap();
+        assert_eq!(ids(&reranked), ids(&candidates));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_parallel_search_matches_serial() {
+        let (db, path) = temp_db("test_search_parallel");
+        let mut rng = SplitMix64(99);
+        seed_vectors(&db, 2_000, 16, 7);
+        let query = random_vector(&mut rng, 16);
+
+        let serial = SearchOptions {
+            k: 25,
+            threads: Some(1),
+            ..SearchOptions::default()
+        };
+        let expected = search_similar_with(&db, &query, &serial).unwrap();
+        assert_eq!(expected.len(), 25);
+        for threads in [None, Some(2), Some(4)] {
+            let options = SearchOptions {
+                threads,
+                ..serial.clone()
+            };
+            assert_eq!(
+                search_similar_with(&db, &query, &options).unwrap(),
+                expected
+            );
+        }
+        // El pool de cada tamaño se crea una sola vez
+        assert!(Arc::ptr_eq(
+            &thread_pool(2).unwrap(),
+            &thread_pool(2).unwrap()
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_quantized_search_recall() {
+        let (db, path) = temp_db("test_search_q8_recall");
+        crate::services::database::set_embedding_quantization(&db, true);
+        let doc = Document::new("doc-v".into(), "v.pdf".into(), "/tmp/v.pdf".into(), 1);
+        insert_document(&db, &doc).unwrap();
+
+        // 20 grupos de 50 vectores alrededor de centros aleatorios
+        let dim = 64;
+        let mut rng = SplitMix64(5);
+        let centers: Vec<Vec<f32>> = (0..20).map(|_| random_vector(&mut rng, dim)).collect();
+        let mut exact = Vec::new();
+        for i in 0..1_000 {
+            let center = &centers[i % centers.len()];
+            let noise = random_vector(&mut rng, dim);
+            let v: Vec<f32> = center
+                .iter()
+                .zip(&noise)
+                .map(|(c, n)| c + n * 0.02)
+                .collect();
+            let id = format!("v-{}", i);
+            insert_chunk(
+                &db,
+                &Chunk::new(id.clone(), "doc-v".into(), "t".into(), i, 1),
+            )
+            .unwrap();
+            insert_embedding(&db, &id, &v, "test", dim).unwrap();
+            exact.push((id, v));
+        }
+
+        // recall@10 contra la búsqueda exacta en f32
+        let mut hits = 0;
+        let queries = 20;
+        for center in centers.iter().take(queries) {
+            let query = random_vector(&mut rng, dim);
+            let query: Vec<f32> = center
+                .iter()
+                .zip(&query)
+                .map(|(c, n)| c + n * 0.02)
+                .collect();
+            let mut baseline: Vec<(f32, &str)> = exact
+                .iter()
+                .map(|(id, v)| (cosine_similarity(&query, v), id.as_str()))
+                .collect();
+            baseline.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let found = search_similar(&db, &query, 10).unwrap();
+            hits += baseline[..10]
+                .iter()
+                .filter(|(_, id)| found.iter().any(|(c, _)| c.id == *id))
+                .count();
+        }
+        let recall = hits as f32 / (queries * 10) as f32;
+        assert!(recall >= 0.9, "recall@10 = {}", recall);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_hits_include_document_name() {
+        let (db, path) = temp_db("test_search_hits");
+        let doc = Document::new(
+            "doc-1".into(),
+            "Biología.pdf".into(),
+            "/tmp/b.pdf".into(),
+            3,
+        );
+        insert_document(&db, &doc).unwrap();
+        insert_chunk(
+            &db,
+            &Chunk::new("c-0".into(), "doc-1".into(), "t".into(), 0, 3),
+        )
+        .unwrap();
+        insert_embedding(&db, "c-0", &[1.0, 0.0], "test", 2).unwrap();
+        let orphan = Document::new("doc-2".into(), "b.pdf".into(), "/tmp/b.pdf".into(), 1);
+        insert_document(&db, &orphan).unwrap();
+        insert_chunk(
+            &db,
+            &Chunk::new("c-1".into(), "doc-2".into(), "t".into(), 0, 1),
+        )
+        .unwrap();
+        insert_embedding(&db, "c-1", &[0.5, 0.5], "test", 2).unwrap();
+        // Documento borrado sin pasar por delete_document: sus chunks quedan huérfanos
+        crate::services::database::open_documents_tree(&db)
+            .unwrap()
+            .remove("doc-2")
+            .unwrap();
+
+        let hits = search(&db, &[1.0, 0.0], 2).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].chunk.id, "c-0");
+        assert_eq!(hits[0].document_name, "Biología.pdf");
+        assert_eq!(hits[0].page_number, 3);
+        assert_eq!(hits[1].document_name, UNKNOWN_DOCUMENT_NAME);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_options_from_json() {
+        let options: SearchOptions = serde_json::from_str(r#"{"k": 3, "min_score": 0.5}"#).unwrap();
+        assert_eq!(options.k, 3);
+        assert_eq!(options.min_score, Some(0.5));
+        assert_eq!(options.doc_filter, None);
+
+        let defaults: SearchOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults, SearchOptions::default());
+    }
+
+    #[test]
+    fn test_normalized_and_cosine_paths_rank_identically() {
+        let (db, path) = temp_db("test_search_normalized");
+        seed_vectors(&db, 60, 16, 42);
+        let query = random_vector(&mut SplitMix64(99), 16);
+
+        let before = search_similar(&db, &query, 10).unwrap();
+
+        let mut reports = Vec::new();
+        let converted =
+            normalize_embeddings(&db, |done, total| reports.push((done, total))).unwrap();
+        assert_eq!(converted, 60);
+        assert_eq!(reports.last(), Some(&(60, 60)));
+        // Una segunda pasada no tiene nada que convertir
+        assert_eq!(normalize_embeddings(&db, |_, _| {}).unwrap(), 0);
+
+        let after = search_similar(&db, &query, 10).unwrap();
+        let ids =
+            |hits: &[(Chunk, f32)]| hits.iter().map(|(c, _)| c.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&before), ids(&after));
+        for ((_, a), (_, b)) in before.iter().zip(&after) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_keyword_matches() {
+        assert!(keyword_matches("Redes Neuronales", "neuronales"));
+        assert!(keyword_matches(
+            "redes neuronales profundas",
+            "profundas REDES"
+        ));
+        assert!(!keyword_matches("redes neuronales", "redes bayesianas"));
+        assert!(!keyword_matches("cualquier texto", "   "));
+    }
+
+    #[test]
+    fn test_make_snippet_long_text() {
+        let text = format!(
+            "{} La fotosíntesis ocurre en los cloroplastos. {}",
+            "relleno ".repeat(200),
+            "más relleno ".repeat(200)
+        );
+        let snippet = make_snippet(&text, "FOTOSÍNTESIS", 30);
+        assert!(snippet.contains("fotosíntesis"));
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        // Coincidencia + contexto a cada lado + los dos "…"
+        assert_eq!(snippet.chars().count(), "fotosíntesis".len() - 1 + 60 + 2);
+
+        // Coincidencia al principio: sin "…" adelante
+        assert_eq!(make_snippet("Redes neuronales", "redes", 3), "Redes ne…");
+        // Texto corto: se devuelve completo
+        assert_eq!(make_snippet("hola mundo", "mundo", 50), "hola mundo");
+        // Sin coincidencia: el comienzo del texto
+        assert_eq!(make_snippet("abcdefgh", "xyz", 3), "abc…");
+    }
+
+    #[test]
+    fn test_find_in_chunk_positions() {
+        let text = "Óptica: la óptica estudia la luz. ÓPTICA aplicada.";
+        let chunk = sample_chunk(0, text);
+
+        let found = find_in_chunk(&chunk, "óptica");
+        assert_eq!(found.len(), 3);
+        for &at in &found {
+            assert!(text.is_char_boundary(at));
+            assert_eq!(
+                text[at..]
+                    .chars()
+                    .take(6)
+                    .collect::<String>()
+                    .to_lowercase(),
+                "óptica"
+            );
+        }
+        assert_eq!(found[0], 0);
+        assert_eq!(&text[found[1]..found[1] + "óptica".len()], "óptica");
+
+        // Superpuestas y repetidas
+        let chunk = sample_chunk(1, "aaaa");
+        assert_eq!(find_in_chunk(&chunk, "AA"), [0, 1, 2]);
+        assert!(find_in_chunk(&chunk, "").is_empty());
+        assert!(find_in_chunk(&chunk, "b").is_empty());
+    }
+
+    #[test]
+    fn test_search_chunks_by_keyword() {
+        let (db, path) = temp_db("test_keyword_search");
+        seed_library(&db);
+
+        let hits = search_chunks_by_keyword(&db, "aprendizaje", 10, 5).unwrap();
+        let ids: Vec<&str> = hits.iter().map(|h| h.chunk_id.as_str()).collect();
+        assert_eq!(ids, ["c-0", "c-1", "c-3"]);
+        assert_eq!(hits[0].snippet, "El aprendizaje auto…");
+        assert_eq!(hits[2].snippet, "APRENDIZAJE por …");
+        assert_eq!(hits[0].document_id, "doc-1");
+
+        assert_eq!(
+            search_chunks_by_keyword(&db, "aprendizaje", 2, 5)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_count_keyword_matches_is_exact() {
+        let (db, path) = temp_db("test_count_keyword");
+        seed_library(&db);
+
+        let exact = get_chunks_for_document(&db, "doc-1")
+            .unwrap()
+            .iter()
+            .filter(|c| c.text.to_lowercase().contains("aprendizaje"))
+            .count();
+        assert_eq!(exact, 3);
+        assert_eq!(count_keyword_matches(&db, "aprendizaje").unwrap(), exact);
+        assert_eq!(count_keyword_matches(&db, "inexistente").unwrap(), 0);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_sampled_estimate() {
+        let (db, path) = temp_db("test_estimate_sampled");
+        seed_library(&db);
+
+        // Si la muestra cubre toda la biblioteca el resultado es exacto
+        let full =
+            estimate_match_count_sampled(&db, 100, |c| keyword_matches(&c.text, "aprendizaje"))
+                .unwrap();
+        assert_eq!(
+            full,
+            MatchEstimate {
+                estimate: 3,
+                total: 5,
+                sampled: 5,
+                margin: 0,
+            }
+        );
+
+        // Con una muestra parcial el estimado queda dentro del rango posible
+        let partial =
+            estimate_match_count_sampled(&db, 2, |c| keyword_matches(&c.text, "aprendizaje"))
+                .unwrap();
+        assert_eq!(partial.sampled, 2);
+        assert_eq!(partial.total, 5);
+        assert!(partial.estimate <= 5);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_rejects_query_from_other_model() {
+        let (db, path) = temp_db("test_search_model_mismatch");
+        let vectors = seed_vectors(&db, 5, 4, 3);
+
+        let mut options = SearchOptions {
+            k: 2,
+            model: Some("otro".into()),
+            ..SearchOptions::default()
+        };
+        let err = search_similar_with(&db, &vectors[0], &options).unwrap_err();
+        assert!(matches!(
+            err,
+            EmbedError::ModelMismatch { ref stored, ref requested }
+                if stored == "test" && requested == "otro"
+        ));
+
+        options.allow_model_mismatch = true;
+        assert_eq!(
+            search_similar_with(&db, &vectors[0], &options)
+                .unwrap()
+                .len(),
+            2
+        );
+        options.model = Some("test".into());
+        options.allow_model_mismatch = false;
+        let hits = search_similar_with(&db, &vectors[0], &options).unwrap();
+        assert_eq!(hits[0].0.id, "v-0");
+
+        // Sin modelo se compara la dimensión registrada de la biblioteca
+        let other_dimension = vec![1.0; vectors[0].len() + 1];
+        assert!(matches!(
+            search_similar(&db, &other_dimension, 2),
+            Err(EmbedError::ModelMismatch { ref stored, .. }) if stored == "test"
+        ));
+        assert!(matches!(
+            search(&db, &other_dimension, 2),
+            Err(EmbedError::ModelMismatch { .. })
+        ));
+        assert_eq!(search(&db, &vectors[0], 2).unwrap().len(), 2);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_hybrid_search_rewards_exact_keywords() {
+        let (db, path) = temp_db("test_hybrid_search");
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        let chunks = [
+            ("c-exact", "Teorema de Pitágoras con ejemplos"),
+            ("c-close", "Geometría de triángulos rectángulos"),
+            ("c-far", "Recetas de cocina"),
+            ("c-plain", "Demostración del teorema de Pitágoras"),
+        ];
+        for (i, (id, text)) in chunks.iter().enumerate() {
+            insert_chunk(
+                &db,
+                &Chunk::new(id.to_string(), "doc-1".into(), text.to_string(), i, 1),
+            )
+            .unwrap();
+        }
+        // c-exact tiene un vector mediocre (coseno 0.6) y c-close uno muy
+        // parecido (0.9) sin la palabra buscada; c-plain no tiene embedding
+        insert_embedding(&db, "c-exact", &[0.6, 0.8], "test", 2).unwrap();
+        insert_embedding(&db, "c-close", &[0.9, 0.435_889_9], "test", 2).unwrap();
+        insert_embedding(&db, "c-far", &[0.0, -1.0], "test", 2).unwrap();
+
+        let query = [1.0, 0.0];
+        let vector_only = search(&db, &query, 2).unwrap();
+        assert_eq!(vector_only[0].chunk.id, "c-close");
+
+        let hits = hybrid_search(&db, "pitágoras", &query, 4).unwrap();
+        let ids: Vec<&str> = hits.iter().map(|h| h.chunk.id.as_str()).collect();
+        assert_eq!(ids, ["c-exact", "c-close", "c-plain"]);
+        assert!((hits[0].score - (0.7 * 0.6 + 0.3)).abs() < 1e-4);
+        assert!((hits[2].score - 0.3).abs() < 1e-4);
+        assert_eq!(hits[0].document_name, "a.pdf");
+
+        // Solo semántico: vuelve el orden vectorial y c-plain desaparece
+        let semantic = hybrid_search_with(&db, "pitágoras", &query, 4, 1.0).unwrap();
+        let ids: Vec<&str> = semantic.iter().map(|h| h.chunk.id.as_str()).collect();
+        assert_eq!(ids, ["c-close", "c-exact"]);
+        assert!(hybrid_search(&db, "pitágoras", &query, 0)
+            .unwrap()
+            .is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_in_document_ignores_other_documents() {
+        let (db, path) = temp_db("test_search_in_document");
+        for (doc_id, vectors) in [
+            ("doc-1", [[0.0, 1.0], [0.6, 0.8]]),
+            ("doc-2", [[1.0, 0.0], [0.9, 0.1]]),
+        ] {
+            let doc = Document::new(doc_id.into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1);
+            insert_document(&db, &doc).unwrap();
+            for (i, vector) in vectors.iter().enumerate() {
+                let id = format!("{}-{}", doc_id, i);
+                let chunk = Chunk::new(id.clone(), doc_id.into(), "texto".into(), i, 1);
+                insert_chunk(&db, &chunk).unwrap();
+                insert_embedding(&db, &id, vector, "test", 2).unwrap();
+            }
+        }
+
+        // doc-2 tiene los vectores más cercanos, pero solo cuenta doc-1
+        let query = [1.0, 0.0];
+        assert_eq!(search_similar(&db, &query, 1).unwrap()[0].0.id, "doc-2-0");
+        let hits = search_in_document(&db, "doc-1", &query, 5).unwrap();
+        let ids: Vec<&str> = hits.iter().map(|(c, _)| c.id.as_str()).collect();
+        assert_eq!(ids, ["doc-1-1", "doc-1-0"]);
+        assert!(search_in_document(&db, "nope", &query, 5)
+            .unwrap()
+            .is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_search_skips_soft_deleted_documents() {
+        let (db, path) = temp_db("test_search_soft_deleted");
+        let vectors = seed_vectors(&db, 10, 8, 11);
+        seed_library(&db);
+        soft_delete_document(&db, "doc-v").unwrap();
+
+        let hits = search_similar(&db, &vectors[3], 5).unwrap();
+        assert!(hits.iter().all(|(c, _)| c.document_id != "doc-v"));
+        assert_eq!(count_keyword_matches(&db, "aprendizaje").unwrap(), 3);
+        soft_delete_document(&db, "doc-1").unwrap();
+        assert_eq!(count_keyword_matches(&db, "aprendizaje").unwrap(), 0);
+        assert!(search_chunks_by_keyword(&db, "aprendizaje", 10, 20)
+            .unwrap()
+            .is_empty());
+
+        restore_document(&db, "doc-v").unwrap();
+        let hits = search_similar(&db, &vectors[3], 1).unwrap();
+        assert_eq!(hits[0].0.id, "v-3");
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
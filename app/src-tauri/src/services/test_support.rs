@@ -0,0 +1,154 @@
+//! Utilidades compartidas por los tests de los servicios
+
+use crate::models::{Chunk, Document};
+use crate::services::database::init_db_at;
+use crate::services::embeddings::{EmbeddingProvider, HashingEmbedder};
+use crate::services::error::EmbedError;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Abre una BD nueva en el directorio temporal, única por proceso
+///
+/// Retorna también la ruta para borrarla al terminar el test.
+pub(crate) fn temp_db(name: &str) -> (Arc<sled::Db>, PathBuf) {
+    let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let db = init_db_at(path.clone()).unwrap();
+    (db, path)
+}
+
+/// Documento `doc-1` ("a.pdf", una página)
+pub(crate) fn sample_document() -> Document {
+    Document::new("doc-1".into(), "a.pdf".into(), "/tmp/a.pdf".into(), 1)
+}
+
+/// Chunk `c-<index>` de `doc-1`, en la página 1
+pub(crate) fn sample_chunk(index: usize, text: impl Into<String>) -> Chunk {
+    Chunk::new(
+        format!("c-{}", index),
+        "doc-1".into(),
+        text.into(),
+        index,
+        1,
+    )
+}
+
+/// Proveedor de embeddings para los tests
+///
+/// Calcula con un `HashingEmbedder` y cuenta llamadas, textos y llamadas
+/// simultáneas; los métodos `with_*` le cambian el modelo o le agregan
+/// fallas y demoras.
+pub(crate) struct TestProvider {
+    inner: HashingEmbedder,
+    model: Option<String>,
+    /// Cantidad de llamadas que responde antes de empezar a fallar
+    fail_after: Option<usize>,
+    /// Falla si algún texto contiene esto
+    fail_on: Option<String>,
+    /// Devuelve un vector menos que textos
+    drop_last: bool,
+    delay: Duration,
+    calls: AtomicUsize,
+    texts: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl TestProvider {
+    pub(crate) fn new(dimension: usize) -> Self {
+        Self {
+            inner: HashingEmbedder::new(dimension),
+            model: None,
+            fail_after: None,
+            fail_on: None,
+            drop_last: false,
+            delay: Duration::ZERO,
+            calls: AtomicUsize::new(0),
+            texts: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// Informa `model` como nombre de modelo
+    pub(crate) fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    /// Responde `calls` llamadas y después falla con `EmbedError::Backend`
+    pub(crate) fn with_failure_after(mut self, calls: usize) -> Self {
+        self.fail_after = Some(calls);
+        self
+    }
+
+    /// Falla con `EmbedError::Backend` si algún texto contiene `needle`
+    pub(crate) fn with_failure_on(mut self, needle: &str) -> Self {
+        self.fail_on = Some(needle.to_string());
+        self
+    }
+
+    /// Devuelve un vector menos que los textos pedidos
+    pub(crate) fn with_missing_vector(mut self) -> Self {
+        self.drop_last = true;
+        self
+    }
+
+    /// Tarda `delay` en cada llamada
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Llamadas a `embed` hasta ahora
+    pub(crate) fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Textos pedidos hasta ahora, sumando todas las llamadas
+    pub(crate) fn texts(&self) -> usize {
+        self.texts.load(Ordering::SeqCst)
+    }
+
+    /// Mayor cantidad de llamadas en curso a la vez
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl EmbeddingProvider for TestProvider {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        self.texts.fetch_add(texts.len(), Ordering::SeqCst);
+        if self.fail_after.is_some_and(|n| call >= n) {
+            return Err(EmbedError::Backend("connection refused".to_string()));
+        }
+        if let Some(needle) = &self.fail_on {
+            if texts.iter().any(|t| t.contains(needle.as_str())) {
+                return Err(EmbedError::Backend("cannot embed".to_string()));
+            }
+        }
+
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(self.delay);
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let mut vectors = self.inner.embed(texts)?;
+        if self.drop_last {
+            vectors.pop();
+        }
+        Ok(vectors)
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn model_name(&self) -> String {
+        self.model
+            .clone()
+            .unwrap_or_else(|| self.inner.model_name())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::models::document::unix_now;
+use crate::models::{Chunk, Document};
+use crate::services::database::{
+    decode, delete_chunks_for_document, encode, encode_chunk, ensure_writable, flush_after_write,
+    get_chunks_for_document, get_document_required, open_chunk_ids_tree, open_chunks_tree,
+    open_documents_tree, open_hash_index_tree, open_tree, DbError,
+};
+use crate::services::embeddings::{decode_embedding, open_embeddings_tree};
+use crate::services::keys::{chunk_key, TRASH_TREE};
+use crate::services::{attachments, blobs, vector_index};
+use serde::{Deserialize, Serialize};
+use sled;
+use std::sync::Arc;
+
+/// Documento en la papelera, tal como lo ve la UI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedDocument {
+    pub document: Document,
+    /// Momento (timestamp Unix) en que se envió a la papelera
+    pub deleted_at: u64,
+}
+
+/// Lo que se guarda en la papelera: el documento, sus chunks y sus embeddings
+///
+/// Los embeddings se copian en bruto para restaurarlos tal cual estaban. Los
+/// blobs y adjuntos no se mueven: siguen bajo el id del documento hasta que
+/// se purga.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashEntry {
+    document: Document,
+    deleted_at: u64,
+    chunks: Vec<Chunk>,
+    embeddings: Vec<(String, Vec<u8>)>,
+}
+
+pub(crate) fn open_trash_tree(db: &sled::Db) -> Result<sled::Tree, DbError> {
+    open_tree(db, TRASH_TREE)
+}
+
+/// Envía un documento a la papelera en vez de borrarlo
+///
+/// El documento deja de aparecer en la biblioteca y en las búsquedas, pero se
+/// puede recuperar con `restore_document` hasta que se purgue.
+pub fn trash_document(db: &Arc<sled::Db>, id: &str) -> Result<(), DbError> {
+    let _write = ensure_writable(db)?;
+    let document = get_document_required(db, id)?;
+    let chunks = get_chunks_for_document(db, id)?;
+
+    let vectors = open_embeddings_tree(db)?;
+    let mut embeddings = Vec::new();
+    for chunk in &chunks {
+        if let Some(bytes) = vectors.get(chunk.id.as_bytes())? {
+            embeddings.push((chunk.id.clone(), bytes.to_vec()));
+        }
+    }
+
+    let entry = TrashEntry {
+        document,
+        deleted_at: unix_now(),
+        chunks,
+        embeddings,
+    };
+    let trash = open_trash_tree(db)?;
+    trash.insert(id.as_bytes(), encode(&entry)?)?;
+
+    // Recién con la copia guardada se quita de la biblioteca. sled escribe a
+    // disco en orden, así que tras un corte nunca queda el borrado sin la copia
+    open_documents_tree(db)?.remove(id.as_bytes())?;
+    if let Some(hash) = &entry.document.sha256 {
+        open_hash_index_tree(db)?.remove(hash.as_bytes())?;
+    }
+    delete_chunks_for_document(db, id)?;
+    flush_after_write(db)
+}
+
+/// Recupera un documento de la papelera con sus chunks bajo las claves originales
+///
+/// Falla con `DbError::Conflict` si mientras tanto se creó otro documento con
+/// el mismo id o algún chunk con el mismo id; en ese caso la papelera no cambia.
+pub fn restore_document(db: &Arc<sled::Db>, id: &str) -> Result<Document, DbError> {
+    let _write = ensure_writable(db)?;
+    let trash = open_trash_tree(db)?;
+    let bytes = trash
+        .get(id.as_bytes())?
+        .ok_or_else(|| DbError::NotFound(id.to_string()))?;
+    let entry: TrashEntry = decode(&bytes)?;
+
+    let docs = open_documents_tree(db)?;
+    let ids = open_chunk_ids_tree(db)?;
+    if docs.contains_key(id.as_bytes())? {
+        return Err(DbError::Conflict(format!("document {} already exists", id)));
+    }
+    for chunk in &entry.chunks {
+        if ids.contains_key(chunk.id.as_bytes())? {
+            return Err(DbError::Conflict(format!(
+                "chunk {} already exists",
+                chunk.id
+            )));
+        }
+    }
+
+    let mut chunk_batch = sled::Batch::default();
+    let mut id_batch = sled::Batch::default();
+    for chunk in &entry.chunks {
+        let key = chunk_key(&chunk.document_id, chunk.index, &chunk.id);
+        chunk_batch.insert(key.as_bytes(), encode_chunk(db, chunk)?);
+        id_batch.insert(chunk.id.as_bytes(), key.as_bytes());
+    }
+    let mut embedding_batch = sled::Batch::default();
+    let mut vectors = Vec::with_capacity(entry.embeddings.len());
+    for (chunk_id, bytes) in &entry.embeddings {
+        vectors.push((chunk_id, decode_embedding(bytes)?.vector));
+        embedding_batch.insert(chunk_id.as_bytes(), bytes.as_slice());
+    }
+    open_chunks_tree(db)?.apply_batch(chunk_batch)?;
+    ids.apply_batch(id_batch)?;
+    open_embeddings_tree(db)?.apply_batch(embedding_batch)?;
+    for (chunk_id, vector) in &vectors {
+        vector_index::on_embedding_stored(db, chunk_id, vector);
+    }
+
+    docs.insert(id.as_bytes(), encode(&entry.document)?)?;
+    if let Some(hash) = &entry.document.sha256 {
+        open_hash_index_tree(db)?.insert(hash.as_bytes(), id.as_bytes())?;
+    }
+    trash.remove(id.as_bytes())?;
+    flush_after_write(db)?;
+    Ok(entry.document)
+}
+
+/// Lista los documentos en la papelera, del borrado más reciente al más viejo
+pub fn list_trash(db: &Arc<sled::Db>) -> Result<Vec<TrashedDocument>, DbError> {
+    let mut out = Vec::new();
+    for item in open_trash_tree(db)?.iter() {
+        let (_k, v) = item?;
+        let entry: TrashEntry = decode(&v)?;
+        out.push(TrashedDocument {
+            document: entry.document,
+            deleted_at: entry.deleted_at,
+        });
+    }
+    out.sort_by_key(|t| std::cmp::Reverse(t.deleted_at));
+    Ok(out)
+}
+
+/// Borra definitivamente lo que lleva en la papelera al menos `older_than_secs`
+///
+/// Equivale al borrado definitivo de `delete_document`: también se eliminan
+/// el archivo original guardado y los adjuntos. Con `0` vacía la papelera.
+/// Retorna cuántos documentos se purgaron.
+pub fn purge_trash(db: &Arc<sled::Db>, older_than_secs: u64) -> Result<usize, DbError> {
+    let _write = ensure_writable(db)?;
+    let trash = open_trash_tree(db)?;
+    let cutoff = unix_now().saturating_sub(older_than_secs);
+
+    let mut purged = 0;
+    for item in trash.iter() {
+        let (k, v) = item?;
+        let entry: TrashEntry = decode(&v)?;
+        if entry.deleted_at > cutoff {
+            continue;
+        }
+        // Si el id se reutilizó, los blobs y adjuntos ya son del documento nuevo
+        if !open_documents_tree(db)?.contains_key(&k)? {
+            blobs::delete_document_blob(db, &entry.document.id)?;
+            attachments::delete_attachments(db, &entry.document.id)?;
+        }
+        trash.remove(&k)?;
+        purged += 1;
+    }
+    flush_after_write(db)?;
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{
+        count_chunks, get_all_documents, get_chunk, get_document, insert_chunk, insert_document,
+    };
+    use crate::services::embeddings::{get_embedding, insert_embedding};
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db};
+
+    fn seed(db: &Arc<sled::Db>) -> Document {
+        let doc = sample_document().with_sha256("abc".into());
+        insert_document(db, &doc).unwrap();
+        for i in 0..3 {
+            let chunk = sample_chunk(i, format!("texto {}", i));
+            insert_chunk(db, &chunk).unwrap();
+        }
+        insert_embedding(db, "c-1", &[1.0, 2.0], "m", 2).unwrap();
+        get_document(db, "doc-1").unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let (db, path) = temp_db("test_trash_restore");
+        let original = seed(&db);
+        let chunks = get_chunks_for_document(&db, "doc-1").unwrap();
+
+        trash_document(&db, "doc-1").unwrap();
+        assert!(get_document(&db, "doc-1").unwrap().is_none());
+        assert!(get_all_documents(&db).unwrap().is_empty());
+        assert_eq!(count_chunks(&db, None).unwrap(), 0);
+        assert!(get_embedding(&db, "c-1").unwrap().is_none());
+
+        let trashed = list_trash(&db).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].document.id, "doc-1");
+
+        let restored = restore_document(&db, "doc-1").unwrap();
+        assert_eq!(restored, original);
+        assert_eq!(get_document(&db, "doc-1").unwrap(), Some(original.clone()));
+        assert_eq!(get_all_documents(&db).unwrap(), [original]);
+        assert_eq!(get_chunks_for_document(&db, "doc-1").unwrap(), chunks);
+        assert_eq!(get_chunk(&db, "c-2").unwrap(), Some(chunks[2].clone()));
+        assert_eq!(get_embedding(&db, "c-1").unwrap(), Some(vec![1.0, 2.0]));
+        assert!(list_trash(&db).unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_trash_and_purge() {
+        let (db, path) = temp_db("test_trash_purge");
+        seed(&db);
+        blobs::store_document_blob(&db, "doc-1", b"%PDF").unwrap();
+
+        trash_document(&db, "doc-1").unwrap();
+        // Recién borrado: no es más viejo que una hora
+        assert_eq!(purge_trash(&db, 3600).unwrap(), 0);
+        assert_eq!(purge_trash(&db, 0).unwrap(), 1);
+
+        assert!(list_trash(&db).unwrap().is_empty());
+        assert!(blobs::get_document_blob(&db, "doc-1").unwrap().is_none());
+        assert!(matches!(
+            restore_document(&db, "doc-1"),
+            Err(DbError::NotFound(_))
+        ));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,436 @@
+use crate::services::database::DbError;
+use crate::services::embeddings::iter_embeddings;
+use hnsw_rs::prelude::{DataId, DistCosine, Hnsw};
+use sled;
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak,
+};
+
+/// Vecinos máximos por nodo y capa
+const MAX_NEIGHBORS: usize = 16;
+/// Capas máximas del grafo
+const MAX_LAYERS: usize = 16;
+/// Candidatos explorados al insertar; más alto = mejor grafo, inserción más lenta
+const EF_CONSTRUCTION: usize = 100;
+/// Candidatos mínimos explorados al consultar
+const EF_SEARCH: usize = 64;
+/// Nodos quitados a partir de los cuales se puede compactar el grafo
+const MIN_TOMBSTONES_TO_COMPACT: usize = 64;
+
+/// Índice vectorial en memoria (HNSW, con `hnsw_rs`) para búsqueda
+/// aproximada de vecinos
+///
+/// La consulta cuesta aproximadamente `log(n)` en lugar de recorrer todos los
+/// vectores como `search_similar`. El resultado es aproximado: en casos raros
+/// puede no encontrar el vecino exacto. La distancia es la distancia coseno
+/// (`1 - similitud`), así que 0 es un vector idéntico.
+///
+/// El índice no se persiste: se arma al iniciar con `build_vector_index` y
+/// desde entonces se mantiene al día solo, porque cada embedding que se guarda
+/// o se borra en esa BD (insertar, borrar chunks o documentos, papelera) se
+/// aplica a los índices abiertos. `hnsw_rs` no permite quitar nodos, así que
+/// los reemplazados quedan marcados y el grafo se rearma cuando son la mitad.
+#[derive(Clone)]
+pub struct VectorIndex {
+    state: Arc<RwLock<IndexState>>,
+}
+
+struct IndexState {
+    hnsw: Hnsw<'static, f32, DistCosine>,
+    /// Chunk de cada `DataId` del grafo; `None` si se reemplazó o quitó
+    slots: Vec<Option<String>>,
+    by_id: HashMap<String, DataId>,
+    dimension: Option<usize>,
+}
+
+impl Default for VectorIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndexState {
+    fn new(capacity: usize, dimension: Option<usize>) -> Self {
+        Self {
+            hnsw: Hnsw::new(
+                MAX_NEIGHBORS,
+                capacity.max(1),
+                MAX_LAYERS,
+                EF_CONSTRUCTION,
+                DistCosine,
+            ),
+            slots: Vec::with_capacity(capacity),
+            by_id: HashMap::with_capacity(capacity),
+            dimension,
+        }
+    }
+
+    fn insert(&mut self, chunk_id: &str, vector: &[f32]) -> Result<(), DbError> {
+        match self.dimension {
+            Some(dim) if dim != vector.len() => {
+                return Err(DbError::InvalidInput(format!(
+                    "vector for chunk {} has {} dimensions, index uses {}",
+                    chunk_id,
+                    vector.len(),
+                    dim
+                )))
+            }
+            _ => self.dimension = Some(vector.len()),
+        }
+        self.remove(chunk_id);
+
+        let data_id = self.slots.len();
+        self.hnsw.insert_slice((vector, data_id));
+        self.slots.push(Some(chunk_id.to_string()));
+        self.by_id.insert(chunk_id.to_string(), data_id);
+        Ok(())
+    }
+
+    fn remove(&mut self, chunk_id: &str) -> bool {
+        let Some(data_id) = self.by_id.remove(chunk_id) else {
+            return false;
+        };
+        self.slots[data_id] = None;
+        let tombstones = self.slots.len() - self.by_id.len();
+        if tombstones >= MIN_TOMBSTONES_TO_COMPACT && tombstones * 2 >= self.slots.len() {
+            self.compact();
+        }
+        true
+    }
+
+    /// Rearma el grafo solo con los nodos vigentes
+    fn compact(&mut self) {
+        let mut live: Vec<(String, Vec<f32>)> = Vec::with_capacity(self.by_id.len());
+        for point in self.hnsw.get_point_indexation() {
+            if let Some(Some(chunk_id)) = self.slots.get(point.get_origin_id()) {
+                live.push((chunk_id.clone(), point.get_v().to_vec()));
+            }
+        }
+        let mut fresh = IndexState::new(live.len(), self.dimension);
+        for (chunk_id, vector) in &live {
+            let data_id = fresh.slots.len();
+            fresh.hnsw.insert_slice((vector, data_id));
+            fresh.slots.push(Some(chunk_id.clone()));
+            fresh.by_id.insert(chunk_id.clone(), data_id);
+        }
+        *self = fresh;
+    }
+}
+
+impl VectorIndex {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(RwLock::new(IndexState::new(0, None))),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, IndexState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, IndexState> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cantidad de vectores vigentes en el índice
+    pub fn len(&self) -> usize {
+        self.read().by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Dimensión de los vectores indexados (la fija el primer insert)
+    pub fn dimension(&self) -> Option<usize> {
+        self.read().dimension
+    }
+
+    /// Nodos del grafo, contando los quitados que todavía no se compactaron
+    pub fn graph_size(&self) -> usize {
+        self.read().slots.len()
+    }
+
+    /// Agrega (o reemplaza) el vector de un chunk
+    pub fn insert(&self, chunk_id: &str, vector: &[f32]) -> Result<(), DbError> {
+        self.write().insert(chunk_id, vector)
+    }
+
+    /// Quita un chunk del índice; retorna `false` si no estaba
+    pub fn remove(&self, chunk_id: &str) -> bool {
+        self.write().remove(chunk_id)
+    }
+
+    /// Rearma el grafo sin los nodos quitados o reemplazados
+    ///
+    /// Se hace solo cuando los quitados llegan a la mitad del grafo.
+    pub fn compact(&self) {
+        self.write().compact();
+    }
+
+    /// Los `top_k` chunks más cercanos a `vector`, como `(chunk_id, distancia)`
+    /// ordenados de menor a mayor distancia
+    pub fn query(&self, vector: &[f32], top_k: usize) -> Vec<(String, f32)> {
+        let state = self.read();
+        if top_k == 0 || state.by_id.is_empty() || Some(vector.len()) != state.dimension {
+            return Vec::new();
+        }
+        let live = |data_id: &DataId| matches!(state.slots.get(*data_id), Some(Some(_)));
+        state
+            .hnsw
+            .search_filter(vector, top_k, EF_SEARCH.max(top_k), Some(&live))
+            .into_iter()
+            .filter_map(|n| {
+                let chunk_id = state.slots.get(n.get_origin_id())?.as_ref()?;
+                Some((chunk_id.clone(), n.get_distance()))
+            })
+            .collect()
+    }
+}
+
+/// Índices abiertos de cada BD, para aplicarles los cambios de embeddings
+///
+/// Igual que el estado de `database`, se registra por la dirección del `Arc`
+/// con un `Weak` para descartar BDs cerradas e índices ya soltados.
+type IndexMap = HashMap<usize, (Weak<sled::Db>, Vec<Weak<RwLock<IndexState>>>)>;
+
+fn registry() -> MutexGuard<'static, IndexMap> {
+    static REGISTRY: OnceLock<Mutex<IndexMap>> = OnceLock::new();
+    let mut indexes = REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    indexes.retain(|_, (db, states)| {
+        states.retain(|state| state.strong_count() > 0);
+        db.strong_count() > 0 && !states.is_empty()
+    });
+    indexes
+}
+
+fn open_indexes(db: &Arc<sled::Db>) -> Vec<VectorIndex> {
+    registry()
+        .get(&(Arc::as_ptr(db) as usize))
+        .map(|(_, states)| {
+            states
+                .iter()
+                .filter_map(Weak::upgrade)
+                .map(|state| VectorIndex { state })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Aplica a los índices abiertos el vector recién guardado de un chunk
+///
+/// Un vector de otra dimensión (cambio de modelo) saca al chunk del índice;
+/// tras reindexar con el modelo nuevo hay que volver a armarlo.
+pub(crate) fn on_embedding_stored(db: &Arc<sled::Db>, chunk_id: &str, vector: &[f32]) {
+    for index in open_indexes(db) {
+        if index.insert(chunk_id, vector).is_err() {
+            index.remove(chunk_id);
+        }
+    }
+}
+
+/// Quita de los índices abiertos los chunks cuyos embeddings se borraron
+pub(crate) fn on_embeddings_removed<S: AsRef<str>>(db: &Arc<sled::Db>, chunk_ids: &[S]) {
+    for index in open_indexes(db) {
+        let mut state = index.write();
+        for id in chunk_ids {
+            state.remove(id.as_ref());
+        }
+    }
+}
+
+/// Vacía los índices abiertos (p. ej. tras `clear_all`)
+pub(crate) fn on_embeddings_cleared(db: &Arc<sled::Db>) {
+    for index in open_indexes(db) {
+        *index.write() = IndexState::new(0, None);
+    }
+}
+
+/// Arma el índice con todos los embeddings guardados en la BD
+///
+/// Desde ese momento el índice sigue los cambios de embeddings de `db`.
+/// Todos los embeddings deben tener la misma dimensión; si la biblioteca
+/// mezcla modelos se retorna `DbError::InvalidInput`.
+pub fn build_vector_index(db: &Arc<sled::Db>) -> Result<VectorIndex, DbError> {
+    let index = VectorIndex::new();
+    // Se registra antes de leer, con el lock tomado: un cambio que llegue
+    // mientras tanto espera y se aplica después, así no se pierde ninguno
+    let mut state = index.write();
+    registry()
+        .entry(Arc::as_ptr(db) as usize)
+        .or_insert_with(|| (Arc::downgrade(db), Vec::new()))
+        .1
+        .push(Arc::downgrade(&index.state));
+    for item in iter_embeddings(db)? {
+        let (chunk_id, embedding) = item?;
+        state.insert(&chunk_id, &embedding.vector)?;
+    }
+    drop(state);
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+    use crate::services::database::{delete_chunk, delete_document, insert_chunk, insert_document};
+    use crate::services::embeddings::{cosine_similarity, insert_embedding};
+    use crate::services::test_support::{sample_chunk, sample_document, temp_db};
+    use crate::services::trash;
+
+    /// Vectores pseudoaleatorios reproducibles
+    fn vectors(n: usize, dim: usize, mut seed: u64) -> Vec<Vec<f32>> {
+        (0..n)
+            .map(|_| {
+                (0..dim)
+                    .map(|_| {
+                        seed = seed
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((seed >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn brute_force_top1(data: &[Vec<f32>], query: &[f32]) -> usize {
+        (0..data.len())
+            .max_by(|&a, &b| {
+                cosine_similarity(query, &data[a]).total_cmp(&cosine_similarity(query, &data[b]))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hnsw_top1_matches_brute_force() {
+        let (db, path) = temp_db("test_hnsw_top1");
+        let data = vectors(300, 16, 1);
+
+        let doc = sample_document();
+        insert_document(&db, &doc).unwrap();
+        for (i, v) in data.iter().enumerate() {
+            let id = format!("c-{}", i);
+            insert_chunk(
+                &db,
+                &Chunk::new(id.clone(), "doc-1".into(), "t".into(), i, 1),
+            )
+            .unwrap();
+            insert_embedding(&db, &id, v, "test", 16).unwrap();
+        }
+
+        let index = build_vector_index(&db).unwrap();
+        assert_eq!(index.len(), 300);
+        assert_eq!(index.dimension(), Some(16));
+
+        for query in vectors(25, 16, 2) {
+            let expected = format!("c-{}", brute_force_top1(&data, &query));
+            let hits = index.query(&query, 5);
+            assert_eq!(hits.len(), 5);
+            assert_eq!(hits[0].0, expected);
+            assert!(hits.windows(2).all(|w| w[0].1 <= w[1].1));
+        }
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_hnsw_insert_and_remove() {
+        let index = VectorIndex::new();
+        assert!(index.query(&[1.0, 0.0], 3).is_empty());
+
+        index.insert("a", &[1.0, 0.0]).unwrap();
+        index.insert("b", &[0.0, 1.0]).unwrap();
+        assert_eq!(index.query(&[0.9, 0.1], 1)[0].0, "a");
+
+        // Reemplazar el vector de "a" y quitar "b"
+        index.insert("a", &[-1.0, 0.0]).unwrap();
+        assert!(index.remove("b"));
+        assert!(!index.remove("b"));
+        let hits = index.query(&[0.9, 0.1], 5);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "a");
+        assert!(hits[0].1 > 1.5);
+
+        assert!(matches!(
+            index.insert("c", &[1.0, 2.0, 3.0]),
+            Err(DbError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_index_follows_library_changes() {
+        let (db, path) = temp_db("test_hnsw_follows");
+        insert_document(&db, &sample_document()).unwrap();
+        for i in 0..3 {
+            insert_chunk(&db, &sample_chunk(i, "t")).unwrap();
+        }
+        insert_embedding(&db, "c-0", &[1.0, 0.0], "test", 2).unwrap();
+
+        let index = build_vector_index(&db).unwrap();
+        assert_eq!(index.len(), 1);
+
+        // Insertar y reemplazar vectores después de armarlo
+        insert_embedding(&db, "c-1", &[0.0, 1.0], "test", 2).unwrap();
+        insert_embedding(&db, "c-2", &[0.7, 0.7], "test", 2).unwrap();
+        assert_eq!(index.query(&[0.0, 1.0], 1)[0].0, "c-1");
+        insert_embedding(&db, "c-1", &[-1.0, 0.0], "test", 2).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.query(&[-1.0, 0.1], 1)[0].0, "c-1");
+
+        // Borrar un chunk lo saca del índice
+        assert!(delete_chunk(&db, "c-1").unwrap());
+        assert_eq!(index.len(), 2);
+        assert!(index.query(&[-1.0, 0.0], 5).iter().all(|h| h.0 != "c-1"));
+
+        // Papelera: sale al tirarlo y vuelve al restaurarlo
+        trash::trash_document(&db, "doc-1").unwrap();
+        assert!(index.is_empty());
+        trash::restore_document(&db, "doc-1").unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.query(&[1.0, 0.0], 1)[0].0, "c-0");
+
+        delete_document(&db, "doc-1").unwrap();
+        assert!(index.is_empty());
+        assert!(index.query(&[1.0, 0.0], 1).is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_removed_nodes_are_compacted() {
+        let index = VectorIndex::new();
+        let data = vectors(200, 8, 3);
+        for (i, v) in data.iter().enumerate() {
+            index.insert(&format!("c-{}", i), v).unwrap();
+        }
+        for i in 0..99 {
+            assert!(index.remove(&format!("c-{}", i)));
+        }
+        // Todavía no llegan a la mitad del grafo
+        assert_eq!(index.graph_size(), 200);
+
+        assert!(index.remove("c-99"));
+        assert_eq!(index.len(), 100);
+        assert_eq!(index.graph_size(), 100);
+
+        // Reemplazar tampoco hace crecer el grafo sin límite
+        for _ in 0..3 {
+            for (i, v) in data.iter().enumerate().skip(100) {
+                index.insert(&format!("c-{}", i), v).unwrap();
+            }
+        }
+        assert!(index.graph_size() < 200);
+
+        for query in vectors(10, 8, 4) {
+            let expected = 100 + brute_force_top1(&data[100..], &query);
+            assert_eq!(index.query(&query, 1)[0].0, format!("c-{}", expected));
+        }
+    }
+}